@@ -0,0 +1,97 @@
+//! BCP-47 language codes for `stt.language`
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LanguageError {
+    #[error("'{code}' is not a language code like en-US")]
+    Invalid { code: String },
+}
+
+/// Locales macOS dictation recognizes
+pub const KNOWN_LANGUAGES: &[&str] = &[
+    "ar-SA", "ca-ES", "cs-CZ", "da-DK", "de-AT", "de-CH", "de-DE", "el-GR", "en-AU", "en-CA", "en-GB",
+    "en-IE", "en-IN", "en-NZ", "en-SG", "en-US", "en-ZA", "es-CL", "es-CO", "es-ES", "es-MX", "es-US",
+    "fi-FI", "fr-BE", "fr-CA", "fr-CH", "fr-FR", "he-IL", "hi-IN", "hr-HR", "hu-HU", "id-ID", "it-CH",
+    "it-IT", "ja-JP", "ko-KR", "ms-MY", "nb-NO", "nl-BE", "nl-NL", "pl-PL", "pt-BR", "pt-PT", "ro-RO",
+    "ru-RU", "sk-SK", "sv-SE", "th-TH", "tr-TR", "uk-UA", "vi-VN", "yue-CN", "zh-CN", "zh-HK", "zh-TW",
+];
+
+/// Canonical form of a `language[-Script][-REGION]` code
+///
+/// `_` is accepted as a separator and case is fixed up, so `en_us`
+/// becomes `en-US` and `zh_hant_tw` becomes `zh-Hant-TW`.
+pub fn normalize_language(code: &str) -> Result<String, LanguageError> {
+    let invalid = || LanguageError::Invalid { code: code.to_string() };
+    let mut subtags = code.trim().split(['-', '_']);
+
+    let language = subtags.next().filter(|s| (2..=3).contains(&s.len()) && is_alpha(s)).ok_or_else(invalid)?;
+    let mut normalized = language.to_ascii_lowercase();
+
+    let mut next = subtags.next();
+    if let Some(script) = next.filter(|s| s.len() == 4 && is_alpha(s)) {
+        normalized.push('-');
+        normalized.push_str(&script[..1].to_ascii_uppercase());
+        normalized.push_str(&script[1..].to_ascii_lowercase());
+        next = subtags.next();
+    }
+    if let Some(region) = next {
+        let is_region = (region.len() == 2 && is_alpha(region))
+            || (region.len() == 3 && region.bytes().all(|b| b.is_ascii_digit()));
+        if !is_region {
+            return Err(invalid());
+        }
+        normalized.push('-');
+        normalized.push_str(&region.to_ascii_uppercase());
+    }
+
+    match subtags.next() {
+        Some(_) => Err(invalid()),
+        None => Ok(normalized),
+    }
+}
+
+/// Whether `code`, once normalized, is a locale dictation is known to support
+pub fn is_known_language(code: &str) -> bool {
+    normalize_language(code).is_ok_and(|code| KNOWN_LANGUAGES.contains(&code.as_str()))
+}
+
+fn is_alpha(s: &str) -> bool {
+    s.bytes().all(|b| b.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalizes_separators_and_case() {
+        assert_eq!(normalize_language("en_us").unwrap(), "en-US");
+        assert_eq!(normalize_language(" EN-gb ").unwrap(), "en-GB");
+        assert_eq!(normalize_language("zh_hant_tw").unwrap(), "zh-Hant-TW");
+        assert_eq!(normalize_language("es-419").unwrap(), "es-419");
+        assert_eq!(normalize_language("fr").unwrap(), "fr");
+    }
+
+    #[test]
+    fn test_rejects_malformed_codes() {
+        for code in ["", "english", "e", "en-USA", "en-U5", "en--US", "en-US-extra", "12-US"] {
+            assert_eq!(
+                normalize_language(code),
+                Err(LanguageError::Invalid { code: code.to_string() }),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_known_languages() {
+        assert!(is_known_language("en_US"));
+        assert!(is_known_language("yue-cn"));
+        // Well-formed, but not a dictation locale
+        assert!(!is_known_language("xx-YY"));
+        assert!(!is_known_language("en"));
+        assert!(KNOWN_LANGUAGES.iter().all(|code| normalize_language(code).as_deref() == Ok(*code)));
+    }
+}
@@ -2,8 +2,68 @@
 //!
 //! Handles speech-to-text and text-to-speech
 
+pub mod language;
+pub mod null;
+pub mod recording;
 pub mod stt;
 pub mod tts;
 
-pub use stt::MacOSSTT;
-pub use tts::MacOSTTS;
+pub use language::{is_known_language, normalize_language, LanguageError};
+pub use null::{NullStt, NullTts};
+pub use recording::Recorder;
+pub use stt::{AvailabilityStatus, Cancelled, EndOfInput, MacOSSTT, SttBackend, Transcription};
+pub use tts::{MacOSTTS, SpeakOptions, TtsBackend};
+
+use crate::config::{SttConfig, TtsConfig};
+
+/// Speech-to-text backend named by `stt.provider` (`macos-native` or `none`)
+pub fn stt_backend(config: &SttConfig) -> anyhow::Result<Box<dyn SttBackend>> {
+    match config.provider.as_str() {
+        "macos-native" => {
+            let language = normalize_language(&config.language).map_err(|e| anyhow::anyhow!("stt.language {}", e))?;
+            Ok(Box::new(MacOSSTT::new(language)))
+        }
+        "none" => Ok(Box::new(NullStt::new())),
+        other => anyhow::bail!("Unknown STT provider '{}' (expected macos-native or none)", other),
+    }
+}
+
+/// Text-to-speech backend named by `tts.provider` (`macos-native` or `none`)
+pub fn tts_backend(config: &TtsConfig) -> anyhow::Result<Box<dyn TtsBackend>> {
+    match config.provider.as_str() {
+        "macos-native" => Ok(Box::new(MacOSTTS::new(&config.voice, config.rate))),
+        "none" => Ok(Box::new(NullTts)),
+        other => anyhow::bail!("Unknown TTS provider '{}' (expected macos-native or none)", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_none_selects_null_backends() {
+        let stt = SttConfig { provider: "none".to_string(), ..Default::default() };
+        let stt = stt_backend(&stt).unwrap();
+        assert!(!stt.push_to_talk());
+        assert_eq!(stt.availability(), AvailabilityStatus { available: true, reason: None });
+
+        let tts = TtsConfig { provider: "none".to_string(), ..Default::default() };
+        let tts = tts_backend(&tts).unwrap();
+        assert!(tts.available());
+        tts.speak("Nobody hears this").unwrap();
+    }
+
+    #[test]
+    fn test_default_and_unknown_providers() {
+        assert!(stt_backend(&SttConfig::default()).unwrap().push_to_talk());
+        assert!(tts_backend(&TtsConfig::default()).is_ok());
+
+        let stt = SttConfig { provider: "whisper".to_string(), ..Default::default() };
+        let err = stt_backend(&stt).err().unwrap();
+        assert!(err.to_string().contains("Unknown STT provider 'whisper'"));
+
+        let tts = TtsConfig { provider: "espeak".to_string(), ..Default::default() };
+        assert!(tts_backend(&tts).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! Silent backends for headless machines and CI
+//!
+//! `NullTts` discards speech and `NullStt` reads typed lines, so the voice
+//! loop can run with stdin standing in for the microphone.
+
+use anyhow::Result;
+use std::io::BufRead;
+use std::sync::Mutex;
+
+use super::stt::{transcription_from_bytes, AvailabilityStatus, EndOfInput, SttBackend, Transcription};
+use super::tts::TtsBackend;
+
+/// Text-to-speech that says nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullTts;
+
+impl TtsBackend for NullTts {
+    fn speak(&self, _text: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn speak_async(&self, _text: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Speech-to-text that reads one line per utterance
+pub struct NullStt {
+    input: Mutex<Box<dyn BufRead + Send>>,
+}
+
+impl NullStt {
+    /// Read utterances from stdin
+    pub fn new() -> Self {
+        Self::from_reader(std::io::BufReader::new(std::io::stdin()))
+    }
+
+    /// Read utterances from any line source
+    pub fn from_reader(input: impl BufRead + Send + 'static) -> Self {
+        Self { input: Mutex::new(Box::new(input)) }
+    }
+}
+
+impl Default for NullStt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SttBackend for NullStt {
+    /// Fails with `EndOfInput` once the reader is exhausted
+    fn transcribe(&self) -> Result<Transcription> {
+        let mut line = Vec::new();
+        let read = self.input.lock().unwrap().read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Err(EndOfInput.into());
+        }
+
+        transcription_from_bytes(&line)
+    }
+
+    fn availability(&self) -> AvailabilityStatus {
+        AvailabilityStatus::ready()
+    }
+
+    /// Each line is its own utterance, so there's no Enter to wait for
+    fn push_to_talk(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_null_tts_discards_speech() {
+        let tts: &dyn TtsBackend = &NullTts;
+        assert!(tts.available());
+        tts.speak("Hello").unwrap();
+        tts.speak_async("Hello").unwrap();
+        tts.speak_with("Careful", &Default::default()).unwrap();
+    }
+
+    #[test]
+    fn test_null_stt_reads_lines_until_end() {
+        let stt: Box<dyn SttBackend> = Box::new(NullStt::from_reader(Cursor::new("run the tests\n\n  use opus  \n")));
+        assert!(stt.availability().available);
+        assert!(!stt.push_to_talk());
+
+        assert_eq!(stt.transcribe().unwrap(), Transcription::new("run the tests"));
+        assert!(stt.transcribe().unwrap_err().to_string().contains("No speech"));
+        assert_eq!(stt.transcribe().unwrap().text, "use opus");
+        assert!(stt.transcribe().unwrap_err().is::<EndOfInput>());
+    }
+
+    #[test]
+    fn test_null_stt_rejects_undecodable_lines() {
+        let stt = NullStt::from_reader(Cursor::new(b"\xff\xfe\nok \xff\n".to_vec()));
+
+        assert!(stt.transcribe().unwrap_err().to_string().contains("not valid UTF-8"));
+        assert_eq!(stt.transcribe().unwrap().text, "ok \u{FFFD}");
+    }
+}
@@ -0,0 +1,226 @@
+//! Microphone recording to WAV for debugging transcription
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{SampleFormat, SampleRate, Stream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+/// Records mono 16-bit samples from an input device
+pub struct Recorder {
+    device_name: String,
+    sample_rate: u32,
+}
+
+/// A recording in progress; samples accumulate until `finish`
+pub struct ActiveRecording {
+    stream: Stream,
+    samples: Arc<Mutex<Vec<i16>>>,
+    sample_rate: u32,
+}
+
+impl Recorder {
+    pub fn new(device_name: impl Into<String>, sample_rate: u32) -> Self {
+        Self {
+            device_name: device_name.into(),
+            sample_rate,
+        }
+    }
+
+    /// Start capturing from the configured device
+    pub fn start(&self) -> Result<ActiveRecording> {
+        let host = cpal::default_host();
+        let device = if self.device_name == "default" {
+            host.default_input_device()
+        } else {
+            host.input_devices()?
+                .find(|d| d.name().map(|n| n == self.device_name).unwrap_or(false))
+        }
+        .with_context(|| format!("Input device not found: {}", self.device_name))?;
+
+        let supported = device
+            .supported_input_configs()?
+            .find(|c| {
+                c.min_sample_rate().0 <= self.sample_rate && c.max_sample_rate().0 >= self.sample_rate
+            })
+            .with_context(|| format!("Input device does not support {} Hz", self.sample_rate))?
+            .with_sample_rate(SampleRate(self.sample_rate));
+
+        let channels = supported.channels() as usize;
+        let format = supported.sample_format();
+        let config = supported.config();
+
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let on_error = |e| tracing::warn!("Recording stream error: {}", e);
+
+        // Keep the first channel of each frame so the WAV is mono
+        let stream = match format {
+            SampleFormat::I16 => {
+                let sink = samples.clone();
+                device.build_input_stream(
+                    &config,
+                    move |data: &[i16], _: &_| {
+                        sink.lock().unwrap().extend(data.iter().step_by(channels));
+                    },
+                    on_error,
+                    None,
+                )?
+            }
+            SampleFormat::U16 => {
+                let sink = samples.clone();
+                device.build_input_stream(
+                    &config,
+                    move |data: &[u16], _: &_| {
+                        sink.lock().unwrap().extend(
+                            data.iter().step_by(channels).map(|s| (*s as i32 - 32768) as i16),
+                        );
+                    },
+                    on_error,
+                    None,
+                )?
+            }
+            SampleFormat::F32 => {
+                let sink = samples.clone();
+                device.build_input_stream(
+                    &config,
+                    move |data: &[f32], _: &_| {
+                        sink.lock().unwrap().extend(
+                            data.iter()
+                                .step_by(channels)
+                                .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16),
+                        );
+                    },
+                    on_error,
+                    None,
+                )?
+            }
+            other => anyhow::bail!("Unsupported input sample format: {:?}", other),
+        };
+
+        stream.play()?;
+
+        Ok(ActiveRecording {
+            stream,
+            samples,
+            sample_rate: self.sample_rate,
+        })
+    }
+}
+
+impl ActiveRecording {
+    /// Stop capturing and return the recorded samples
+    pub fn finish(self) -> Vec<i16> {
+        drop(self.stream);
+        std::mem::take(&mut *self.samples.lock().unwrap())
+    }
+
+    /// Stop capturing and write the recording to a WAV file
+    pub fn save(self, path: &Path) -> Result<()> {
+        let sample_rate = self.sample_rate;
+        let samples = self.finish();
+        write_wav(path, &samples, sample_rate)
+    }
+}
+
+/// Build a recording path from a template containing `{timestamp}`
+pub fn recording_path(dir: &Path, template: &str, at: DateTime<Utc>) -> PathBuf {
+    let timestamp = at.format("%Y%m%d-%H%M%S%.3f").to_string();
+    dir.join(template.replace("{timestamp}", &timestamp))
+}
+
+/// Write mono 16-bit PCM samples to a WAV file
+pub fn write_wav(path: &Path, samples: &[i16], sample_rate: u32) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    for sample in samples {
+        writer.write_sample(*sample)?;
+    }
+    writer.finalize()?;
+
+    Ok(())
+}
+
+/// Read a 16-bit PCM WAV file, returning samples and sample rate
+pub fn read_wav(path: &Path) -> Result<(Vec<i16>, u32)> {
+    let mut reader = hound::WavReader::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let sample_rate = reader.spec().sample_rate;
+    let samples = reader.samples::<i16>().collect::<Result<Vec<_>, _>>()?;
+
+    Ok((samples, sample_rate))
+}
+
+/// Play a WAV file through the system player
+pub fn play(path: &Path) -> Result<()> {
+    if !path.exists() {
+        anyhow::bail!("Recording not found: {}", path.display());
+    }
+
+    let player = if cfg!(target_os = "macos") { "afplay" } else { "aplay" };
+    let status = Command::new(player)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to run {}", player))?;
+
+    if !status.success() {
+        anyhow::bail!("{} exited with non-zero status", player);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_wav_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("nested").join("turn.wav");
+
+        // One cycle of a square-ish wave covering the full i16 range
+        let samples: Vec<i16> = (0..1600)
+            .map(|i| match i % 4 {
+                0 => i16::MIN,
+                1 => -1,
+                2 => 0,
+                _ => i16::MAX,
+            })
+            .collect();
+
+        write_wav(&path, &samples, 16000).unwrap();
+        let (read, sample_rate) = read_wav(&path).unwrap();
+
+        assert_eq!(sample_rate, 16000);
+        assert_eq!(read, samples);
+    }
+
+    #[test]
+    fn test_recording_path_template() {
+        let at = DateTime::parse_from_rfc3339("2026-01-05T09:30:15.250Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let path = recording_path(Path::new("/tmp/recordings"), "turn-{timestamp}.wav", at);
+
+        assert_eq!(path, PathBuf::from("/tmp/recordings/turn-20260105-093015.250.wav"));
+    }
+
+    #[test]
+    fn test_play_missing_file() {
+        assert!(play(Path::new("/nonexistent/turn.wav")).is_err());
+    }
+}
@@ -3,6 +3,188 @@
 use anyhow::Result;
 use std::process::Command;
 
+use crate::text::decode_output;
+
+/// Whether speech input can work, and why not if it can't
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvailabilityStatus {
+    pub available: bool,
+    /// Explanation or warning for the user (set whenever something is off)
+    pub reason: Option<String>,
+}
+
+impl AvailabilityStatus {
+    pub(crate) fn ready() -> Self {
+        Self { available: true, reason: None }
+    }
+
+    fn warn(reason: impl Into<String>) -> Self {
+        Self { available: true, reason: Some(reason.into()) }
+    }
+
+    fn unavailable(reason: impl Into<String>) -> Self {
+        Self { available: false, reason: Some(reason.into()) }
+    }
+}
+
+/// Text recognized from one utterance
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcription {
+    pub text: String,
+    /// Recognizer confidence, 0.0-1.0 (None if the backend doesn't report one)
+    pub confidence: Option<f32>,
+}
+
+impl Transcription {
+    /// Text with no confidence score
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into(), confidence: None }
+    }
+
+    pub fn with_confidence(mut self, confidence: f32) -> Self {
+        self.confidence = Some(confidence);
+        self
+    }
+
+    /// Overall confidence given the intent classifier's: the lower of the two
+    pub fn combined_confidence(&self, intent_confidence: f32) -> f32 {
+        self.confidence.map_or(intent_confidence, |c| c.min(intent_confidence))
+    }
+}
+
+/// Decode raw recognizer output into a transcription
+///
+/// Invalid UTF-8 is replaced; a transcript with nothing left but
+/// replacement characters counts as a failed transcription.
+pub(crate) fn transcription_from_bytes(bytes: &[u8]) -> Result<Transcription> {
+    let (text, lossy) = decode_output(bytes);
+    let text = text.trim();
+    if text.is_empty() {
+        anyhow::bail!("No speech detected");
+    }
+    if lossy {
+        if text.chars().all(|c| c == char::REPLACEMENT_CHARACTER || c.is_whitespace()) {
+            anyhow::bail!("Transcription was not valid UTF-8");
+        }
+        tracing::warn!("Transcription contained invalid UTF-8, replaced with U+FFFD");
+    }
+    Ok(Transcription::new(text))
+}
+
+/// Something that can turn speech into text
+pub trait SttBackend {
+    /// Capture one utterance and return what was said
+    fn transcribe(&self) -> Result<Transcription>;
+
+    /// Whether transcription can work here, and why not if it can't
+    fn availability(&self) -> AvailabilityStatus;
+
+    /// Whether the user presses Enter before each utterance
+    fn push_to_talk(&self) -> bool {
+        true
+    }
+}
+
+/// Input ended, so there is nothing more to transcribe
+#[derive(Debug, thiserror::Error)]
+#[error("End of input")]
+pub struct EndOfInput;
+
+/// The user dismissed the dictation dialog without saying anything
+#[derive(Debug, thiserror::Error)]
+#[error("Dictation cancelled")]
+pub struct Cancelled;
+
+/// AppleScript's "User canceled." error number
+const USER_CANCELED: &str = "(-128)";
+
+/// Classify what osascript returned from the dictation dialog
+///
+/// Cancel, or OK with nothing typed, is `Cancelled` so the caller can just
+/// ask again; any other failure is a real error, with osascript's message.
+fn dictation_result(success: bool, stdout: &[u8], stderr: &[u8]) -> Result<Transcription> {
+    if !success {
+        let stderr = String::from_utf8_lossy(stderr);
+        if stderr.contains(USER_CANCELED) {
+            return Err(Cancelled.into());
+        }
+        match stderr.trim() {
+            "" => anyhow::bail!("Failed to get speech input"),
+            message => anyhow::bail!("Failed to get speech input: {}", message),
+        }
+    }
+    if String::from_utf8_lossy(stdout).trim().is_empty() {
+        return Err(Cancelled.into());
+    }
+    transcription_from_bytes(stdout)
+}
+
+/// Microphone authorization as reported by AVCaptureDevice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MicrophoneAuthorization {
+    NotDetermined,
+    Restricted,
+    Denied,
+    Authorized,
+    /// Probe failed or returned something unexpected
+    Unknown,
+}
+
+impl MicrophoneAuthorization {
+    /// Map AVAuthorizationStatus raw values
+    fn from_raw(value: &str) -> Self {
+        match value.trim() {
+            "0" => MicrophoneAuthorization::NotDetermined,
+            "1" => MicrophoneAuthorization::Restricted,
+            "2" => MicrophoneAuthorization::Denied,
+            "3" => MicrophoneAuthorization::Authorized,
+            _ => MicrophoneAuthorization::Unknown,
+        }
+    }
+}
+
+/// System calls used to probe STT availability (mockable in tests)
+pub trait SystemProbe {
+    /// Can osascript drive System Events to show the dictation dialog
+    fn can_show_dialog(&self) -> bool;
+
+    /// Current microphone authorization for this process
+    fn microphone_authorization(&self) -> MicrophoneAuthorization;
+}
+
+/// Real probe backed by osascript
+pub struct MacOSProbe;
+
+impl SystemProbe for MacOSProbe {
+    fn can_show_dialog(&self) -> bool {
+        Command::new("osascript")
+            .arg("-e")
+            .arg(r#"tell application "System Events" to return name"#)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false)
+    }
+
+    fn microphone_authorization(&self) -> MicrophoneAuthorization {
+        // JXA bridge to AVCaptureDevice, prints the raw status number
+        let output = Command::new("osascript")
+            .arg("-l")
+            .arg("JavaScript")
+            .arg("-e")
+            .arg("ObjC.import('AVFoundation'); $.AVCaptureDevice.authorizationStatusForMediaType($.AVMediaTypeAudio)")
+            .output();
+
+        match output {
+            Ok(output) if output.status.success() => {
+                MicrophoneAuthorization::from_raw(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => MicrophoneAuthorization::Unknown,
+        }
+    }
+}
+
 pub struct MacOSSTT {
     language: String,
 }
@@ -14,9 +196,15 @@ impl MacOSSTT {
         }
     }
 
+    /// Language used for recognition
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
     /// Record and transcribe using macOS dictation
-    /// Returns transcribed text
-    pub fn transcribe(&self) -> Result<String> {
+    ///
+    /// Fails with `Cancelled` if the user dismisses the dialog.
+    pub fn transcribe(&self) -> Result<Transcription> {
         println!("🎤 Speak now... (will auto-detect when you stop)");
 
         // Use osascript to trigger dictation
@@ -31,29 +219,53 @@ impl MacOSSTT {
             "#)
             .output()?;
 
-        if !output.status.success() {
-            anyhow::bail!("Failed to get speech input");
-        }
+        dictation_result(output.status.success(), &output.stdout, &output.stderr)
+    }
 
-        let text = String::from_utf8_lossy(&output.stdout);
-        let text = text.trim();
+    /// Check if STT is available
+    pub fn is_available() -> bool {
+        Self::availability().available
+    }
+
+    /// Probe STT availability with a reason for the user
+    pub fn availability() -> AvailabilityStatus {
+        Self::availability_with(&MacOSProbe)
+    }
 
-        if text.is_empty() {
-            anyhow::bail!("No speech detected");
+    /// Probe STT availability using the given system probe
+    pub fn availability_with(probe: &impl SystemProbe) -> AvailabilityStatus {
+        if !probe.can_show_dialog() {
+            return AvailabilityStatus::unavailable(
+                "Cannot show the dictation dialog (allow your terminal to control System Events in System Settings > Privacy & Security > Automation)",
+            );
         }
 
-        Ok(text.to_string())
+        match probe.microphone_authorization() {
+            MicrophoneAuthorization::Authorized => AvailabilityStatus::ready(),
+            MicrophoneAuthorization::Denied => AvailabilityStatus::unavailable(
+                "Microphone access denied (grant microphone access in System Settings > Privacy & Security > Microphone)",
+            ),
+            MicrophoneAuthorization::Restricted => AvailabilityStatus::unavailable(
+                "Microphone access is restricted by system policy",
+            ),
+            MicrophoneAuthorization::NotDetermined => AvailabilityStatus::warn(
+                "Microphone permission not requested yet (macOS will prompt on first use)",
+            ),
+            MicrophoneAuthorization::Unknown => AvailabilityStatus::warn(
+                "Could not determine microphone permission",
+            ),
+        }
     }
+}
 
-    /// Check if STT is available
-    pub fn is_available() -> bool {
-        // Check if osascript is available (always on macOS)
-        Command::new("osascript")
-            .arg("-e")
-            .arg("return 1")
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+impl SttBackend for MacOSSTT {
+    /// Dictation doesn't report a confidence
+    fn transcribe(&self) -> Result<Transcription> {
+        MacOSSTT::transcribe(self)
+    }
+
+    fn availability(&self) -> AvailabilityStatus {
+        MacOSSTT::availability()
     }
 }
 
@@ -61,8 +273,104 @@ impl MacOSSTT {
 mod tests {
     use super::*;
 
+    struct MockProbe {
+        dialog: bool,
+        microphone: MicrophoneAuthorization,
+    }
+
+    impl SystemProbe for MockProbe {
+        fn can_show_dialog(&self) -> bool {
+            self.dialog
+        }
+
+        fn microphone_authorization(&self) -> MicrophoneAuthorization {
+            self.microphone
+        }
+    }
+
+    fn status(dialog: bool, microphone: MicrophoneAuthorization) -> AvailabilityStatus {
+        MacOSSTT::availability_with(&MockProbe { dialog, microphone })
+    }
+
     #[test]
+    #[cfg(target_os = "macos")]
     fn test_is_available() {
         assert!(MacOSSTT::is_available());
     }
+
+    #[test]
+    fn test_combined_confidence_takes_lower() {
+        assert_eq!(Transcription::new("hi").combined_confidence(0.9), 0.9);
+        assert_eq!(Transcription::new("hi").with_confidence(0.4).combined_confidence(0.9), 0.4);
+        assert_eq!(Transcription::new("hi").with_confidence(0.95).combined_confidence(0.5), 0.5);
+    }
+
+    #[test]
+    fn test_transcription_from_bytes() {
+        assert_eq!(transcription_from_bytes(b"  run the tests\n").unwrap(), Transcription::new("run the tests"));
+        assert_eq!(transcription_from_bytes(b"caf\xe9 order").unwrap().text, "caf\u{FFFD} order");
+
+        let err = transcription_from_bytes(b"\xff\xfe \xfd\n").unwrap_err();
+        assert!(err.to_string().contains("not valid UTF-8"));
+        assert!(transcription_from_bytes(b" \n").unwrap_err().to_string().contains("No speech"));
+    }
+
+    #[test]
+    fn test_dictation_result() {
+        assert_eq!(dictation_result(true, b"run the tests\n", b"").unwrap(), Transcription::new("run the tests"));
+
+        // Clicking Cancel, or OK on an empty answer
+        let cancel = b"36:180: execution error: User canceled. (-128)\n";
+        assert!(dictation_result(false, b"", cancel).unwrap_err().is::<Cancelled>());
+        assert!(dictation_result(true, b" \n", b"").unwrap_err().is::<Cancelled>());
+
+        // Anything else is a real failure, with osascript's explanation
+        let denied = b"execution error: Not authorized to send Apple events to System Events. (-1743)\n";
+        let err = dictation_result(false, b"", denied).unwrap_err();
+        assert!(!err.is::<Cancelled>());
+        assert!(err.to_string().contains("Not authorized"));
+        assert_eq!(dictation_result(false, b"", b"").unwrap_err().to_string(), "Failed to get speech input");
+    }
+
+    #[test]
+    fn test_availability_authorized() {
+        assert_eq!(status(true, MicrophoneAuthorization::Authorized), AvailabilityStatus::ready());
+    }
+
+    #[test]
+    fn test_availability_denied_microphone() {
+        let status = status(true, MicrophoneAuthorization::Denied);
+        assert!(!status.available);
+        assert!(status.reason.unwrap().contains("grant microphone access"));
+
+        assert!(!MacOSSTT::availability_with(&MockProbe {
+            dialog: true,
+            microphone: MicrophoneAuthorization::Restricted,
+        }).available);
+    }
+
+    #[test]
+    fn test_availability_no_dialog() {
+        // Dialog failure wins regardless of microphone state
+        let status = status(false, MicrophoneAuthorization::Authorized);
+        assert!(!status.available);
+        assert!(status.reason.unwrap().contains("System Events"));
+    }
+
+    #[test]
+    fn test_availability_undetermined_warns() {
+        for microphone in [MicrophoneAuthorization::NotDetermined, MicrophoneAuthorization::Unknown] {
+            let status = status(true, microphone);
+            assert!(status.available);
+            assert!(status.reason.is_some());
+        }
+    }
+
+    #[test]
+    fn test_microphone_authorization_from_raw() {
+        assert_eq!(MicrophoneAuthorization::from_raw("3\n"), MicrophoneAuthorization::Authorized);
+        assert_eq!(MicrophoneAuthorization::from_raw("2"), MicrophoneAuthorization::Denied);
+        assert_eq!(MicrophoneAuthorization::from_raw("0"), MicrophoneAuthorization::NotDetermined);
+        assert_eq!(MicrophoneAuthorization::from_raw("garbage"), MicrophoneAuthorization::Unknown);
+    }
 }
@@ -1,11 +1,49 @@
 //! Text-to-speech using macOS say command
 
 use anyhow::Result;
-use std::process::Command;
+use std::process::{Child, Command};
+use std::sync::Mutex;
+
+/// Per-utterance overrides of a backend's default voice and rate
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpeakOptions {
+    pub voice: Option<String>,
+    /// Words per minute
+    pub rate: Option<u32>,
+}
+
+/// Something that can speak text aloud
+pub trait TtsBackend {
+    /// Speak text, blocking until done
+    fn speak(&self, text: &str) -> Result<()>;
+
+    /// Speak text without waiting for it to finish
+    fn speak_async(&self, text: &str) -> Result<()>;
+
+    /// Speak text with one-off overrides, blocking until done
+    ///
+    /// Backends that can't change voice or rate ignore the overrides.
+    fn speak_with(&self, text: &str, options: &SpeakOptions) -> Result<()> {
+        let _ = options;
+        self.speak(text)
+    }
+
+    /// Cut off speech started by `speak_async`, if any is still playing
+    fn stop(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether the backend can speak on this machine
+    fn available(&self) -> bool {
+        true
+    }
+}
 
 pub struct MacOSTTS {
     voice: String,
     rate: u32,
+    /// `say` process started by `speak_async`
+    speaking: Mutex<Option<Child>>,
 }
 
 impl MacOSTTS {
@@ -13,35 +51,60 @@ impl MacOSTTS {
         Self {
             voice: voice.into(),
             rate,
+            speaking: Mutex::new(None),
         }
     }
 
+    /// `say` arguments for text, with overrides taking precedence over defaults
+    fn args(&self, text: &str, options: &SpeakOptions) -> Vec<String> {
+        vec![
+            "-v".to_string(),
+            options.voice.clone().unwrap_or_else(|| self.voice.clone()),
+            "-r".to_string(),
+            options.rate.unwrap_or(self.rate).to_string(),
+            "--".to_string(),
+            text.to_string(),
+        ]
+    }
+
     /// Speak text
     pub fn speak(&self, text: &str) -> Result<()> {
-        Command::new("say")
-            .arg("-v")
-            .arg(&self.voice)
-            .arg("-r")
-            .arg(self.rate.to_string())
-            .arg(text)
-            .status()?;
-
-        Ok(())
+        self.speak_with(text, &SpeakOptions::default())
     }
 
     /// Speak text asynchronously (non-blocking)
+    ///
+    /// Replaces anything still playing from an earlier call.
     pub fn speak_async(&self, text: &str) -> Result<()> {
-        Command::new("say")
-            .arg("-v")
-            .arg(&self.voice)
-            .arg("-r")
-            .arg(self.rate.to_string())
-            .arg(text)
+        self.stop()?;
+        let child = Command::new("say")
+            .args(self.args(text, &SpeakOptions::default()))
             .spawn()?;
+        *self.speaking.lock().unwrap() = Some(child);
 
         Ok(())
     }
 
+    /// Speak text with a one-off voice and/or rate
+    pub fn speak_with(&self, text: &str, options: &SpeakOptions) -> Result<()> {
+        self.stop()?;
+        Command::new("say").args(self.args(text, options)).status()?;
+
+        Ok(())
+    }
+
+    /// Kill the `say` started by `speak_async` if it's still talking
+    pub fn stop(&self) -> Result<()> {
+        if let Some(mut child) = self.speaking.lock().unwrap().take() {
+            if child.try_wait()?.is_none() {
+                // It may exit on its own between the check and the kill
+                let _ = child.kill();
+            }
+            child.wait()?;
+        }
+        Ok(())
+    }
+
     /// Check if TTS is available
     pub fn is_available() -> bool {
         Command::new("say")
@@ -73,15 +136,93 @@ impl MacOSTTS {
     }
 }
 
+impl TtsBackend for MacOSTTS {
+    fn speak(&self, text: &str) -> Result<()> {
+        MacOSTTS::speak(self, text)
+    }
+
+    fn speak_async(&self, text: &str) -> Result<()> {
+        MacOSTTS::speak_async(self, text)
+    }
+
+    fn speak_with(&self, text: &str, options: &SpeakOptions) -> Result<()> {
+        MacOSTTS::speak_with(self, text, options)
+    }
+
+    fn stop(&self) -> Result<()> {
+        MacOSTTS::stop(self)
+    }
+
+    fn available(&self) -> bool {
+        MacOSTTS::is_available()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_args_use_defaults() {
+        let tts = MacOSTTS::new("Samantha", 200);
+        assert_eq!(
+            tts.args("Done", &SpeakOptions::default()),
+            vec!["-v", "Samantha", "-r", "200", "--", "Done"]
+        );
+    }
+
+    #[test]
+    fn test_args_apply_per_call_overrides() {
+        let tts = MacOSTTS::new("Samantha", 200);
+
+        let options = SpeakOptions { voice: Some("Alex".to_string()), rate: Some(140) };
+        assert_eq!(
+            tts.args("Queue is full", &options),
+            vec!["-v", "Alex", "-r", "140", "--", "Queue is full"]
+        );
+
+        let options = SpeakOptions { rate: Some(120), ..Default::default() };
+        assert_eq!(tts.args("Hi", &options)[..4], ["-v", "Samantha", "-r", "120"]);
+    }
+
+    /// Records what it was asked to say; has no voice controls
+    #[derive(Default)]
+    struct Recording(RefCell<Vec<String>>);
+
+    impl TtsBackend for Recording {
+        fn speak(&self, text: &str) -> Result<()> {
+            self.0.borrow_mut().push(text.to_string());
+            Ok(())
+        }
+
+        fn speak_async(&self, text: &str) -> Result<()> {
+            self.speak(text)
+        }
+    }
+
+    #[test]
+    fn test_stop_with_nothing_playing() {
+        MacOSTTS::new("Samantha", 200).stop().unwrap();
+        Recording::default().stop().unwrap();
+    }
+
+    #[test]
+    fn test_default_speak_with_ignores_overrides() {
+        let backend = Recording::default();
+        let options = SpeakOptions { voice: Some("Alex".to_string()), rate: Some(100) };
+
+        backend.speak_with("Careful", &options).unwrap();
+        assert_eq!(*backend.0.borrow(), vec!["Careful"]);
+    }
 
+    #[cfg(target_os = "macos")]
     #[test]
     fn test_is_available() {
         assert!(MacOSTTS::is_available());
     }
 
+    #[cfg(target_os = "macos")]
     #[test]
     fn test_list_voices() {
         let voices = MacOSTTS::list_voices().unwrap();
@@ -1,19 +1,32 @@
 //! Configuration management
 
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::audio::{is_known_language, normalize_language};
+use crate::error::FasterError;
+use crate::intent::meta::is_known_model;
+use crate::intent::{DirectiveTemplates, EntityExtractors, Intent, ModelAliases, DEFAULT_FILLERS};
+use crate::logging::LogFormat;
+use crate::timezone::Timezone;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    pub general: GeneralConfig,
     pub audio: AudioConfig,
     pub stt: SttConfig,
     pub tts: TtsConfig,
     pub intent: IntentConfig,
     pub confirmation: ConfirmationConfig,
     pub knowledge: KnowledgeConfig,
+    pub queue: QueueConfig,
     pub claude: ClaudeConfig,
     pub observability: ObservabilityConfig,
+    pub daemon: DaemonConfig,
+    /// Spoken shortcuts, expanded before classification (phrase -> command)
+    pub aliases: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +35,11 @@ pub struct AudioConfig {
     pub input_device: String,
     pub sample_rate: u32,
     pub vad_threshold: f32,
+    /// Save each voice turn's raw audio as a WAV file
+    pub save_recordings: bool,
+    pub recordings_dir: PathBuf,
+    /// File name template, `{timestamp}` is replaced per turn
+    pub recording_template: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +47,10 @@ pub struct AudioConfig {
 pub struct SttConfig {
     pub provider: String,
     pub language: String,
+    /// Shortest time between voice turns; doubles with each failed transcription
+    pub min_turn_interval_ms: u64,
+    /// Failed transcriptions in a row before voice mode pauses (0 = never)
+    pub max_consecutive_errors: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +59,10 @@ pub struct TtsConfig {
     pub provider: String,
     pub voice: String,
     pub rate: u32,
+    /// Voice for warnings (None = same as `voice`)
+    pub warning_voice: Option<String>,
+    /// Slower rate so warnings stand out
+    pub warning_rate: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +71,17 @@ pub struct IntentConfig {
     pub model: String,
     pub confidence_threshold: f32,
     pub ensemble_size: usize,
+    /// Entity patterns by name, e.g. `ticket = '[A-Z]+-\d+'`; compiled on load
+    pub extractors: EntityExtractors,
+    /// Filler words and phrases removed from directives, matched as whole words
+    pub fillers: Vec<String>,
+    /// Remove `fillers` from directives; off keeps the transcript as spoken
+    pub clean_directive: bool,
+    /// Directive rewrites by intent name, using `{directive}` and `{entities}`; checked on load
+    pub templates: DirectiveTemplates,
+    /// Voice commands scored below this are answered with a request to rephrase
+    /// instead of being queued (0.0 = never; no keyword or leading verb scores 0.60)
+    pub clarify_threshold: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +89,53 @@ pub struct IntentConfig {
 pub struct ConfirmationConfig {
     pub mode: String,
     pub timeout_ms: u64,
+    /// Mode overrides keyed by intent name, e.g. `orchestrate = "always"`
+    pub per_intent: BTreeMap<String, String>,
+}
+
+/// When to ask before queueing a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationMode {
+    Always,
+    Never,
+    /// Only when the intent classification isn't confident
+    Smart,
+}
+
+impl ConfirmationMode {
+    /// Parse a mode name; anything unrecognized is treated as smart
+    pub fn from_name(name: &str) -> Self {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "always" => ConfirmationMode::Always,
+            "never" => ConfirmationMode::Never,
+            _ => ConfirmationMode::Smart,
+        }
+    }
+}
+
+impl ConfirmationConfig {
+    /// Mode for an intent: its `per_intent` override, else the global mode
+    pub fn mode_for(&self, intent: Option<Intent>) -> ConfirmationMode {
+        let mode = intent
+            .and_then(|intent| {
+                self.per_intent
+                    .iter()
+                    .find(|(name, _)| name.parse::<Intent>().ok() == Some(intent))
+                    .map(|(_, mode)| mode)
+            })
+            .unwrap_or(&self.mode);
+
+        ConfirmationMode::from_name(mode)
+    }
+
+    /// Whether to ask before queueing a command with this intent
+    pub fn should_confirm(&self, intent: Option<Intent>, confident: bool) -> bool {
+        match self.mode_for(intent) {
+            ConfirmationMode::Always => true,
+            ConfirmationMode::Never => false,
+            ConfirmationMode::Smart => !confident,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,17 +146,116 @@ pub struct KnowledgeConfig {
     pub sync_mode: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueConfig {
+    /// Maximum queued tasks before enqueue is rejected (0 = unbounded)
+    pub max_pending: u64,
+    /// Per-task stored output cap in bytes (0 = unbounded)
+    pub max_output_bytes: usize,
+    /// Tasks that keep their output; older outputs are dropped (0 = all)
+    pub keep_last_outputs: u64,
+    /// Longest accepted command in bytes, after normalizing (0 = unbounded)
+    pub max_command_len: usize,
+    /// Keep the queue in memory for one process instead of `knowledge.local_db`
+    pub ephemeral: bool,
+    /// Record each task's directory, git commit and branch at enqueue (see `faster get --env`)
+    pub snapshot_env: bool,
+    /// Environment variables recorded in the snapshot, when set
+    pub snapshot_env_vars: Vec<String>,
+    /// How far back `faster stats` looks, by creation time (0 = every task)
+    pub stats_window_secs: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct ClaudeConfig {
     pub cli_path: String,
     pub model: String,
+    /// Cap on context file bytes appended to a single prompt
+    pub max_context_bytes: usize,
+    /// Directory for full per-task output files, `<id>.log` (off when unset)
+    pub output_dir: Option<PathBuf>,
+    /// Let Claude wait for terminal input; off runs it with `--print` and no stdin
+    pub interactive: bool,
+    /// Kill runs after this many seconds unless the task sets its own timeout (0 = no limit)
+    pub timeout_secs: u64,
+    /// Short names accepted wherever a model is, e.g. `fast = "haiku"`
+    pub model_aliases: ModelAliases,
+    /// Standing instructions put before every prompt (unless the task sets its own)
+    pub prompt_prefix: String,
+    /// Standing instructions put after every prompt, context files included
+    pub prompt_suffix: String,
+}
+
+impl ClaudeConfig {
+    /// `claude.model` with aliases resolved, or None when unset
+    pub fn default_model(&self) -> Option<String> {
+        (!self.model.is_empty()).then(|| self.model_aliases.resolve(&self.model))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct ObservabilityConfig {
     pub conduit_endpoint: Option<String>,
+    /// Address for the daemon's `/healthz`, `/livez` and `/events` endpoints (off when unset)
+    pub health_addr: Option<String>,
+    /// Daemon loop tick age after which `/healthz` reports unhealthy
+    pub max_tick_age_secs: u64,
+    /// Tracing output format, `pretty` or `json` (overridden by `--log-format`)
+    pub log_format: LogFormat,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneralConfig {
+    /// Zone schedules are read in and times shown in: `local`, `UTC` or an offset like `+02:00`
+    pub timezone: String,
+}
+
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        Self { timezone: "local".to_string() }
+    }
+}
+
+impl GeneralConfig {
+    /// The configured timezone
+    pub fn timezone(&self) -> Result<Timezone, ConfigError> {
+        self.timezone
+            .parse()
+            .map_err(|_| ConfigError::InvalidTimezone { value: self.timezone.clone() })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Recorded on each task this daemon claims (default `hostname:pid`)
+    pub name: Option<String>,
+    /// How long Ctrl+C waits for a running task before killing it and requeueing it
+    pub shutdown_grace_ms: u64,
+    /// Running tasks started longer ago than this are requeued at startup and
+    /// on reconnect (0 = only reclaim this daemon's own)
+    pub stale_after_secs: u64,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self { name: None, shutdown_grace_ms: 30_000, stale_after_secs: 21_600 }
+    }
+}
+
+impl Default for ObservabilityConfig {
+    fn default() -> Self {
+        Self {
+            conduit_endpoint: None,
+            health_addr: None,
+            max_tick_age_secs: 30,
+            log_format: LogFormat::Pretty,
+        }
+    }
 }
 
 // Defaults
@@ -88,6 +271,14 @@ fn default_vad_threshold() -> f32 {
     0.5
 }
 
+fn default_recordings_dir() -> PathBuf {
+    default_faster_home().join("recordings")
+}
+
+fn default_recording_template() -> String {
+    "turn-{timestamp}.wav".to_string()
+}
+
 fn default_stt_provider() -> String {
     "macos-native".to_string()
 }
@@ -129,10 +320,36 @@ fn default_timeout_ms() -> u64 {
 }
 
 fn default_local_db() -> PathBuf {
-    dirs::home_dir()
-        .expect("Could not find home directory")
-        .join(".faster")
-        .join("knowledge.db")
+    default_faster_home().join("knowledge.db")
+}
+
+/// Directory for faster's config and data
+///
+/// `$FASTER_HOME` if set, else `~/.faster`, else `.faster` in the current
+/// directory for environments without a home (some containers and CI).
+pub fn faster_home() -> Result<PathBuf, ConfigError> {
+    resolve_faster_home(
+        std::env::var_os("FASTER_HOME").map(PathBuf::from),
+        dirs::home_dir(),
+        std::env::current_dir().ok(),
+    )
+}
+
+fn resolve_faster_home(
+    faster_home: Option<PathBuf>,
+    home: Option<PathBuf>,
+    cwd: Option<PathBuf>,
+) -> Result<PathBuf, ConfigError> {
+    faster_home
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .or_else(|| home.map(|home| home.join(".faster")))
+        .or_else(|| cwd.map(|cwd| cwd.join(".faster")))
+        .ok_or(ConfigError::NoHome)
+}
+
+/// `faster_home()` for defaults, which can't fail; a relative `.faster` as a last resort
+fn default_faster_home() -> PathBuf {
+    faster_home().unwrap_or_else(|_| PathBuf::from(".faster"))
 }
 
 fn default_cli_path() -> String {
@@ -143,6 +360,10 @@ fn default_claude_model() -> String {
     "sonnet".to_string()
 }
 
+fn default_max_context_bytes() -> usize {
+    100_000
+}
+
 fn default_sync_mode() -> String {
     "non-sensitive".to_string()
 }
@@ -153,6 +374,9 @@ impl Default for AudioConfig {
             input_device: default_input_device(),
             sample_rate: default_sample_rate(),
             vad_threshold: default_vad_threshold(),
+            save_recordings: false,
+            recordings_dir: default_recordings_dir(),
+            recording_template: default_recording_template(),
         }
     }
 }
@@ -162,6 +386,8 @@ impl Default for SttConfig {
         Self {
             provider: default_stt_provider(),
             language: default_language(),
+            min_turn_interval_ms: 250,
+            max_consecutive_errors: 5,
         }
     }
 }
@@ -172,6 +398,8 @@ impl Default for TtsConfig {
             provider: default_tts_provider(),
             voice: default_voice(),
             rate: default_rate(),
+            warning_voice: None,
+            warning_rate: 150,
         }
     }
 }
@@ -182,6 +410,11 @@ impl Default for IntentConfig {
             model: default_model(),
             confidence_threshold: default_confidence_threshold(),
             ensemble_size: default_ensemble_size(),
+            extractors: EntityExtractors::default(),
+            fillers: DEFAULT_FILLERS.iter().map(|f| f.to_string()).collect(),
+            clean_directive: true,
+            templates: DirectiveTemplates::default(),
+            clarify_threshold: 0.0,
         }
     }
 }
@@ -191,6 +424,7 @@ impl Default for ConfirmationConfig {
         Self {
             mode: default_mode(),
             timeout_ms: default_timeout_ms(),
+            per_intent: BTreeMap::new(),
         }
     }
 }
@@ -205,90 +439,308 @@ impl Default for KnowledgeConfig {
     }
 }
 
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            max_pending: 0,
+            max_output_bytes: 64 * 1024,
+            keep_last_outputs: 100,
+            max_command_len: 16 * 1024,
+            ephemeral: false,
+            snapshot_env: true,
+            snapshot_env_vars: ["SHELL", "LANG", "VIRTUAL_ENV", "CONDA_DEFAULT_ENV", "NODE_ENV", "RUSTUP_TOOLCHAIN"]
+                .map(String::from)
+                .to_vec(),
+            stats_window_secs: 7 * 24 * 3600,
+        }
+    }
+}
+
 impl Default for ClaudeConfig {
     fn default() -> Self {
         Self {
             cli_path: default_cli_path(),
             model: default_claude_model(),
+            max_context_bytes: default_max_context_bytes(),
+            output_dir: None,
+            interactive: false,
+            timeout_secs: 0,
+            model_aliases: ModelAliases::default(),
+            prompt_prefix: String::new(),
+            prompt_suffix: String::new(),
         }
     }
 }
 
-impl Default for ObservabilityConfig {
-    fn default() -> Self {
-        Self {
-            conduit_endpoint: None,
+/// Whether `mode` is a confirmation mode name `ConfirmationMode::from_name` knows
+fn is_confirmation_mode(mode: &str) -> bool {
+    matches!(mode.trim().to_ascii_lowercase().as_str(), "always" | "never" | "smart")
+}
+
+/// Expand a leading `~` and any `$VAR`/`${VAR}` references
+///
+/// Unset variables are left as written so the mistake is visible in errors.
+/// Where `write_atomically` stages `path` before renaming it into place
+fn staging_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Replace `path` with `contents` all at once, keeping its permissions
+fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let staging = staging_path(path);
+    let written = (|| {
+        let mut file = std::fs::File::create(&staging)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if let Ok(existing) = std::fs::metadata(path) {
+            std::fs::set_permissions(&staging, existing.permissions())?;
         }
+        std::fs::rename(&staging, path)
+    })();
+    if written.is_err() {
+        let _ = std::fs::remove_file(&staging);
     }
+    written
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            audio: AudioConfig {
-                input_device: default_input_device(),
-                sample_rate: default_sample_rate(),
-                vad_threshold: default_vad_threshold(),
-            },
-            stt: SttConfig {
-                provider: default_stt_provider(),
-                language: default_language(),
-            },
-            tts: TtsConfig {
-                provider: default_tts_provider(),
-                voice: default_voice(),
-                rate: default_rate(),
-            },
-            intent: IntentConfig {
-                model: default_model(),
-                confidence_threshold: default_confidence_threshold(),
-                ensemble_size: default_ensemble_size(),
-            },
-            confirmation: ConfirmationConfig {
-                mode: default_mode(),
-                timeout_ms: default_timeout_ms(),
-            },
-            knowledge: KnowledgeConfig {
-                local_db: default_local_db(),
-                sync_endpoint: None,
-                sync_mode: default_sync_mode(),
-            },
-            claude: ClaudeConfig {
-                cli_path: default_cli_path(),
-                model: default_claude_model(),
-            },
-            observability: ObservabilityConfig {
-                conduit_endpoint: None,
-            },
+pub fn expand_path(path: &Path) -> PathBuf {
+    let raw = path.to_string_lossy();
+    let mut expanded = String::with_capacity(raw.len());
+
+    let rest = if raw == "~" || raw.starts_with("~/") {
+        match dirs::home_dir() {
+            Some(home) => {
+                expanded.push_str(&home.to_string_lossy());
+                &raw[1..]
+            }
+            None => &raw[..],
+        }
+    } else {
+        &raw[..]
+    };
+
+    let mut chars = rest.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        let braced = chars.peek().map(|(_, c)| *c) == Some('{');
+        let name_start = if braced { i + 2 } else { i + 1 };
+        let name_end = rest[name_start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map(|n| name_start + n)
+            .unwrap_or(rest.len());
+        let closed = !braced || rest[name_end..].starts_with('}');
+        let name = &rest[name_start..name_end];
+
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() && closed => {
+                expanded.push_str(&value);
+                let end = if braced { name_end + 1 } else { name_end };
+                while chars.peek().is_some_and(|(j, _)| *j < end) {
+                    chars.next();
+                }
+            }
+            _ => expanded.push(c),
+        }
+    }
+
+    PathBuf::from(expanded)
+}
+
+/// Config files that can't be read, parsed or written
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// A config file named explicitly (e.g. with `--config`) doesn't exist
+    #[error("Config file {} not found", path.display())]
+    NotFound { path: PathBuf },
+    #[error("{extension} config files aren't supported in this build (use .toml or .json, or build with --features yaml)")]
+    UnsupportedFormat { extension: String },
+    #[error("Invalid JSON config: {0}")]
+    Json(#[from] serde_json::Error),
+    /// Bad YAML on load, or a config that can't be written as YAML
+    #[cfg(feature = "yaml")]
+    #[error("Invalid YAML config: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Invalid TOML config: {0}")]
+    Toml(#[from] toml::de::Error),
+    /// The config couldn't be written as TOML
+    #[error("Can't write config as TOML: {0}")]
+    TomlWrite(#[from] toml::ser::Error),
+    #[error("general.timezone '{value}' is not local, UTC or an offset like +02:00")]
+    InvalidTimezone { value: String },
+    /// No `FASTER_HOME`, home directory, or current directory to keep files in
+    #[error("Can't find a directory for faster's files; set FASTER_HOME (or HOME)")]
+    NoHome,
+}
+
+/// Serialization used for a config path, chosen by extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// `.json` is JSON, `.yaml`/`.yml` YAML; anything else, including no extension, is TOML
+    ///
+    /// Without the `yaml` feature, YAML files are rejected rather than misread as TOML.
+    pub fn from_path(path: &Path) -> Result<Self, ConfigError> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Ok(ConfigFormat::Json),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            #[cfg(not(feature = "yaml"))]
+            "yaml" | "yml" => Err(ConfigError::UnsupportedFormat { extension: format!(".{}", extension) }),
+            _ => Ok(ConfigFormat::Toml),
         }
     }
 }
 
 impl Config {
-    /// Load from TOML file
-    pub fn load(path: &PathBuf) -> anyhow::Result<Self> {
+    /// Load from a TOML, JSON or (with the `yaml` feature) YAML file, picked by extension
+    ///
+    /// Path-valued fields have `~`, `$VAR`, and `${VAR}` expanded.
+    pub fn load(path: &PathBuf) -> Result<Self, FasterError> {
+        let format = ConfigFormat::from_path(path)?;
         let contents = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&contents)?;
+        let mut config: Config = match format {
+            ConfigFormat::Toml => toml::from_str(&contents).map_err(ConfigError::from)?,
+            ConfigFormat::Json => serde_json::from_str(&contents).map_err(ConfigError::from)?,
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => serde_yaml::from_str(&contents).map_err(ConfigError::from)?,
+        };
+        config.expand_paths();
+        config.normalize_language();
         Ok(config)
     }
 
-    /// Save to TOML file
-    pub fn save(&self, path: &PathBuf) -> anyhow::Result<()> {
+    /// Field values that parse but can't work, one message per problem
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Err(e) = self.general.timezone() {
+            problems.push(e.to_string());
+        }
+        if !(0.0..=1.0).contains(&self.intent.confidence_threshold) {
+            problems.push(format!(
+                "intent.confidence_threshold is {}, expected 0.0-1.0",
+                self.intent.confidence_threshold
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.intent.clarify_threshold) {
+            problems.push(format!(
+                "intent.clarify_threshold is {}, expected 0.0-1.0",
+                self.intent.clarify_threshold
+            ));
+        }
+        if self.intent.ensemble_size == 0 {
+            problems.push("intent.ensemble_size must be at least 1".to_string());
+        }
+        if !is_confirmation_mode(&self.confirmation.mode) {
+            problems.push(format!(
+                "confirmation.mode '{}' is not always, never or smart",
+                self.confirmation.mode
+            ));
+        }
+        for (intent, mode) in &self.confirmation.per_intent {
+            if intent.parse::<Intent>().is_err() {
+                problems.push(format!("confirmation.per_intent has unknown intent '{}'", intent));
+            }
+            if !is_confirmation_mode(mode) {
+                problems.push(format!(
+                    "confirmation.per_intent.{} '{}' is not always, never or smart",
+                    intent, mode
+                ));
+            }
+        }
+        for (field, provider) in [("stt.provider", &self.stt.provider), ("tts.provider", &self.tts.provider)] {
+            if !matches!(provider.as_str(), "macos-native" | "none") {
+                problems.push(format!("{} '{}' is not macos-native or none", field, provider));
+            }
+        }
+        if let Err(e) = normalize_language(&self.stt.language) {
+            problems.push(format!("stt.language {}", e));
+        }
+        if self.tts.rate == 0 {
+            problems.push("tts.rate must be above 0".to_string());
+        }
+        for (alias, model) in self.claude.model_aliases.resolved() {
+            if !is_known_model(&model) {
+                problems.push(format!("claude.model_aliases.{} resolves to unknown model '{}'", alias, model));
+            }
+        }
+        if !is_known_model(&self.claude.model_aliases.resolve(&self.claude.model)) {
+            problems.push(format!("claude.model '{}' is not a known model", self.claude.model));
+        }
+        if let Some(addr) = &self.observability.health_addr {
+            if addr.parse::<std::net::SocketAddr>().is_err() {
+                problems.push(format!("observability.health_addr '{}' is not a host:port address", addr));
+            }
+        }
+
+        problems
+    }
+
+    /// Expand `~` and environment variables in all path-valued fields
+    fn expand_paths(&mut self) {
+        self.knowledge.local_db = expand_path(&self.knowledge.local_db);
+        self.audio.recordings_dir = expand_path(&self.audio.recordings_dir);
+    }
+
+    /// Write `stt.language` canonically (`en_us` is `en-US`); malformed codes are left for `validate`
+    fn normalize_language(&mut self) {
+        if let Ok(language) = normalize_language(&self.stt.language) {
+            self.stt.language = language;
+        }
+    }
+
+    /// Values that work but are probably mistakes, one message per warning
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.stt.provider == "macos-native"
+            && normalize_language(&self.stt.language).is_ok()
+            && !is_known_language(&self.stt.language)
+        {
+            warnings.push(format!(
+                "stt.language '{}' is not a locale macOS dictation is known to support",
+                self.stt.language
+            ));
+        }
+        warnings
+    }
+
+    /// Save in the format matching the path's extension
+    ///
+    /// The file is written beside `path` and renamed over it, so a crash or
+    /// full disk mid-write leaves the old config as it was.
+    pub fn save(&self, path: &Path) -> Result<(), FasterError> {
+        let format = ConfigFormat::from_path(path)?;
         // Create parent directories if they don't exist
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
-        let contents = toml::to_string_pretty(self)?;
-        std::fs::write(path, contents)?;
+        let contents = match format {
+            ConfigFormat::Toml => toml::to_string_pretty(self).map_err(ConfigError::from)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(self).map_err(ConfigError::from)?,
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => serde_yaml::to_string(self).map_err(ConfigError::from)?,
+        };
+        write_atomically(path, contents.as_bytes())?;
         Ok(())
     }
 
-    /// Get config path
-    pub fn path() -> PathBuf {
-        dirs::home_dir()
-            .expect("Could not find home directory")
-            .join(".faster")
-            .join("config.toml")
+    /// Default config path, `config.toml` in `faster_home()`
+    pub fn path() -> Result<PathBuf, ConfigError> {
+        Ok(faster_home()?.join("config.toml"))
     }
 }
 
@@ -305,15 +757,22 @@ mod tests {
         assert_eq!(config.audio.input_device, "default");
         assert_eq!(config.audio.sample_rate, 16000);
         assert_eq!(config.audio.vad_threshold, 0.5);
+        assert!(!config.audio.save_recordings);
+        assert!(config.audio.recordings_dir.ends_with(".faster/recordings"));
+        assert_eq!(config.audio.recording_template, "turn-{timestamp}.wav");
 
         // STT defaults
         assert_eq!(config.stt.provider, "macos-native");
         assert_eq!(config.stt.language, "en-US");
+        assert_eq!(config.stt.min_turn_interval_ms, 250);
+        assert_eq!(config.stt.max_consecutive_errors, 5);
 
         // TTS defaults
         assert_eq!(config.tts.provider, "macos-native");
         assert_eq!(config.tts.voice, "Samantha");
         assert_eq!(config.tts.rate, 200);
+        assert_eq!(config.tts.warning_voice, None);
+        assert_eq!(config.tts.warning_rate, 150);
 
         // Intent defaults
         assert_eq!(config.intent.model, "llama-3.2-3b-instruct");
@@ -323,18 +782,36 @@ mod tests {
         // Confirmation defaults
         assert_eq!(config.confirmation.mode, "smart");
         assert_eq!(config.confirmation.timeout_ms, 1000);
+        assert!(config.confirmation.per_intent.is_empty());
 
         // Knowledge defaults
         assert!(config.knowledge.local_db.to_string_lossy().contains(".faster"));
         assert_eq!(config.knowledge.sync_endpoint, None);
         assert_eq!(config.knowledge.sync_mode, "non-sensitive");
 
+        // Queue defaults
+        assert_eq!(config.queue.max_pending, 0);
+        assert_eq!(config.queue.max_output_bytes, 64 * 1024);
+        assert_eq!(config.queue.keep_last_outputs, 100);
+        assert_eq!(config.queue.max_command_len, 16 * 1024);
+        assert!(config.queue.snapshot_env);
+        assert!(config.queue.snapshot_env_vars.contains(&"VIRTUAL_ENV".to_string()));
+
         // Claude defaults
         assert_eq!(config.claude.cli_path, "claude");
         assert_eq!(config.claude.model, "sonnet");
+        assert_eq!(config.claude.max_context_bytes, 100_000);
 
         // Observability defaults
         assert_eq!(config.observability.conduit_endpoint, None);
+        assert_eq!(config.observability.health_addr, None);
+        assert_eq!(config.observability.max_tick_age_secs, 30);
+        assert_eq!(config.daemon.shutdown_grace_ms, 30_000);
+        assert_eq!(config.daemon.stale_after_secs, 21_600);
+        assert_eq!(config.observability.log_format, LogFormat::Pretty);
+
+        // No aliases by default
+        assert!(config.aliases.is_empty());
     }
 
     #[test]
@@ -373,6 +850,131 @@ mod tests {
         assert!(toml_str.contains("sonnet"));
     }
 
+    #[test]
+    fn test_aliases_table() {
+        let toml_str = r#"
+            [aliases]
+            "ship it" = "run the full CI pipeline and deploy to staging"
+        "#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            config.aliases.get("ship it").map(String::as_str),
+            Some("run the full CI pipeline and deploy to staging")
+        );
+
+        // Survives a save/load round trip
+        let reparsed: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(reparsed.aliases, config.aliases);
+    }
+
+    #[test]
+    fn test_intent_fillers_config() {
+        let config: Config = toml::from_str("[intent]\nfillers = ['äh', 'also']\nclean_directive = false\n").unwrap();
+        assert_eq!(config.intent.fillers, vec!["äh", "also"]);
+        assert!(!config.intent.clean_directive);
+
+        let defaults = Config::default().intent;
+        assert!(defaults.clean_directive);
+        assert!(defaults.fillers.contains(&"you know".to_string()));
+    }
+
+    #[test]
+    fn test_intent_extractors_compile_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, r#"
+            [intent.extractors]
+            ticket = '\b[A-Z]+-\d+\b'
+            version = 'v\d+\.\d+\.\d+'
+        "#).unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(
+            config.intent.extractors.extract("Ship JIRA-1234 as v2.3.1"),
+            vec!["ticket:JIRA-1234", "version:v2.3.1"]
+        );
+
+        // Round-trips as the original pattern strings
+        let reparsed: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(reparsed.intent.extractors.extract("OPS-9"), vec!["ticket:OPS-9"]);
+        assert!(Config::default().intent.extractors.is_empty());
+    }
+
+    #[test]
+    fn test_model_aliases_resolve_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[claude]\nmodel = \"quick\"\n\n[claude.model_aliases]\nfast = \"haiku\"\nquick = \"fast\"\nodd = \"gpt\"\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.claude.default_model().as_deref(), Some("haiku"));
+        assert_eq!(config.claude.model_aliases.resolve("fast"), "haiku");
+        assert_eq!(config.validate(), vec!["claude.model_aliases.odd resolves to unknown model 'gpt'"]);
+        assert_eq!(Config::default().claude.default_model().as_deref(), Some("sonnet"));
+    }
+
+    #[test]
+    fn test_model_alias_cycle_rejected_at_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[claude.model_aliases]\nfast = \"quick\"\nquick = \"fast\"\n").unwrap();
+
+        let err = Config::load(&path).unwrap_err();
+        assert!(err.to_string().contains("Model alias cycle: fast -> quick -> fast"), "{}", err);
+    }
+
+    #[test]
+    fn test_stt_language_normalized_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[stt]\nlanguage = \"en_us\"\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.stt.language, "en-US");
+        assert!(config.validate().is_empty() && config.warnings().is_empty());
+
+        std::fs::write(&path, "[stt]\nlanguage = \"xx_yy\"\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.stt.language, "xx-YY");
+        assert_eq!(
+            config.warnings(),
+            vec!["stt.language 'xx-YY' is not a locale macOS dictation is known to support"]
+        );
+
+        std::fs::write(&path, "[stt]\nlanguage = \"english\"\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.stt.language, "english");
+        assert_eq!(config.validate(), vec!["stt.language 'english' is not a language code like en-US"]);
+    }
+
+    #[test]
+    fn test_intent_templates_checked_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[intent.templates]\ntest = 'Run the test suite for {entities}: {directive}'\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.intent.templates.get(Intent::Test), Some("Run the test suite for {entities}: {directive}"));
+        assert_eq!(config.intent.templates.get(Intent::Code), None);
+
+        std::fs::write(&path, "[intent.templates]\ndeploy = 'Ship {directive}'\n").unwrap();
+        let err = Config::load(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("unknown intent 'deploy'"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_malformed_extractor_rejected_at_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[intent.extractors]\nticket = '[A-Z+-(\\d'\n").unwrap();
+
+        let err = Config::load(&path).unwrap_err();
+        assert!(err.to_string().contains("extractor 'ticket'"), "{}", err);
+    }
+
     #[test]
     fn test_config_deserialization_partial() {
         let toml_str = r#"
@@ -396,11 +998,152 @@ mod tests {
 
     #[test]
     fn test_config_path() {
-        let path = Config::path();
+        let path = Config::path().unwrap();
         assert!(path.to_string_lossy().contains(".faster"));
         assert!(path.to_string_lossy().contains("config.toml"));
     }
 
+    #[test]
+    fn test_faster_home_without_home() {
+        let faster_home = PathBuf::from("/srv/faster");
+        let cwd = PathBuf::from("/work");
+
+        // FASTER_HOME wins, whether or not there's a home directory
+        assert_eq!(resolve_faster_home(Some(faster_home.clone()), None, Some(cwd.clone())).unwrap(), faster_home);
+        assert_eq!(
+            resolve_faster_home(Some(faster_home.clone()), Some("/home/me".into()), None).unwrap(),
+            faster_home
+        );
+        assert_eq!(resolve_faster_home(None, Some("/home/me".into()), None).unwrap(), PathBuf::from("/home/me/.faster"));
+
+        // Empty counts as unset; with no home the current directory is used
+        assert_eq!(resolve_faster_home(Some(PathBuf::new()), None, Some(cwd)).unwrap(), PathBuf::from("/work/.faster"));
+        assert!(matches!(resolve_faster_home(None, None, None), Err(ConfigError::NoHome)));
+    }
+
+    #[test]
+    fn test_expand_path_tilde() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_path(Path::new("~/foo")), home.join("foo"));
+        assert_eq!(expand_path(Path::new("~")), home);
+        // Only a leading ~ is special
+        assert_eq!(expand_path(Path::new("/a/~/b")), PathBuf::from("/a/~/b"));
+    }
+
+    #[test]
+    fn test_expand_path_env_vars() {
+        std::env::set_var("FASTER_TEST_EXPAND_DIR", "/data/faster");
+        assert_eq!(
+            expand_path(Path::new("$FASTER_TEST_EXPAND_DIR/knowledge.db")),
+            PathBuf::from("/data/faster/knowledge.db")
+        );
+        assert_eq!(
+            expand_path(Path::new("${FASTER_TEST_EXPAND_DIR}_old/x.db")),
+            PathBuf::from("/data/faster_old/x.db")
+        );
+
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand_path(Path::new("$HOME/foo")), PathBuf::from(home).join("foo"));
+
+        // Unset variables stay literal
+        assert_eq!(
+            expand_path(Path::new("$FASTER_TEST_UNSET_VAR/x")),
+            PathBuf::from("$FASTER_TEST_UNSET_VAR/x")
+        );
+    }
+
+    #[test]
+    fn test_expand_path_absolute_untouched() {
+        assert_eq!(expand_path(Path::new("/var/lib/faster.db")), PathBuf::from("/var/lib/faster.db"));
+    }
+
+    #[test]
+    fn test_load_expands_paths() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "[knowledge]\nlocal_db = \"~/.faster/other.db\"\n").unwrap();
+
+        let config = Config::load(&config_path).unwrap();
+        assert_eq!(
+            config.knowledge.local_db,
+            dirs::home_dir().unwrap().join(".faster/other.db")
+        );
+    }
+
+    #[test]
+    fn test_load_toml_and_json_identically() {
+        let dir = tempdir().unwrap();
+        let toml_path = dir.path().join("config.toml");
+        std::fs::write(
+            &toml_path,
+            "[tts]\nvoice = \"Alex\"\nrate = 180\n\n[queue]\nmax_pending = 5\n\n[aliases]\nship = \"run the tests\"\n",
+        )
+        .unwrap();
+        let json_path = dir.path().join("config.json");
+        std::fs::write(
+            &json_path,
+            r#"{"tts": {"voice": "Alex", "rate": 180}, "queue": {"max_pending": 5}, "aliases": {"ship": "run the tests"}}"#,
+        )
+        .unwrap();
+
+        let from_toml = Config::load(&toml_path).unwrap();
+        let from_json = Config::load(&json_path).unwrap();
+        assert_eq!(from_toml.tts.voice, "Alex");
+        assert_eq!(from_toml.queue.max_pending, 5);
+        assert_eq!(serde_json::to_value(&from_toml).unwrap(), serde_json::to_value(&from_json).unwrap());
+    }
+
+    #[test]
+    fn test_save_round_trips_across_formats() {
+        let dir = tempdir().unwrap();
+        let mut config = Config::default();
+        config.claude.model = "opus".to_string();
+        config.daemon.name = Some("worker-1".to_string());
+        config.confirmation.per_intent.insert("code".to_string(), "always".to_string());
+
+        let json_path = dir.path().join("config.json");
+        config.save(&json_path).unwrap();
+        assert!(std::fs::read_to_string(&json_path).unwrap().trim_start().starts_with('{'));
+        let toml_path = dir.path().join("config.toml");
+        Config::load(&json_path).unwrap().save(&toml_path).unwrap();
+
+        let reloaded = Config::load(&toml_path).unwrap();
+        assert_eq!(serde_json::to_value(&reloaded).unwrap(), serde_json::to_value(&config).unwrap());
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    #[test]
+    fn test_yaml_config_is_rejected() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, "tts:\n  voice: Alex\n").unwrap();
+
+        let err = Config::load(&path).unwrap_err();
+        assert!(matches!(err, FasterError::Config(ConfigError::UnsupportedFormat { .. })));
+        assert!(Config::default().save(&dir.path().join("out.yml")).is_err());
+        assert_eq!(ConfigFormat::from_path(Path::new("config")).unwrap(), ConfigFormat::Toml);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_config_loads_and_round_trips() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, "tts:\n  voice: Alex\nqueue:\n  max_pending: 5\n").unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.tts.voice, "Alex");
+        assert_eq!(config.queue.max_pending, 5);
+
+        let yml_path = dir.path().join("out.yml");
+        config.save(&yml_path).unwrap();
+        let reloaded = Config::load(&yml_path).unwrap();
+        assert_eq!(serde_json::to_value(&reloaded).unwrap(), serde_json::to_value(&config).unwrap());
+
+        std::fs::write(&path, "tts: [not, a, table]\n").unwrap();
+        assert!(matches!(Config::load(&path).unwrap_err(), FasterError::Config(ConfigError::Yaml(_))));
+    }
+
     #[test]
     fn test_save_creates_parent_directory() {
         let dir = tempdir().unwrap();
@@ -411,4 +1154,95 @@ mod tests {
 
         assert!(nested_path.exists());
     }
+
+    #[test]
+    fn test_failed_save_leaves_config_intact() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let mut config = Config::default();
+        config.claude.model = "opus".to_string();
+        config.save(&path).unwrap();
+        let before = std::fs::read_to_string(&path).unwrap();
+
+        // Nothing can be staged where a directory already is
+        std::fs::create_dir(staging_path(&path)).unwrap();
+        config.claude.model = "haiku".to_string();
+        assert!(config.save(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+        assert_eq!(Config::load(&path).unwrap().claude.model, "opus");
+
+        std::fs::remove_dir(staging_path(&path)).unwrap();
+        config.save(&path).unwrap();
+        assert_eq!(Config::load(&path).unwrap().claude.model, "haiku");
+        assert!(!staging_path(&path).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_keeps_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        Config::default().save(&path).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        Config::default().save(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_per_intent_confirmation_overrides_global() {
+        let config: Config = toml::from_str(
+            r#"
+            [confirmation]
+            mode = "smart"
+
+            [confirmation.per_intent]
+            orchestrate = "always"
+            research = "never"
+            "#,
+        )
+        .unwrap();
+        let confirmation = &config.confirmation;
+
+        assert_eq!(confirmation.mode_for(Some(Intent::Orchestrate)), ConfirmationMode::Always);
+        assert_eq!(confirmation.mode_for(Some(Intent::Research)), ConfirmationMode::Never);
+        assert!(confirmation.should_confirm(Some(Intent::Orchestrate), true));
+        assert!(!confirmation.should_confirm(Some(Intent::Research), false));
+    }
+
+    #[test]
+    fn test_unspecified_intent_uses_global_confirmation() {
+        let mut confirmation = ConfirmationConfig::default();
+        confirmation.per_intent.insert("orchestrate".to_string(), "always".to_string());
+
+        assert_eq!(confirmation.mode_for(Some(Intent::Code)), ConfirmationMode::Smart);
+        assert_eq!(confirmation.mode_for(None), ConfirmationMode::Smart);
+        assert!(confirmation.should_confirm(Some(Intent::Code), false));
+        assert!(!confirmation.should_confirm(Some(Intent::Code), true));
+
+        confirmation.mode = "never".to_string();
+        assert!(!confirmation.should_confirm(Some(Intent::Test), false));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Config::default().validate(), Vec::<String>::new());
+
+        let mut config = Config::default();
+        config.intent.confidence_threshold = 1.5;
+        config.confirmation.mode = "sometimes".to_string();
+        config.confirmation.per_intent.insert("dance".to_string(), "always".to_string());
+        config.tts.provider = "espeak".to_string();
+        config.observability.health_addr = Some("localhost".to_string());
+        config.general.timezone = "Mars/Olympus".to_string();
+
+        let problems = config.validate();
+        assert_eq!(problems.len(), 6, "{:?}", problems);
+        assert!(problems[0].contains("general.timezone 'Mars/Olympus'"));
+        assert!(problems[1].contains("confidence_threshold"));
+        assert!(problems.iter().any(|p| p.contains("'dance'")));
+        assert!(problems.iter().any(|p| p.contains("tts.provider 'espeak'")));
+    }
 }
@@ -0,0 +1,1669 @@
+//! Daemon layer
+//!
+//! Pulls tasks off the queue and runs them through Claude Code
+
+use anyhow::{Context, Result};
+use colored::*;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::config::{ClaudeConfig, Config};
+use crate::error::FasterError;
+use crate::executor::{extract_session_id, ClaudeExecutor, ExecutionOutput, Executor};
+use crate::queue::{truncate_with_marker, ListQuery, QueueError, Task, TaskQueue, TaskStatus};
+
+/// How hard the daemon tries to get its database back
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Reconnect attempts before giving up
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+/// How often a running task beats the heartbeat
+const BEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When the daemon loop last ticked, shared with health checks
+///
+/// A task in flight keeps beating it, so a long run doesn't look like a hang,
+/// but only until its timeout; a run stuck past that goes stale like a hung loop.
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeat(Arc<Mutex<Option<Instant>>>);
+
+impl Heartbeat {
+    /// Record a tick now
+    pub fn beat(&self) {
+        *self.0.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Time since the last tick, or None if it never ticked
+    pub fn age(&self) -> Option<Duration> {
+        self.0.lock().unwrap().map(|at| at.elapsed())
+    }
+
+    /// Call `run`, beating every `BEAT_INTERVAL` from another thread until it
+    /// returns or `limit` has passed (None = no limit)
+    fn beating_while<T>(&self, limit: Option<Duration>, run: impl FnOnce() -> T) -> T {
+        let (done, finished) = std::sync::mpsc::channel::<()>();
+        let started = Instant::now();
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                while let Err(std::sync::mpsc::RecvTimeoutError::Timeout) = finished.recv_timeout(BEAT_INTERVAL) {
+                    if limit.is_some_and(|limit| started.elapsed() >= limit) {
+                        break;
+                    }
+                    self.beat();
+                }
+            });
+            let result = run();
+            drop(done);
+            result
+        })
+    }
+}
+
+/// How a daemon is asked to stop, shared with whatever handles Ctrl+C
+///
+/// `request` stops it claiming new tasks; `force` also kills the task in
+/// flight, which goes back in the queue.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    stop: CancellationToken,
+    kill: CancellationToken,
+}
+
+impl Shutdown {
+    /// Let the running task finish, then stop
+    pub fn request(&self) {
+        self.stop.cancel();
+    }
+
+    /// Stop now, killing the running task
+    pub fn force(&self) {
+        self.stop.cancel();
+        self.kill.cancel();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.stop.is_cancelled()
+    }
+
+    pub fn is_forced(&self) -> bool {
+        self.kill.is_cancelled()
+    }
+}
+
+/// Drive `shutdown` from `interrupt` (e.g. Ctrl+C)
+///
+/// The first interrupt requests a shutdown. The running task is killed at
+/// the second, or once `grace` has passed.
+pub async fn escalate_shutdown<F, Fut>(shutdown: &Shutdown, grace: Duration, mut interrupt: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    interrupt().await;
+    shutdown.request();
+    eprintln!(
+        "{} Stopping after the running task (up to {:.1}s, Ctrl+C again to kill it)",
+        "■".bright_blue(),
+        grace.as_secs_f64()
+    );
+
+    tokio::select! {
+        _ = tokio::time::sleep(grace) => {}
+        _ = interrupt() => {}
+    }
+    shutdown.force();
+}
+
+/// Queue-processing daemon
+pub struct Daemon {
+    queue: TaskQueue,
+    config: Config,
+    executor: Box<dyn Executor>,
+    policy: ReconnectPolicy,
+    heartbeat: Heartbeat,
+    shutdown: Shutdown,
+    session: SessionStats,
+    started: Instant,
+}
+
+/// What a daemon has done since it started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    pub processed: usize,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+impl SessionStats {
+    fn record(&mut self, status: TaskStatus) {
+        self.processed += 1;
+        match status {
+            TaskStatus::Completed => self.completed += 1,
+            TaskStatus::Failed => self.failed += 1,
+            _ => {}
+        }
+    }
+}
+
+/// One-line account of a daemon session, for printing on exit
+///
+/// `queued` is left out when it couldn't be counted.
+pub fn format_session_summary(stats: &SessionStats, runtime: Duration, queued: Option<usize>) -> String {
+    let mut summary = format!(
+        "Daemon ran {}: {} processed ({} completed, {} failed)",
+        format_runtime(runtime),
+        stats.processed,
+        stats.completed,
+        stats.failed
+    );
+    if let Some(queued) = queued {
+        summary.push_str(&format!(", {} still queued", queued));
+    }
+    summary
+}
+
+/// Whole seconds as e.g. `42s`, `3m 05s`, `2h 00m 07s`
+fn format_runtime(runtime: Duration) -> String {
+    let secs = runtime.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Outcome of draining the queue with `drain`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrainSummary {
+    pub processed: usize,
+    pub failed: usize,
+}
+
+impl Daemon {
+    pub fn new(queue: TaskQueue, config: Config) -> Self {
+        let executor = Box::new(ClaudeExecutor::from_config(&config.claude));
+        Self {
+            queue,
+            config,
+            executor,
+            policy: ReconnectPolicy::default(),
+            heartbeat: Heartbeat::default(),
+            shutdown: Shutdown::default(),
+            session: SessionStats::default(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Run tasks with this executor instead of the Claude CLI
+    pub fn with_executor(mut self, executor: impl Executor + 'static) -> Self {
+        self.executor = Box::new(executor);
+        self
+    }
+
+    /// Share a heartbeat that is updated on every tick and while a task runs
+    pub fn with_heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    /// Stop when `shutdown` is requested, killing the running task if it's forced
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Set reconnect policy
+    pub fn with_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Take the queue's daemon lock for this process
+    ///
+    /// Fails if another live daemon holds it, unless `force` is set, in which
+    /// case a warning is printed. Stale locks from dead processes are reclaimed.
+    pub async fn lock(&self, force: bool) -> Result<()> {
+        if let Some(displaced) = self
+            .queue
+            .acquire_daemon_lock(std::process::id(), force, pid_alive)
+            .await?
+        {
+            eprintln!(
+                "{} Daemon pid {} (started {}) is still running; both will pull tasks",
+                "⚠".yellow(),
+                displaced.pid,
+                displaced.started_at.to_rfc3339()
+            );
+        }
+        Ok(())
+    }
+
+    /// Requeue running tasks this daemon or a dead one left behind, returning how many
+    ///
+    /// Run at startup and after reconnecting. This daemon's own running tasks
+    /// can't still be in flight; anyone's that started more than
+    /// `daemon.stale_after_secs` ago are taken as abandoned.
+    pub async fn reclaim_stale(&self) -> Result<u64> {
+        let max_age = Some(self.config.daemon.stale_after_secs).filter(|&secs| secs > 0).map(Duration::from_secs);
+        let reclaimed = self.queue.requeue_stale(&daemon_name(&self.config), max_age).await?;
+        if reclaimed > 0 {
+            eprintln!("{} Requeued {} stale running task(s)", "⚠".yellow(), reclaimed);
+            tracing::warn!(reclaimed, "Requeued stale running tasks");
+        }
+        Ok(reclaimed)
+    }
+
+    /// Release the lock taken by `lock`
+    pub async fn unlock(&self) -> Result<()> {
+        Ok(self.queue.release_daemon_lock(std::process::id()).await?)
+    }
+
+    /// Tasks processed since this daemon was created
+    pub fn session(&self) -> SessionStats {
+        self.session
+    }
+
+    /// Summary of this session for printing on exit, with the queued count if it can be read
+    pub async fn session_summary(&self) -> String {
+        let queued = self
+            .queue
+            .query(&ListQuery::default().with_status(TaskStatus::Queued))
+            .await
+            .ok()
+            .map(|tasks| tasks.len());
+        format_session_summary(&self.session, self.started.elapsed(), queued)
+    }
+
+    /// Checkpoint and close the queue database on shutdown
+    pub async fn close(self) -> Result<()> {
+        Ok(self.queue.close().await?)
+    }
+
+    /// Run until shutdown is requested, sleeping when the queue is empty
+    pub async fn run(&mut self) -> Result<()> {
+        while !self.shutdown.is_requested() {
+            if !self.tick().await? {
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                    _ = self.shutdown.stop.cancelled() => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the tasks queued right now, then stop
+    ///
+    /// The queued set is snapshotted first, so tasks enqueued while it runs
+    /// are left for the next pass. Unlike `tick`, a lost connection is an error.
+    pub async fn run_once(&mut self) -> Result<DrainSummary> {
+        self.heartbeat.beat();
+        let snapshot: Vec<String> = self
+            .queue
+            .query(&ListQuery::default().with_status(TaskStatus::Queued))
+            .await?
+            .into_iter()
+            .map(|task| task.id)
+            .collect();
+
+        let mut summary = DrainSummary::default();
+        while !self.shutdown.is_requested() {
+            let next = process_next(&self.queue, &self.config, self.executor.as_ref(), Some(&snapshot), &self.heartbeat, &self.shutdown.kill);
+            let Some(status) = next.await? else {
+                break;
+            };
+            self.heartbeat.beat();
+            self.session.record(status);
+            summary.processed += 1;
+            if status == TaskStatus::Failed {
+                summary.failed += 1;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Process at most one task, recovering the database connection if it was lost
+    ///
+    /// Returns whether a task was processed. Errors only once reconnecting has
+    /// been retried `max_retries` times. A recreated database starts empty, so
+    /// tasks that were in flight in the old file are gone with it.
+    pub async fn tick(&mut self) -> Result<bool> {
+        self.heartbeat.beat();
+
+        if self.queue.backing_file_missing() {
+            eprintln!("{} Queue database disappeared, reconnecting", "⚠".yellow());
+            self.reconnect().await?;
+            return Ok(false);
+        }
+
+        match process_next(&self.queue, &self.config, self.executor.as_ref(), None, &self.heartbeat, &self.shutdown.kill).await {
+            Ok(Some(status)) => {
+                self.session.record(status);
+                Ok(true)
+            }
+            Ok(None) => Ok(false),
+            Err(e) if is_connection_error(&e) => {
+                eprintln!("{} Queue error: {}, reconnecting", "⚠".yellow(), e);
+                self.reconnect().await?;
+                Ok(false)
+            }
+            Err(e) => {
+                eprintln!("{} Queue error: {}", "⚠".yellow(), e);
+                tracing::warn!(error = %e, "Queue error");
+                Ok(false)
+            }
+        }
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        let mut backoff = self.policy.initial_backoff;
+
+        for attempt in 1..=self.policy.max_retries {
+            match self.queue.reconnect().await {
+                Ok(()) => {
+                    println!("{} Reconnected to queue database", "✓".green());
+                    // Recovered either way; stale tasks get another chance at startup
+                    if let Err(e) = self.reclaim_stale().await {
+                        tracing::warn!("Failed to requeue stale tasks: {:#}", e);
+                    }
+                    return Ok(());
+                }
+                Err(e) => {
+                    eprintln!(
+                        "{} Reconnect attempt {}/{} failed: {}",
+                        "✗".red(),
+                        attempt,
+                        self.policy.max_retries,
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.policy.max_backoff);
+                }
+            }
+        }
+
+        anyhow::bail!(
+            "Could not reconnect to queue database after {} attempts",
+            self.policy.max_retries
+        )
+    }
+}
+
+/// Whether `err` means the database connection itself failed, so reconnecting may help
+///
+/// Anything else (a rejected statement, a corrupt row) would fail the same
+/// way on a fresh connection.
+fn is_connection_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let db = match cause.downcast_ref::<FasterError>() {
+            Some(FasterError::Db(e)) => e,
+            Some(FasterError::Io(_)) => return true,
+            _ => match cause.downcast_ref::<sqlx::Error>() {
+                Some(e) => e,
+                None => return cause.is::<std::io::Error>(),
+            },
+        };
+        match db {
+            sqlx::Error::Io(_) | sqlx::Error::PoolClosed | sqlx::Error::PoolTimedOut | sqlx::Error::WorkerCrashed => true,
+            // SQLITE_IOERR, SQLITE_CANTOPEN, or the file was moved or deleted underneath us
+            sqlx::Error::Database(e) => {
+                let code = e.code().and_then(|code| code.parse::<i32>().ok());
+                matches!(code.map(|code| code & 0xff), Some(10 | 14)) || code == Some(1032)
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Whether a process with this PID exists
+pub fn pid_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Name recorded on claimed tasks: `daemon.name`, or `hostname:pid`
+pub fn daemon_name(config: &Config) -> String {
+    match &config.daemon.name {
+        Some(name) => name.clone(),
+        None => format!("{}:{}", hostname(), std::process::id()),
+    }
+}
+
+/// This machine's hostname, looked up once
+fn hostname() -> &'static str {
+    static HOSTNAME: OnceLock<String> = OnceLock::new();
+    HOSTNAME.get_or_init(|| {
+        std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+            .or_else(|| {
+                let output = std::process::Command::new("hostname").output().ok()?;
+                output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+            })
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "unknown".to_string())
+    })
+}
+
+/// Run the next queued task, if any. Returns whether a task was processed.
+pub async fn process_once(queue: &TaskQueue, config: &Config, executor: &dyn Executor) -> Result<bool> {
+    Ok(process_next(queue, config, executor, None, &Heartbeat::default(), &CancellationToken::new()).await?.is_some())
+}
+
+/// Process up to `max` tasks (all of them if None), stopping early once the queue is empty
+pub async fn drain(
+    queue: &TaskQueue,
+    config: &Config,
+    executor: &dyn Executor,
+    max: Option<usize>,
+) -> Result<DrainSummary> {
+    let mut summary = DrainSummary::default();
+
+    while max.is_none_or(|max| summary.processed < max) {
+        let Some(status) = process_next(queue, config, executor, None, &Heartbeat::default(), &CancellationToken::new()).await? else {
+            break;
+        };
+        summary.processed += 1;
+        if status == TaskStatus::Failed {
+            summary.failed += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Claim and run the next queued task, only from `among` if given, returning its final status
+///
+/// A run killed by `kill` is requeued, so its status is `Queued`.
+async fn process_next(
+    queue: &TaskQueue,
+    config: &Config,
+    executor: &dyn Executor,
+    among: Option<&[String]>,
+    heartbeat: &Heartbeat,
+    kill: &CancellationToken,
+) -> Result<Option<TaskStatus>> {
+    // Claiming marks the task running, so a second daemon can't pick it up too
+    let claimed = match among {
+        Some(ids) => queue.claim_next_among(ids, &daemon_name(config)).await?,
+        None => queue.claim_next_as(&daemon_name(config)).await?,
+    };
+    let Some(task) = claimed else {
+        return Ok(None);
+    };
+
+    let span = tracing::info_span!("task", task_id = %task.id);
+    run_claimed(queue, config, executor, task, heartbeat, kill).instrument(span).await.map(Some)
+}
+
+/// Run a task already claimed by `process_next`
+async fn run_claimed(
+    queue: &TaskQueue,
+    config: &Config,
+    executor: &dyn Executor,
+    mut task: Task,
+    heartbeat: &Heartbeat,
+    kill: &CancellationToken,
+) -> Result<TaskStatus> {
+    // Follow-ups run in their parent's Claude session
+    if task.session_id.is_none() {
+        if let Some(parent_id) = &task.parent_id {
+            if let Some(session_id) = queue.get(parent_id).await?.and_then(|p| p.session_id) {
+                queue.set_session_id(&task.id, &session_id).await?;
+                task.session_id = Some(session_id);
+            }
+        }
+    }
+
+    println!("{} [{}] {}", "→".blue(), task.id.bright_cyan(), task.command);
+    tracing::info!(command = %task.command, "Task started");
+
+    // Tasks queued through the API or specs may still name an alias
+    task.model = task.model.map(|model| config.claude.model_aliases.resolve(&model));
+    let model = task.model.clone().or_else(|| config.claude.default_model());
+    let attempt = queue.start_attempt(&task.id, model.as_deref()).await?;
+
+    // Render and persist the exact prompt Claude receives; a replay reuses its original's
+    let context_files = queue.context_files(&task.id).await?;
+    let rendered = match (&task.replayed_from, &task.rendered_prompt) {
+        (Some(_), Some(prompt)) => Ok(prompt.clone()),
+        _ => build_prompt(&task, &context_files, config.claude.max_context_bytes)
+            .map(|prompt| wrap_prompt(&task, &config.claude, &prompt)),
+    };
+    let prompt = match rendered {
+        Ok(prompt) => prompt,
+        Err(e) => {
+            if !settle(queue, &task.id, Some(&e.to_string())).await? {
+                return cancelled_mid_run(queue, &task.id, attempt, None).await;
+            }
+            queue.finish_attempt(&task.id, attempt, TaskStatus::Failed, Some(&e.to_string())).await?;
+            eprintln!("{} [{}] Failed: {}", "✗".red(), task.id.bright_cyan(), e);
+            tracing::warn!(error = %e, "Task failed");
+            println!();
+            return Ok(TaskStatus::Failed);
+        }
+    };
+    queue.set_rendered_prompt(&task.id, &prompt).await?;
+
+    // Full output goes to a file too, so `faster logs` isn't limited by the DB cap
+    if let Some(dir) = &config.claude.output_dir {
+        let path = dir.join(format!("{}.log", task.id));
+        queue.set_output_path(&task.id, &path).await?;
+        task.output_path = Some(path);
+    }
+
+    let timeout = task_timeout(&task, config);
+    task.timeout_ms = timeout.map(|t| t.as_millis() as u64);
+
+    // Execute; a run that exits non-zero still has output worth keeping
+    let status = match heartbeat.beating_while(timeout, || executor.execute_task_until(&task, &prompt, kill)) {
+        Ok(output) if output.success() => {
+            if !settle(queue, &task.id, None).await? {
+                return cancelled_mid_run(queue, &task.id, attempt, Some(&output)).await;
+            }
+            queue.finish_attempt(&task.id, attempt, TaskStatus::Completed, None).await?;
+            record_output(queue, &task.id, &output).await?;
+            if let Some(session_id) = extract_session_id(&output.stdout) {
+                queue.set_session_id(&task.id, &session_id).await?;
+            }
+            println!("{} [{}] Completed", "✓".green(), task.id.bright_cyan());
+            tracing::info!("Task completed");
+            TaskStatus::Completed
+        }
+        // Killed by shutdown, so it gets a fresh run next time
+        _ if kill.is_cancelled() => {
+            queue.finish_attempt(&task.id, attempt, TaskStatus::Cancelled, Some("Killed by daemon shutdown")).await?;
+            queue.requeue_interrupted(&task.id).await?;
+            eprintln!("{} [{}] Killed by shutdown, requeued", "⚠".yellow(), task.id.bright_cyan());
+            tracing::warn!("Task killed by shutdown, requeued");
+            TaskStatus::Queued
+        }
+        Ok(output) => {
+            let message = output.failure_message();
+            if !settle(queue, &task.id, Some(&message)).await? {
+                return cancelled_mid_run(queue, &task.id, attempt, Some(&output)).await;
+            }
+            queue.finish_attempt(&task.id, attempt, TaskStatus::Failed, Some(&message)).await?;
+            record_output(queue, &task.id, &output).await?;
+            eprintln!("{} [{}] Failed: {}", "✗".red(), task.id.bright_cyan(), message);
+            tracing::warn!(error = %message, "Task failed");
+            TaskStatus::Failed
+        }
+        Err(e) => {
+            if !settle(queue, &task.id, Some(&e.to_string())).await? {
+                return cancelled_mid_run(queue, &task.id, attempt, None).await;
+            }
+            queue.finish_attempt(&task.id, attempt, TaskStatus::Failed, Some(&e.to_string())).await?;
+            eprintln!("{} [{}] Failed: {}", "✗".red(), task.id.bright_cyan(), e);
+            tracing::warn!(error = %e, "Task failed");
+            TaskStatus::Failed
+        }
+    };
+
+    println!();
+    Ok(status)
+}
+
+/// Move a finished run's task to `Completed`, or `Failed` with `error`
+///
+/// Returns false when the task is no longer running, i.e. it was cancelled
+/// while the run was in flight; that cancel stands.
+async fn settle(queue: &TaskQueue, id: &str, error: Option<&str>) -> Result<bool> {
+    let result = match error {
+        None => queue.update_status(id, TaskStatus::Completed).await,
+        Some(error) => queue.fail(id, error).await,
+    };
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) if matches!(e.queue_error(), Some(QueueError::InvalidTransition { .. })) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Close out a run whose task was cancelled underneath it, keeping any output
+async fn cancelled_mid_run(
+    queue: &TaskQueue,
+    id: &str,
+    attempt: u32,
+    output: Option<&ExecutionOutput>,
+) -> Result<TaskStatus> {
+    queue.finish_attempt(id, attempt, TaskStatus::Cancelled, Some("Cancelled while running")).await?;
+    if let Some(output) = output {
+        record_output(queue, id, output).await?;
+    }
+    eprintln!("{} [{}] Cancelled while running", "⚠".yellow(), id.bright_cyan());
+    tracing::warn!("Task cancelled while running");
+    println!();
+    Ok(TaskStatus::Cancelled)
+}
+
+/// How long a task may run: its own timeout, else `claude.timeout_secs` (None = no limit)
+pub fn task_timeout(task: &Task, config: &Config) -> Option<Duration> {
+    match (task.timeout_ms.filter(|&ms| ms > 0), config.claude.timeout_secs) {
+        (Some(ms), _) => Some(Duration::from_millis(ms)),
+        (None, 0) => None,
+        (None, secs) => Some(Duration::from_secs(secs)),
+    }
+}
+
+/// Store a run's stdout, duration and token counts on its task
+async fn record_output(queue: &TaskQueue, id: &str, output: &ExecutionOutput) -> Result<()> {
+    queue.set_output(id, &output.stdout, output.lossy).await?;
+    queue.set_run_stats(id, output.duration, output.tokens.map(|t| (t.input, t.output))).await?;
+    if output.lossy {
+        tracing::warn!("Output contained invalid UTF-8, replaced with U+FFFD");
+    }
+    tracing::debug!(duration_ms = output.duration.as_millis() as u64, exit_code = ?output.exit_code, "Run finished");
+    Ok(())
+}
+
+/// Build the prompt sent to Claude for a task
+///
+/// Context file contents are appended after the command, sharing a budget of
+/// `max_context_bytes`. A missing file is an error so the task fails clearly.
+pub fn build_prompt(task: &Task, context_files: &[PathBuf], max_context_bytes: usize) -> Result<String> {
+    let mut prompt = task.command.clone();
+    let mut budget = max_context_bytes;
+
+    for path in context_files {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Context file not found or unreadable: {}", path.display()))?;
+
+        prompt.push_str(&format!("\n\nContext file: {}\n```\n", path.display()));
+        prompt.push_str(&truncate_with_marker(&contents, budget));
+        budget = budget.saturating_sub(contents.len());
+        if !prompt.ends_with('\n') {
+            prompt.push('\n');
+        }
+        prompt.push_str("```");
+    }
+
+    Ok(prompt)
+}
+
+/// `prompt` between the task's prefix and suffix, falling back to `claude.prompt_prefix`/`prompt_suffix`
+pub fn wrap_prompt(task: &Task, claude: &ClaudeConfig, prompt: &str) -> String {
+    let prefix = task.prompt_prefix.as_deref().unwrap_or(&claude.prompt_prefix).trim();
+    let suffix = task.prompt_suffix.as_deref().unwrap_or(&claude.prompt_suffix).trim();
+
+    [prefix, prompt, suffix]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn test_config() -> Config {
+        let mut config = Config::default();
+        // `true` accepts any arguments and exits 0
+        config.claude.cli_path = "true".to_string();
+        config
+    }
+
+    fn fast_policy() -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(20),
+        }
+    }
+
+    /// Fails any prompt containing "fail", succeeds otherwise
+    struct MockExecutor;
+
+    impl Executor for MockExecutor {
+        fn execute_task(&self, _task: &Task, prompt: &str) -> Result<ExecutionOutput> {
+            if prompt.contains("fail") {
+                anyhow::bail!("mock failure");
+            }
+            Ok(format!("ran {}", prompt).into())
+        }
+    }
+
+    /// Reports the session it was resumed with, or starts "fresh-session"
+    struct SessionExecutor;
+
+    impl Executor for SessionExecutor {
+        fn execute_task(&self, task: &Task, _prompt: &str) -> Result<ExecutionOutput> {
+            let session = task.session_id.as_deref().unwrap_or("fresh-session");
+            Ok(format!("{{\"session_id\":\"{}\"}}\n", session).into())
+        }
+    }
+
+    /// Remembers the timeout each task was run with
+    #[derive(Default)]
+    struct TimeoutExecutor {
+        seen: std::sync::Mutex<Vec<Option<u64>>>,
+    }
+
+    impl Executor for TimeoutExecutor {
+        fn execute_task(&self, task: &Task, _prompt: &str) -> Result<ExecutionOutput> {
+            self.seen.lock().unwrap().push(task.timeout_ms);
+            Ok("done".to_string().into())
+        }
+    }
+
+    /// Fails its first `failures` runs with a numbered error, then succeeds
+    struct FlakyExecutor {
+        failures: usize,
+        runs: std::sync::atomic::AtomicUsize,
+    }
+
+    impl Executor for FlakyExecutor {
+        fn execute_task(&self, _task: &Task, _prompt: &str) -> Result<ExecutionOutput> {
+            let run = self.runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            if run <= self.failures {
+                anyhow::bail!("flaky failure {}", run);
+            }
+            Ok("done".to_string().into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retried_task_records_each_attempt() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let id = queue.enqueue("Deploy", Some("opus".to_string())).await.unwrap();
+        let executor = FlakyExecutor { failures: 2, runs: Default::default() };
+
+        assert!(process_once(&queue, &test_config(), &executor).await.unwrap());
+        queue.update_status(&id, TaskStatus::Queued).await.unwrap();
+        assert!(process_once(&queue, &test_config(), &executor).await.unwrap());
+        queue.update_status(&id, TaskStatus::Queued).await.unwrap();
+        assert!(process_once(&queue, &test_config(), &executor).await.unwrap());
+
+        let attempts = queue.attempts(&id).await.unwrap();
+        assert_eq!(attempts.len(), 3);
+        for (i, attempt) in attempts.iter().enumerate() {
+            assert_eq!(attempt.task_id, id);
+            assert_eq!(attempt.attempt_no, i as u32 + 1);
+            assert_eq!(attempt.model.as_deref(), Some("opus"));
+            assert!(attempt.completed_at.unwrap() >= attempt.started_at);
+        }
+        assert_eq!(attempts[0].status, TaskStatus::Failed);
+        assert_eq!(attempts[0].error.as_deref(), Some("flaky failure 1"));
+        assert_eq!(attempts[1].status, TaskStatus::Failed);
+        assert_eq!(attempts[1].error.as_deref(), Some("flaky failure 2"));
+        assert_eq!(attempts[2].status, TaskStatus::Completed);
+        assert_eq!(attempts[2].error, None);
+        assert!(attempts[1].started_at >= attempts[0].completed_at.unwrap());
+
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_attempt_model_falls_back_to_config() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let id = queue.enqueue("Task fail", None).await.unwrap();
+
+        drain(&queue, &test_config(), &MockExecutor, None).await.unwrap();
+
+        let attempts = queue.attempts(&id).await.unwrap();
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].model.as_deref(), Some("sonnet"));
+        assert_eq!(attempts[0].error.as_deref(), Some("mock failure"));
+    }
+
+    #[tokio::test]
+    async fn test_follow_up_resumes_parent_session() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let first = queue.enqueue("Start", None).await.unwrap();
+        let second = queue.enqueue("Continue", None).await.unwrap();
+        queue.set_parent(&second, &first).await.unwrap();
+
+        drain(&queue, &test_config(), &SessionExecutor, None).await.unwrap();
+
+        let first = queue.get(&first).await.unwrap().unwrap();
+        let second = queue.get(&second).await.unwrap().unwrap();
+        assert_eq!(first.session_id.as_deref(), Some("fresh-session"));
+        assert_eq!(second.session_id, first.session_id);
+    }
+
+    /// A Claude CLI that replies in JSON only when asked to, as the real one does
+    ///
+    /// A resumed run reports the session it was given; a fresh one starts `sess-1`.
+    fn fake_claude(dir: &std::path::Path) -> ClaudeExecutor {
+        let script = dir.join("fake-claude");
+        std::fs::write(
+            &script,
+            r#"#!/bin/sh
+case "$*" in --version) echo '1.0.3 (Claude Code)'; exit 0 ;; esac
+case "$*" in *'--output-format json'*) ;; *) echo 'plain reply'; exit 0 ;; esac
+session=sess-1
+case "$*" in *'--resume '*) session=$(echo "$*" | sed 's/.*--resume \([^ ]*\).*/\1/') ;; esac
+printf '{"type":"result","result":"done","session_id":"%s","usage":{"input_tokens":120,"output_tokens":45}}\n' "$session"
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        let mut config = test_config();
+        config.claude.cli_path = script.to_string_lossy().into_owned();
+        ClaudeExecutor::from_config(&config.claude).with_echo(false)
+    }
+
+    #[tokio::test]
+    async fn test_session_id_recorded_from_real_cli_args() {
+        let dir = tempdir().unwrap();
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let first = queue.enqueue("Start", None).await.unwrap();
+        let second = queue.enqueue("Continue", None).await.unwrap();
+        queue.set_parent(&second, &first).await.unwrap();
+
+        drain(&queue, &test_config(), &fake_claude(dir.path()), None).await.unwrap();
+
+        let first = queue.get(&first).await.unwrap().unwrap();
+        let second = queue.get(&second).await.unwrap().unwrap();
+        assert_eq!(first.status, TaskStatus::Completed);
+        assert_eq!(first.session_id.as_deref(), Some("sess-1"));
+        assert_eq!(second.status, TaskStatus::Completed);
+        assert_eq!(second.session_id.as_deref(), Some("sess-1"));
+    }
+
+    #[tokio::test]
+    async fn test_tokens_recorded_from_real_cli_run() {
+        let dir = tempdir().unwrap();
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let json = queue.enqueue("Count tokens", None).await.unwrap();
+        process_once(&queue, &test_config(), &fake_claude(dir.path())).await.unwrap();
+
+        let task = queue.get(&json).await.unwrap().unwrap();
+        assert_eq!((task.input_tokens, task.output_tokens), (Some(120), Some(45)));
+
+        // Interactive runs can't ask for JSON; they complete without token counts
+        let plain = queue.enqueue("No tokens", None).await.unwrap();
+        process_once(&queue, &test_config(), &fake_claude(dir.path()).with_print(false)).await.unwrap();
+
+        let task = queue.get(&plain).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert_eq!(task.output.as_deref(), Some("plain reply\n"));
+        assert_eq!((task.input_tokens, task.output_tokens), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_drain_stops_at_max() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        for i in 0..4 {
+            queue.enqueue(&format!("Task {}", i), None).await.unwrap();
+        }
+
+        let summary = drain(&queue, &test_config(), &MockExecutor, Some(3)).await.unwrap();
+        assert_eq!(summary, DrainSummary { processed: 3, failed: 0 });
+
+        let queued = queue.list().await.unwrap();
+        assert_eq!(queued.iter().filter(|t| t.status == TaskStatus::Queued).count(), 1);
+        assert_eq!(queued.iter().filter(|t| t.status == TaskStatus::Completed).count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_drain_stops_when_empty_and_counts_failures() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        queue.enqueue("Task ok", None).await.unwrap();
+        let failing = queue.enqueue("Task fail", None).await.unwrap();
+
+        let summary = drain(&queue, &test_config(), &MockExecutor, Some(5)).await.unwrap();
+        assert_eq!(summary, DrainSummary { processed: 2, failed: 1 });
+
+        let task = queue.get(&failing).await.unwrap().unwrap();
+        assert_eq!(task.error.as_deref(), Some("mock failure"));
+
+        let summary = drain(&queue, &test_config(), &MockExecutor, None).await.unwrap();
+        assert_eq!(summary, DrainSummary::default());
+    }
+
+    /// Queues another task (through a second connection) every time it runs one
+    struct EnqueuingExecutor {
+        db_path: String,
+    }
+
+    impl Executor for EnqueuingExecutor {
+        fn execute_task(&self, task: &Task, _prompt: &str) -> Result<ExecutionOutput> {
+            let db_path = self.db_path.clone();
+            let command = format!("Late after {}", task.command);
+            std::thread::spawn(move || {
+                let queue = crate::queue::BlockingTaskQueue::new(&db_path)?;
+                queue.enqueue(&command, None)?;
+                queue.close()
+            })
+            .join()
+            .unwrap()?;
+            Ok("done".to_string().into())
+        }
+    }
+
+    /// Takes `duration` to succeed, or stops early with `Cancelled` when killed
+    struct SlowExecutor {
+        duration: Duration,
+    }
+
+    impl Executor for SlowExecutor {
+        fn execute_task(&self, task: &Task, prompt: &str) -> Result<ExecutionOutput> {
+            self.execute_task_until(task, prompt, &CancellationToken::new())
+        }
+
+        fn execute_task_until(&self, _task: &Task, _prompt: &str, cancel: &CancellationToken) -> Result<ExecutionOutput> {
+            let started = Instant::now();
+            while started.elapsed() < self.duration {
+                if cancel.is_cancelled() {
+                    return Err(crate::executor::ExecutorError::Cancelled.into());
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Ok("done".to_string().into())
+        }
+    }
+
+    // Multi-threaded so the cancel lands while the executor blocks
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cancel_while_running_stands() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let id = queue.enqueue("Slow task", None).await.unwrap();
+        let canceller = tokio::spawn({
+            let (queue, id) = (queue.clone(), id.clone());
+            async move {
+                while queue.get(&id).await.unwrap().unwrap().status != TaskStatus::Running {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                queue.update_status(&id, TaskStatus::Cancelled).await.unwrap();
+            }
+        });
+
+        let mut daemon = Daemon::new(queue.clone(), test_config())
+            .with_executor(SlowExecutor { duration: Duration::from_millis(300) });
+        // Processed, not treated as a queue error and reconnected
+        assert!(daemon.tick().await.unwrap());
+        canceller.await.unwrap();
+
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Cancelled);
+        assert_eq!(task.output.as_deref(), Some("done"));
+        let attempts = queue.attempts(&id).await.unwrap();
+        assert_eq!(attempts[0].status, TaskStatus::Cancelled);
+        assert!(attempts[0].completed_at.is_some());
+    }
+
+    /// Run one slow task, sending `interrupts` Ctrl+Cs 50ms apart once it's started
+    async fn interrupt_slow_task(run_for: Duration, grace: Duration, interrupts: usize) -> (TaskQueue, String, Shutdown) {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let id = queue.enqueue("Slow task", None).await.unwrap();
+
+        let shutdown = Shutdown::default();
+        let signal = Arc::new(tokio::sync::Notify::new());
+        let watcher = tokio::spawn({
+            let (shutdown, signal) = (shutdown.clone(), signal.clone());
+            async move {
+                let interrupt = || {
+                    let signal = signal.clone();
+                    async move { signal.notified().await }
+                };
+                escalate_shutdown(&shutdown, grace, interrupt).await
+            }
+        });
+        tokio::spawn({
+            let signal = signal.clone();
+            async move {
+                for _ in 0..interrupts {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    signal.notify_one();
+                }
+            }
+        });
+
+        let mut daemon = Daemon::new(queue.clone(), test_config())
+            .with_executor(SlowExecutor { duration: run_for })
+            .with_shutdown(shutdown.clone());
+        tokio::time::timeout(Duration::from_secs(10), daemon.run()).await.unwrap().unwrap();
+        watcher.abort();
+        (queue, id, shutdown)
+    }
+
+    // Multi-threaded so the watcher runs while the executor blocks
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shutdown_lets_running_task_finish_within_grace() {
+        let (queue, id, shutdown) = interrupt_slow_task(Duration::from_millis(300), Duration::from_secs(30), 1).await;
+
+        assert!(shutdown.is_requested());
+        assert!(!shutdown.is_forced());
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Completed);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shutdown_kills_and_requeues_after_grace() {
+        let started = Instant::now();
+        let (queue, id, shutdown) = interrupt_slow_task(Duration::from_secs(30), Duration::from_millis(100), 1).await;
+
+        assert!(shutdown.is_forced());
+        assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Queued);
+        // Back in the queue as if never claimed
+        assert_eq!((task.error, task.started_at, task.completed_at, task.processed_by), (None, None, None, None));
+        let attempts = queue.attempts(&id).await.unwrap();
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].status, TaskStatus::Cancelled);
+        assert_eq!(attempts[0].error.as_deref(), Some("Killed by daemon shutdown"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_second_interrupt_kills_without_waiting_for_grace() {
+        let started = Instant::now();
+        let (queue, id, shutdown) = interrupt_slow_task(Duration::from_secs(30), Duration::from_secs(30), 2).await;
+
+        assert!(shutdown.is_forced());
+        assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Queued);
+    }
+
+    #[tokio::test]
+    async fn test_run_once_ignores_tasks_queued_mid_drain() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("queue.db").to_string_lossy().into_owned();
+        let queue = TaskQueue::new(&db_path).await.unwrap();
+        let present = [
+            queue.enqueue("Task 1", None).await.unwrap(),
+            queue.enqueue("Task 2", None).await.unwrap(),
+        ];
+
+        let mut daemon = Daemon::new(queue.clone(), test_config())
+            .with_executor(EnqueuingExecutor { db_path: db_path.clone() });
+        let summary = daemon.run_once().await.unwrap();
+        assert_eq!(summary, DrainSummary { processed: 2, failed: 0 });
+        assert_eq!(daemon.session().completed, 2);
+
+        let tasks = queue.list().await.unwrap();
+        assert_eq!(tasks.len(), 4);
+        for task in &tasks {
+            let expected = if present.contains(&task.id) { TaskStatus::Completed } else { TaskStatus::Queued };
+            assert_eq!(task.status, expected, "{}", task.command);
+        }
+
+        // The next pass picks up what arrived during this one
+        let summary = Daemon::new(queue, test_config()).with_executor(MockExecutor).run_once().await.unwrap();
+        assert_eq!(summary, DrainSummary { processed: 2, failed: 0 });
+    }
+
+    #[tokio::test]
+    async fn test_run_once_counts_failures_and_leaves_future_tasks() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        queue.enqueue("Task ok", None).await.unwrap();
+        queue.enqueue("Task fail", None).await.unwrap();
+        let later = queue
+            .build("Task later")
+            .schedule(chrono::Utc::now() + chrono::Duration::hours(1))
+            .enqueue()
+            .await
+            .unwrap();
+
+        let mut daemon = Daemon::new(queue.clone(), test_config()).with_executor(MockExecutor);
+        assert_eq!(daemon.run_once().await.unwrap(), DrainSummary { processed: 2, failed: 1 });
+        assert_eq!(queue.get(&later).await.unwrap().unwrap().status, TaskStatus::Queued);
+        assert_eq!(daemon.run_once().await.unwrap(), DrainSummary::default());
+    }
+
+    #[tokio::test]
+    async fn test_replay_runs_original_prompt() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = dir.path().join("notes.md");
+        std::fs::write(&context, "before").unwrap();
+
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let id = queue.enqueue("Summarize", None).await.unwrap();
+        queue.add_context_files(&id, std::slice::from_ref(&context)).await.unwrap();
+        process_once(&queue, &test_config(), &MockExecutor).await.unwrap();
+
+        // The context changed since, but a replay sends what was sent then
+        std::fs::write(&context, "after").unwrap();
+        let copy = queue.replay(&id).await.unwrap();
+        process_once(&queue, &test_config(), &MockExecutor).await.unwrap();
+        let (original, copy) = (queue.get(&id).await.unwrap().unwrap(), queue.get(&copy).await.unwrap().unwrap());
+        assert_eq!(copy.status, TaskStatus::Completed);
+        assert_eq!(copy.output, original.output);
+        assert!(copy.output.unwrap().contains("before"));
+    }
+
+    #[tokio::test]
+    async fn test_task_timeout_overrides_global() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let own = queue.build("Long migration").timeout(Duration::from_secs(300)).enqueue().await.unwrap();
+        queue.enqueue("Quick lint", None).await.unwrap();
+        let zero = queue.enqueue("Zero means global", None).await.unwrap();
+        queue.set_timeout(&zero, Some(Duration::ZERO)).await.unwrap();
+        assert_eq!(queue.get(&own).await.unwrap().unwrap().timeout_ms, Some(300_000));
+
+        let mut config = test_config();
+        config.claude.timeout_secs = 60;
+        let executor = TimeoutExecutor::default();
+        drain(&queue, &config, &executor, None).await.unwrap();
+        assert_eq!(*executor.seen.lock().unwrap(), vec![Some(300_000), Some(60_000), Some(60_000)]);
+
+        // Without a global limit, only the task's own applies
+        config.claude.timeout_secs = 0;
+        queue.enqueue("Unlimited", None).await.unwrap();
+        process_once(&queue, &config, &executor).await.unwrap();
+        assert_eq!(executor.seen.lock().unwrap().last(), Some(&None));
+    }
+
+    #[test]
+    fn test_pid_alive() {
+        assert!(pid_alive(std::process::id()));
+        assert!(!pid_alive(u32::MAX / 2));
+    }
+
+    #[tokio::test]
+    async fn test_second_daemon_refused() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let first = Daemon::new(queue.clone(), test_config());
+        let second = Daemon::new(queue.clone(), test_config());
+
+        // A lock held by another live process blocks startup
+        queue.acquire_daemon_lock(1, false, |_| true).await.unwrap();
+        assert!(second.lock(false).await.is_err());
+        queue.release_daemon_lock(1).await.unwrap();
+
+        first.lock(false).await.unwrap();
+        first.unlock().await.unwrap();
+        assert!(queue.daemon_lock().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tick_processes_task() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let id = queue.enqueue("Run tests", None).await.unwrap();
+
+        let heartbeat = Heartbeat::default();
+        assert_eq!(heartbeat.age(), None);
+
+        let mut daemon = Daemon::new(queue.clone(), test_config()).with_heartbeat(heartbeat.clone());
+        assert!(daemon.tick().await.unwrap());
+        assert!(!daemon.tick().await.unwrap());
+        assert!(heartbeat.age().unwrap() < Duration::from_secs(5));
+
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert_eq!(task.rendered_prompt.as_deref(), Some("Run tests"));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_beats_while_task_runs() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        queue.enqueue("Slow task", None).await.unwrap();
+
+        // The run blocks this thread, so the health check samples from another
+        let heartbeat = Heartbeat::default();
+        let sampler = std::thread::spawn({
+            let heartbeat = heartbeat.clone();
+            move || {
+                std::thread::sleep(BEAT_INTERVAL * 3 / 2);
+                heartbeat.age()
+            }
+        });
+
+        let mut daemon = Daemon::new(queue, test_config())
+            .with_executor(SlowExecutor { duration: BEAT_INTERVAL * 2 })
+            .with_heartbeat(heartbeat);
+        assert!(daemon.tick().await.unwrap());
+
+        assert!(sampler.join().unwrap().unwrap() < BEAT_INTERVAL);
+    }
+
+    #[test]
+    fn test_heartbeat_stops_beating_past_the_run_limit() {
+        let heartbeat = Heartbeat::default();
+        heartbeat.beat();
+
+        heartbeat.beating_while(Some(Duration::ZERO), || std::thread::sleep(BEAT_INTERVAL * 3 / 2));
+        assert!(heartbeat.age().unwrap() >= BEAT_INTERVAL);
+
+        heartbeat.beating_while(None, || std::thread::sleep(BEAT_INTERVAL * 3 / 2));
+        assert!(heartbeat.age().unwrap() < BEAT_INTERVAL);
+    }
+
+    fn task(command: &str) -> Task {
+        Task {
+            id: "task0001".to_string(),
+            command: command.to_string(),
+            status: TaskStatus::Queued,
+            model: None,
+            created_at: chrono::Utc::now(),
+            started_at: None,
+            completed_at: None,
+            error: None,
+            rendered_prompt: None,
+            intent: None,
+            confidence: None,
+            output: None,
+            original_command: None,
+            parent_id: None,
+            session_id: None,
+            priority: 0,
+            tags: Vec::new(),
+            cwd: None,
+            scheduled_for: None,
+            processed_by: None,
+            output_lossy: false,
+            depends_on: None,
+            output_path: None,
+            duration_ms: None,
+            input_tokens: None,
+            output_tokens: None,
+            env_snapshot: None,
+            timeout_ms: None,
+            replayed_from: None,
+            metadata: Default::default(),
+            prompt_prefix: None,
+            prompt_suffix: None,
+        }
+    }
+
+    #[test]
+    fn test_build_prompt_without_context() {
+        assert_eq!(build_prompt(&task("Run tests"), &[], 100).unwrap(), "Run tests");
+    }
+
+    #[test]
+    fn test_build_prompt_with_context_files() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.rs");
+        let b = dir.path().join("b.rs");
+        std::fs::write(&a, "fn a() {}\n").unwrap();
+        std::fs::write(&b, "fn b() {}").unwrap();
+
+        let prompt = build_prompt(&task("Explain"), std::slice::from_ref(&a), 1000).unwrap();
+        assert_eq!(
+            prompt,
+            format!("Explain\n\nContext file: {}\n```\nfn a() {{}}\n```", a.display())
+        );
+
+        let prompt = build_prompt(&task("Explain"), &[a.clone(), b.clone()], 1000).unwrap();
+        let a_pos = prompt.find("fn a() {}").unwrap();
+        let b_pos = prompt.find("fn b() {}").unwrap();
+        assert!(a_pos < b_pos);
+        assert!(prompt.contains(&format!("Context file: {}", b.display())));
+    }
+
+    #[test]
+    fn test_build_prompt_truncates_to_budget() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        std::fs::write(&a, "0123456789").unwrap();
+        std::fs::write(&b, "abcdefghij").unwrap();
+
+        // First file fits whole, second gets the remaining 5 bytes
+        let prompt = build_prompt(&task("Explain"), &[a, b], 15).unwrap();
+        assert!(prompt.contains("0123456789"));
+        assert!(prompt.contains("abcde\n…[truncated 5 bytes]"));
+        assert!(!prompt.contains("abcdef"));
+    }
+
+    #[test]
+    fn test_prefix_and_suffix_wrap_directive_and_context() {
+        let dir = tempdir().unwrap();
+        let notes = dir.path().join("notes.md");
+        std::fs::write(&notes, "use tabs").unwrap();
+        let claude = ClaudeConfig {
+            prompt_prefix: "You are working in the faster repo.\n".to_string(),
+            prompt_suffix: "Always run `cargo fmt` after editing.".to_string(),
+            ..ClaudeConfig::default()
+        };
+
+        let built = build_prompt(&task("Fix the parser"), std::slice::from_ref(&notes), 1000).unwrap();
+        let prompt = wrap_prompt(&task("Fix the parser"), &claude, &built);
+        assert_eq!(
+            prompt,
+            format!(
+                "You are working in the faster repo.\n\nFix the parser\n\nContext file: {}\n```\nuse tabs\n```\n\n\
+                 Always run `cargo fmt` after editing.",
+                notes.display()
+            )
+        );
+
+        // Unset config leaves the prompt alone
+        assert_eq!(wrap_prompt(&task("Fix"), &ClaudeConfig::default(), "Fix"), "Fix");
+    }
+
+    #[test]
+    fn test_task_prompt_overrides_replace_config() {
+        let claude = ClaudeConfig {
+            prompt_prefix: "Global prefix".to_string(),
+            prompt_suffix: "Global suffix".to_string(),
+            ..ClaudeConfig::default()
+        };
+        let mut task = task("Deploy");
+        task.prompt_prefix = Some("Staging only".to_string());
+        task.prompt_suffix = Some(String::new());
+
+        assert_eq!(wrap_prompt(&task, &claude, "Deploy"), "Staging only\n\nDeploy");
+    }
+
+    #[tokio::test]
+    async fn test_builder_overrides_reach_the_rendered_prompt() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let id = queue.build("Deploy").prompt_prefix("Staging only").enqueue().await.unwrap();
+        let mut config = test_config();
+        config.claude.prompt_prefix = "Global prefix".to_string();
+        config.claude.prompt_suffix = "Global suffix".to_string();
+
+        process_once(&queue, &config, &MockExecutor).await.unwrap();
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.prompt_prefix.as_deref(), Some("Staging only"));
+        assert_eq!(task.rendered_prompt.as_deref(), Some("Staging only\n\nDeploy\n\nGlobal suffix"));
+    }
+
+    #[test]
+    fn test_build_prompt_missing_file_errors() {
+        let err = build_prompt(&task("Explain"), &["/nonexistent/file.rs".into()], 100).unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/file.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_context_file_fails_task() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let id = queue.enqueue("Explain", None).await.unwrap();
+        queue.add_context_files(&id, &["/nonexistent/file.rs".into()]).await.unwrap();
+
+        let executor = ClaudeExecutor::new("true");
+        assert!(process_once(&queue, &test_config(), &executor).await.unwrap());
+
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Failed);
+        assert!(task.error.unwrap().contains("Context file not found"));
+    }
+
+    /// Daemon over a file-backed queue, plus a separate handle like the CLI's
+    #[tokio::test]
+    async fn test_statement_error_does_not_reconnect() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut daemon, queue) = file_daemon(&dir).await;
+        let path = dir.path().join("queue.db");
+        let pool = sqlx::SqlitePool::connect(&format!("sqlite://{}", path.display())).await.unwrap();
+        sqlx::query("CREATE TRIGGER reject_output BEFORE UPDATE OF output ON tasks BEGIN SELECT RAISE(ABORT, 'rejected'); END")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        let own = queue.enqueue("Own", None).await.unwrap();
+        queue.claim_next_as(&daemon_name(&test_config())).await.unwrap().unwrap();
+        let id = queue.enqueue("Task ok", None).await.unwrap();
+
+        assert!(!daemon.tick().await.unwrap());
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Completed);
+
+        // A reconnect would have requeued the task this daemon has running
+        assert_eq!(queue.get(&own).await.unwrap().unwrap().status, TaskStatus::Running);
+    }
+
+    async fn file_daemon(dir: &tempfile::TempDir) -> (Daemon, TaskQueue) {
+        let path = dir.path().join("queue.db").to_string_lossy().to_string();
+        let daemon_queue = TaskQueue::new(&path).await.unwrap();
+        let daemon = Daemon::new(daemon_queue, test_config()).with_executor(MockExecutor);
+        (daemon, TaskQueue::new(&path).await.unwrap())
+    }
+
+    #[test]
+    fn test_format_session_summary() {
+        let stats = SessionStats { processed: 5, completed: 4, failed: 1 };
+        assert_eq!(
+            format_session_summary(&stats, Duration::from_secs(3725), Some(3)),
+            "Daemon ran 1h 02m 05s: 5 processed (4 completed, 1 failed), 3 still queued"
+        );
+        assert_eq!(
+            format_session_summary(&SessionStats::default(), Duration::from_millis(42_900), None),
+            "Daemon ran 42s: 0 processed (0 completed, 0 failed)"
+        );
+        assert_eq!(format_runtime(Duration::from_secs(185)), "3m 05s");
+    }
+
+    #[tokio::test]
+    async fn test_session_counts_ticks() {
+        let dir = tempdir().unwrap();
+        let (mut daemon, cli) = file_daemon(&dir).await;
+        cli.enqueue("Run the tests", None).await.unwrap();
+        cli.enqueue("Make it fail", None).await.unwrap();
+        cli.enqueue("Later", None).await.unwrap();
+
+        assert!(daemon.tick().await.unwrap());
+        assert!(daemon.tick().await.unwrap());
+        assert_eq!(daemon.session(), SessionStats { processed: 2, completed: 1, failed: 1 });
+        assert!(daemon.session_summary().await.ends_with("2 processed (1 completed, 1 failed), 1 still queued"));
+    }
+
+    #[tokio::test]
+    async fn test_end_to_end_task_completes() {
+        let dir = tempdir().unwrap();
+        let (mut daemon, cli) = file_daemon(&dir).await;
+        let id = cli.enqueue("Run the tests", None).await.unwrap();
+
+        assert!(daemon.tick().await.unwrap());
+
+        let task = cli.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert!(task.started_at.is_some());
+        assert!(task.completed_at.is_some());
+        assert_eq!(task.error, None);
+        assert_eq!(task.rendered_prompt.as_deref(), Some("Run the tests"));
+        assert_eq!(task.output.as_deref(), Some("ran Run the tests"));
+    }
+
+    #[tokio::test]
+    async fn test_end_to_end_task_failure_records_error() {
+        let dir = tempdir().unwrap();
+        let (mut daemon, cli) = file_daemon(&dir).await;
+        let failing = cli.enqueue("Task fail", None).await.unwrap();
+        let next = cli.enqueue("Task ok", None).await.unwrap();
+
+        assert!(daemon.tick().await.unwrap());
+        let task = cli.get(&failing).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Failed);
+        assert_eq!(task.error.as_deref(), Some("mock failure"));
+        assert!(task.completed_at.is_some());
+
+        // A failure doesn't stop the daemon
+        assert!(daemon.tick().await.unwrap());
+        assert_eq!(cli.get(&next).await.unwrap().unwrap().status, TaskStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_end_to_end_empty_queue() {
+        let dir = tempdir().unwrap();
+        let (mut daemon, cli) = file_daemon(&dir).await;
+
+        assert!(!daemon.tick().await.unwrap());
+        assert!(!process_once(&cli, &test_config(), &MockExecutor).await.unwrap());
+
+        // Drafts aren't work for the daemon either
+        let draft = crate::intent::Command::new(crate::intent::Intent::Code, "Maybe later", vec![], 0.1);
+        cli.enqueue_draft(&draft, None).await.unwrap();
+        assert!(!daemon.tick().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_recovers_from_deleted_database() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("queue.db");
+        let path_str = path.to_string_lossy().to_string();
+
+        let queue = TaskQueue::new(&path_str).await.unwrap();
+        queue.enqueue("Before", None).await.unwrap();
+
+        let mut daemon = Daemon::new(queue, test_config()).with_policy(fast_policy());
+        assert!(daemon.tick().await.unwrap());
+
+        // Pull the file out from under the daemon
+        std::fs::remove_file(&path).unwrap();
+        assert!(!daemon.tick().await.unwrap());
+        assert!(path.exists());
+
+        // Work queued against the recreated file is picked up
+        let fresh = TaskQueue::new(&path_str).await.unwrap();
+        let id = fresh.enqueue("After", None).await.unwrap();
+        assert!(daemon.tick().await.unwrap());
+
+        let task = fresh.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+
+        // Reconnecting requeues this daemon's running tasks and anyone's abandoned ones
+        let clock = crate::clock::MockClock::new(chrono::Utc::now() - chrono::Duration::hours(7));
+        let old = TaskQueue::new(&path_str).await.unwrap().with_clock(clock);
+        let own = fresh.enqueue("Own", None).await.unwrap();
+        fresh.claim_next_as(&daemon_name(&test_config())).await.unwrap().unwrap();
+        let abandoned = old.enqueue("Abandoned", None).await.unwrap();
+        old.claim_next_as("other-host:1").await.unwrap().unwrap();
+        let live = fresh.enqueue("Live", None).await.unwrap();
+        fresh.claim_next_as("other-host:2").await.unwrap().unwrap();
+
+        daemon.reconnect().await.unwrap();
+
+        for id in [&own, &abandoned] {
+            let task = fresh.get(id).await.unwrap().unwrap();
+            assert_eq!(task.status, TaskStatus::Queued);
+            assert_eq!((task.started_at, task.processed_by), (None, None));
+        }
+        assert_eq!(fresh.get(&live).await.unwrap().unwrap().status, TaskStatus::Running);
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_stale_only_own_when_age_check_disabled() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let old = queue.clone().with_clock(crate::clock::MockClock::new(chrono::Utc::now() - chrono::Duration::days(30)));
+        let mut config = test_config();
+        config.daemon.stale_after_secs = 0;
+
+        let own = queue.enqueue("Own", None).await.unwrap();
+        queue.claim_next_as(&daemon_name(&config)).await.unwrap().unwrap();
+        let other = old.enqueue("Other", None).await.unwrap();
+        old.claim_next_as("other-host:1").await.unwrap().unwrap();
+
+        let daemon = Daemon::new(queue.clone(), config);
+        assert_eq!(daemon.reclaim_stale().await.unwrap(), 1);
+        assert_eq!(queue.get(&own).await.unwrap().unwrap().status, TaskStatus::Queued);
+        assert_eq!(queue.get(&other).await.unwrap().unwrap().status, TaskStatus::Running);
+    }
+
+    /// Succeeds with output that had invalid UTF-8 replaced
+    struct LossyExecutor;
+
+    impl Executor for LossyExecutor {
+        fn execute_task(&self, _task: &Task, _prompt: &str) -> Result<ExecutionOutput> {
+            Ok(ExecutionOutput { lossy: true, ..ExecutionOutput::from("caf\u{FFFD}".to_string()) })
+        }
+    }
+
+    /// Runs, prints and reports tokens, then exits non-zero
+    struct ExitCodeExecutor;
+
+    impl Executor for ExitCodeExecutor {
+        fn execute_task(&self, _task: &Task, _prompt: &str) -> Result<ExecutionOutput> {
+            Ok(ExecutionOutput {
+                stderr: "error: tests failed\n".to_string(),
+                exit_code: Some(2),
+                duration: Duration::from_millis(1500),
+                tokens: Some(crate::executor::TokenUsage { input: 120, output: 45 }),
+                ..ExecutionOutput::from("3 passed, 1 failed\n".to_string())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_stats_kept_when_claude_exits_non_zero() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let failed = queue.enqueue("Run the tests", None).await.unwrap();
+        process_once(&queue, &test_config(), &ExitCodeExecutor).await.unwrap();
+
+        let task = queue.get(&failed).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Failed);
+        assert_eq!(task.error.as_deref(), Some("Claude CLI exited with status 2: error: tests failed"));
+        assert_eq!(task.output.as_deref(), Some("3 passed, 1 failed\n"));
+        assert_eq!(task.duration_ms, Some(1500));
+        assert_eq!((task.input_tokens, task.output_tokens), (Some(120), Some(45)));
+
+        let done = queue.enqueue("Build", None).await.unwrap();
+        process_once(&queue, &test_config(), &MockExecutor).await.unwrap();
+        let task = queue.get(&done).await.unwrap().unwrap();
+        assert_eq!(task.duration_ms, Some(0));
+        assert_eq!(task.input_tokens, None);
+    }
+
+    #[tokio::test]
+    async fn test_lossy_output_is_flagged() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let lossy = queue.enqueue("Lossy", None).await.unwrap();
+        process_once(&queue, &test_config(), &LossyExecutor).await.unwrap();
+        let clean = queue.enqueue("Clean", None).await.unwrap();
+        process_once(&queue, &test_config(), &MockExecutor).await.unwrap();
+
+        let task = queue.get(&lossy).await.unwrap().unwrap();
+        assert_eq!(task.output.as_deref(), Some("caf\u{FFFD}"));
+        assert!(task.output_lossy);
+        assert!(!queue.get(&clean).await.unwrap().unwrap().output_lossy);
+    }
+
+    #[tokio::test]
+    async fn test_end_to_end_records_daemon_name() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("queue.db").to_string_lossy().to_string();
+        let mut config = test_config();
+        config.daemon.name = Some("worker-1".to_string());
+        let mut daemon = Daemon::new(TaskQueue::new(&path).await.unwrap(), config).with_executor(MockExecutor);
+        let cli = TaskQueue::new(&path).await.unwrap();
+        let id = cli.enqueue("Run the tests", None).await.unwrap();
+
+        assert!(daemon.tick().await.unwrap());
+        assert_eq!(cli.get(&id).await.unwrap().unwrap().processed_by.as_deref(), Some("worker-1"));
+    }
+
+    #[test]
+    fn test_daemon_name_defaults_to_host_and_pid() {
+        let name = daemon_name(&Config::default());
+        let (host, pid) = name.rsplit_once(':').unwrap();
+        assert!(!host.is_empty());
+        assert_eq!(pid, std::process::id().to_string());
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_gives_up_after_retries() {
+        let dir = tempdir().unwrap();
+        let blocker = dir.path().join("not-a-dir");
+        let path = blocker.join("queue.db");
+
+        let queue = TaskQueue::new(&path.to_string_lossy()).await.unwrap();
+        let mut daemon = Daemon::new(queue, test_config()).with_policy(fast_policy());
+
+        // Replace the parent directory with a file so it can't be recreated
+        std::fs::remove_dir_all(&blocker).unwrap();
+        std::fs::write(&blocker, "").unwrap();
+
+        let err = daemon.tick().await.unwrap_err();
+        assert!(err.to_string().contains("after 3 attempts"));
+    }
+}
@@ -0,0 +1,253 @@
+//! Installation diagnostics for `faster doctor`
+//!
+//! Each check returns a `Diagnostic` saying what's wrong and how to fix it.
+//! Checks take their inputs as arguments so failures can be simulated in tests.
+
+use std::path::Path;
+
+use crate::audio::MacOSTTS;
+use crate::config::Config;
+use crate::daemon::pid_alive;
+use crate::executor::ClaudeExecutor;
+use crate::intent::processor::similarity;
+use crate::queue::{DaemonLock, TaskQueue};
+
+/// Result of one check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Heading the check is printed under
+    pub group: &'static str,
+    pub ok: bool,
+    /// A failed critical check makes `doctor` exit non-zero
+    pub critical: bool,
+    pub message: String,
+    /// What to do about a failure
+    pub fix_hint: Option<String>,
+}
+
+impl Diagnostic {
+    fn pass(group: &'static str, message: impl Into<String>) -> Self {
+        Self { group, ok: true, critical: false, message: message.into(), fix_hint: None }
+    }
+
+    fn fail(group: &'static str, message: impl Into<String>, fix_hint: impl Into<String>) -> Self {
+        Self { group, ok: false, critical: true, message: message.into(), fix_hint: Some(fix_hint.into()) }
+    }
+
+    /// A failure that doesn't stop faster from working
+    fn warn(group: &'static str, message: impl Into<String>, fix_hint: impl Into<String>) -> Self {
+        Self { critical: false, ..Self::fail(group, message, fix_hint) }
+    }
+
+    /// Whether this is a failed critical check
+    pub fn is_critical_failure(&self) -> bool {
+        !self.ok && self.critical
+    }
+}
+
+/// Run every check against this machine, in display order
+pub async fn run_all(config: &Config) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![check_claude_cli(&config.claude.cli_path)];
+
+    if config.tts.provider == "macos-native" {
+        diagnostics.push(match MacOSTTS::list_voices() {
+            Ok(voices) if !voices.is_empty() => check_tts_voice(&config.tts.voice, &voices),
+            _ => Diagnostic::warn(
+                "Audio",
+                "Could not list TTS voices (is `say` installed?)",
+                "On machines without `say`, set tts.provider = \"none\"",
+            ),
+        });
+    }
+
+    let db = check_db_dir(&config.knowledge.local_db);
+    let db_ok = db.ok;
+    diagnostics.push(db);
+    diagnostics.extend(check_config(config));
+
+    // Opening the queue creates the database, so only try where that can work
+    if db_ok {
+        let lock = match TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await {
+            Ok(queue) => queue.daemon_lock().await,
+            Err(e) => Err(e),
+        };
+        diagnostics.push(match lock {
+            Ok(lock) => check_daemon_lock(lock.as_ref(), pid_alive),
+            Err(e) => Diagnostic::fail(
+                "Storage",
+                format!("Cannot open queue database: {}", e),
+                format!("Move or delete {} if it is corrupt", config.knowledge.local_db.display()),
+            ),
+        });
+    }
+
+    diagnostics
+}
+
+/// The Claude CLI at `cli_path` runs
+pub fn check_claude_cli(cli_path: &str) -> Diagnostic {
+    match ClaudeExecutor::new(cli_path).version() {
+        Ok(version) if version.is_empty() => Diagnostic::pass("Claude", format!("Claude CLI runs ({})", cli_path)),
+        Ok(version) => Diagnostic::pass("Claude", format!("Claude CLI runs ({}, {})", cli_path, version)),
+        Err(_) => Diagnostic::fail(
+            "Claude",
+            format!("Claude CLI not found at '{}'", cli_path),
+            "Install it from https://claude.ai/code, or set claude.cli_path",
+        ),
+    }
+}
+
+/// The configured voice is one `say` knows, suggesting the closest if not
+pub fn check_tts_voice(voice: &str, available: &[String]) -> Diagnostic {
+    if available.iter().any(|v| v.eq_ignore_ascii_case(voice)) {
+        return Diagnostic::pass("Audio", format!("TTS voice '{}' is installed", voice));
+    }
+
+    let nearest = available
+        .iter()
+        .map(|v| (similarity(&v.to_lowercase(), &voice.to_lowercase()), v))
+        .max_by(|a, b| a.0.total_cmp(&b.0));
+    let hint = match nearest {
+        Some((_, name)) => format!("Did you mean '{}'? Set tts.voice (see `say -v ?`)", name),
+        None => "Set tts.voice to one listed by `say -v ?`".to_string(),
+    };
+    Diagnostic::warn("Audio", format!("TTS voice '{}' is not installed", voice), hint)
+}
+
+/// The database's directory exists (or can be created) and is writable
+pub fn check_db_dir(db_path: &Path) -> Diagnostic {
+    let dir = match db_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        return Diagnostic::fail(
+            "Storage",
+            format!("Cannot create database directory {}: {}", dir.display(), e),
+            format!("Create it with `mkdir -p {}`, or set knowledge.local_db", dir.display()),
+        );
+    }
+
+    let probe = dir.join(format!(".faster-doctor-{}", std::process::id()));
+    let written = std::fs::write(&probe, b"");
+    let _ = std::fs::remove_file(&probe);
+    match written {
+        Ok(()) => Diagnostic::pass("Storage", format!("Database directory {} is writable", dir.display())),
+        Err(e) => Diagnostic::fail(
+            "Storage",
+            format!("Database directory {} is not writable: {}", dir.display(), e),
+            format!("Run `chmod u+w {}`", dir.display()),
+        ),
+    }
+}
+
+/// Config field values are usable (see `Config::validate` and `Config::warnings`)
+pub fn check_config(config: &Config) -> Vec<Diagnostic> {
+    let problems = config.validate();
+    let warnings = config.warnings();
+    if problems.is_empty() && warnings.is_empty() {
+        return vec![Diagnostic::pass("Config", "Config values are valid")];
+    }
+
+    let hint = "Fix it with `faster config --edit`";
+    problems
+        .into_iter()
+        .map(|problem| Diagnostic::fail("Config", problem, hint))
+        .chain(warnings.into_iter().map(|warning| Diagnostic::warn("Config", warning, hint)))
+        .collect()
+}
+
+/// The daemon lock, if held, belongs to a live process
+pub fn check_daemon_lock(lock: Option<&DaemonLock>, is_alive: impl Fn(u32) -> bool) -> Diagnostic {
+    match lock {
+        None => Diagnostic::pass("Daemon", "No daemon running"),
+        Some(lock) if is_alive(lock.pid) => {
+            Diagnostic::pass("Daemon", format!("Daemon running (pid {})", lock.pid))
+        }
+        Some(lock) => Diagnostic::warn(
+            "Daemon",
+            format!("Stale daemon lock from pid {} (not running)", lock.pid),
+            "Starting `faster daemon` reclaims it",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_missing_claude_cli() {
+        let diagnostic = check_claude_cli("/nonexistent/claude");
+        assert!(diagnostic.is_critical_failure());
+        assert!(diagnostic.fix_hint.unwrap().contains("https://claude.ai/code"));
+
+        assert!(check_claude_cli("true").ok);
+    }
+
+    #[test]
+    fn test_tts_voice_suggests_nearest() {
+        let voices = vec!["Alex".to_string(), "Samantha".to_string(), "Victoria".to_string()];
+        assert!(check_tts_voice("samantha", &voices).ok);
+
+        let diagnostic = check_tts_voice("Samanta", &voices);
+        assert!(!diagnostic.ok);
+        assert!(!diagnostic.critical);
+        assert!(diagnostic.fix_hint.unwrap().contains("'Samantha'"));
+    }
+
+    #[test]
+    fn test_unwritable_db_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(check_db_dir(&dir.path().join("new/queue.db")).ok);
+
+        // A file where the directory should be
+        let blocker = dir.path().join("file");
+        std::fs::write(&blocker, "").unwrap();
+        let diagnostic = check_db_dir(&blocker.join("queue.db"));
+        assert!(diagnostic.is_critical_failure());
+        assert!(diagnostic.fix_hint.unwrap().contains("mkdir -p"));
+    }
+
+    #[test]
+    fn test_invalid_config_fields() {
+        assert!(check_config(&Config::default()).iter().all(|d| d.ok));
+
+        let mut config = Config::default();
+        config.intent.ensemble_size = 0;
+        config.stt.provider = "whisper".to_string();
+        let diagnostics = check_config(&config);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(Diagnostic::is_critical_failure));
+        assert!(diagnostics[0].message.contains("ensemble_size"));
+    }
+
+    #[test]
+    fn test_stt_language_flagged() {
+        let mut config = Config::default();
+        config.stt.language = "en_US_x".to_string();
+        let diagnostics = check_config(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_critical_failure());
+        assert!(diagnostics[0].message.contains("stt.language"));
+
+        // Well-formed but unrecognized only warns
+        config.stt.language = "xx-YY".to_string();
+        let diagnostics = check_config(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(!diagnostics[0].ok && !diagnostics[0].critical);
+    }
+
+    #[test]
+    fn test_stale_daemon_lock() {
+        let lock = DaemonLock { pid: 4242, started_at: Utc::now() };
+        assert!(check_daemon_lock(None, |_| false).ok);
+        assert!(check_daemon_lock(Some(&lock), |_| true).ok);
+
+        let diagnostic = check_daemon_lock(Some(&lock), |_| false);
+        assert!(!diagnostic.ok);
+        assert!(diagnostic.message.contains("4242"));
+    }
+}
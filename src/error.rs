@@ -0,0 +1,119 @@
+//! The error type returned by faster's public library APIs
+//!
+//! `TaskQueue`, `BlockingTaskQueue`, `ClaudeExecutor` and `Config` return
+//! `FasterError`, so embedders can match on what went wrong. Extension
+//! points (`TaskStore`, `Executor`) stay on `anyhow` so implementations can
+//! fail however they like; those errors surface as `FasterError::Other`
+//! unless they wrap one of faster's own error types.
+
+use thiserror::Error;
+
+use crate::config::ConfigError;
+use crate::executor::ExecutorError;
+use crate::queue::QueueError;
+
+pub type Result<T, E = FasterError> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum FasterError {
+    /// No task with this id (or id prefix)
+    #[error("Task not found: {id}")]
+    NotFound { id: String },
+
+    /// The queue refused the operation; see `QueueError`
+    #[error(transparent)]
+    Queue(QueueError),
+
+    /// The config file is missing or invalid
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    /// Claude couldn't be run, or its run was stopped
+    #[error(transparent)]
+    Executor(#[from] ExecutorError),
+
+    /// The queue database failed
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// Input that should have been JSON, such as a spec file, wasn't
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// Anything else, usually from a custom `TaskStore` or `Executor`
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl FasterError {
+    /// The queue error behind this one, counting `NotFound`
+    pub fn queue_error(&self) -> Option<QueueError> {
+        match self {
+            FasterError::NotFound { id } => Some(QueueError::NotFound { id: id.clone() }),
+            FasterError::Queue(e) => Some(e.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl From<QueueError> for FasterError {
+    fn from(e: QueueError) -> Self {
+        match e {
+            QueueError::NotFound { id } => FasterError::NotFound { id },
+            e => FasterError::Queue(e),
+        }
+    }
+}
+
+/// Recover the typed error from an `anyhow` chain where there is one
+impl From<anyhow::Error> for FasterError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<FasterError>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<QueueError>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<ConfigError>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<ExecutorError>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        match e.downcast::<sqlx::Error>() {
+            Ok(e) => e.into(),
+            Err(e) => FasterError::Other(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_errors_survive_anyhow() {
+        let err = FasterError::from(anyhow::Error::from(QueueError::QueueFull { limit: 3 }));
+        assert!(matches!(err, FasterError::Queue(QueueError::QueueFull { limit: 3 })));
+
+        let err = FasterError::from(anyhow::Error::from(QueueError::NotFound { id: "abc".to_string() }));
+        assert!(matches!(&err, FasterError::NotFound { id } if id == "abc"));
+        assert_eq!(err.to_string(), "Task not found: abc");
+        assert_eq!(err.queue_error(), Some(QueueError::NotFound { id: "abc".to_string() }));
+
+        // Wrapping twice doesn't nest
+        let err = FasterError::from(anyhow::Error::from(FasterError::from(ConfigError::NoHome)));
+        assert!(matches!(err, FasterError::Config(ConfigError::NoHome)));
+
+        let err = FasterError::from(anyhow::anyhow!("custom store failure"));
+        assert!(matches!(err, FasterError::Other(_)));
+        assert_eq!(err.to_string(), "custom store failure");
+    }
+}
@@ -1,13 +1,207 @@
 //! Claude Code CLI integration
 
 use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use colored::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use futures::FutureExt;
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+use super::{ExecutionOutput, Executor, TokenUsage};
+use crate::config::ClaudeConfig;
+use crate::error::FasterError;
+use crate::queue::Task;
+use crate::text::decode_output;
+
+/// Executor errors callers may want to handle specifically
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ExecutorError {
+    /// The configured Claude CLI can't be run
+    #[error("Claude CLI not available at '{cli_path}' (install from https://claude.ai/code)")]
+    Unavailable { cli_path: String },
+
+    /// The run's cancellation token fired, and Claude was killed
+    #[error("Claude run was cancelled")]
+    Cancelled,
+
+    /// The run took longer than its timeout, and Claude was killed
+    #[error("Claude run timed out after {}s", .after.as_secs_f64())]
+    TimedOut { after: Duration },
+}
+
+/// Why a watched run was killed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stopped {
+    Cancelled,
+    TimedOut,
+}
+
+/// Kills a run's process group if its token is cancelled, or its timeout
+/// passes, before the run ends
+struct CancelWatcher {
+    done: futures::channel::oneshot::Sender<()>,
+    thread: std::thread::JoinHandle<bool>,
+    /// Dropped to stop the timeout thread early
+    stop_timer: Option<mpsc::Sender<()>>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl CancelWatcher {
+    fn spawn(token: CancellationToken, pid: u32, timeout: Option<Duration>) -> Self {
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let stop_timer = timeout.map(|timeout| {
+            let (stop, stopped) = mpsc::channel::<()>();
+            let (token, timed_out) = (token.clone(), timed_out.clone());
+            std::thread::spawn(move || {
+                if let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(timeout) {
+                    timed_out.store(true, Ordering::SeqCst);
+                    token.cancel();
+                }
+            });
+            stop
+        });
+
+        let (done, finished) = futures::channel::oneshot::channel();
+        let thread = std::thread::spawn(move || {
+            let cancelled = futures::executor::block_on(async {
+                futures::select! {
+                    _ = Box::pin(token.cancelled()).fuse() => true,
+                    _ = finished.fuse() => false,
+                }
+            });
+            if cancelled {
+                kill_process_group(pid);
+            }
+            cancelled
+        });
+        Self { done, thread, stop_timer, timed_out }
+    }
+
+    /// Stop watching, returning why the run was killed, if it was
+    fn finish(self) -> Option<Stopped> {
+        drop(self.stop_timer);
+        let _ = self.done.send(());
+        let killed = self.thread.join().unwrap_or(false);
+        match (killed, self.timed_out.load(Ordering::SeqCst)) {
+            (false, _) => None,
+            (true, true) => Some(Stopped::TimedOut),
+            (true, false) => Some(Stopped::Cancelled),
+        }
+    }
+}
+
+/// SIGKILL every process in group `pgid`, so nothing Claude started outlives it
+fn kill_process_group(pgid: u32) {
+    let _ = Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", pgid)])
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Longest single argument Linux will pass to a program (MAX_ARG_STRLEN)
+const MAX_ARG_LEN: usize = 128 * 1024;
+
+/// Conservative budget for argv plus environment (macOS ARG_MAX is 1 MiB)
+const MAX_ARGV_BYTES: usize = 1024 * 1024;
+
+/// Warn about argv sizes above a platform limit, or within 10% of one
+///
+/// Spawning past these fails with `E2BIG` ("Argument list too long").
+pub fn argv_limit_warning(args: &[String]) -> Option<String> {
+    let longest = args.iter().map(String::len).max().unwrap_or(0);
+    let env: usize = std::env::vars_os().map(|(k, v)| k.len() + v.len() + 2).sum();
+    let total = args.iter().map(|a| a.len() + 1).sum::<usize>() + env;
+
+    if cfg!(target_os = "linux") && longest >= MAX_ARG_LEN / 10 * 9 {
+        Some(format!("Prompt argument is {} bytes, near Linux's {} byte per-argument limit", longest, MAX_ARG_LEN))
+    } else if total >= MAX_ARGV_BYTES / 10 * 9 {
+        Some(format!("Arguments and environment total {} bytes, near the {} byte limit", total, MAX_ARGV_BYTES))
+    } else {
+        None
+    }
+}
+
+/// A Claude CLI release, as reported by `claude --version`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ClaudeVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ClaudeVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major, minor, patch }
+    }
+
+    /// The first `x.y.z` in output like `1.0.3 (Claude Code)`
+    pub fn parse(output: &str) -> Option<Self> {
+        output.split_whitespace().find_map(|word| {
+            let word = word.trim_start_matches('v');
+            let mut parts = word.split(['.', '-', '+']).map(|part| part.parse::<u32>());
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch))) => Some(Self::new(major, minor, patch)),
+                _ => None,
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for ClaudeVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Optional CLI flags faster passes to Claude
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClaudeFeatures {
+    pub resume: bool,
+    pub output_format_json: bool,
+}
+
+impl ClaudeFeatures {
+    /// What a CLI that doesn't identify itself gets: none of the optional flags
+    pub const CONSERVATIVE: Self = Self { resume: false, output_format_json: false };
+
+    /// Every flag for a CLI that reports a version, none for one that doesn't
+    ///
+    /// No minimum versions are known for these flags, so the version number
+    /// itself isn't compared; this only holds back a `cli_path` that doesn't
+    /// identify itself as Claude Code.
+    pub fn for_version(version: Option<ClaudeVersion>) -> Self {
+        match version {
+            Some(_) => Self { resume: true, output_format_json: true },
+            None => Self::CONSERVATIVE,
+        }
+    }
+}
 
 /// Claude Code executor
+#[derive(Debug, Clone)]
 pub struct ClaudeExecutor {
     cli_path: String,
     model: Option<String>,
+    /// Claude session to resume instead of starting fresh
+    resume: Option<String>,
+    /// Directory to run in (None = the current one)
+    cwd: Option<PathBuf>,
+    /// File stdout and stderr are also written to
+    output_file: Option<PathBuf>,
+    /// Echo output to the terminal as it arrives
+    echo: bool,
+    /// Pass `--print` so Claude answers and exits instead of waiting for input
+    print: bool,
+    /// Kill the run after this long (None = no limit)
+    timeout: Option<Duration>,
+    /// `--version` output, looked up once and shared by clones
+    version: Arc<OnceLock<String>>,
 }
 
 impl ClaudeExecutor {
@@ -16,44 +210,305 @@ impl ClaudeExecutor {
         Self {
             cli_path: cli_path.into(),
             model: None,
+            resume: None,
+            cwd: None,
+            output_file: None,
+            echo: true,
+            print: false,
+            timeout: None,
+            version: Arc::default(),
         }
     }
 
+    /// Executor for `claude.cli_path`, non-interactive unless `claude.interactive`
+    pub fn from_config(config: &ClaudeConfig) -> Self {
+        Self::new(&config.cli_path).with_print(!config.interactive)
+    }
+
     /// Set Claude model
     pub fn with_model(mut self, model: impl Into<String>) -> Self {
         self.model = Some(model.into());
         self
     }
 
-    /// Execute prompt in current directory context
+    /// Resume an existing Claude session
+    pub fn with_resume(mut self, session_id: impl Into<String>) -> Self {
+        self.resume = Some(session_id.into());
+        self
+    }
+
+    /// Run Claude in `cwd` so it picks up that project's context
+    pub fn with_cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Tee stdout and stderr to `path`, replacing it; parent directories are created
+    pub fn with_output_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output_file = Some(path.into());
+        self
+    }
+
+    /// Whether output is also echoed to the terminal (default true)
+    pub fn with_echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// Run with `--print` and stdin closed, so nothing can wait on the terminal
+    pub fn with_print(mut self, print: bool) -> Self {
+        self.print = print;
+        self
+    }
+
+    /// Kill runs that take longer than `timeout`, failing with `ExecutorError::TimedOut`
+    ///
+    /// Like cancellation, this runs Claude in its own process group.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Execute prompt in current directory context, discarding what it printed
+    ///
+    /// Fails if Claude exits non-zero; use `run` for the output itself.
+    pub fn execute(&self, prompt: &str) -> Result<(), FasterError> {
+        let output = self.run(prompt)?;
+        if !output.success() {
+            return Err(FasterError::Other(anyhow::anyhow!(output.failure_message())));
+        }
+        Ok(())
+    }
+
+    /// Run a prompt and capture everything about the run
     /// Claude Code automatically picks up folder context
-    pub fn execute(&self, prompt: &str) -> Result<()> {
+    ///
+    /// Stdout and stderr are echoed to the terminal as they arrive and
+    /// returned. With an output file, both are written there too, byte for
+    /// byte; failing to create or write it fails the run. Exiting non-zero
+    /// is not an error here: see `ExecutionOutput::exit_code`.
+    pub fn run(&self, prompt: &str) -> Result<ExecutionOutput, FasterError> {
+        Ok(self.run_until(prompt, None)?)
+    }
+
+    /// Like `run`, but killed as soon as `token` is cancelled
+    ///
+    /// Claude runs in its own process group, and the whole group is killed
+    /// and reaped on cancellation, which fails with `ExecutorError::Cancelled`.
+    /// Being in its own group, it doesn't get the terminal's Ctrl+C.
+    pub async fn run_cancellable(
+        &self,
+        prompt: &str,
+        token: CancellationToken,
+    ) -> Result<ExecutionOutput, FasterError> {
+        let executor = self.clone();
+        let prompt = prompt.to_string();
+        let run = tokio::task::spawn_blocking(move || executor.run_until(&prompt, Some(token)))
+            .await
+            .context("Claude run panicked")?;
+        Ok(run?)
+    }
+
+    fn run_until(&self, prompt: &str, cancel: Option<CancellationToken>) -> Result<ExecutionOutput> {
+        let args = self.args(prompt);
+        if let Some(warning) = argv_limit_warning(&args) {
+            tracing::warn!("{}", warning);
+        }
+
+        // A timeout cancels a token of the run's own, never the caller's
+        let cancel = match (cancel, self.timeout) {
+            (Some(token), _) => Some(token.child_token()),
+            (None, Some(_)) => Some(CancellationToken::new()),
+            (None, None) => None,
+        };
+
         let mut cmd = Command::new(&self.cli_path);
+        cmd.args(args);
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+        #[cfg(unix)]
+        if cancel.is_some() {
+            std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+        }
+
+        let file = self.output_file.as_deref().map(OutputFile::create).transpose()?;
+
+        // Pipe output so it can be captured while still streaming to the terminal
+        cmd.stdin(if self.print { Stdio::null() } else { Stdio::inherit() });
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+
+        // Execute in current directory (Claude picks up context)
+        let started = Instant::now();
+        let mut child = match cmd.spawn() {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(ExecutorError::Unavailable { cli_path: self.cli_path.clone() }.into());
+            }
+            result => result.context("Failed to execute Claude CLI")?,
+        };
+        let watcher = cancel.map(|token| CancelWatcher::spawn(token, child.id(), self.timeout));
+
+        // Stderr is read on its own thread so neither pipe can fill up and stall Claude
+        let stderr = child.stderr.take().map(|stderr| {
+            let file = file.clone();
+            let echo = self.echo;
+            std::thread::spawn(move || -> Result<(String, bool)> {
+                let mut terminal = std::io::stderr();
+                let (mut captured, mut lossy) = (String::new(), false);
+                for line in BufReader::new(stderr).split(b'\n') {
+                    let line = line?;
+                    if let Some(file) = &file {
+                        file.write_line(&line)?;
+                    }
+                    let (line, line_lossy) = decode_output(&line);
+                    if echo {
+                        writeln!(terminal, "{}", line)?;
+                    }
+                    captured.push_str(&line);
+                    captured.push('\n');
+                    lossy |= line_lossy;
+                }
+                Ok((captured, lossy))
+            })
+        });
+
+        let mut output = ExecutionOutput::from(String::new());
+        let streamed = (|| -> Result<()> {
+            let Some(stdout) = child.stdout.take() else {
+                return Ok(());
+            };
+            let mut terminal = std::io::stdout();
+            for line in BufReader::new(stdout).split(b'\n') {
+                let line = line?;
+                if let Some(file) = &file {
+                    file.write_line(&line)?;
+                }
+                let (line, lossy) = decode_output(&line);
+                if self.echo {
+                    writeln!(terminal, "{}", line)?;
+                }
+                output.stdout.push_str(&line);
+                output.stdout.push('\n');
+                output.lossy |= lossy;
+            }
+            Ok(())
+        })();
+        if let Err(e) = streamed {
+            // Don't leave Claude running with nobody reading its output
+            let _ = child.kill();
+            let _ = child.wait();
+            watcher.map(CancelWatcher::finish);
+            return Err(e);
+        }
+
+        // Stopped before reaping, so a killed group can't be a reused pid
+        let stopped = watcher.and_then(CancelWatcher::finish);
+        let status = child.wait().context("Failed to wait for Claude CLI")?;
+        if let Some(stopped) = stopped {
+            if let Some(stderr) = stderr {
+                let _ = stderr.join();
+            }
+            return Err(match (stopped, self.timeout) {
+                (Stopped::TimedOut, Some(after)) => ExecutorError::TimedOut { after },
+                _ => ExecutorError::Cancelled,
+            }
+            .into());
+        }
+        output.duration = started.elapsed();
+        output.exit_code = status.code();
+        if let Some(stderr) = stderr {
+            let (stderr, lossy) = stderr.join().map_err(|_| anyhow::anyhow!("Claude stderr reader panicked"))??;
+            output.stderr = stderr;
+            output.lossy |= lossy;
+        }
+        output.tokens = extract_token_usage(&output.stdout);
+
+        Ok(output)
+    }
 
-        // Add prompt as single argument
-        cmd.arg(prompt);
+    /// Arguments passed to the CLI for a prompt
+    ///
+    /// Options come first and `--` ends them, so a prompt like `--help` is
+    /// always data. The prompt is one argument, newlines and quotes included;
+    /// no shell is involved, so nothing needs escaping.
+    fn args(&self, prompt: &str) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if self.print {
+            args.push("--print".to_string());
+            // The JSON result carries the session id and token usage
+            if self.features().output_format_json {
+                args.push("--output-format".to_string());
+                args.push("json".to_string());
+            }
+        }
 
         // Add model if specified
         if let Some(model) = &self.model {
-            cmd.arg("--model").arg(model);
+            args.push("--model".to_string());
+            args.push(model.clone());
         }
 
-        // Inherit stdio so output streams directly to terminal
-        cmd.stdin(Stdio::inherit());
-        cmd.stdout(Stdio::inherit());
-        cmd.stderr(Stdio::inherit());
+        if let Some(session_id) = &self.resume {
+            if self.features().resume {
+                args.push("--resume".to_string());
+                args.push(session_id.clone());
+            } else {
+                tracing::warn!("Claude CLI may not support --resume; starting a new session instead");
+            }
+        }
 
-        // Execute in current directory (Claude picks up context)
-        let status = cmd.status()
-            .context("Failed to execute Claude CLI")?;
+        args.push("--".to_string());
+        args.push(prompt.to_string());
+        args
+    }
+
+    /// Check that this executor's CLI can be run
+    pub fn check_available(&self) -> Result<(), FasterError> {
+        let runs = Command::new(&self.cli_path)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
 
-        if !status.success() {
-            anyhow::bail!("Claude CLI exited with non-zero status");
+        if !runs {
+            return Err(ExecutorError::Unavailable { cli_path: self.cli_path.clone() }.into());
         }
 
         Ok(())
     }
 
+    /// First line of what `claude --version` prints, run once per executor
+    pub fn version(&self) -> Result<String, FasterError> {
+        if let Some(version) = self.version.get() {
+            return Ok(version.clone());
+        }
+
+        let output = Command::new(&self.cli_path)
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|_| ExecutorError::Unavailable { cli_path: self.cli_path.clone() })?;
+        if !output.status.success() {
+            return Err(ExecutorError::Unavailable { cli_path: self.cli_path.clone() }.into());
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let version = stdout.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default().to_string();
+        Ok(self.version.get_or_init(|| version).clone())
+    }
+
+    /// Optional flags to pass this executor's CLI
+    ///
+    /// A CLI whose version can't be run or read gets the conservative set.
+    pub fn features(&self) -> ClaudeFeatures {
+        ClaudeFeatures::for_version(self.version().ok().and_then(|v| ClaudeVersion::parse(&v)))
+    }
+
     /// Check if Claude CLI is available
     pub fn is_available() -> bool {
         Command::new("claude")
@@ -66,10 +521,147 @@ impl ClaudeExecutor {
     }
 }
 
+/// Output file shared by the stdout and stderr readers
+#[derive(Clone)]
+struct OutputFile {
+    path: PathBuf,
+    file: Arc<Mutex<File>>,
+}
+
+impl OutputFile {
+    fn create(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create output directory {}", parent.display()))?;
+        }
+        let file = File::create(path).with_context(|| format!("Failed to create output file {}", path.display()))?;
+        Ok(Self { path: path.to_path_buf(), file: Arc::new(Mutex::new(file)) })
+    }
+
+    fn write_line(&self, line: &[u8]) -> Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(line)
+            .and_then(|()| file.write_all(b"\n"))
+            .with_context(|| format!("Failed to write output file {}", self.path.display()))
+    }
+}
+
+/// Find a Claude session id in JSON output lines, preferring the last one
+pub fn extract_session_id(output: &str) -> Option<String> {
+    output.lines().rev().find_map(|line| {
+        let value: serde_json::Value = serde_json::from_str(line.trim()).ok()?;
+        value.get("session_id")?.as_str().map(str::to_string)
+    })
+}
+
+/// Find token usage in JSON output lines, preferring the last report
+///
+/// Claude's JSON output carries `"usage": {"input_tokens": .., "output_tokens": ..}`.
+pub fn extract_token_usage(output: &str) -> Option<TokenUsage> {
+    output.lines().rev().find_map(|line| {
+        let value: serde_json::Value = serde_json::from_str(line.trim()).ok()?;
+        let usage = value.get("usage")?;
+        Some(TokenUsage {
+            input: usage.get("input_tokens")?.as_u64()?,
+            output: usage.get("output_tokens")?.as_u64()?,
+        })
+    })
+}
+
+impl ClaudeExecutor {
+    /// This executor with the task's model, session, cwd and timeout when it has them
+    fn for_task(&self, task: &Task) -> Result<ClaudeExecutor> {
+        let mut executor = self.clone();
+        if let Some(model) = &task.model {
+            executor = executor.with_model(model);
+        }
+        if let Some(session_id) = &task.session_id {
+            executor = executor.with_resume(session_id);
+        }
+        if let Some(cwd) = &task.cwd {
+            // It may have been removed since the task was queued
+            if !cwd.is_dir() {
+                anyhow::bail!("Working directory {} no longer exists", cwd.display());
+            }
+            executor = executor.with_cwd(cwd);
+        }
+        if let Some(path) = &task.output_path {
+            executor = executor.with_output_file(path);
+        }
+        if let Some(ms) = task.timeout_ms.filter(|&ms| ms > 0) {
+            executor = executor.with_timeout(Duration::from_millis(ms));
+        }
+        Ok(executor)
+    }
+}
+
+impl Executor for ClaudeExecutor {
+    /// Uses the task's model, session, cwd and timeout when it has them
+    fn execute_task(&self, task: &Task, prompt: &str) -> Result<ExecutionOutput> {
+        Ok(self.for_task(task)?.run(prompt)?)
+    }
+
+    /// Kills the run's process group once `cancel` fires
+    fn execute_task_until(&self, task: &Task, prompt: &str, cancel: &CancellationToken) -> Result<ExecutionOutput> {
+        self.for_task(task)?.run_until(prompt, Some(cancel.clone()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_cancel_kills_run_and_its_children() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("fake-claude");
+        let pid_file = dir.path().join("sleep.pid");
+        std::fs::write(&script, format!("#!/bin/sh\nsleep 30 &\necho $! > {}\nwait\n", pid_file.display())).unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            cancel.cancel();
+        });
+
+        let started = Instant::now();
+        let executor = ClaudeExecutor::new(script.to_string_lossy()).with_echo(false);
+        let err = executor.run_cancellable("hello", token).await.unwrap_err();
+        assert!(matches!(err, FasterError::Executor(ExecutorError::Cancelled)), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+
+        // The backgrounded sleep was in the same group, so it's gone too (or
+        // a zombie waiting for init to reap it)
+        let sleep_pid = std::fs::read_to_string(&pid_file).unwrap().trim().to_string();
+        let state = Command::new("ps").args(["-o", "stat=", "-p", &sleep_pid]).output().unwrap();
+        let state = String::from_utf8_lossy(&state.stdout);
+        assert!(state.trim().is_empty() || state.trim().starts_with('Z'), "sleep is still {}", state);
+
+        // A token that never fires leaves the run alone
+        let output = ClaudeExecutor::new("echo").run_cancellable("hi", CancellationToken::new()).await.unwrap();
+        assert_eq!(output.stdout, "-- hi\n");
+    }
+
+    #[test]
+    fn test_timeout_kills_run() {
+        let started = Instant::now();
+        let executor = ClaudeExecutor::new("sleep").with_echo(false).with_timeout(Duration::from_millis(200));
+        // `sleep -- 30`
+        let err = executor.run("30").unwrap_err();
+        assert!(matches!(
+            &err,
+            FasterError::Executor(ExecutorError::TimedOut { after }) if *after == Duration::from_millis(200)
+        ));
+        assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+        assert_eq!(err.to_string(), "Claude run timed out after 0.2s");
+
+        // Finishing in time is a normal run
+        let output = ClaudeExecutor::new("echo").with_timeout(Duration::from_secs(30)).run("hi").unwrap();
+        assert_eq!(output.stdout, "-- hi\n");
+    }
+
     #[test]
     fn test_executor_creation() {
         let executor = ClaudeExecutor::new("claude");
@@ -85,6 +677,274 @@ mod tests {
         assert_eq!(executor.model, Some("opus".to_string()));
     }
 
+    #[test]
+    fn test_missing_cli_is_unavailable() {
+        let executor = ClaudeExecutor::new("/nonexistent/claude");
+
+        let err = executor.check_available().unwrap_err();
+        assert!(matches!(
+            &err,
+            FasterError::Executor(ExecutorError::Unavailable { cli_path }) if cli_path == "/nonexistent/claude"
+        ));
+
+        let err = executor.run("hi").unwrap_err();
+        assert!(matches!(err, FasterError::Executor(ExecutorError::Unavailable { .. })));
+
+        assert!(ClaudeExecutor::new("true").check_available().is_ok());
+    }
+
+    #[test]
+    fn test_args_keep_flag_like_prompt_positional() {
+        let executor = ClaudeExecutor::new("claude").with_model("opus");
+
+        assert_eq!(
+            executor.args("--help"),
+            vec!["--model", "opus", "--", "--help"]
+        );
+        assert_eq!(ClaudeExecutor::new("claude").args("-x"), vec!["--", "-x"]);
+    }
+
+    /// A CLI that prints `version` for `--version`
+    fn fake_cli(dir: &Path, version: &str) -> String {
+        let script = dir.join("fake-claude");
+        std::fs::write(&script, format!("#!/bin/sh\necho '{}'\n", version)).unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        script.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_args_resume_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = ClaudeExecutor::new(fake_cli(dir.path(), "1.0.3 (Claude Code)")).with_resume("sess-1");
+        assert_eq!(executor.args("Next"), vec!["--resume", "sess-1", "--", "Next"]);
+
+        let args = ClaudeExecutor::new("claude").args("Next");
+        assert!(!args.contains(&"--resume".to_string()));
+    }
+
+    #[test]
+    fn test_resume_dropped_when_version_unknown() {
+        let dir = tempfile::tempdir().unwrap();
+        let unversioned = ClaudeExecutor::new(fake_cli(dir.path(), "Claude Code")).with_resume("sess-1");
+        assert_eq!(unversioned.args("Next"), vec!["--", "Next"]);
+
+        let missing = ClaudeExecutor::new("/nonexistent/claude").with_resume("sess-1");
+        assert_eq!(missing.args("Next"), vec!["--", "Next"]);
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(ClaudeVersion::parse("1.0.3 (Claude Code)"), Some(ClaudeVersion::new(1, 0, 3)));
+        assert_eq!(ClaudeVersion::parse("claude v2.0.14\n"), Some(ClaudeVersion::new(2, 0, 14)));
+        assert_eq!(ClaudeVersion::parse("0.2.125-beta.1"), Some(ClaudeVersion::new(0, 2, 125)));
+        assert_eq!(ClaudeVersion::parse("Claude Code"), None);
+        assert_eq!(ClaudeVersion::parse("1.0"), None);
+        assert_eq!(ClaudeVersion::new(1, 0, 3).to_string(), "1.0.3");
+    }
+
+    #[test]
+    fn test_features_for_version() {
+        let features = ClaudeFeatures::for_version(Some(ClaudeVersion::new(1, 0, 3)));
+        assert!(features.resume && features.output_format_json);
+        assert_eq!(ClaudeFeatures::for_version(None), ClaudeFeatures::CONSERVATIVE);
+    }
+
+    #[test]
+    fn test_version_is_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = ClaudeExecutor::new(fake_cli(dir.path(), "1.0.3 (Claude Code)"));
+        assert_eq!(executor.version().unwrap(), "1.0.3 (Claude Code)");
+
+        // Clones share the lookup, so a changed binary isn't run again
+        std::fs::remove_file(dir.path().join("fake-claude")).unwrap();
+        assert_eq!(executor.clone().version().unwrap(), "1.0.3 (Claude Code)");
+        assert!(ClaudeExecutor::new("/nonexistent/claude").version().is_err());
+    }
+
+    #[test]
+    fn test_args_print_when_not_interactive() {
+        let mut config = ClaudeConfig { cli_path: "/nonexistent/claude".to_string(), ..ClaudeConfig::default() };
+        assert_eq!(
+            ClaudeExecutor::from_config(&config).with_model("opus").args("Go"),
+            vec!["--print", "--model", "opus", "--", "Go"]
+        );
+
+        config.interactive = true;
+        assert_eq!(ClaudeExecutor::from_config(&config).args("Go"), vec!["--", "Go"]);
+    }
+
+    #[test]
+    fn test_args_output_format_json_when_supported() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = ClaudeExecutor::new(fake_cli(dir.path(), "1.0.3 (Claude Code)")).with_print(true);
+        assert_eq!(executor.args("Go"), vec!["--print", "--output-format", "json", "--", "Go"]);
+
+        // Ignored outside print mode, and not risked on an unknown CLI
+        assert_eq!(executor.clone().with_print(false).args("Go"), vec!["--", "Go"]);
+        let unknown = ClaudeExecutor::new("/nonexistent/claude").with_print(true);
+        assert_eq!(unknown.args("Go"), vec!["--print", "--", "Go"]);
+    }
+
+    #[test]
+    fn test_print_closes_stdin() {
+        // Reading stdin would block forever on an inherited terminal
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("fake-claude");
+        std::fs::write(&script, "#!/bin/sh
+read line
+echo \"$1 read [$line]\"\n").unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+        let output = ClaudeExecutor::new(script.to_string_lossy()).with_print(true).with_echo(false).run("hi").unwrap();
+        assert_eq!(output.stdout, "--print read []\n");
+    }
+
+    #[test]
+    fn test_extract_session_id() {
+        let output = "working...\n{\"type\":\"result\",\"session_id\":\"abc-123\",\"result\":\"done\"}\n";
+        assert_eq!(extract_session_id(output).as_deref(), Some("abc-123"));
+
+        assert_eq!(extract_session_id("plain text reply\n"), None);
+        assert_eq!(extract_session_id("{\"result\":\"no session\"}"), None);
+    }
+
+    #[test]
+    fn test_args_multiline_prompt_is_one_argument() {
+        let prompt = "Fix \"this\"\nthen run 'tests'\n--verbose";
+        let args = ClaudeExecutor::new("claude").args(prompt);
+
+        assert_eq!(args, vec!["--", prompt]);
+    }
+
+    #[test]
+    fn test_execute_captures_stdout() {
+        // `echo` prints its arguments, standing in for Claude's reply
+        let executor = ClaudeExecutor::new("echo").with_model("opus");
+
+        let output = executor.run("hello").unwrap();
+        assert_eq!(output.stdout, "--model opus -- hello\n");
+        assert!(!output.lossy);
+        assert!(output.success());
+    }
+
+    #[test]
+    fn test_execute_flags_invalid_utf8() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("fake-claude");
+        std::fs::write(&script, "#!/bin/sh\nprintf 'bad \\377 byte\\n'\n").unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+        let output = ClaudeExecutor::new(script.to_string_lossy()).run("hello").unwrap();
+        assert_eq!(output.stdout, "bad \u{FFFD} byte\n");
+        assert!(output.lossy);
+    }
+
+    #[test]
+    fn test_run_reports_exit_code_stderr_duration_and_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("fake-claude");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\n\
+             echo '{\"type\":\"result\",\"usage\":{\"input_tokens\":120,\"output_tokens\":45}}'\n\
+             echo 'rate limited' >&2\n\
+             sleep 0.1\n\
+             exit 3\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        let executor = ClaudeExecutor::new(script.to_string_lossy()).with_echo(false);
+
+        let output = executor.run("hello").unwrap();
+        assert!(output.stdout.contains("\"input_tokens\":120"));
+        assert_eq!(output.stderr, "rate limited\n");
+        assert_eq!(output.exit_code, Some(3));
+        assert!(!output.success());
+        assert!(output.duration >= std::time::Duration::from_millis(100));
+        assert_eq!(output.tokens, Some(TokenUsage { input: 120, output: 45 }));
+        assert_eq!(output.failure_message(), "Claude CLI exited with status 3: rate limited");
+
+        // `execute` keeps only success or failure
+        let err = executor.execute("hello").unwrap_err();
+        assert!(err.to_string().contains("status 3"));
+        assert!(ClaudeExecutor::new("true").execute("hello").is_ok());
+    }
+
+    #[test]
+    fn test_extract_token_usage() {
+        assert_eq!(extract_token_usage("plain text\n"), None);
+        assert_eq!(extract_token_usage("{\"usage\":{\"input_tokens\":5}}"), None);
+        assert_eq!(
+            extract_token_usage("{\"usage\":{\"input_tokens\":1,\"output_tokens\":2}}\nbye\n"),
+            Some(TokenUsage { input: 1, output: 2 })
+        );
+    }
+
+    #[test]
+    fn test_execute_tees_output_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("fake-claude");
+        std::fs::write(&script, "#!/bin/sh\necho \"working on $2\"\necho oops >&2\necho done\n").unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        let path = dir.path().join("logs/nested/task.log");
+
+        let output = ClaudeExecutor::new(script.to_string_lossy())
+            .with_output_file(&path)
+            .with_echo(false)
+            .run("hello")
+            .unwrap();
+        assert_eq!(output.stdout, "working on hello\ndone\n");
+        assert_eq!(output.stderr, "oops\n");
+
+        // Both streams land in the file
+        let logged = std::fs::read_to_string(&path).unwrap();
+        assert!(logged.contains("working on hello\n"));
+        assert!(logged.contains("oops\n"));
+        assert!(logged.contains("done\n"));
+    }
+
+    #[test]
+    fn test_execute_fails_when_output_file_cant_be_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let blocker = dir.path().join("not-a-dir");
+        std::fs::write(&blocker, "").unwrap();
+
+        let err = ClaudeExecutor::new("echo")
+            .with_output_file(blocker.join("task.log"))
+            .run("hello")
+            .unwrap_err();
+        assert!(err.to_string().contains("Failed to create output directory"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_task_checks_cwd_still_exists() {
+        let queue = crate::queue::TaskQueue::new(":memory:").await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let id = queue.build("Build").cwd(dir.path()).enqueue().await.unwrap();
+        let task = queue.get(&id).await.unwrap().unwrap();
+        let executor = ClaudeExecutor::new("echo");
+
+        assert!(executor.execute_task(&task, "hello").is_ok());
+
+        drop(dir);
+        let err = executor.execute_task(&task, "hello").unwrap_err();
+        assert!(err.to_string().contains("no longer exists"));
+    }
+
+    #[test]
+    fn test_argv_limit_warning() {
+        assert_eq!(argv_limit_warning(&["--".to_string(), "Run tests".to_string()]), None);
+
+        let huge = vec!["--".to_string(), "x".repeat(MAX_ARGV_BYTES)];
+        assert!(argv_limit_warning(&huge).unwrap().contains("limit"));
+
+        #[cfg(target_os = "linux")]
+        {
+            let long = vec!["--".to_string(), "x".repeat(MAX_ARG_LEN - 100)];
+            assert!(argv_limit_warning(&long).unwrap().contains("per-argument limit"));
+        }
+    }
+
     #[test]
     fn test_is_available() {
         let available = ClaudeExecutor::is_available();
@@ -4,4 +4,81 @@
 
 pub mod claude;
 
-pub use claude::ClaudeExecutor;
+pub use claude::{extract_session_id, extract_token_usage, ClaudeExecutor, ExecutorError};
+
+use anyhow::Result;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::queue::Task;
+
+/// Tokens a run used, as reported in Claude's JSON output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input: u64,
+    pub output: u64,
+}
+
+/// Everything captured from one run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// Invalid UTF-8 was replaced while decoding
+    pub lossy: bool,
+    /// Process exit code (None if it was killed by a signal)
+    pub exit_code: Option<i32>,
+    /// Wall-clock time from spawn to exit
+    pub duration: Duration,
+    /// Token counts, when the output reports them
+    pub tokens: Option<TokenUsage>,
+}
+
+impl ExecutionOutput {
+    /// Whether the process exited with status 0
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// Why a run that didn't succeed failed, with the last line of stderr
+    pub fn failure_message(&self) -> String {
+        let mut message = match self.exit_code {
+            Some(code) => format!("Claude CLI exited with status {}", code),
+            None => "Claude CLI was killed by a signal".to_string(),
+        };
+        if let Some(last) = self.stderr.lines().rev().map(str::trim).find(|l| !l.is_empty()) {
+            message.push_str(": ");
+            message.push_str(last);
+        }
+        message
+    }
+}
+
+/// A successful run that printed `stdout`
+impl From<String> for ExecutionOutput {
+    fn from(stdout: String) -> Self {
+        Self {
+            stdout,
+            stderr: String::new(),
+            lossy: false,
+            exit_code: Some(0),
+            duration: Duration::ZERO,
+            tokens: None,
+        }
+    }
+}
+
+/// Runs a task's rendered prompt
+pub trait Executor: Send + Sync {
+    /// Run `prompt` on behalf of `task`, returning what it captured
+    ///
+    /// A run that starts but exits non-zero is still `Ok`; check `success`.
+    fn execute_task(&self, task: &Task, prompt: &str) -> Result<ExecutionOutput>;
+
+    /// Like `execute_task`, but stops the run early once `cancel` fires
+    ///
+    /// The default ignores `cancel` and runs to completion.
+    fn execute_task_until(&self, task: &Task, prompt: &str, _cancel: &CancellationToken) -> Result<ExecutionOutput> {
+        self.execute_task(task, prompt)
+    }
+}
@@ -0,0 +1,91 @@
+//! User-defined entity extractors from `[intent.extractors]`
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ExtractorError {
+    #[error("Invalid regex for extractor '{name}': {source}")]
+    InvalidPattern { name: String, source: regex::Error },
+}
+
+/// Named regexes, compiled once, that add `name:match` entities
+///
+/// Patterns run against the original text, so case is preserved
+/// ("JIRA-1234", not "jira-1234").
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "BTreeMap<String, String>", into = "BTreeMap<String, String>")]
+pub struct EntityExtractors(Vec<(String, Regex)>);
+
+impl EntityExtractors {
+    /// Compile each `(name, pattern)`, failing on the first invalid regex
+    pub fn new(patterns: impl IntoIterator<Item = (String, String)>) -> Result<Self, ExtractorError> {
+        patterns
+            .into_iter()
+            .map(|(name, pattern)| match Regex::new(&pattern) {
+                Ok(regex) => Ok((name, regex)),
+                Err(source) => Err(ExtractorError::InvalidPattern { name, source }),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `name:match` for every hit of every pattern, in pattern then text order
+    pub fn extract(&self, text: &str) -> Vec<String> {
+        self.0
+            .iter()
+            .flat_map(|(name, regex)| regex.find_iter(text).map(move |m| format!("{}:{}", name, m.as_str())))
+            .collect()
+    }
+}
+
+impl TryFrom<BTreeMap<String, String>> for EntityExtractors {
+    type Error = ExtractorError;
+
+    fn try_from(patterns: BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        Self::new(patterns)
+    }
+}
+
+impl From<EntityExtractors> for BTreeMap<String, String> {
+    fn from(extractors: EntityExtractors) -> Self {
+        extractors
+            .0
+            .into_iter()
+            .map(|(name, regex)| (name, regex.as_str().to_string()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extractors() -> EntityExtractors {
+        EntityExtractors::new([
+            ("ticket".to_string(), r"\b[A-Z][A-Z0-9]+-\d+\b".to_string()),
+            ("version".to_string(), r"\bv\d+\.\d+\.\d+\b".to_string()),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_extracts_named_matches() {
+        let entities = extractors().extract("Fix JIRA-1234 and OPS-7 before tagging v2.3.1");
+        assert_eq!(entities, vec!["ticket:JIRA-1234", "ticket:OPS-7", "version:v2.3.1"]);
+
+        assert!(extractors().extract("nothing to see here").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_pattern_names_extractor() {
+        let err = EntityExtractors::new([("broken".to_string(), "([a-z".to_string())]).unwrap_err();
+        assert!(err.to_string().contains("extractor 'broken'"));
+    }
+}
@@ -0,0 +1,242 @@
+//! Session meta-commands recognized before intent classification
+//!
+//! These change how voice mode behaves ("use opus") instead of queueing a task.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Model names Claude Code accepts as aliases; full `claude-*` ids are allowed too
+pub const KNOWN_MODELS: &[&str] = &["opus", "sonnet", "haiku"];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ModelAliasError {
+    #[error("Model alias cycle: {}", chain.join(" -> "))]
+    Cycle { chain: Vec<String> },
+}
+
+/// Short names for models from `[claude.model_aliases]`, e.g. `fast = "haiku"`
+///
+/// Names are matched case-insensitively. An alias may point at another
+/// alias; cycles are rejected when the aliases are built.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "BTreeMap<String, String>", into = "BTreeMap<String, String>")]
+pub struct ModelAliases(BTreeMap<String, String>);
+
+impl ModelAliases {
+    /// Check `(alias, target)` pairs, failing on the first cycle
+    pub fn new(aliases: impl IntoIterator<Item = (String, String)>) -> Result<Self, ModelAliasError> {
+        let aliases = Self(aliases.into_iter().map(|(name, target)| (name.to_lowercase(), target)).collect());
+        for name in aliases.0.keys() {
+            aliases.chain(name)?;
+        }
+        Ok(aliases)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn is_alias(&self, name: &str) -> bool {
+        self.0.contains_key(&name.to_lowercase())
+    }
+
+    /// The model `name` stands for, following aliases of aliases; other names pass through
+    pub fn resolve(&self, name: &str) -> String {
+        match self.chain(name) {
+            Ok(chain) if chain.len() > 1 => chain.last().cloned().unwrap_or_default(),
+            _ => name.to_string(),
+        }
+    }
+
+    /// Each `(alias, model)` with the model fully resolved
+    pub fn resolved(&self) -> impl Iterator<Item = (&str, String)> {
+        self.0.keys().map(|name| (name.as_str(), self.resolve(name)))
+    }
+
+    /// `name` then every name it leads to, ending at one that isn't an alias
+    fn chain(&self, name: &str) -> Result<Vec<String>, ModelAliasError> {
+        let mut chain = vec![name.to_string()];
+        while let Some(target) = self.0.get(&chain[chain.len() - 1].to_lowercase()) {
+            let seen = chain.iter().any(|n| n.eq_ignore_ascii_case(target));
+            chain.push(target.clone());
+            if seen {
+                return Err(ModelAliasError::Cycle { chain });
+            }
+        }
+        Ok(chain)
+    }
+}
+
+impl TryFrom<BTreeMap<String, String>> for ModelAliases {
+    type Error = ModelAliasError;
+
+    fn try_from(aliases: BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        Self::new(aliases)
+    }
+}
+
+impl From<ModelAliases> for BTreeMap<String, String> {
+    fn from(aliases: ModelAliases) -> Self {
+        aliases.0
+    }
+}
+
+/// An instruction to the session rather than a task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetaCommand {
+    /// Queue later tasks with this model
+    UseModel(String),
+    /// Asked to switch to a model that isn't known
+    UnknownModel(String),
+}
+
+/// Words around the request that don't change its meaning
+const FILLERS: &[&str] = &["please", "ok", "okay", "now", "instead", "the", "a"];
+
+/// Openers of a model switch, longest first; `true` if the opener names "model" itself
+const SWITCH_PREFIXES: &[(&[&str], bool)] = &[
+    (&["switch", "model", "to"], true),
+    (&["change", "model", "to"], true),
+    (&["set", "model", "to"], true),
+    (&["switch", "to"], false),
+    (&["change", "to"], false),
+    (&["use"], false),
+];
+
+/// Recognize a model switch such as "use opus" or "switch to haiku"
+///
+/// Bare forms only match known models, so "switch to main" is still
+/// classified as a task. Forms that say "model" ("use the gpt model")
+/// report an unknown name instead.
+pub fn parse_meta(text: &str) -> Option<MetaCommand> {
+    parse_meta_with(text, &ModelAliases::default())
+}
+
+/// `parse_meta`, also accepting `aliases` ("use fast") as the model they stand for
+pub fn parse_meta_with(text: &str, aliases: &ModelAliases) -> Option<MetaCommand> {
+    let cleaned: String = text
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c.to_ascii_lowercase() } else { ' ' })
+        .collect();
+    let words: Vec<&str> = cleaned
+        .split_whitespace()
+        .map(|w| w.trim_matches('.'))
+        .filter(|w| !w.is_empty() && !FILLERS.contains(w))
+        .collect();
+
+    let (rest, mut explicit) = SWITCH_PREFIXES
+        .iter()
+        .find_map(|(prefix, explicit)| words.strip_prefix(*prefix).map(|rest| (rest, *explicit)))?;
+
+    let name = match rest {
+        [name] => *name,
+        ["model", name] | [name, "model"] => {
+            explicit = true;
+            *name
+        }
+        _ => return None,
+    };
+
+    if aliases.is_alias(name) {
+        Some(MetaCommand::UseModel(aliases.resolve(name)))
+    } else if is_known_model(name) {
+        Some(MetaCommand::UseModel(name.to_string()))
+    } else if explicit {
+        Some(MetaCommand::UnknownModel(name.to_string()))
+    } else {
+        None
+    }
+}
+
+/// Whether Claude Code would accept `name` as a model
+pub fn is_known_model(name: &str) -> bool {
+    KNOWN_MODELS.contains(&name) || (name.starts_with("claude-") && name.len() > "claude-".len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn use_model(name: &str) -> Option<MetaCommand> {
+        Some(MetaCommand::UseModel(name.to_string()))
+    }
+
+    #[test]
+    fn test_recognizes_model_switches() {
+        assert_eq!(parse_meta("use opus"), use_model("opus"));
+        assert_eq!(parse_meta("Switch to Haiku."), use_model("haiku"));
+        assert_eq!(parse_meta("okay, switch to sonnet please"), use_model("sonnet"));
+        assert_eq!(parse_meta("use the opus model"), use_model("opus"));
+        assert_eq!(parse_meta("change model to haiku"), use_model("haiku"));
+        assert_eq!(parse_meta("use model claude-opus-4-1"), use_model("claude-opus-4-1"));
+    }
+
+    #[test]
+    fn test_rejects_unknown_model_names() {
+        assert_eq!(parse_meta("use the gpt model"), Some(MetaCommand::UnknownModel("gpt".to_string())));
+        assert_eq!(parse_meta("switch model to banana"), Some(MetaCommand::UnknownModel("banana".to_string())));
+        assert_eq!(parse_meta("use model claude-"), Some(MetaCommand::UnknownModel("claude-".to_string())));
+    }
+
+    fn aliases() -> ModelAliases {
+        ModelAliases::new([
+            ("fast".to_string(), "haiku".to_string()),
+            ("Smart".to_string(), "opus".to_string()),
+            ("quick".to_string(), "fast".to_string()),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolves_aliases() {
+        let aliases = aliases();
+        assert_eq!(aliases.resolve("fast"), "haiku");
+        assert_eq!(aliases.resolve("SMART"), "opus");
+        assert_eq!(aliases.resolve("sonnet"), "sonnet");
+        assert_eq!(aliases.resolve("claude-opus-4-1"), "claude-opus-4-1");
+        assert!(aliases.is_alias("smart") && !aliases.is_alias("opus"));
+    }
+
+    #[test]
+    fn test_resolves_aliases_transitively() {
+        let aliases = aliases();
+        assert_eq!(aliases.resolve("quick"), "haiku");
+        assert_eq!(
+            aliases.resolved().collect::<Vec<_>>(),
+            vec![("fast", "haiku".to_string()), ("quick", "haiku".to_string()), ("smart", "opus".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_rejects_alias_cycles() {
+        let err = ModelAliases::new([
+            ("a".to_string(), "b".to_string()),
+            ("b".to_string(), "c".to_string()),
+            ("c".to_string(), "A".to_string()),
+        ])
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Model alias cycle: a -> b -> c -> A");
+
+        let err = ModelAliases::new([("self".to_string(), "self".to_string())]).unwrap_err();
+        assert_eq!(err, ModelAliasError::Cycle { chain: vec!["self".to_string(), "self".to_string()] });
+    }
+
+    #[test]
+    fn test_recognizes_alias_switches() {
+        let aliases = aliases();
+        assert_eq!(parse_meta_with("use fast", &aliases), use_model("haiku"));
+        assert_eq!(parse_meta_with("switch to smart", &aliases), use_model("opus"));
+        assert_eq!(parse_meta_with("use the quick model", &aliases), use_model("haiku"));
+        assert_eq!(parse_meta_with("use opus", &aliases), use_model("opus"));
+        assert_eq!(parse_meta("use fast"), None);
+    }
+
+    #[test]
+    fn test_ordinary_commands_are_not_meta() {
+        assert_eq!(parse_meta("switch to main"), None);
+        assert_eq!(parse_meta("use ripgrep to find the config loader"), None);
+        assert_eq!(parse_meta("run the tests with opus"), None);
+        assert_eq!(parse_meta(""), None);
+    }
+}
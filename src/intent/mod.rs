@@ -4,9 +4,15 @@
 
 pub mod schema;
 pub mod processor;
+pub mod meta;
+pub mod extractors;
+pub mod templates;
 
 pub use schema::{Command, Intent, IntentExtractionResult, AmbiguityResolution};
-pub use processor::IntentProcessor;
+pub use processor::{IntentProcessor, DEFAULT_FILLERS};
+pub use meta::{parse_meta, parse_meta_with, MetaCommand, ModelAliasError, ModelAliases};
+pub use extractors::{EntityExtractors, ExtractorError};
+pub use templates::{DirectiveTemplates, TemplateError};
 
 // TODO: Implement ensemble module
 // pub mod ensemble;
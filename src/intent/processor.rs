@@ -1,78 +1,379 @@
 //! Intent processor - translates messy human speech into deterministic Commands
 
 use anyhow::Result;
-use super::schema::{Command, Intent};
-use std::collections::HashMap;
+use super::extractors::EntityExtractors;
+use super::schema::{AmbiguityResolution, Command, Intent, IntentExtractionResult};
+use std::collections::{HashMap, HashSet};
 use chrono::Utc;
+use std::time::Instant;
+
+/// Keyword patterns per intent, in classification priority order
+const INTENT_KEYWORDS: &[(Intent, &[&str])] = &[
+    (Intent::Orchestrate, &["run", "execute", "start", "launch", "deploy", "build"]),
+    (Intent::Research, &["find", "search", "look", "where", "what", "show", "list"]),
+    (Intent::Test, &["test", "debug", "fix", "check", "verify"]),
+    (Intent::Code, &["create", "add", "write", "update", "refactor", "implement", "generate"]),
+];
+
+/// Leading verbs of imperative commands, per intent
+const LEADING_VERBS: &[(Intent, &[&str])] = &[
+    (Intent::Orchestrate, &[
+        "run", "execute", "start", "launch", "deploy", "build", "ship", "restart",
+        "stop", "install", "publish", "release",
+    ]),
+    (Intent::Research, &[
+        "find", "search", "look", "show", "list", "explain", "describe", "summarize",
+        "tell", "locate",
+    ]),
+    (Intent::Test, &[
+        "test", "debug", "fix", "check", "verify", "diagnose", "investigate", "reproduce",
+    ]),
+    (Intent::Code, &[
+        "make", "create", "add", "write", "update", "refactor", "implement", "generate",
+        "rename", "move", "change", "convert", "extract", "remove", "delete", "replace",
+        "rewrite", "clean", "optimize", "document",
+    ]),
+];
+
+/// Words that open a question
+const QUESTION_WORDS: &[&str] = &["where", "what", "which", "who", "why", "how"];
+
+/// Words that start questions and commands alike ("do the migration", "when
+/// it fails, retry"); a question only with a subject after them or a `?` at the end
+const AMBIGUOUS_QUESTION_WORDS: &[&str] = &["when", "is", "are", "does", "do", "did"];
+
+/// Subjects that mark an ambiguous question word as asking ("do we", "is it")
+const QUESTION_SUBJECTS: &[&str] = &["i", "you", "we", "they", "it", "he", "she", "this", "that", "these", "those", "there"];
+
+/// Politeness and filler skipped before looking for the leading verb
+const LEADING_FILLERS: &[&str] = &[
+    "um", "uh", "please", "just", "can you", "could you", "would you", "will you",
+    "i want you to", "i need you to", "go ahead and", "lets", "now",
+];
+
+/// Filler removed from directives unless `intent.fillers` says otherwise
+pub const DEFAULT_FILLERS: &[&str] = &[
+    "um", "uh", "like", "you know", "actually", "basically",
+    "just", "please", "can you", "could you", "i want", "i need",
+];
+
+/// Words that end a segment when STT leaves out punctuation
+const SEGMENT_MARKERS: &[&str] = &["so", "okay", "ok", "alright", "anyway"];
+
+/// Words that start a later step of a compound command
+const SEQUENCE_WORDS: &[&str] = &["then", "afterwards", "afterward"];
+
+/// Minimum similarity (0.0-1.0) for a transcript to count as a spoken alias
+const ALIAS_SIMILARITY: f32 = 0.8;
 
 pub struct IntentProcessor {
     // TODO: Add Llama model for advanced processing
     confidence_threshold: f32,
+    /// Spoken shortcuts as (normalized phrase, expansion)
+    aliases: Vec<(String, String)>,
+    /// User-defined patterns adding `name:match` entities
+    extractors: EntityExtractors,
+    /// Filler phrases removed from directives, as lowercase words
+    fillers: Vec<Vec<String>>,
+    /// Remove fillers at all; off passes the text through verbatim
+    clean_directives: bool,
+    /// Commands scored below this are too unclear to act on (0.0 = never)
+    clarify_below: f32,
 }
 
 impl IntentProcessor {
     pub fn new(confidence_threshold: f32) -> Self {
         Self {
             confidence_threshold,
+            aliases: Vec::new(),
+            extractors: EntityExtractors::default(),
+            fillers: Vec::new(),
+            clean_directives: true,
+            clarify_below: 0.0,
         }
+        .with_fillers(DEFAULT_FILLERS.iter().map(|f| f.to_string()))
+    }
+
+    /// Remove these phrases from directives instead of `DEFAULT_FILLERS`
+    pub fn with_fillers(mut self, fillers: impl IntoIterator<Item = String>) -> Self {
+        self.fillers = fillers
+            .into_iter()
+            .map(|filler| filler.split_whitespace().map(str::to_lowercase).collect::<Vec<_>>())
+            .filter(|words| !words.is_empty())
+            .collect();
+        // Longest first, so "you know" goes before a lone "you" could
+        self.fillers.sort_by_key(|words| std::cmp::Reverse(words.len()));
+        self
+    }
+
+    /// Whether to remove fillers from directives at all
+    pub fn with_directive_cleaning(mut self, clean: bool) -> Self {
+        self.clean_directives = clean;
+        self
+    }
+
+    /// Treat commands scored below `threshold` as unclassifiable, see `needs_clarification`
+    ///
+    /// A transcript with no intent keyword or leading verb scores 0.60, so a
+    /// threshold just above that catches only those.
+    pub fn with_clarify_threshold(mut self, threshold: f32) -> Self {
+        self.clarify_below = threshold;
+        self
+    }
+
+    /// Also emit `name:match` entities for these patterns
+    pub fn with_extractors(mut self, extractors: EntityExtractors) -> Self {
+        self.extractors = extractors;
+        self
+    }
+
+    /// Expand transcripts matching one of these phrases before classifying
+    pub fn with_aliases(mut self, aliases: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.aliases = aliases
+            .into_iter()
+            .map(|(phrase, expansion)| (normalize_phrase(&phrase), expansion))
+            .collect();
+        self
+    }
+
+    /// Find the alias a transcript was meant to be, if any
+    ///
+    /// Matching ignores case and punctuation, and tolerates small STT slips
+    /// ("shipit" for "ship it"). The closest alias above the similarity
+    /// threshold wins.
+    pub fn expand_alias(&self, transcript: &str) -> Option<AmbiguityResolution> {
+        let spoken = normalize_phrase(transcript);
+
+        let (phrase, expansion, score) = self
+            .aliases
+            .iter()
+            .map(|(phrase, expansion)| (phrase, expansion, similarity(&spoken, phrase)))
+            .filter(|(_, _, score)| *score >= ALIAS_SIMILARITY)
+            .max_by(|a, b| a.2.total_cmp(&b.2))?;
+
+        Some(AmbiguityResolution::new(
+            transcript,
+            expansion.clone(),
+            format!("alias \"{}\"", phrase),
+            score,
+        ))
     }
 
     /// Process raw transcript into deterministic Command
     /// Uses pattern matching + keyword detection (MVP)
     /// TODO: Enhance with Llama 3.2 for complex cases
     pub fn process(&self, transcript: &str) -> Result<Command> {
-        let transcript_lower = transcript.to_lowercase();
+        Ok(self.extract(transcript)?.command)
+    }
+
+    /// Process a transcript that may hold several commands
+    ///
+    /// "run the tests and then deploy" gives two commands, the second marked
+    /// `after_previous`. Steps split on "then", "after that" and "afterwards"
+    /// (ordered), or on ";" and "and" before a command verb (unordered). If
+    /// any step isn't actionable, or the transcript is an alias, it is one
+    /// command, as `process` would return.
+    pub fn process_multi(&self, transcript: &str) -> Result<Vec<Command>> {
+        let steps = split_steps(transcript);
+
+        if steps.len() < 2
+            || self.expand_alias(transcript).is_some()
+            || !steps.iter().all(|(step, _)| self.is_actionable(step))
+        {
+            return Ok(vec![self.process(transcript)?]);
+        }
+
+        steps
+            .iter()
+            .map(|(step, after_previous)| {
+                let mut command = self.process(step)?;
+                command.after_previous = *after_previous;
+                Ok(command)
+            })
+            .collect()
+    }
+
+    /// Process transcript, keeping details of how the command was found
+    ///
+    /// Rambling transcripts are split into sentence-like segments and the last
+    /// actionable one becomes the command; the rest are recorded as discarded.
+    pub fn extract(&self, transcript: &str) -> Result<IntentExtractionResult> {
+        let started = Instant::now();
+
+        let alias = self.expand_alias(transcript);
+        let text = alias.as_ref().map_or(transcript, |a| a.to_entity.as_str());
+        let segments = self.segment(text);
+
+        let actionable = if segments.len() > 1 {
+            segments.iter().rposition(|s| self.is_actionable(s))
+        } else {
+            None
+        };
+
+        let (command, discarded_segments) = match actionable {
+            Some(index) => {
+                let discarded = segments
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != index)
+                    .map(|(_, s)| s.clone())
+                    .collect();
+                (self.process_segment(&segments[index], text), discarded)
+            }
+            None => (self.process_segment(text, text), Vec::new()),
+        };
+
+        Ok(IntentExtractionResult {
+            command,
+            transcript: transcript.to_string(),
+            processing_time_ms: started.elapsed().as_millis() as u64,
+            ambiguities_resolved: alias.into_iter().collect(),
+            discarded_segments,
+        })
+    }
+
+    /// Split a transcript into sentence-like segments
+    ///
+    /// STT output often lacks punctuation, so discourse markers ("so", "okay",
+    /// "alright", "anyway") also end a segment.
+    pub fn segment(&self, transcript: &str) -> Vec<String> {
+        let mut segments = Vec::new();
+        let mut current: Vec<&str> = Vec::new();
+
+        let mut flush = |current: &mut Vec<&str>| {
+            let text = current.join(" ");
+            let text = text.trim_end_matches(['.', '?', '!', ';', ',']).trim();
+            if !text.is_empty() {
+                segments.push(text.to_string());
+            }
+            current.clear();
+        };
+
+        for word in transcript.split_whitespace() {
+            let bare = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+
+            if SEGMENT_MARKERS.contains(&bare.as_str()) {
+                flush(&mut current);
+                continue;
+            }
+
+            current.push(word);
+            if word.ends_with(['.', '?', '!', ';']) {
+                flush(&mut current);
+            }
+        }
+        flush(&mut current);
 
+        segments
+    }
+
+    /// Whether a segment contains any intent keyword
+    fn is_actionable(&self, segment: &str) -> bool {
+        let text = segment.to_lowercase();
+        INTENT_KEYWORDS
+            .iter()
+            .any(|(_, keywords)| self.contains_any(&text, keywords))
+    }
+
+    /// Build a command from the chosen segment, with context from the whole transcript
+    fn process_segment(&self, segment: &str, transcript: &str) -> Command {
         // Detect intent
-        let (intent, confidence) = self.detect_intent(&transcript_lower);
+        let (intent, confidence) = self.classify_scores(segment)[0];
 
         // Extract entities
-        let entities = self.extract_entities(&transcript_lower, &intent);
+        let entities = self.extract_entities(segment, &intent);
 
         // Clean directive
-        let directive = self.clean_directive(transcript, &intent);
+        let directive = self.clean_directive(segment, &intent);
 
         // Build context
-        let context = self.build_context(&transcript_lower);
+        let context = self.build_context(&transcript.to_lowercase());
 
-        Ok(Command {
+        Command {
             intent,
             directive,
             entities,
             context,
             confidence,
             created_at: Utc::now(),
-        })
+            after_previous: false,
+        }
     }
 
-    /// Detect intent from transcript using pattern matching
-    fn detect_intent(&self, text: &str) -> (Intent, f32) {
-        // Orchestrate patterns
-        if self.contains_any(text, &["run", "execute", "start", "launch", "deploy", "build"]) {
-            return (Intent::Orchestrate, 0.85);
-        }
+    /// Process transcript with a caller-chosen intent, skipping classification
+    pub fn process_with_intent(&self, transcript: &str, intent: Intent) -> Result<Command> {
+        let transcript_lower = transcript.to_lowercase();
 
-        // Research patterns
-        if self.contains_any(text, &["find", "search", "look", "where", "what", "show", "list"]) {
-            return (Intent::Research, 0.85);
-        }
+        Ok(Command {
+            intent,
+            directive: self.clean_directive(transcript, &intent),
+            entities: self.extract_entities(transcript, &intent),
+            context: self.build_context(&transcript_lower),
+            confidence: 1.0,
+            created_at: Utc::now(),
+            after_previous: false,
+        })
+    }
 
-        // Test patterns
-        if self.contains_any(text, &["test", "debug", "fix", "check", "verify"]) {
-            return (Intent::Test, 0.85);
-        }
+    /// Score every intent for a transcript, ranked highest first
+    ///
+    /// The top entry is what `process` picks. Remaining probability mass is
+    /// spread over the other intents by keyword hits, so scores sum to 1.0.
+    pub fn classify_scores(&self, transcript: &str) -> Vec<(Intent, f32)> {
+        let text = transcript.to_lowercase();
+
+        let hits: Vec<(Intent, usize)> = INTENT_KEYWORDS
+            .iter()
+            .map(|(intent, keywords)| {
+                (*intent, keywords.iter().filter(|k| text.contains(*k)).count())
+            })
+            .collect();
+
+        // First intent with any keyword hit (priority order)
+        let keyword = hits.iter().find(|(_, count)| *count > 0).map(|(intent, _)| *intent);
+
+        // Sentence shape decides when it disagrees with keywords, since the
+        // leading verb is what was actually asked for
+        let (winner, winner_score) = match (structural_intent(transcript), keyword) {
+            (Some(shape), Some(keyword)) if shape == keyword => (shape, 0.95),
+            (Some(shape), Some(_)) => (shape, 0.75),
+            (Some(shape), None) => (shape, 0.80),
+            (None, Some(keyword)) => (keyword, 0.85),
+            (None, None) => (Intent::Code, 0.60),
+        };
+
+        let others: Vec<(Intent, f32)> = hits
+            .iter()
+            .filter(|(intent, _)| *intent != winner)
+            .map(|(intent, count)| (*intent, 1.0 + *count as f32))
+            .collect();
+        let total_weight: f32 = others.iter().map(|(_, w)| w).sum();
+
+        let mut scores = vec![(winner, winner_score)];
+        scores.extend(
+            others
+                .into_iter()
+                .map(|(intent, w)| (intent, (1.0 - winner_score) * w / total_weight)),
+        );
+
+        // Stable sort keeps priority order for equal scores
+        scores[1..].sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores
+    }
 
-        // Code patterns
-        if self.contains_any(text, &["create", "add", "write", "update", "refactor", "implement", "generate"]) {
-            return (Intent::Code, 0.85);
-        }
+    /// Check whether a command clears this processor's confidence threshold
+    pub fn is_confident(&self, command: &Command) -> bool {
+        command.is_confident(self.confidence_threshold)
+    }
 
-        // Default to Code with lower confidence
-        (Intent::Code, 0.60)
+    /// Whether a command is a guess worth asking the user to rephrase rather than queueing
+    pub fn needs_clarification(&self, command: &Command) -> bool {
+        command.confidence < self.clarify_below
     }
 
-    /// Extract key entities from transcript
-    fn extract_entities(&self, text: &str, intent: &Intent) -> Vec<String> {
+    /// Extract key entities from transcript, each once in first-seen order
+    fn extract_entities(&self, transcript: &str, intent: &Intent) -> Vec<String> {
+        let text = &transcript.to_lowercase();
         let mut entities = Vec::new();
 
         // Extract file patterns
@@ -114,25 +415,45 @@ impl IntentProcessor {
             }
         }
 
+        entities.extend(self.extractors.extract(transcript));
+
+        // A word can match several patterns ("class helper.rs" is a file and a class)
+        let mut seen = HashSet::new();
+        entities.retain(|entity| seen.insert(entity.clone()));
         entities
     }
 
     /// Clean directive by removing filler words
+    ///
+    /// Fillers only match whole words, ignoring case and surrounding
+    /// punctuation, so "like" leaves "unlike" and "just" leaves "adjust".
     fn clean_directive(&self, text: &str, _intent: &Intent) -> String {
-        let filler_words = [
-            "um", "uh", "like", "you know", "actually", "basically",
-            "just", "please", "can you", "could you", "i want", "i need"
-        ];
-
-        let mut cleaned = text.to_string();
-        for filler in &filler_words {
-            cleaned = cleaned.replace(filler, "");
+        if !self.clean_directives {
+            return text.to_string();
         }
 
-        // Clean extra whitespace
-        cleaned.split_whitespace()
-            .collect::<Vec<_>>()
-            .join(" ")
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let bare: Vec<String> = words
+            .iter()
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'').to_lowercase())
+            .collect();
+
+        let mut kept = Vec::with_capacity(words.len());
+        let mut i = 0;
+        while i < words.len() {
+            let filler = self
+                .fillers
+                .iter()
+                .find(|filler| bare[i..].starts_with(filler));
+            match filler {
+                Some(filler) => i += filler.len(),
+                None => {
+                    kept.push(words[i]);
+                    i += 1;
+                }
+            }
+        }
+        kept.join(" ")
     }
 
     /// Build context map from transcript
@@ -160,10 +481,185 @@ impl IntentProcessor {
     }
 }
 
+/// Split a transcript into steps, each with whether it follows the one before
+fn split_steps(transcript: &str) -> Vec<(String, bool)> {
+    let words: Vec<&str> = transcript.split_whitespace().collect();
+    let bare = |i: usize| {
+        words
+            .get(i)
+            .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+            .unwrap_or_default()
+    };
+    let is_verb = |word: &str| LEADING_VERBS.iter().any(|(_, verbs)| verbs.contains(&word));
+
+    let mut steps = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut after_previous = false;
+
+    let mut flush = |current: &mut Vec<&str>, after_previous: bool| {
+        let text = current.join(" ");
+        let text = text.trim_end_matches([',', ';']).trim();
+        if !text.is_empty() {
+            steps.push((text.to_string(), after_previous));
+        }
+        current.clear();
+    };
+
+    let mut i = 0;
+    while i < words.len() {
+        let word = bare(i);
+        // "after that" and "and then" are treated like "then"
+        let sequence_len = if SEQUENCE_WORDS.contains(&word.as_str()) {
+            1
+        } else if word == "after" && bare(i + 1) == "that" {
+            2
+        } else {
+            0
+        };
+        let and_sequence = word == "and"
+            && (SEQUENCE_WORDS.contains(&bare(i + 1).as_str()) || (bare(i + 1) == "after" && bare(i + 2) == "that"));
+
+        if sequence_len > 0 && !current.is_empty() {
+            flush(&mut current, after_previous);
+            after_previous = true;
+            i += sequence_len;
+        } else if and_sequence {
+            i += 1;
+        } else if word == "and" && !current.is_empty() && is_verb(&bare(i + 1)) {
+            flush(&mut current, after_previous);
+            after_previous = false;
+            i += 1;
+        } else {
+            current.push(words[i]);
+            if words[i].ends_with(';') {
+                flush(&mut current, after_previous);
+                after_previous = false;
+            }
+            i += 1;
+        }
+    }
+    flush(&mut current, after_previous);
+
+    steps
+}
+
+/// Classify by sentence structure: questions are Research, imperatives go by their verb
+///
+/// Returns None when the transcript neither asks a question nor opens with a
+/// known verb.
+fn structural_intent(transcript: &str) -> Option<Intent> {
+    let normalized = normalize_phrase(transcript);
+    let words: Vec<&str> = normalized.split(' ').collect();
+    let mut rest = words.as_slice();
+
+    // Peel polite openers ("can you", "please just") off the front
+    while let Some(filler) = LEADING_FILLERS.iter().find(|filler| {
+        let filler: Vec<&str> = filler.split(' ').collect();
+        rest.len() > filler.len() && rest.starts_with(&filler)
+    }) {
+        rest = &rest[filler.split(' ').count()..];
+    }
+
+    let first = *rest.first()?;
+    let asked = transcript.trim_end().ends_with('?')
+        || rest.get(1).is_some_and(|next| QUESTION_SUBJECTS.contains(next) || AMBIGUOUS_QUESTION_WORDS.contains(next));
+    if QUESTION_WORDS.contains(&first) || (AMBIGUOUS_QUESTION_WORDS.contains(&first) && asked) {
+        return Some(Intent::Research);
+    }
+
+    LEADING_VERBS
+        .iter()
+        .find(|(_, verbs)| verbs.contains(&first))
+        .map(|(intent, _)| *intent)
+}
+
+/// Lowercase, drop punctuation and collapse whitespace for alias matching
+fn normalize_phrase(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Edit-distance similarity between two strings, 1.0 for identical
+pub(crate) fn similarity(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    // Levenshtein distance, keeping one row of the table
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    1.0 - row[b.len()] as f32 / longest as f32
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_intent_examples_classify_as_their_intent() {
+        let processor = IntentProcessor::new(0.8);
+        for intent in Intent::all() {
+            for example in intent.examples() {
+                assert_eq!(processor.process(example).unwrap().intent, *intent, "{}", example);
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_extractors_add_entities() {
+        let extractors = EntityExtractors::new([
+            ("ticket".to_string(), r"\b[A-Z]+-\d+\b".to_string()),
+            ("version".to_string(), r"\bv\d+\.\d+\.\d+\b".to_string()),
+        ])
+        .unwrap();
+        let processor = IntentProcessor::new(0.8).with_extractors(extractors);
+
+        let command = processor.process("Fix the crash in JIRA-1234 before v2.3.1").unwrap();
+        assert!(command.entities.contains(&"ticket:JIRA-1234".to_string()));
+        assert!(command.entities.contains(&"version:v2.3.1".to_string()));
+
+        let command = processor.process_with_intent("release v1.0.0", Intent::Orchestrate).unwrap();
+        assert_eq!(command.entities, vec!["version:v1.0.0"]);
+
+        // Without extractors nothing pattern-based appears
+        let command = IntentProcessor::new(0.8).process("Fix JIRA-1234").unwrap();
+        assert!(!command.entities.iter().any(|e| e.starts_with("ticket:")));
+    }
+
+    #[test]
+    fn test_entities_are_unique_in_first_seen_order() {
+        let extractors = EntityExtractors::new([("ticket".to_string(), r"\b[A-Z]+-\d+\b".to_string())]).unwrap();
+        let processor = IntentProcessor::new(0.8).with_extractors(extractors);
+
+        // helper.rs matches as a file twice and again after "class" and "function"
+        let command = processor
+            .process("update the class helper.rs and the function helper.rs in main.rs for JIRA-7, see JIRA-7")
+            .unwrap();
+        assert_eq!(command.entities, vec!["helper.rs", "main.rs", "ticket:JIRA-7"]);
+
+        let again = processor
+            .process("update the class helper.rs and the function helper.rs in main.rs for JIRA-7, see JIRA-7")
+            .unwrap();
+        assert_eq!(again.entities, command.entities);
+    }
+
     #[test]
     fn test_orchestrate_intent() {
         let processor = IntentProcessor::new(0.80);
@@ -193,4 +689,293 @@ mod tests {
         let cmd = processor.process("um like can you just run the tests please").unwrap();
         assert_eq!(cmd.directive, "run the tests");
     }
+
+    #[test]
+    fn test_clean_directive_respects_word_boundaries() {
+        let processor = IntentProcessor::new(0.80);
+        let cleaned = processor.clean_directive("Um, adjust the unlike button, plugin and Umbrella please.", &Intent::Code);
+        assert_eq!(cleaned, "adjust the unlike button, plugin and Umbrella");
+    }
+
+    #[test]
+    fn test_clean_directive_custom_fillers() {
+        let processor = IntentProcessor::new(0.80).with_fillers(["äh".to_string(), "bitte".to_string(), "Sort Of".to_string()]);
+        let cleaned = processor.clean_directive("äh just sort of run the tests bitte", &Intent::Test);
+        // "just" isn't in the custom list, so it stays
+        assert_eq!(cleaned, "just run the tests");
+    }
+
+    #[test]
+    fn test_clean_directive_disabled_is_verbatim() {
+        let processor = IntentProcessor::new(0.80).with_directive_cleaning(false);
+        let text = "um  can you just run the tests please";
+        assert_eq!(processor.clean_directive(text, &Intent::Test), text);
+    }
+
+    #[test]
+    fn test_segment_punctuated() {
+        let processor = IntentProcessor::new(0.80);
+        let segments = processor.segment("I looked. Then we ran it? Fine!");
+        assert_eq!(segments, vec!["I looked", "Then we ran it", "Fine"]);
+    }
+
+    #[test]
+    fn test_segment_unpunctuated_uses_markers() {
+        let processor = IntentProcessor::new(0.80);
+        let segments = processor.segment("um so maybe I was thinking okay run the auth tests");
+        assert_eq!(segments, vec!["um", "maybe I was thinking", "run the auth tests"]);
+    }
+
+    #[test]
+    fn test_long_transcript_picks_later_command() {
+        let processor = IntentProcessor::new(0.80);
+        let result = processor
+            .extract("Um, so, maybe I was thinking about the thing. Hmm. Run the auth tests.")
+            .unwrap();
+
+        assert_eq!(result.command.intent, Intent::Orchestrate);
+        assert_eq!(result.command.directive, "Run the auth tests");
+        // Leading verb and keywords agree
+        assert_eq!(result.command.confidence, 0.95);
+        assert_eq!(result.discarded_segments, vec!["Um", "maybe I was thinking about the thing", "Hmm"]);
+    }
+
+    #[test]
+    fn test_unpunctuated_transcript_picks_last_actionable_segment() {
+        let processor = IntentProcessor::new(0.80);
+        let cmd = processor
+            .process("uh so i dunno the page looks weird okay fix the login page layout")
+            .unwrap();
+
+        assert_eq!(cmd.intent, Intent::Test);
+        assert_eq!(cmd.directive, "fix the login page layout");
+    }
+
+    #[test]
+    fn test_single_segment_unchanged() {
+        let processor = IntentProcessor::new(0.80);
+        let result = processor.extract("find all the auth files").unwrap();
+        assert_eq!(result.command.directive, "find all the auth files");
+        assert!(result.discarded_segments.is_empty());
+    }
+
+    #[test]
+    fn test_process_with_intent_override() {
+        let processor = IntentProcessor::new(0.80);
+
+        // Classification would say Orchestrate because of "run"
+        let cmd = processor.process_with_intent("run a refactor of the parser", Intent::Code).unwrap();
+        assert_eq!(cmd.intent, Intent::Code);
+        assert_eq!(cmd.confidence, 1.0);
+        assert_eq!(cmd.directive, "run a refactor of the parser");
+    }
+
+    #[test]
+    fn test_classify_scores_ranks_all_intents() {
+        let processor = IntentProcessor::new(0.80);
+        let scores = processor.classify_scores("run the tests");
+
+        assert_eq!(scores.len(), Intent::all().len());
+        assert_eq!(scores[0], (Intent::Orchestrate, 0.95));
+        // "tests" also hits the Test keywords, so it ranks second
+        assert_eq!(scores[1].0, Intent::Test);
+
+        let total: f32 = scores.iter().map(|(_, s)| s).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+        assert!(scores.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+
+    #[test]
+    fn test_classify_scores_default_when_no_keywords() {
+        let processor = IntentProcessor::new(0.80);
+        let scores = processor.classify_scores("hmm the login page");
+
+        assert_eq!(scores[0], (Intent::Code, 0.60));
+        let total: f32 = scores.iter().map(|(_, s)| s).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_clarification_only_for_unclassifiable_phrases() {
+        let processor = IntentProcessor::new(0.80).with_clarify_threshold(0.65);
+
+        // No keyword and no leading verb: Code at 0.60 would just be a guess
+        let vague = processor.process("hmm the login page").unwrap();
+        assert!(processor.needs_clarification(&vague));
+
+        for clear in ["fix the login page", "where is the login page handled", "the login page needs a test"] {
+            let command = processor.process(clear).unwrap();
+            assert!(!processor.needs_clarification(&command), "{}", clear);
+        }
+
+        // Off by default
+        assert!(!IntentProcessor::new(0.80).needs_clarification(&vague));
+    }
+
+    #[test]
+    fn test_classify_scores_matches_process() {
+        let processor = IntentProcessor::new(0.80);
+        for phrase in ["find the auth module", "fix the failing check", "write a parser"] {
+            let (intent, confidence) = processor.classify_scores(phrase)[0];
+            let cmd = processor.process(phrase).unwrap();
+            assert_eq!(cmd.intent, intent);
+            assert_eq!(cmd.confidence, confidence);
+        }
+    }
+
+    #[test]
+    fn test_classify_scores_deterministic() {
+        let processor = IntentProcessor::new(0.80);
+        let a = processor.classify_scores("search and fix the build");
+        let b = processor.classify_scores("search and fix the build");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_structure_classifies_keyword_free_phrases() {
+        let processor = IntentProcessor::new(0.80);
+
+        let cases = [
+            ("make the login page responsive", Intent::Code),
+            ("rename the session module", Intent::Code),
+            ("can you please explain the retry logic", Intent::Research),
+            ("which file handles auth middleware", Intent::Research),
+            ("how does the daemon pick tasks", Intent::Research),
+            ("investigate the flaky upload", Intent::Test),
+            ("ship the release branch", Intent::Orchestrate),
+        ];
+        for (phrase, expected) in cases {
+            let (intent, confidence) = processor.classify_scores(phrase)[0];
+            assert_eq!(intent, expected, "{}", phrase);
+            assert_eq!(confidence, 0.80, "{}", phrase);
+        }
+    }
+
+    #[test]
+    fn test_structure_and_keywords() {
+        let processor = IntentProcessor::new(0.80);
+
+        // Agreement raises confidence
+        assert_eq!(processor.classify_scores("write a parser")[0], (Intent::Code, 0.95));
+
+        // A question about the build is research, not a build
+        assert_eq!(
+            processor.classify_scores("what does the build script do")[0],
+            (Intent::Research, 0.75)
+        );
+
+        // Keywords alone still work when there's no recognizable structure
+        assert_eq!(processor.classify_scores("the parser needs a test")[0], (Intent::Test, 0.85));
+        assert_eq!(structural_intent("the parser needs a test"), None);
+        assert_eq!(structural_intent(""), None);
+    }
+
+    #[test]
+    fn test_ambiguous_question_words_need_a_subject_or_question_mark() {
+        for question in ["do we retry failed uploads", "is it deployed", "when is the release", "is the build green?"] {
+            assert_eq!(structural_intent(question), Some(Intent::Research), "{}", question);
+        }
+        for command in ["do the migration", "is_valid should return false", "when the build fails retry it"] {
+            assert_ne!(structural_intent(command), Some(Intent::Research), "{}", command);
+        }
+    }
+
+    fn alias_processor() -> IntentProcessor {
+        IntentProcessor::new(0.80).with_aliases([(
+            "Ship it".to_string(),
+            "run the full CI pipeline and deploy to staging".to_string(),
+        )])
+    }
+
+    #[test]
+    fn test_alias_exact_match_ignores_case_and_punctuation() {
+        let processor = alias_processor();
+
+        let resolved = processor.expand_alias("SHIP IT!").unwrap();
+        assert_eq!(resolved.to_entity, "run the full CI pipeline and deploy to staging");
+        assert_eq!(resolved.from_phrase, "SHIP IT!");
+        assert_eq!(resolved.confidence, 1.0);
+    }
+
+    #[test]
+    fn test_alias_fuzzy_match() {
+        let processor = alias_processor();
+
+        // Typical STT slips still match
+        assert!(processor.expand_alias("shipit").is_some());
+        assert!(processor.expand_alias("chip it").is_some());
+
+        // Different commands don't
+        assert!(processor.expand_alias("ship the docs").is_none());
+        assert!(processor.expand_alias("find the auth files").is_none());
+    }
+
+    #[test]
+    fn test_alias_expansion_is_classified() {
+        let processor = alias_processor();
+
+        let result = processor.extract("ship it").unwrap();
+        assert_eq!(result.transcript, "ship it");
+        assert_eq!(result.command.intent, Intent::Orchestrate);
+        assert_eq!(result.command.directive, "run the full CI pipeline and deploy to staging");
+        assert_eq!(result.ambiguities_resolved.len(), 1);
+        assert!(result.ambiguities_resolved[0].context.contains("ship it"));
+
+        // No alias means nothing recorded
+        let result = processor.extract("find the auth files").unwrap();
+        assert!(result.ambiguities_resolved.is_empty());
+    }
+
+    #[test]
+    fn test_process_multi_splits_sequenced_steps() {
+        let processor = IntentProcessor::new(0.80);
+
+        let commands = processor.process_multi("run the tests and then deploy to staging").unwrap();
+        let steps: Vec<_> = commands.iter().map(|c| (c.intent, c.after_previous)).collect();
+        assert_eq!(steps, vec![(Intent::Orchestrate, false), (Intent::Orchestrate, true)]);
+        assert!(commands[1].directive.to_lowercase().starts_with("deploy"));
+
+        let commands = processor.process_multi("fix the login bug, after that update the docs").unwrap();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].intent, Intent::Test);
+        assert!(commands[1].after_previous);
+    }
+
+    #[test]
+    fn test_process_multi_unordered_steps() {
+        let processor = IntentProcessor::new(0.80);
+
+        let commands = processor.process_multi("find the auth files; fix the flaky test").unwrap();
+        assert_eq!(commands.len(), 2);
+        assert!(commands.iter().all(|c| !c.after_previous));
+
+        let commands = processor.process_multi("build the app and deploy it").unwrap();
+        assert_eq!(commands.len(), 2);
+        assert!(!commands[1].after_previous);
+    }
+
+    #[test]
+    fn test_process_multi_single_command() {
+        let processor = IntentProcessor::new(0.80);
+
+        // "and" without a verb after it, or a step with nothing to do, stays one command
+        for transcript in ["add tests and docs", "run the tests then hmm", "run the auth tests"] {
+            let commands = processor.process_multi(transcript).unwrap();
+            assert_eq!(commands.len(), 1, "{}", transcript);
+            assert!(!commands[0].after_previous);
+        }
+
+        // Aliases expand to one command even when the expansion reads as two
+        let commands = alias_processor().process_multi("ship it").unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].directive, "run the full CI pipeline and deploy to staging");
+    }
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity("ship it", "ship it"), 1.0);
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("abc", ""), 0.0);
+        assert!((similarity("kitten", "sitting") - (1.0 - 3.0 / 7.0)).abs() < 1e-6);
+    }
 }
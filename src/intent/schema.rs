@@ -6,6 +6,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use super::templates::DirectiveTemplates;
+
 /// The four intent categories Claude can handle
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -43,6 +45,16 @@ impl Intent {
             Intent::Test => "Run tests, debug failures, fix issues",
         }
     }
+
+    /// Phrases that classify as this intent, for help text
+    pub fn examples(&self) -> &'static [&'static str] {
+        match self {
+            Intent::Orchestrate => &["deploy the staging build", "run the nightly pipeline"],
+            Intent::Research => &["find where sessions are stored", "show the open TODOs"],
+            Intent::Code => &["refactor the config loader", "add a retry flag to sync"],
+            Intent::Test => &["debug the login crash", "fix the failing auth tests"],
+        }
+    }
 }
 
 impl std::fmt::Display for Intent {
@@ -56,6 +68,19 @@ impl std::fmt::Display for Intent {
     }
 }
 
+impl std::str::FromStr for Intent {
+    type Err = String;
+
+    /// Parse an intent name, case-insensitively
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Intent::all()
+            .iter()
+            .find(|intent| intent.to_string().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or_else(|| format!("unknown intent '{}' (expected orchestrate, research, code, or test)", s))
+    }
+}
+
 /// Deterministic command structure
 ///
 /// This is the rigid schema that local AI forces messy speech into.
@@ -82,6 +107,10 @@ pub struct Command {
 
     /// When this command was created
     pub created_at: DateTime<Utc>,
+
+    /// Spoken as a later step ("and then ...") of the command before it
+    #[serde(default)]
+    pub after_previous: bool,
 }
 
 impl Command {
@@ -99,6 +128,7 @@ impl Command {
             context: HashMap::new(),
             confidence,
             created_at: Utc::now(),
+            after_previous: false,
         }
     }
 
@@ -121,7 +151,12 @@ impl Command {
 
     /// Convert to Claude prompt string
     pub fn to_claude_prompt(&self) -> String {
-        let mut prompt = self.directive.clone();
+        self.to_claude_prompt_with(&DirectiveTemplates::default())
+    }
+
+    /// Like `to_claude_prompt`, with the directive rewritten by its intent's template
+    pub fn to_claude_prompt_with(&self, templates: &DirectiveTemplates) -> String {
+        let mut prompt = self.render_directive(templates);
 
         // Add context if available
         if !self.context.is_empty() {
@@ -134,6 +169,11 @@ impl Command {
         prompt
     }
 
+    /// The directive wrapped in its intent's template, or as spoken without one
+    pub fn render_directive(&self, templates: &DirectiveTemplates) -> String {
+        templates.render(self.intent, &self.directive, &self.entities)
+    }
+
     /// Serialize to JSON
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string_pretty(self)
@@ -154,6 +194,9 @@ pub struct IntentExtractionResult {
 
     /// Ambiguities that were resolved
     pub ambiguities_resolved: Vec<AmbiguityResolution>,
+
+    /// Transcript segments that were not used for the command
+    pub discarded_segments: Vec<String>,
 }
 
 /// Record of an ambiguity that was resolved
@@ -251,12 +294,68 @@ mod tests {
         assert!(prompt.contains("module: auth"));
     }
 
+    #[test]
+    fn test_templates_wrap_directive_by_intent() {
+        let templates = DirectiveTemplates::new([
+            ("orchestrate".to_string(), "Plan and coordinate: {directive}".to_string()),
+            ("research".to_string(), "Investigate and summarize findings: {directive}".to_string()),
+            ("code".to_string(), "Implement {directive} (touching {entities})".to_string()),
+            ("test".to_string(), "Run the test suite for {entities} and report failures. Asked: {directive}".to_string()),
+        ])
+        .unwrap();
+
+        let wrapped = |intent| Command::new(intent, "tests for auth", vec!["auth".to_string(), "login".to_string()], 0.9);
+        assert_eq!(
+            wrapped(Intent::Orchestrate).render_directive(&templates),
+            "Plan and coordinate: tests for auth"
+        );
+        assert_eq!(
+            wrapped(Intent::Research).render_directive(&templates),
+            "Investigate and summarize findings: tests for auth"
+        );
+        assert_eq!(
+            wrapped(Intent::Code).render_directive(&templates),
+            "Implement tests for auth (touching auth, login)"
+        );
+        let prompt = wrapped(Intent::Test).with_context("module", "auth").to_claude_prompt_with(&templates);
+        assert!(prompt.starts_with("Run the test suite for auth, login and report failures. Asked: tests for auth"));
+        assert!(prompt.contains("module: auth"));
+    }
+
+    #[test]
+    fn test_missing_or_empty_template_keeps_directive() {
+        let templates = DirectiveTemplates::new([
+            ("code".to_string(), "Implement: {directive}".to_string()),
+            ("test".to_string(), String::new()),
+        ])
+        .unwrap();
+
+        let cmd = Command::new(Intent::Test, "tests for {entities}", vec!["auth".to_string()], 0.9);
+        assert_eq!(cmd.render_directive(&templates), "tests for {entities}");
+        assert_eq!(cmd.to_claude_prompt_with(&templates), cmd.to_claude_prompt());
+        // Only the template's own placeholders are expanded
+        let code = Command::new(Intent::Code, "echo {entities}", vec!["x".to_string()], 0.9);
+        assert_eq!(code.render_directive(&templates), "Implement: echo {entities}");
+    }
+
     #[test]
     fn test_intent_display() {
         assert_eq!(Intent::Orchestrate.to_string(), "ORCHESTRATE");
         assert_eq!(Intent::Code.to_string(), "CODE");
     }
 
+    #[test]
+    fn test_intent_from_str() {
+        assert_eq!("code".parse::<Intent>(), Ok(Intent::Code));
+        assert_eq!("RESEARCH".parse::<Intent>(), Ok(Intent::Research));
+        assert_eq!(" Test ".parse::<Intent>(), Ok(Intent::Test));
+        assert!("deploy".parse::<Intent>().is_err());
+
+        for intent in Intent::all() {
+            assert_eq!(intent.to_string().parse::<Intent>(), Ok(*intent));
+        }
+    }
+
     #[test]
     fn test_json_serialization() {
         let cmd = Command::new(
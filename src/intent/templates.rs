@@ -0,0 +1,112 @@
+//! Per-intent directive templates from `[intent.templates]`
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use super::schema::Intent;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("intent.templates has unknown intent '{name}'")]
+    UnknownIntent { name: String },
+    #[error("intent.templates.{name} never uses {{directive}}")]
+    MissingDirective { name: String },
+}
+
+/// Rewrites of a directive by intent, e.g.
+/// `test = "Run the test suite for {entities} and report failures: {directive}"`
+///
+/// `{directive}` is the spoken directive and `{entities}` its entities joined
+/// with ", ". Intents without a template, or with an empty one, keep the
+/// directive unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "BTreeMap<String, String>", into = "BTreeMap<String, String>")]
+pub struct DirectiveTemplates(Vec<(Intent, String)>);
+
+impl DirectiveTemplates {
+    /// Check each `(intent, template)`, failing on the first unknown intent or unused directive
+    pub fn new(templates: impl IntoIterator<Item = (String, String)>) -> Result<Self, TemplateError> {
+        templates
+            .into_iter()
+            .filter(|(_, template)| !template.trim().is_empty())
+            .map(|(name, template)| {
+                let Ok(intent) = name.parse::<Intent>() else {
+                    return Err(TemplateError::UnknownIntent { name });
+                };
+                if !template.contains("{directive}") {
+                    return Err(TemplateError::MissingDirective { name });
+                }
+                Ok((intent, template))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Template configured for `intent`, if any
+    pub fn get(&self, intent: Intent) -> Option<&str> {
+        self.0.iter().find(|(i, _)| *i == intent).map(|(_, template)| template.as_str())
+    }
+
+    /// `directive` wrapped in `intent`'s template, or unchanged without one
+    pub fn render(&self, intent: Intent, directive: &str, entities: &[String]) -> String {
+        match self.get(intent) {
+            // Entities first, so a directive that happens to contain "{entities}" is left alone
+            Some(template) => template.replace("{entities}", &entities.join(", ")).replace("{directive}", directive),
+            None => directive.to_string(),
+        }
+    }
+}
+
+impl TryFrom<BTreeMap<String, String>> for DirectiveTemplates {
+    type Error = TemplateError;
+
+    fn try_from(templates: BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        Self::new(templates)
+    }
+}
+
+impl From<DirectiveTemplates> for BTreeMap<String, String> {
+    fn from(templates: DirectiveTemplates) -> Self {
+        templates
+            .0
+            .into_iter()
+            .map(|(intent, template)| (intent.to_string().to_lowercase(), template))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn templates(pairs: &[(&str, &str)]) -> Result<DirectiveTemplates, TemplateError> {
+        DirectiveTemplates::new(pairs.iter().map(|(name, template)| (name.to_string(), template.to_string())))
+    }
+
+    #[test]
+    fn test_rejects_unknown_intents_and_unused_directive() {
+        assert_eq!(
+            templates(&[("deploy", "Deploy {directive}")]),
+            Err(TemplateError::UnknownIntent { name: "deploy".to_string() })
+        );
+        assert_eq!(
+            templates(&[("test", "Run the tests")]),
+            Err(TemplateError::MissingDirective { name: "test".to_string() })
+        );
+        // Empty means no template, so it needs no placeholder
+        assert!(templates(&[("test", " ")]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_round_trips_through_config_form() {
+        let templates = templates(&[("Code", "Implement: {directive}")]).unwrap();
+        let map: BTreeMap<String, String> = templates.clone().into();
+        assert_eq!(map.get("code").map(String::as_str), Some("Implement: {directive}"));
+        assert_eq!(DirectiveTemplates::try_from(map).unwrap(), templates);
+    }
+}
@@ -7,16 +7,26 @@
 
 pub mod audio;
 pub mod bridge;
+pub mod clock;
 pub mod config;
+pub mod daemon;
+pub mod doctor;
+pub mod error;
 pub mod executor;
 pub mod intent;
 pub mod knowledge;
+pub mod logging;
 pub mod queue;
+pub mod server;
+pub mod text;
+pub mod timezone;
+pub mod voice;
 
 #[cfg(feature = "plugin")]
 pub mod plugin;
 
 // Re-exports
 pub use config::Config;
+pub use error::FasterError;
 pub use queue::{TaskQueue, TaskStatus, Task};
 pub use executor::ClaudeExecutor;
@@ -0,0 +1,146 @@
+//! Tracing output setup, pretty for terminals or JSON lines for log pipelines
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::{Format, Json, JsonFields};
+use tracing_subscriber::fmt::{MakeWriter, SubscriberBuilder};
+use tracing_subscriber::EnvFilter;
+
+/// How tracing output is rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Pretty,
+    /// One JSON object per event
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{}' (expected pretty or json)", s)),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogFormat::Pretty => "pretty",
+            LogFormat::Json => "json",
+        })
+    }
+}
+
+/// Install the global subscriber, filtered by `RUST_LOG` with `faster=info` on top
+pub fn init(format: LogFormat) {
+    let filter = EnvFilter::from_default_env().add_directive("faster=info".parse().unwrap());
+
+    // try_init: a subscriber may already be set (e.g. by an embedding app)
+    let _ = match format {
+        LogFormat::Pretty => tracing_subscriber::fmt().with_env_filter(filter).try_init(),
+        LogFormat::Json => json_builder(std::io::stdout).with_env_filter(filter).try_init(),
+    };
+}
+
+/// Subscriber builder that writes JSON lines to `writer`
+///
+/// Keys are `timestamp`, `level`, `target`, `fields` (including `message`),
+/// and, inside spans, `span` (the innermost) and `spans` (root first). Span
+/// objects carry their `name` alongside their fields, e.g. `task_id`.
+pub fn json_builder<W>(writer: W) -> SubscriberBuilder<JsonFields, Format<Json>, LevelFilter, W>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_writer(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    /// Writer capturing output in memory
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn capture_json(f: impl FnOnce()) -> Vec<Value> {
+        let capture = Capture::default();
+        let sink = capture.clone();
+        let subscriber = json_builder(move || sink.clone()).finish();
+        tracing::subscriber::with_default(subscriber, f);
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        output.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_json_event_keys() {
+        let lines = capture_json(|| {
+            let span = tracing::info_span!("task", task_id = "abc12345");
+            let _entered = span.enter();
+            tracing::info!(attempt = 2, ok = true, "Task started");
+        });
+
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        assert!(line["timestamp"].is_string());
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["target"], "faster::logging::tests");
+        assert_eq!(line["fields"]["message"], "Task started");
+        assert_eq!(line["fields"]["attempt"], 2);
+        assert_eq!(line["fields"]["ok"], true);
+        assert_eq!(line["span"]["name"], "task");
+        assert_eq!(line["span"]["task_id"], "abc12345");
+        assert_eq!(line["spans"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_json_event_outside_span_and_recorded_fields() {
+        let lines = capture_json(|| {
+            tracing::warn!("No span");
+
+            let span = tracing::info_span!("task", task_id = "abc", status = tracing::field::Empty);
+            span.record("status", "failed");
+            span.in_scope(|| tracing::error!("Inside"));
+        });
+
+        assert_eq!(lines[0]["level"], "WARN");
+        assert!(lines[0].get("span").is_none());
+        assert_eq!(lines[1]["span"]["task_id"], "abc");
+        assert_eq!(lines[1]["span"]["status"], "failed");
+    }
+
+    #[test]
+    fn test_log_format_parsing() {
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!("Pretty".parse::<LogFormat>(), Ok(LogFormat::Pretty));
+        assert!("xml".parse::<LogFormat>().is_err());
+        assert_eq!(LogFormat::default(), LogFormat::Pretty);
+    }
+}
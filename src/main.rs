@@ -1,22 +1,29 @@
 //! Faster - Voice-driven deterministic intent processor for Claude Code
 
-mod audio;
-mod bridge;
-mod config;
-mod executor;
-mod intent;
-mod knowledge;
-mod queue;
-
+use anyhow::Context;
 use clap::{Parser, Subcommand};
 use colored::*;
-use std::path::PathBuf;
-
-use config::Config;
-use executor::ClaudeExecutor;
-use queue::{TaskQueue, TaskStatus};
-use audio::{MacOSSTT, MacOSTTS};
-use intent::IntentProcessor;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use faster::daemon::{self, Daemon, Heartbeat, Shutdown};
+use faster::doctor::{self, Diagnostic};
+use faster::server::{self, EventHub, HealthState};
+use faster::executor::{ClaudeExecutor, ExecutorError};
+use faster::queue::{parse_specs, parse_specs_each, BatchResult, EventSource, ListQuery, QueueError, SpecError, TaskEvent};
+use faster::queue::{EnvSnapshot, LatencyStats, QueueStats, TaskAttempt};
+use faster::config::ConfigError;
+use faster::timezone::Timezone;
+use faster::{Config, FasterError, Task, TaskQueue, TaskStatus};
+use faster::audio::{self, recording, Cancelled, EndOfInput, Recorder, SpeakOptions, Transcription, TtsBackend};
+use faster::intent::{parse_meta_with, Command, Intent, IntentProcessor, MetaCommand, ModelAliases};
+use faster::logging::{self, LogFormat};
+use faster::voice::{StepOutcome, TurnOutcome, VoiceSession};
 
 #[derive(Parser)]
 #[command(name = "faster")]
@@ -34,6 +41,41 @@ struct Cli {
     #[arg(short, long)]
     model: Option<String>,
 
+    /// Append this file's contents to the prompt when the task runs (repeatable)
+    #[arg(long = "context-file", value_name = "PATH")]
+    context_files: Vec<PathBuf>,
+
+    /// Continue the Claude session of this earlier task
+    #[arg(long = "continue", value_name = "TASK_ID")]
+    continue_from: Option<String>,
+
+    /// Wait for a free slot instead of failing when the queue is full
+    #[arg(long)]
+    block: bool,
+
+    /// Kill the task if it runs longer than this (e.g. 300s, 1h), instead of
+    /// after claude.timeout_secs
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    timeout: Option<chrono::Duration>,
+
+    /// Skip classification and queue the command with this intent
+    /// (orchestrate, research, code, test)
+    #[arg(short, long)]
+    intent: Option<Intent>,
+
+    /// Config file to use instead of ~/.faster/config.toml or $FASTER_HOME/config.toml (must exist)
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Keep the queue in memory for this run only, writing nothing to disk;
+    /// a quick command then runs right away
+    #[arg(long)]
+    ephemeral: bool,
+
+    /// Log output format: pretty or json (defaults to observability.log_format)
+    #[arg(long, value_name = "FORMAT")]
+    log_format: Option<LogFormat>,
+
     /// Enable debug mode
     #[arg(short, long)]
     debug: bool,
@@ -44,28 +86,235 @@ enum Commands {
     /// Start voice mode (default)
     Voice,
 
+    /// Process queued tasks, then exit (non-zero if any failed)
+    Run {
+        /// Stop after this many tasks
+        #[arg(long)]
+        max: Option<usize>,
+    },
+
     /// Start background daemon
-    Daemon,
+    Daemon {
+        /// Start even if another daemon holds the queue lock
+        #[arg(long)]
+        force: bool,
+
+        /// Run the tasks queued now, then exit (non-zero if any failed); for cron
+        #[arg(long)]
+        once: bool,
+    },
 
     /// Show task queue status
     Status {
         /// Show all tasks (including completed)
         #[arg(short, long)]
         all: bool,
+
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Don't truncate commands to the terminal width
+        #[arg(long)]
+        wide: bool,
+
+        /// Only tasks in this status (queued, running, completed, failed, cancelled, draft, blocked)
+        #[arg(long, value_parser = parse_status)]
+        status: Option<TaskStatus>,
+
+        /// Only tasks created within this long ago, e.g. 30m, 1h, 2d
+        #[arg(long, value_parser = parse_duration)]
+        since: Option<chrono::Duration>,
+
+        /// Show times in UTC instead of general.timezone
+        #[arg(long)]
+        utc: bool,
+
+        /// Show tasks in sections, e.g. `tag` for one section per tag
+        #[arg(long, value_name = "FIELD")]
+        group_by: Option<GroupBy>,
+
+        /// Leave out rows that can't be read back (logged as warnings) instead of failing
+        #[arg(long)]
+        skip_corrupt: bool,
+    },
+
+    /// Follow task transitions as they happen, until Ctrl+C
+    Tail {
+        /// Replay transitions already recorded before following
+        #[arg(long)]
+        from_start: bool,
+
+        /// Show times in UTC instead of general.timezone
+        #[arg(long)]
+        utc: bool,
+    },
+
+    /// Print a task's full output, from its output file if it has one
+    Logs {
+        /// Task ID (or unique prefix)
+        task_id: String,
+    },
+
+    /// Show a single task
+    Get {
+        /// Task ID (or unique prefix)
+        task_id: String,
+
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Print only the prompt that was sent to Claude
+        #[arg(long)]
+        prompt: bool,
+
+        /// List each attempt at running the task
+        #[arg(long, conflicts_with = "prompt")]
+        attempts: bool,
+
+        /// Show the directory, git state and environment it was enqueued with
+        #[arg(long, conflicts_with_all = ["prompt", "attempts"])]
+        env: bool,
+
+        /// Show times in UTC instead of general.timezone
+        #[arg(long)]
+        utc: bool,
     },
 
     /// Cancel a task
     Cancel {
         /// Task ID to cancel
-        task_id: String,
+        #[arg(required_unless_present_any = ["all", "matching"])]
+        task_id: Option<String>,
+
+        /// Cancel every queued and draft task (running tasks are left alone)
+        #[arg(long, conflicts_with_all = ["task_id", "matching"])]
+        all: bool,
+
+        /// Cancel queued and draft tasks whose command contains this, ignoring case
+        #[arg(long, value_name = "SUBSTRING", conflicts_with = "task_id")]
+        matching: Option<String>,
+
+        /// Don't ask for confirmation with --all or --matching
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Clear completed tasks
     Clear,
 
+    /// Reclaim disk space left by cleared tasks (stop the daemon first)
+    Compact,
+
+    /// Delete every task, whatever its status (knowledge and config are kept)
+    Reset {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Move a low-confidence draft onto the queue
+    Promote {
+        /// Draft task ID (or unique prefix)
+        task_id: String,
+
+        /// Classify the command again instead of keeping its intent
+        #[arg(long)]
+        reclassify: bool,
+    },
+
+    /// Hold a queued task so the daemon leaves it alone until unheld
+    Hold {
+        /// Task ID (or unique prefix)
+        task_id: String,
+    },
+
+    /// Put a held task back on the queue
+    Unhold {
+        /// Task ID (or unique prefix)
+        task_id: String,
+    },
+
+    /// Queue a fresh copy of a task with the same inputs, to reproduce its run
+    Replay {
+        /// Task ID (or unique prefix), in any status
+        task_id: String,
+    },
+
+    /// List failed tasks, most recent first, with their errors and attempts
+    Failures {
+        /// Show at most this many tasks (0 = all)
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+
+        /// Put every failed task back on the queue after listing them
+        #[arg(long)]
+        retry_all: bool,
+
+        /// Show times in UTC instead of general.timezone
+        #[arg(long)]
+        utc: bool,
+    },
+
+    /// Show task counts and how long tasks wait and run (p50/p95/max)
+    Stats {
+        /// Only tasks created within this long ago, e.g. 1d (defaults to queue.stats_window_secs)
+        #[arg(long, value_parser = parse_duration)]
+        since: Option<chrono::Duration>,
+
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List recently queued commands, newest first, without repeats
+    History {
+        /// Show at most this many commands (0 = all)
+        #[arg(long, default_value_t = 20)]
+        limit: u64,
+
+        /// Queue the Nth command in the list again
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        run: Option<u64>,
+    },
+
+    /// Put tasks back on the queue
+    Requeue {
+        /// Task ID to requeue
+        #[arg(required_unless_present = "all_failed")]
+        task_id: Option<String>,
+
+        /// Requeue every failed task at once
+        #[arg(long, conflicts_with = "task_id")]
+        all_failed: bool,
+    },
+
+    /// Queue tasks described in a JSON file
+    Enqueue {
+        /// JSON array of task specs (command, model, priority, tags, cwd,
+        /// scheduled_for, timeout_ms, metadata); `-` reads stdin
+        #[arg(long, value_name = "PATH")]
+        json_file: PathBuf,
+
+        /// Attach KEY=VALUE to every task, unless its spec sets KEY (repeatable)
+        #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_meta_pair)]
+        metadata: Vec<(String, String)>,
+
+        /// Queue the valid specs even if others fail, then report the failures
+        #[arg(long)]
+        best_effort: bool,
+    },
+
     /// Test installation and components
     Test,
 
+    /// Diagnose problems and suggest fixes (exits non-zero on critical failures)
+    Doctor,
+
+    /// List the intent categories commands are sorted into, with examples
+    Intents,
+
     /// Show or edit configuration
     Config {
         /// Show current configuration
@@ -83,10 +332,25 @@ enum Commands {
         action: KnowledgeCommands,
     },
 
+    /// Inspect saved voice recordings
+    Audio {
+        #[command(subcommand)]
+        action: AudioCommands,
+    },
+
     /// Setup wizard
     Setup,
 }
 
+#[derive(Subcommand)]
+enum AudioCommands {
+    /// Play a saved recording
+    Play {
+        /// WAV file to play
+        file: PathBuf,
+    },
+}
+
 #[derive(Subcommand)]
 enum KnowledgeCommands {
     /// Show speech patterns
@@ -105,55 +369,216 @@ enum KnowledgeCommands {
     Clear,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive("faster=info".parse()?),
-        )
-        .init();
+/// Process exit codes, documented in the README
+mod exit {
+    /// Any error not covered below
+    pub const FAILURE: u8 = 1;
+    /// Bad arguments (clap uses this too) or task not found
+    pub const USAGE: u8 = 2;
+    /// Config file could not be parsed
+    pub const CONFIG: u8 = 3;
+    /// Queue database error, queue full, or another daemon running
+    pub const QUEUE: u8 = 4;
+    /// Claude CLI missing or not runnable
+    pub const CLAUDE_UNAVAILABLE: u8 = 5;
+}
+
+/// Map an error to the process exit code for its outcome
+fn exit_code(err: &anyhow::Error) -> u8 {
+    for cause in err.chain() {
+        // Its variants are transparent, so the chain skips past what they wrap
+        if let Some(e) = cause.downcast_ref::<FasterError>() {
+            return match e {
+                FasterError::NotFound { .. } => exit::USAGE,
+                FasterError::Queue(e) => queue_exit_code(e),
+                FasterError::Config(_) => exit::CONFIG,
+                FasterError::Executor(e) => executor_exit_code(e),
+                FasterError::Db(_) => exit::QUEUE,
+                FasterError::Other(e) => exit_code(e),
+                FasterError::Io(_) | FasterError::Json(_) => exit::FAILURE,
+            };
+        }
+        if let Some(e) = cause.downcast_ref::<QueueError>() {
+            return queue_exit_code(e);
+        }
+        if let Some(e) = cause.downcast_ref::<ExecutorError>() {
+            return executor_exit_code(e);
+        }
+        if cause.is::<toml::de::Error>() || cause.is::<ConfigError>() {
+            return exit::CONFIG;
+        }
+        if cause.is::<sqlx::Error>() {
+            return exit::QUEUE;
+        }
+    }
+    exit::FAILURE
+}
 
+/// Only a CLI that can't be run is "unavailable"; a killed run is a plain failure
+fn executor_exit_code(err: &ExecutorError) -> u8 {
+    match err {
+        ExecutorError::Unavailable { .. } => exit::CLAUDE_UNAVAILABLE,
+        ExecutorError::Cancelled | ExecutorError::TimedOut { .. } => exit::FAILURE,
+    }
+}
+
+/// The queue error behind `err`, whether raised bare or as a `FasterError`
+fn queue_error(err: &anyhow::Error) -> Option<QueueError> {
+    err.chain().find_map(|cause| match cause.downcast_ref::<FasterError>() {
+        Some(e) => e.queue_error(),
+        None => cause.downcast_ref::<QueueError>().cloned(),
+    })
+}
+
+fn queue_exit_code(err: &QueueError) -> u8 {
+    match err {
+        QueueError::NotFound { .. }
+        | QueueError::InvalidCwd { .. }
+        | QueueError::CommandTooLong { .. }
+        | QueueError::InvalidSpecs { .. }
+        | QueueError::InvalidTransition { .. }
+        | QueueError::InvalidBulkTransition { .. } => exit::USAGE,
+        QueueError::QueueFull { .. } | QueueError::DaemonRunning { .. } | QueueError::CorruptRow { .. } => exit::QUEUE,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    // Load config
-    let config = load_or_create_config()?;
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{} {:#}", "Error:".red(), e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    // Load config; ephemeral runs don't write a default one
+    let config_file = cli.config.as_deref();
+    let mut config = if cli.ephemeral { load_config(config_file)? } else { load_or_create_config(config_file)? };
+    config.queue.ephemeral |= cli.ephemeral;
+
+    // Initialize tracing
+    logging::init(cli.log_format.unwrap_or(config.observability.log_format));
 
     // Handle quick command first (e.g., faster "run tests")
     if let Some(cmd) = cli.quick_command {
-        queue_command(&cmd, &config, cli.model).await?;
+        let command = classify_typed(&intent_processor(&config), &cmd, cli.intent);
+        let parent = match &cli.continue_from {
+            Some(prefix) => Some(resolve_task_id(&config, prefix).await?),
+            None => None,
+        };
+        let cwd = std::env::current_dir()?;
+        let options = QueueOptions {
+            parent,
+            timeout: cli.timeout.and_then(|t| t.to_std().ok()),
+            context_files: cli.context_files.iter().map(|f| cwd.join(f)).collect(),
+        };
+        queue_command_after(&command, &config, cli.model, cli.block, options.parent.as_deref(), &options).await?;
+        // Nothing else will ever see an ephemeral queue, so run it here
+        if config.queue.ephemeral {
+            run_tasks(&config, None).await?;
+        }
         return Ok(());
     }
 
     match cli.command {
-        Some(Commands::Daemon) => {
-            run_daemon(&config).await?;
+        Some(Commands::Run { max }) => {
+            run_tasks(&config, max).await?;
+        }
+        Some(Commands::Daemon { force, once }) => {
+            run_daemon(&config, force, once).await?;
+        }
+        Some(Commands::Status { all, json, wide, status, since, utc, group_by, skip_corrupt }) => {
+            let timezone = display_timezone(&config, utc)?;
+            let query = status_query(all, status, since).with_skip_corrupt(skip_corrupt);
+            show_status(&config, &query, json, wide, group_by, &timezone).await?;
         }
-        Some(Commands::Status { all }) => {
-            show_status(&config, all).await?;
+        Some(Commands::Tail { from_start, utc }) => {
+            let timezone = display_timezone(&config, utc)?;
+            tail(&config, from_start, &timezone).await?;
         }
-        Some(Commands::Cancel { task_id }) => {
-            cancel_task(&config, &task_id).await?;
+        Some(Commands::Logs { task_id }) => {
+            show_logs(&config, &task_id).await?;
+        }
+        Some(Commands::Get { task_id, json, prompt, attempts, env, utc }) => {
+            let timezone = display_timezone(&config, utc)?;
+            get_task(&config, &task_id, json, prompt, attempts, env, &timezone).await?;
+        }
+        Some(Commands::Cancel { task_id, all, matching, yes }) => {
+            if all || matching.is_some() {
+                cancel_all(&config, matching.as_deref(), yes).await?;
+            } else {
+                cancel_task(&config, task_id.as_deref().unwrap_or_default()).await?;
+            }
         }
         Some(Commands::Clear) => {
             clear_completed(&config).await?;
         }
+        Some(Commands::Compact) => {
+            compact(&config).await?;
+        }
+        Some(Commands::Reset { yes }) => {
+            reset_queue(&config, yes).await?;
+        }
+        Some(Commands::Promote { task_id, reclassify }) => {
+            promote(&config, &task_id, reclassify).await?;
+        }
+        Some(Commands::Hold { task_id }) => {
+            set_held(&config, &task_id, true).await?;
+        }
+        Some(Commands::Unhold { task_id }) => {
+            set_held(&config, &task_id, false).await?;
+        }
+        Some(Commands::Replay { task_id }) => {
+            replay(&config, &task_id).await?;
+        }
+        Some(Commands::Failures { limit, retry_all, utc }) => {
+            let timezone = display_timezone(&config, utc)?;
+            show_failures(&config, limit, retry_all, &timezone).await?;
+        }
+        Some(Commands::Stats { since, json }) => {
+            show_stats(&config, since, json).await?;
+        }
+        Some(Commands::History { run: Some(n), .. }) => {
+            rerun_history(&config, n).await?;
+        }
+        Some(Commands::History { limit, run: None }) => {
+            show_history(&config, limit).await?;
+        }
+        Some(Commands::Requeue { task_id, all_failed }) => {
+            requeue(&config, task_id.as_deref(), all_failed).await?;
+        }
+        Some(Commands::Enqueue { json_file, metadata, best_effort }) => {
+            enqueue_json_file(&config, &json_file, &metadata, best_effort).await?;
+        }
         Some(Commands::Voice) | None => {
             // Default: start voice mode
             voice_mode(config, cli.debug).await?;
         }
         Some(Commands::Test) => {
-            test_installation()?;
+            test_installation(config_file)?;
+        }
+        Some(Commands::Doctor) => {
+            run_doctor(&config).await?;
+        }
+        Some(Commands::Intents) => {
+            println!("{}", format_intents(&config));
         }
         Some(Commands::Config { show, edit }) => {
-            handle_config_command(show, edit)?;
+            handle_config_command(config_file, show, edit)?;
         }
         Some(Commands::Knowledge { action }) => {
             handle_knowledge_command(action)?;
         }
+        Some(Commands::Audio { action }) => {
+            handle_audio_command(action)?;
+        }
         Some(Commands::Setup) => {
-            setup_wizard()?;
+            setup_wizard(config_file)?;
         }
     }
 
@@ -171,78 +596,368 @@ async fn voice_mode(config: Config, debug: bool) -> anyhow::Result<()> {
     }
 
     // Initialize STT and TTS
-    let stt = MacOSSTT::new(&config.stt.language);
-    let tts = MacOSTTS::new(&config.tts.voice, config.tts.rate);
-
-    // Initialize intent processor
-    let processor = IntentProcessor::new(config.intent.confidence_threshold);
+    let stt = audio::stt_backend(&config.stt)?;
+    let tts = audio::tts_backend(&config.tts)?;
+    let warning = SpeakOptions {
+        voice: config.tts.warning_voice.clone(),
+        rate: Some(config.tts.warning_rate),
+    };
+    let recorder = Recorder::new(&config.audio.input_device, config.audio.sample_rate);
 
     // Check availability
-    if !MacOSSTT::is_available() {
+    let stt_status = stt.availability();
+    if !stt_status.available {
         eprintln!("{}", "✗ Speech-to-text not available".red());
+        if let Some(reason) = stt_status.reason {
+            eprintln!("  {}", reason);
+        }
         return Ok(());
     }
+    if let Some(reason) = stt_status.reason {
+        println!("{} {}", "⚠".yellow(), reason);
+    }
+    for warning in config.warnings() {
+        println!("{} {}", "⚠".yellow(), warning);
+    }
 
-    if !MacOSTTS::is_available() {
+    if !tts.available() {
         eprintln!("{}", "✗ Text-to-speech not available".red());
         return Ok(());
     }
 
+    if let Some(parent) = config.knowledge.local_db.parent().filter(|_| !config.queue.ephemeral) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let signals = Arc::new(VoiceSignals::default());
+    let confirm_signals = signals.clone();
+    let mut voice = VoiceSession::from_config(&config, stt, open_queue(&config).await?).with_confirmation(
+        config.confirmation.clone(),
+        // Ctrl+C may exit while waiting for an answer; nothing is being written then
+        Box::new(move |command| {
+            confirm_signals.busy.store(false, Ordering::SeqCst);
+            let confirmed = confirm_command(command);
+            confirm_signals.busy.store(true, Ordering::SeqCst);
+            confirmed
+        }),
+    );
+
     println!("{}", "✓ Voice mode ready".green());
     println!();
 
+    tokio::spawn(handle_voice_interrupt(signals.clone()));
+    // Not a held lock: confirmations and the stdin STT backend read stdin too
+    let mut read_line = |line: &mut String| std::io::stdin().read_line(line);
+    let mut throttle = TurnThrottle::new(&config.stt);
+
     // Voice loop
     loop {
-        println!("{}", "Press Enter to speak, or Ctrl+C to exit".dimmed());
-
-        // Wait for Enter
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
-
-        // Transcribe
-        match stt.transcribe() {
-            Ok(transcript) => {
-                println!();
-                println!("{} {}", "📝 You said:".blue(), transcript.bright_white());
-
-                // Process intent
-                match processor.process(&transcript) {
-                    Ok(command) => {
-                        if debug {
-                            println!("{} {:?}", "🎯 Intent:".cyan(), command.intent);
-                            println!("{} {}", "📋 Directive:".cyan(), command.directive);
-                            println!("{} {:.0}%", "🎲 Confidence:".cyan(), command.confidence * 100.0);
-                        }
-
-                        // Queue the processed command
-                        if let Err(e) = queue_command(&command.directive, &config, None).await {
-                            eprintln!("{} Failed to queue: {}", "✗".red(), e);
-                            continue;
-                        }
-
-                        // Speak confirmation with intent
-                        let response = format!("{:?}", command.intent);
-                        tts.speak_async(&response)?;
-                    }
-                    Err(e) => {
-                        eprintln!("{} Failed to process intent: {}", "✗".red(), e);
-                        continue;
+        tokio::time::sleep(throttle.next_turn_delay(Instant::now())).await;
+
+        let heard = listen(
+            voice.stt().push_to_talk(),
+            tts.as_ref(),
+            &mut read_line,
+            &signals,
+            &config.claude.model_aliases,
+            &mut || transcribe_recorded(voice.stt(), &recorder, &config, debug),
+        )?;
+        let transcription = match heard {
+            Heard::Exit => break,
+            Heard::Cancelled => continue,
+            Heard::Nothing(e) => {
+                if debug {
+                    eprintln!("{} {}", "✗ STT error:".red(), e);
+                }
+                if throttle.record_error() {
+                    eprintln!(
+                        "{} Speech-to-text failed {} times in a row: {}",
+                        "✗".red(),
+                        config.stt.max_consecutive_errors,
+                        e
+                    );
+                    println!("{}", "Paused. Press Enter to try again, or Ctrl+C to exit".dimmed());
+                    if read_line(&mut String::new())? == 0 {
+                        break;
                     }
                 }
+                continue;
+            }
+            Heard::Meta(meta) => {
+                throttle.record_success();
+                voice.apply_meta(&meta);
+                announce_meta(&meta, tts.as_ref(), &warning)?;
+                continue;
+            }
+            Heard::Utterance(transcription) => {
+                throttle.record_success();
+                transcription
+            }
+        };
+
+        println!();
+        println!("{} {}", "📝 You said:".blue(), transcription.text.bright_white());
+
+        // Queue what was said; Ctrl+C now waits until it's written
+        signals.busy.store(true, Ordering::SeqCst);
+        let outcome = voice.handle(transcription).await;
+        signals.busy.store(false, Ordering::SeqCst);
+        let turn = match outcome {
+            Ok(TurnOutcome::Commands(turn)) => turn,
+            Ok(TurnOutcome::Meta(meta)) => {
+                announce_meta(&meta, tts.as_ref(), &warning)?;
+                continue;
+            }
+            Ok(TurnOutcome::NeedsClarification(_)) => {
+                println!("{} Didn't catch an action, nothing queued", "?".yellow());
+                tts.speak_with("I didn't catch an action. Could you rephrase?", &warning)?;
+                continue;
             }
+            Ok(TurnOutcome::EndOfInput) => break,
+            Ok(TurnOutcome::Empty | TurnOutcome::Cancelled) => continue,
             Err(e) => {
-                if debug {
-                    eprintln!("{} {}", "✗ STT error:".red(), e);
-                }
+                eprintln!("{} {:#}", "✗".red(), e);
                 continue;
             }
+        };
+
+        let mut queued_intents = Vec::new();
+        for step in &turn.steps {
+            let command = &step.command;
+            if debug {
+                println!("{} {:?}", "🎯 Intent:".cyan(), command.intent);
+                println!("{} {}", "📋 Directive:".cyan(), command.directive);
+                println!("{} {:.0}%", "🎲 Confidence:".cyan(), command.confidence * 100.0);
+                if let Some(stt_confidence) = turn.transcript.confidence {
+                    println!("{} {:.0}%", "👂 Heard with:".cyan(), stt_confidence * 100.0);
+                }
+            }
+            match &step.outcome {
+                StepOutcome::Skipped => {
+                    println!("{}", "Skipped".dimmed());
+                    continue;
+                }
+                StepOutcome::Draft(task_id) => print_draft(task_id, command),
+                StepOutcome::Queued(task_id) => print_queued(task_id, &config),
+            }
+            queued_intents.push(format!("{:?}", command.intent));
+        }
+        if let Some(e) = &turn.error {
+            eprintln!("{} Failed to queue: {}", "✗".red(), e);
+            if matches!(queue_error(e), Some(QueueError::QueueFull { .. })) {
+                tts.speak_with("Queue is full, slow down", &warning)?;
+            }
+        }
+        if queued_intents.is_empty() {
+            continue;
         }
 
+        // Speak confirmation with intents while the next turn starts listening
+        let response = queued_intents.join(", then ");
+        tts.speak_async(&response)?;
+
         println!();
     }
+
+    tts.stop()?;
+    Ok(())
+}
+
+/// Voice loop state shared with the Ctrl+C handler
+#[derive(Debug, Default)]
+struct VoiceSignals {
+    /// Ctrl+C was pressed; the loop exits at its next check
+    interrupted: AtomicBool,
+    /// A command is being queued, so Ctrl+C must not exit mid-write
+    busy: AtomicBool,
+}
+
+/// Keeps a failing STT backend from spinning the voice loop
+#[derive(Debug)]
+struct TurnThrottle {
+    min_interval: Duration,
+    /// Errors in a row that pause the loop (0 = never)
+    max_errors: u32,
+    consecutive_errors: u32,
+    /// When the previous turn started
+    last_turn: Option<Instant>,
+}
+
+impl TurnThrottle {
+    fn new(config: &faster::config::SttConfig) -> Self {
+        Self {
+            min_interval: Duration::from_millis(config.min_turn_interval_ms),
+            max_errors: config.max_consecutive_errors,
+            consecutive_errors: 0,
+            last_turn: None,
+        }
+    }
+
+    /// How long to wait before starting a turn at `now`
+    ///
+    /// Turns start at least `min_interval` apart, doubling with each
+    /// consecutive error (up to 64x).
+    fn next_turn_delay(&mut self, now: Instant) -> Duration {
+        let interval = self.min_interval * 2u32.pow(self.consecutive_errors.min(6));
+        let delay = match self.last_turn {
+            Some(last) => interval.saturating_sub(now.saturating_duration_since(last)),
+            None => Duration::ZERO,
+        };
+        self.last_turn = Some(now + delay);
+        delay
+    }
+
+    /// Count a failed turn, returning true when the loop should pause
+    ///
+    /// The count restarts after a pause.
+    fn record_error(&mut self) -> bool {
+        self.consecutive_errors += 1;
+        if self.max_errors > 0 && self.consecutive_errors >= self.max_errors {
+            self.consecutive_errors = 0;
+            return true;
+        }
+        false
+    }
+
+    fn record_success(&mut self) {
+        self.consecutive_errors = 0;
+    }
 }
 
-fn test_installation() -> anyhow::Result<()> {
+/// Exit on Ctrl+C, right away when idle or after the current command is queued
+///
+/// Blocking reads can't be cancelled, so an idle loop is exited from here;
+/// nothing is in flight then, and child processes get the same SIGINT.
+async fn handle_voice_interrupt(session: Arc<VoiceSignals>) {
+    while tokio::signal::ctrl_c().await.is_ok() {
+        session.interrupted.store(true, Ordering::SeqCst);
+        if !session.busy.load(Ordering::SeqCst) {
+            println!();
+            println!("{}", "Exiting voice mode".dimmed());
+            std::process::exit(0);
+        }
+    }
+}
+
+/// What one listen cycle of the voice loop heard
+enum Heard {
+    Utterance(Transcription),
+    Meta(MetaCommand),
+    /// Nothing usable (e.g. a failed transcription); listen again
+    Nothing(anyhow::Error),
+    /// The user dismissed the prompt; listen again without counting an error
+    Cancelled,
+    /// Input ended or Ctrl+C was pressed
+    Exit,
+}
+
+/// Run one listen cycle: wait for the user, then transcribe what they say
+///
+/// Speech from the previous turn keeps playing while this waits, and is
+/// stopped as soon as the user starts talking (pressing Enter with
+/// push-to-talk, otherwise when the utterance arrives).
+fn listen(
+    push_to_talk: bool,
+    tts: &dyn TtsBackend,
+    read_line: &mut dyn FnMut(&mut String) -> std::io::Result<usize>,
+    session: &VoiceSignals,
+    aliases: &ModelAliases,
+    transcribe: &mut dyn FnMut() -> anyhow::Result<Transcription>,
+) -> anyhow::Result<Heard> {
+    let interrupted = || session.interrupted.load(Ordering::SeqCst);
+    if interrupted() {
+        return Ok(Heard::Exit);
+    }
+
+    if push_to_talk {
+        println!("{}", "Press Enter to speak, type e.g. \"use opus\", or Ctrl+C to exit".dimmed());
+
+        // Wait for Enter
+        let mut line = String::new();
+        if read_line(&mut line)? == 0 || interrupted() {
+            return Ok(Heard::Exit);
+        }
+        tts.stop()?;
+
+        if let Some(meta) = parse_meta_with(&line, aliases) {
+            return Ok(Heard::Meta(meta));
+        }
+    } else {
+        println!("{}", "Type a command, or Ctrl+D to exit".dimmed());
+    }
+
+    let transcription = transcribe();
+    if !push_to_talk {
+        tts.stop()?;
+    }
+    if interrupted() {
+        return Ok(Heard::Exit);
+    }
+
+    Ok(match transcription {
+        Ok(transcription) => match parse_meta_with(&transcription.text, aliases) {
+            Some(meta) => Heard::Meta(meta),
+            None => Heard::Utterance(transcription),
+        },
+        Err(e) if e.is::<EndOfInput>() => Heard::Exit,
+        Err(e) if e.is::<Cancelled>() => Heard::Cancelled,
+        Err(e) => Heard::Nothing(e),
+    })
+}
+
+/// Transcribe one utterance, saving raw audio alongside when configured
+fn transcribe_recorded(
+    stt: &dyn audio::SttBackend,
+    recorder: &Recorder,
+    config: &Config,
+    debug: bool,
+) -> anyhow::Result<Transcription> {
+    // Record raw audio alongside the dictation dialog for debugging
+    let active_recording = if config.audio.save_recordings {
+        match recorder.start() {
+            Ok(active) => Some(active),
+            Err(e) => {
+                eprintln!("{} Recording unavailable: {}", "⚠".yellow(), e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let transcription = stt.transcribe();
+
+    if let Some(active) = active_recording {
+        let path = recording::recording_path(
+            &config.audio.recordings_dir,
+            &config.audio.recording_template,
+            chrono::Utc::now(),
+        );
+        match active.save(&path) {
+            Ok(()) if debug => println!("{} {}", "💾 Recording:".cyan(), path.display()),
+            Ok(()) => {}
+            Err(e) => eprintln!("{} Failed to save recording: {}", "⚠".yellow(), e),
+        }
+    }
+
+    transcription
+}
+
+/// Spoken and printed feedback for a voice mode meta-command
+fn announce_meta(meta: &MetaCommand, tts: &dyn TtsBackend, warning: &SpeakOptions) -> anyhow::Result<()> {
+    match meta {
+        MetaCommand::UseModel(model) => {
+            println!("{} Using {} for new tasks", "✓".green(), model.bright_cyan());
+            tts.speak_async(&format!("Using {}", model))?;
+        }
+        MetaCommand::UnknownModel(model) => {
+            eprintln!("{} Unknown model '{}'", "✗".red(), model);
+            tts.speak_with(&format!("I don't know the model {}", model), warning)?;
+        }
+    }
+    Ok(())
+}
+
+fn test_installation(config_file: Option<&Path>) -> anyhow::Result<()> {
     println!("{}", "Testing Faster installation...".bright_cyan());
     println!();
 
@@ -258,8 +973,8 @@ fn test_installation() -> anyhow::Result<()> {
 
     // Check Claude CLI
     print!("Claude Code CLI: ");
-    match std::process::Command::new("claude").arg("--version").output() {
-        Ok(_) => println!("{} Installed", "✓".green()),
+    match ClaudeExecutor::new("claude").version() {
+        Ok(version) => println!("{} {}", "✓".green(), if version.is_empty() { "Installed" } else { &version }),
         Err(_) => {
             println!("{} Not found", "✗".red());
             println!("  Install from: https://claude.ai/code");
@@ -268,14 +983,15 @@ fn test_installation() -> anyhow::Result<()> {
 
     // Check config file
     print!("Configuration: ");
-    if Config::path().exists() {
-        println!("{} {}", "✓".green(), Config::path().display());
+    let config_path = config_path(config_file)?;
+    if config_path.exists() {
+        println!("{} {}", "✓".green(), config_path.display());
     } else {
         println!("{} Not found (will create on first run)", "⚠".yellow());
     }
 
     // Check knowledge DB
-    let config = load_or_create_config()?;
+    let config = load_or_create_config(config_file)?;
     print!("Knowledge database: ");
     if config.knowledge.local_db.exists() {
         println!("{} {}", "✓".green(), config.knowledge.local_db.display());
@@ -293,30 +1009,105 @@ fn test_installation() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn handle_config_command(show: bool, edit: bool) -> anyhow::Result<()> {
-    let config_path = Config::path();
+async fn run_doctor(config: &Config) -> anyhow::Result<()> {
+    let diagnostics = doctor::run_all(config).await;
+    println!("{}", format_diagnostics(&diagnostics));
 
-    if show {
-        if config_path.exists() {
-            let config = Config::load(&config_path)?;
-            let toml = toml::to_string_pretty(&config)?;
-            println!("{}", toml);
-        } else {
-            println!("{} Config file not found", "✗".red());
-            println!("Run {} to create", "faster setup".cyan());
-        }
-    } else if edit {
-        // Open in default editor
-        let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
-        std::process::Command::new(editor)
-            .arg(&config_path)
-            .status()?;
-    } else {
-        println!("Config file: {}", config_path.display());
-        println!();
-        println!("Use:");
-        println!("  {} to view configuration", "faster config --show".cyan());
-        println!("  {} to edit configuration", "faster config --edit".cyan());
+    let critical = diagnostics.iter().filter(|d| d.is_critical_failure()).count();
+    if critical > 0 {
+        anyhow::bail!("{} critical check(s) failed", critical);
+    }
+    Ok(())
+}
+
+/// Diagnostics under their group headings, groups in first-seen order
+fn format_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    let mut groups: Vec<&str> = Vec::new();
+    for diagnostic in diagnostics {
+        if !groups.contains(&diagnostic.group) {
+            groups.push(diagnostic.group);
+        }
+    }
+
+    let mut lines = Vec::new();
+    for group in groups {
+        lines.push(group.bold().to_string());
+        for diagnostic in diagnostics.iter().filter(|d| d.group == group) {
+            let mark = if diagnostic.ok {
+                "✓".green()
+            } else if diagnostic.critical {
+                "✗".red()
+            } else {
+                "⚠".yellow()
+            };
+            lines.push(format!("  {} {}", mark, diagnostic.message));
+            if let Some(hint) = &diagnostic.fix_hint {
+                lines.push(format!("    {} {}", "→".dimmed(), hint));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// Each intent with its description and examples, plus any routing config that's been customized
+fn format_intents(config: &Config) -> String {
+    let defaults = faster::config::ConfirmationConfig::default();
+    let mut lines = Vec::new();
+    for intent in Intent::all() {
+        lines.push(format!("{} {}", intent_badge(Some(*intent)).trim_end(), intent.description()));
+        for example in intent.examples() {
+            lines.push(format!("    {}", format!("\"{}\"", example).dimmed()));
+        }
+        let mode = config.confirmation.mode_for(Some(*intent));
+        if mode != defaults.mode_for(Some(*intent)) {
+            lines.push(format!("    confirm: {}", format!("{:?}", mode).to_lowercase()));
+        }
+        lines.push(String::new());
+    }
+
+    if !config.aliases.is_empty() {
+        lines.push("Aliases:".bold().to_string());
+        for (phrase, command) in &config.aliases {
+            lines.push(format!("    {} → {}", phrase.bright_cyan(), command));
+        }
+        lines.push(String::new());
+    }
+    lines.push(
+        format!(
+            "Classifications under {:.0}% confidence count as uncertain (intent.confidence_threshold)",
+            config.intent.confidence_threshold * 100.0
+        )
+        .dimmed()
+        .to_string(),
+    );
+
+    lines.join("\n")
+}
+
+fn handle_config_command(config_file: Option<&Path>, show: bool, edit: bool) -> anyhow::Result<()> {
+    let config_path = config_path(config_file)?;
+
+    if show {
+        if config_path.exists() {
+            let config = Config::load(&config_path)?;
+            let toml = toml::to_string_pretty(&config)?;
+            println!("{}", toml);
+        } else {
+            println!("{} Config file not found", "✗".red());
+            println!("Run {} to create", "faster setup".cyan());
+        }
+    } else if edit {
+        // Open in default editor
+        let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
+        std::process::Command::new(editor)
+            .arg(&config_path)
+            .status()?;
+    } else {
+        println!("Config file: {}", config_path.display());
+        println!();
+        println!("Use:");
+        println!("  {} to view configuration", "faster config --show".cyan());
+        println!("  {} to edit configuration", "faster config --edit".cyan());
     }
 
     Ok(())
@@ -359,11 +1150,22 @@ fn handle_knowledge_command(action: KnowledgeCommands) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn setup_wizard() -> anyhow::Result<()> {
+fn handle_audio_command(action: AudioCommands) -> anyhow::Result<()> {
+    match action {
+        AudioCommands::Play { file } => {
+            println!("{} {}", "▶ Playing".cyan(), file.display());
+            recording::play(&file)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn setup_wizard(config_file: Option<&Path>) -> anyhow::Result<()> {
     println!("{}", "🚀 Faster Setup Wizard".bright_green().bold());
     println!();
 
-    let config_path = Config::path();
+    let config_path = config_path(config_file)?;
     let config_dir = config_path.parent().unwrap();
 
     // Create config directory
@@ -376,7 +1178,7 @@ fn setup_wizard() -> anyhow::Result<()> {
     println!("{} Created config file: {}", "✓".green(), config_path.display());
     println!();
     println!("Default configuration:");
-    println!("  • STT: macOS native");
+    println!("  • STT: macOS native ({})", config.stt.language);
     println!("  • TTS: macOS native (Samantha voice)");
     println!("  • Model: Llama 3.2 (3B)");
     println!("  • Confirmation: Smart mode");
@@ -387,78 +1189,318 @@ fn setup_wizard() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Intent processor with the aliases, extractors and filler words from `config`
+fn intent_processor(config: &Config) -> IntentProcessor {
+    IntentProcessor::new(config.intent.confidence_threshold)
+        .with_aliases(config.aliases.clone())
+        .with_extractors(config.intent.extractors.clone())
+        .with_fillers(config.intent.fillers.clone())
+        .with_directive_cleaning(config.intent.clean_directive)
+}
+
+/// Classify a typed command, keeping the text verbatim
+///
+/// Unlike speech, typed commands have no filler words to strip, so only the
+/// intent (or the caller's override, at full confidence) is attached. An
+/// alias is still expanded first.
+fn classify_typed(processor: &IntentProcessor, text: &str, intent: Option<Intent>) -> Command {
+    let alias = processor.expand_alias(text);
+    let text = alias.as_ref().map_or(text, |a| a.to_entity.as_str());
+
+    let (intent, confidence) = match intent {
+        Some(intent) => (intent, 1.0),
+        None => processor.classify_scores(text)[0],
+    };
+
+    Command::new(intent, text, vec![], confidence)
+}
+
 async fn queue_command(
-    command: &str,
+    command: &Command,
     config: &Config,
     model_override: Option<String>,
-) -> anyhow::Result<()> {
+    block: bool,
+) -> anyhow::Result<String> {
+    queue_command_after(command, config, model_override, block, None, &QueueOptions::default()).await
+}
+
+/// What a queued command carries besides its model, set in the same insert
+#[derive(Debug, Default)]
+struct QueueOptions {
+    /// Task whose Claude session to continue
+    parent: Option<String>,
+    /// Run time limit instead of `claude.timeout_secs`
+    timeout: Option<Duration>,
+    /// Files appended to the prompt, already resolved against the current directory
+    context_files: Vec<PathBuf>,
+}
+
+/// Queue a command that isn't claimed until task `after` completes
+async fn queue_command_after(
+    command: &Command,
+    config: &Config,
+    model_override: Option<String>,
+    block: bool,
+    after: Option<&str>,
+    options: &QueueOptions,
+) -> anyhow::Result<String> {
     // Ensure database directory exists
-    if let Some(parent) = config.knowledge.local_db.parent() {
+    if let Some(parent) = config.knowledge.local_db.parent().filter(|_| !config.queue.ephemeral) {
         std::fs::create_dir_all(parent)?;
     }
 
-    let queue = TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await?;
+    let queue = open_queue(config).await?;
 
-    let model = model_override.or_else(|| {
-        if !config.claude.model.is_empty() {
-            Some(config.claude.model.clone())
-        } else {
-            None
+    let model = match model_override {
+        Some(model) => Some(config.claude.model_aliases.resolve(&model)),
+        None => config.claude.default_model(),
+    };
+
+    let task = || {
+        let mut task = queue.build_command(command);
+        if let Some(model) = &model {
+            task = task.model(model);
         }
-    });
+        if let Some(after) = after {
+            task = task.after(after);
+        }
+        if let Some(parent) = &options.parent {
+            task = task.continues(parent);
+        }
+        if let Some(timeout) = options.timeout {
+            task = task.timeout(timeout);
+        }
+        for path in &options.context_files {
+            task = task.context_file(path);
+        }
+        task
+    };
+
+    // Uncertain classifications wait as drafts rather than running
+    if !IntentProcessor::new(config.intent.confidence_threshold).is_confident(command) {
+        let task_id = task().draft().enqueue().await?;
+        print_draft(&task_id, command);
+        queue.close().await?;
+        return Ok(task_id);
+    }
+
+    let task_id = loop {
+        match task().enqueue().await {
+            Err(e) if block && matches!(e, FasterError::Queue(QueueError::QueueFull { .. })) => {
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            }
+            result => break result?,
+        }
+    };
+
+    print_queued(&task_id, config);
+    queue.close().await?;
+    Ok(task_id)
+}
 
-    let task_id = queue.enqueue(command, model).await?;
+fn print_draft(task_id: &str, command: &Command) {
+    println!(
+        "{} Saved as draft [{}] ({:.0}% confident it's {})",
+        "✎".magenta(),
+        task_id.bright_cyan(),
+        command.confidence * 100.0,
+        command.intent
+    );
+    println!("Run {} to queue it", format!("faster promote {}", task_id).cyan());
+}
 
+fn print_queued(task_id: &str, config: &Config) {
     println!("{} Queued [{}]", "✓".green(), task_id.bright_cyan());
+    if !config.queue.ephemeral {
+        println!();
+        println!("Run {} to see queue status", "faster status".cyan());
+        println!("Run {} to process queue", "faster daemon".cyan());
+    }
+}
+
+/// Full id of the task matching an id or unique prefix
+async fn resolve_task_id(config: &Config, prefix: &str) -> anyhow::Result<String> {
+    let queue = open_queue(config).await?;
+    Ok(queue.get_required(prefix).await?.id)
+}
+
+/// Ask on the terminal whether to queue a command; Enter means yes
+fn confirm_command(command: &Command) -> anyhow::Result<bool> {
+    println!(
+        "{} Queue {} \"{}\"? [Y/n]",
+        "?".yellow(),
+        command.intent,
+        command.directive
+    );
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(!matches!(answer.trim().to_ascii_lowercase().as_str(), "n" | "no"))
+}
+
+async fn run_daemon(config: &Config, force: bool, once: bool) -> anyhow::Result<()> {
+    println!("{}", "🚀 Starting daemon...".bright_green());
+
+    ClaudeExecutor::new(&config.claude.cli_path).check_available()?;
+    let queue = open_queue(config).await?;
+
+    let heartbeat = Heartbeat::default();
+    if let Some(addr) = &config.observability.health_addr {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        println!("{} Health checks on http://{}/healthz", "✓".green(), listener.local_addr()?);
+        let state = Arc::new(HealthState {
+            queue: queue.clone(),
+            heartbeat: heartbeat.clone(),
+            max_tick_age: Duration::from_secs(config.observability.max_tick_age_secs),
+            events: EventHub::default(),
+        });
+        let (events, watched) = (state.events.clone(), queue.clone());
+        tokio::spawn(async move {
+            let published = match EventSource::start(&watched, false).await {
+                Ok(source) => events.publish_from(source, TAIL_POLL_INTERVAL).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = published {
+                tracing::warn!("Stopped publishing /events: {:#}", e);
+            }
+        });
+        tokio::spawn(server::serve(listener, state));
+    }
+
+    let shutdown = Shutdown::default();
+    let grace = Duration::from_millis(config.daemon.shutdown_grace_ms);
+    let watcher = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move { daemon::escalate_shutdown(&shutdown, grace, interrupted).await }
+    });
+
+    let mut daemon = Daemon::new(queue, config.clone()).with_heartbeat(heartbeat).with_shutdown(shutdown);
+    daemon.lock(force).await?;
+    daemon.reclaim_stale().await?;
+
+    let result = match once {
+        true => daemon.run_once().await.map(Some),
+        false => daemon.run().await.map(|()| None),
+    };
+    watcher.abort();
+    // Printed before cleanup so an error exit still reports what was done
     println!();
-    println!("Run {} to see queue status", "faster status".cyan());
-    println!("Run {} to process queue", "faster daemon".cyan());
+    println!("{} {}", "■".bright_blue(), daemon.session_summary().await);
+    daemon.unlock().await?;
+    daemon.close().await?;
 
+    if let Some(summary) = result? {
+        if summary.failed > 0 {
+            anyhow::bail!("{} of {} task(s) failed", summary.failed, summary.processed);
+        }
+    }
     Ok(())
 }
 
-async fn run_daemon(config: &Config) -> anyhow::Result<()> {
-    println!("{}", "🚀 Starting daemon...".bright_green());
+/// Resolves at the next Ctrl+C, or never if it can't be listened for
+async fn interrupted() {
+    if tokio::signal::ctrl_c().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
 
-    let queue = TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await?;
+/// Drain up to `max` tasks in the foreground, failing if any task failed
+async fn run_tasks(config: &Config, max: Option<usize>) -> anyhow::Result<()> {
+    let queue = open_queue(config).await?;
+    let executor = ClaudeExecutor::from_config(&config.claude);
+
+    let summary = daemon::drain(&queue, config, &executor, max).await?;
+    queue.close().await?;
+    println!(
+        "{} Processed {} task(s), {} failed",
+        if summary.failed == 0 { "✓".green() } else { "✗".red() },
+        summary.processed,
+        summary.failed
+    );
+
+    if summary.failed > 0 {
+        anyhow::bail!("{} of {} task(s) failed", summary.failed, summary.processed);
+    }
 
-    loop {
-        // Get next task
-        if let Some(task) = queue.dequeue().await? {
-            println!("{} [{}] {}", "→".blue(), task.id.bright_cyan(), task.command);
+    Ok(())
+}
 
-            // Mark as running
-            queue.update_status(&task.id, TaskStatus::Running).await?;
+/// Filters for `status`; an explicit `--status` shows finished tasks even without `--all`
+fn status_query(all: bool, status: Option<TaskStatus>, since: Option<chrono::Duration>) -> ListQuery {
+    ListQuery {
+        status,
+        since: since.map(|ago| chrono::Utc::now() - ago),
+        active_only: !all && status.is_none(),
+        ..ListQuery::default()
+    }
+}
 
-            // Create executor
-            let mut executor = ClaudeExecutor::new(&config.claude.cli_path);
-            if let Some(model) = &task.model {
-                executor = executor.with_model(model);
-            }
+/// Zone to show times in: UTC when asked, else `general.timezone`
+fn display_timezone(config: &Config, utc: bool) -> anyhow::Result<Timezone> {
+    if utc {
+        return Ok(Timezone::Utc);
+    }
+    Ok(config.general.timezone()?)
+}
 
-            // Execute
-            match executor.execute(&task.command) {
-                Ok(_) => {
-                    queue.update_status(&task.id, TaskStatus::Completed).await?;
-                    println!("{} [{}] Completed", "✓".green(), task.id.bright_cyan());
-                }
-                Err(e) => {
-                    queue.fail(&task.id, &e.to_string()).await?;
-                    eprintln!("{} [{}] Failed: {}", "✗".red(), task.id.bright_cyan(), e);
-                }
-            }
+/// How `status` splits tasks into sections
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GroupBy {
+    Tag,
+}
 
-            println!();
-        } else {
-            // No tasks, wait a bit
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+impl std::str::FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "tag" | "tags" => Ok(GroupBy::Tag),
+            _ => Err(format!("can't group by '{}' (expected tag)", s)),
+        }
+    }
+}
+
+/// Section for tasks without tags in `group_by_tag`
+const UNTAGGED: &str = "(untagged)";
+
+/// Tasks under each of their tags, tags in order and untagged last
+///
+/// A task with several tags appears in each of their sections. Tasks keep
+/// their order within a section.
+fn group_by_tag(tasks: &[Task]) -> Vec<(&str, Vec<&Task>)> {
+    let mut groups: std::collections::BTreeMap<&str, Vec<&Task>> = std::collections::BTreeMap::new();
+    let mut untagged = Vec::new();
+    for task in tasks {
+        if task.tags.is_empty() {
+            untagged.push(task);
         }
+        for tag in &task.tags {
+            groups.entry(tag.as_str()).or_default().push(task);
+        }
+    }
+
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    if !untagged.is_empty() {
+        groups.push((UNTAGGED, untagged));
     }
+    groups
 }
 
-async fn show_status(config: &Config, show_all: bool) -> anyhow::Result<()> {
-    let queue = TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await?;
-    let tasks = queue.list().await?;
+async fn show_status(
+    config: &Config,
+    query: &ListQuery,
+    json: bool,
+    wide: bool,
+    group_by: Option<GroupBy>,
+    timezone: &Timezone,
+) -> anyhow::Result<()> {
+    let queue = open_queue(config).await?;
+    let tasks = queue.query(query).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&tasks)?);
+        return Ok(());
+    }
 
     if tasks.is_empty() {
         println!("{}", "No tasks in queue".dimmed());
@@ -468,102 +1510,2148 @@ async fn show_status(config: &Config, show_all: bool) -> anyhow::Result<()> {
     println!("{}", "Task Queue".bright_cyan().bold());
     println!();
 
-    for task in tasks {
-        // Skip completed if not showing all
-        if !show_all && (task.status == TaskStatus::Completed || task.status == TaskStatus::Cancelled) {
-            continue;
-        }
-
-        let status_icon = match task.status {
-            TaskStatus::Queued => "⏳",
-            TaskStatus::Running => "→",
-            TaskStatus::Completed => "✓",
-            TaskStatus::Failed => "✗",
-            TaskStatus::Cancelled => "⊘",
-        };
-
-        let status_color = match task.status {
-            TaskStatus::Queued => task.status.as_str().yellow(),
-            TaskStatus::Running => task.status.as_str().blue(),
-            TaskStatus::Completed => task.status.as_str().green(),
-            TaskStatus::Failed => task.status.as_str().red(),
-            TaskStatus::Cancelled => task.status.as_str().dimmed(),
-        };
+    // Piped output is for other tools, so only truncate on a terminal
+    let width = (!wide && std::io::stdout().is_terminal()).then(terminal_width);
+    let layout = StatusLayout::for_tasks(&tasks, config.intent.confidence_threshold);
 
-        println!("{} [{}] {} {}",
-            status_icon,
-            task.id.bright_cyan(),
-            status_color,
-            task.command.dimmed()
-        );
+    let print_task = |task: &Task| {
+        println!("{}", format_status_row(task, &layout, width));
 
-        if let Some(error) = task.error {
+        if let Some(at) = task.scheduled_for.filter(|_| task.status == TaskStatus::Queued) {
+            println!("    {}: {}", "Scheduled".yellow(), timezone.format(at));
+        }
+        if let Some(error) = &task.error {
             println!("    {}: {}", "Error".red(), error);
         }
+    };
+
+    match group_by {
+        None => tasks.iter().for_each(print_task),
+        Some(GroupBy::Tag) => {
+            for (i, (tag, group)) in group_by_tag(&tasks).into_iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("{} {}", tag.bold(), format!("({})", group.len()).dimmed());
+                group.into_iter().for_each(print_task);
+            }
+        }
     }
 
     Ok(())
 }
 
-async fn cancel_task(config: &Config, task_id: &str) -> anyhow::Result<()> {
-    let queue = TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await?;
+/// Widths of the status table's fixed columns, sized to their longest entry
+struct StatusLayout {
+    id: usize,
+    status: usize,
+    intent: usize,
+    /// Commands classified below this confidence are shown in italics
+    low_confidence: f32,
+}
 
-    if let Some(task) = queue.get(task_id).await? {
-        if task.status == TaskStatus::Running {
-            println!("{} Cannot cancel running task", "✗".red());
-            println!("  Kill the daemon to stop it");
-            return Ok(());
+impl StatusLayout {
+    fn for_tasks(tasks: &[Task], low_confidence: f32) -> Self {
+        let widest = |f: &dyn Fn(&Task) -> usize| tasks.iter().map(f).max().unwrap_or(0);
+        Self {
+            id: widest(&|t| t.id.chars().count()),
+            status: widest(&|t| t.status.as_str().len()),
+            intent: widest(&|t| t.intent.map_or(0, |i| intent_badge_len(Some(i)))),
+            low_confidence,
         }
+    }
+}
 
-        queue.update_status(task_id, TaskStatus::Cancelled).await?;
-        println!("{} Cancelled [{}]", "✓".green(), task_id.bright_cyan());
-    } else {
-        println!("{} Task not found: {}", "✗".red(), task_id);
+/// One aligned `status` row, with the command cut to fit `width` columns if given
+///
+/// Columns are padded before coloring so escape codes don't skew alignment.
+fn format_status_row(task: &Task, layout: &StatusLayout, width: Option<usize>) -> String {
+    let status = color_status(task.status, format!("{:<width$}", task.status.as_str(), width = layout.status));
+
+    let id = format!("{:<width$}", task.id, width = layout.id);
+    let intent_pad = " ".repeat(layout.intent - intent_badge_len(task.intent));
+
+    // icon and space, then id, status and intent columns with their gaps
+    let prefix_len = ICON_WIDTH + 1 + layout.id + 2 + layout.status + 2 + layout.intent;
+    let command = task.command.replace('\n', " ");
+    let command = match width {
+        Some(width) => truncate_to_width(&command, width.saturating_sub(prefix_len).max(MIN_COMMAND_WIDTH)),
+        None => command,
+    };
+    let guessed = task.confidence.is_some_and(|c| c < layout.low_confidence);
+    let command = if guessed { command.dimmed().italic() } else { command.dimmed() };
+
+    let icon = status_icon(task.status);
+    format!(
+        "{}{} {}  {}  {}{}{}",
+        icon,
+        " ".repeat(ICON_WIDTH.saturating_sub(icon.width())),
+        id.bright_cyan(),
+        status,
+        intent_badge(task.intent),
+        intent_pad,
+        command
+    )
+}
+
+/// Terminal columns of the widest status icon; ⏳ takes two
+const ICON_WIDTH: usize = 2;
+
+fn status_icon(status: TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Queued => "⏳",
+        TaskStatus::Running => "→",
+        TaskStatus::Completed => "✓",
+        TaskStatus::Failed => "✗",
+        TaskStatus::Cancelled => "⊘",
+        TaskStatus::Draft => "✎",
+        TaskStatus::Blocked => "⏸",
     }
+}
 
-    Ok(())
+/// `text` in the color used for `status`
+fn color_status(status: TaskStatus, text: String) -> ColoredString {
+    match status {
+        TaskStatus::Queued => text.yellow(),
+        TaskStatus::Running => text.blue(),
+        TaskStatus::Completed => text.green(),
+        TaskStatus::Failed => text.red(),
+        TaskStatus::Cancelled => text.dimmed(),
+        TaskStatus::Draft => text.magenta(),
+        TaskStatus::Blocked => text.cyan(),
+    }
 }
 
-async fn clear_completed(config: &Config) -> anyhow::Result<()> {
-    let queue = TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await?;
-    let count = queue.clear_completed().await?;
+/// How often `tail` checks the queue for changes
+const TAIL_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
-    println!("{} Cleared {} completed task(s)", "✓".green(), count);
+/// Print task transitions as they happen until Ctrl+C
+async fn tail(config: &Config, from_start: bool, timezone: &Timezone) -> anyhow::Result<()> {
+    let queue = open_queue(config).await?;
+    let mut events = EventSource::start(&queue, from_start).await?;
+
+    let result = tokio::select! {
+        result = async {
+            loop {
+                let event = events.next(TAIL_POLL_INTERVAL).await?;
+                println!("{}", format_event(&event, timezone));
+            }
+        } => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+    drop(events);
+    queue.close().await?;
+    result
+}
+
+/// One `tail` line: time, status, task and command, plus the error for failures
+fn format_event(event: &TaskEvent, timezone: &Timezone) -> String {
+    let mut line = format!(
+        "{} {} {}  {}  {}",
+        timezone.to_local(event.at).format("%H:%M:%S").to_string().dimmed(),
+        status_icon(event.status),
+        event.task_id.bright_cyan(),
+        color_status(event.status, format!("{:<9}", event.status.as_str())),
+        event.command.replace('\n', " ")
+    );
+    if let Some(error) = &event.error {
+        line.push_str(&format!(" {}", format!("({})", error).red()));
+    }
+    line
+}
+
+/// Narrowest the command column gets, however small the terminal
+const MIN_COMMAND_WIDTH: usize = 10;
 
+/// Cut `s` to `width` characters, ending in an ellipsis if anything was dropped
+fn truncate_to_width(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_string();
+    }
+
+    // Leave a column for the ellipsis; a wide character that won't fit is dropped whole
+    let mut cut = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        used += c.width().unwrap_or(0);
+        if used > width.saturating_sub(1) {
+            break;
+        }
+        cut.push(c);
+    }
+    cut.push('…');
+    cut
+}
+
+/// Terminal width from `$COLUMNS` or `tput`, falling back to 80
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .or_else(|| {
+            let output = std::process::Command::new("tput")
+                .arg("cols")
+                .stderr(std::process::Stdio::inherit())
+                .output()
+                .ok()?;
+            String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+        })
+        .unwrap_or(80)
+}
+
+/// Colored `[INTENT] ` label, empty for tasks queued without one
+fn intent_badge(intent: Option<Intent>) -> String {
+    let Some(intent) = intent else {
+        return String::new();
+    };
+
+    let label = format!("[{}]", intent);
+    let label = match intent {
+        Intent::Orchestrate => label.magenta(),
+        Intent::Research => label.cyan(),
+        Intent::Code => label.green(),
+        Intent::Test => label.yellow(),
+    };
+
+    format!("{} ", label)
+}
+
+/// Visible width of `intent_badge`, without color codes
+fn intent_badge_len(intent: Option<Intent>) -> usize {
+    intent.map_or(0, |i| i.to_string().len() + 3)
+}
+
+async fn show_logs(config: &Config, task_id: &str) -> anyhow::Result<()> {
+    let queue = open_queue(config).await?;
+    let task = queue.get_required(task_id).await?;
+    queue.close().await?;
+
+    match task_logs(&task)? {
+        Some(logs) => print!("{}", logs),
+        None => println!("{}", "No output recorded for this task".dimmed()),
+    }
     Ok(())
 }
 
-fn load_or_create_config() -> anyhow::Result<Config> {
-    let config_path = Config::path();
+/// A task's output file if it's still on disk, else the output kept in the DB
+fn task_logs(task: &Task) -> anyhow::Result<Option<String>> {
+    if let Some(path) = task.output_path.as_deref().filter(|p| p.exists()) {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read output file {}", path.display()))?;
+        return Ok(Some(faster::text::decode_output(&bytes).0));
+    }
+    Ok(task.output.clone())
+}
+
+async fn get_task(
+    config: &Config,
+    task_id: &str,
+    json: bool,
+    prompt: bool,
+    attempts: bool,
+    env: bool,
+    timezone: &Timezone,
+) -> anyhow::Result<()> {
+    let queue = open_queue(config).await?;
 
-    if config_path.exists() {
-        Config::load(&config_path)
+    let task = queue.get_required(task_id).await?;
+
+    if attempts {
+        let attempts = queue.attempts(&task.id).await?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&attempts)?);
+        } else if attempts.is_empty() {
+            println!("{}", "No attempts yet (task hasn't run)".dimmed());
+        } else {
+            for attempt in &attempts {
+                println!("{}", format_attempt(attempt, timezone));
+            }
+        }
+    } else if env {
+        match &task.env_snapshot {
+            Some(snapshot) if json => println!("{}", serde_json::to_string_pretty(snapshot)?),
+            Some(snapshot) => println!("{}", format_env_snapshot(snapshot)),
+            None => println!("{}", "No environment recorded for this task".dimmed()),
+        }
+    } else if prompt {
+        match &task.rendered_prompt {
+            Some(rendered) => println!("{}", rendered),
+            None => println!("{}", "Prompt not rendered yet (task hasn't run)".dimmed()),
+        }
+    } else if json {
+        println!("{}", serde_json::to_string_pretty(&task)?);
     } else {
-        // Create default config silently
-        let config_dir = config_path.parent().unwrap();
-        std::fs::create_dir_all(config_dir)?;
+        println!("{}", format_task_details(&task, timezone));
+    }
 
-        let config = Config::default();
-        config.save(&config_path)?;
+    Ok(())
+}
 
-        Ok(config)
+/// An environment snapshot as `faster get --env` shows it
+fn format_env_snapshot(snapshot: &EnvSnapshot) -> String {
+    let mut lines = vec![format!("  {:<8} {}", "Cwd:", snapshot.cwd.display())];
+    if let Some(branch) = &snapshot.git_branch {
+        lines.push(format!("  {:<8} {}", "Branch:", branch));
+    }
+    if let Some(commit) = &snapshot.git_commit {
+        lines.push(format!("  {:<8} {}", "Commit:", commit));
     }
+    if !snapshot.env.is_empty() {
+        lines.push("  Env:".to_string());
+        lines.extend(snapshot.env.iter().map(|(name, value)| format!("    {}={}", name, value)));
+    }
+    lines.join("\n")
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+fn format_attempt(attempt: &TaskAttempt, timezone: &Timezone) -> String {
+    let mut line = format!(
+        "  #{} {:<9} {} {}",
+        attempt.attempt_no,
+        attempt.status.as_str(),
+        timezone.to_local(attempt.started_at).format("%Y-%m-%d %H:%M:%S"),
+        attempt.model.as_deref().unwrap_or("default").dimmed()
+    );
+    if let Some(completed) = attempt.completed_at {
+        let secs = (completed - attempt.started_at).num_milliseconds() as f64 / 1000.0;
+        line.push_str(&format!(" ({:.1}s)", secs));
+    }
+    if let Some(error) = &attempt.error {
+        line.push_str(&format!("\n     {}: {}", "Error".red(), error));
+    }
+    line
+}
 
-    #[test]
-    fn test_cli_parsing() {
-        let cli = Cli::parse_from(["faster", "--debug"]);
-        assert!(cli.debug);
+fn format_task_details(task: &Task, timezone: &Timezone) -> String {
+    let timestamp = |t: Option<chrono::DateTime<chrono::Utc>>| {
+        t.map(|t| timezone.format(t)).unwrap_or_else(|| "-".to_string())
+    };
+
+    let mut lines = vec![
+        format!("{} [{}]", "Task".bright_cyan().bold(), task.id.bright_cyan()),
+        format!("  {:<11} {}", "Command:", task.command),
+    ];
+    if let Some(original) = &task.original_command {
+        lines.push(format!("  {:<11} {:?}", "Submitted:", original));
+    }
+    lines.extend([
+        format!("  {:<11} {}", "Status:", task.status.as_str()),
+        format!("  {:<11} {}", "Model:", task.model.as_deref().unwrap_or("-")),
+        format!(
+            "  {:<11} {}",
+            "Intent:",
+            task.intent.map(|i| i.to_string()).unwrap_or_else(|| "-".to_string())
+        ),
+        format!(
+            "  {:<11} {}",
+            "Confidence:",
+            task.confidence
+                .map(|c| format!("{:.0}%", c * 100.0))
+                .unwrap_or_else(|| "n/a".to_string())
+        ),
+        format!("  {:<11} {}", "Created:", timezone.format(task.created_at)),
+        format!("  {:<11} {}", "Started:", timestamp(task.started_at)),
+        format!("  {:<11} {}", "Completed:", timestamp(task.completed_at)),
+    ]);
+
+    if task.priority != 0 {
+        lines.push(format!("  {:<11} {}", "Priority:", task.priority));
+    }
+    if !task.tags.is_empty() {
+        lines.push(format!("  {:<11} {}", "Tags:", task.tags.join(", ")));
+    }
+    if let Some(cwd) = &task.cwd {
+        lines.push(format!("  {:<11} {}", "Cwd:", cwd.display()));
+    }
+    if !task.metadata.is_empty() {
+        let pairs: Vec<String> = task.metadata.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+        lines.push(format!("  {:<11} {}", "Metadata:", pairs.join(", ")));
+    }
+    if let Some(scheduled_for) = task.scheduled_for {
+        lines.push(format!("  {:<11} {}", "Scheduled:", timezone.format(scheduled_for)));
+    }
+    if let Some(ms) = task.timeout_ms.filter(|&ms| ms > 0) {
+        lines.push(format!("  {:<11} {}s", "Timeout:", ms as f64 / 1000.0));
+    }
+    if let Some(replayed_from) = &task.replayed_from {
+        lines.push(format!("  {:<11} {}", "Replays:", replayed_from));
+    }
+    if let Some(processed_by) = &task.processed_by {
+        lines.push(format!("  {:<11} {}", "Processed:", processed_by));
+    }
+    if let Some(session_id) = &task.session_id {
+        lines.push(format!("  {:<11} {}", "Session:", session_id));
+    }
+    if let Some(parent_id) = &task.parent_id {
+        lines.push(format!("  {:<11} {}", "Continues:", parent_id));
+    }
+    if let Some(depends_on) = &task.depends_on {
+        lines.push(format!("  {:<11} {}", "After:", depends_on));
+    }
+    if let Some(duration_ms) = task.duration_ms {
+        lines.push(format!("  {:<11} {:.1}s", "Duration:", duration_ms as f64 / 1000.0));
+    }
+    if let (Some(input), Some(output)) = (task.input_tokens, task.output_tokens) {
+        lines.push(format!("  {:<11} {} in / {} out", "Tokens:", input, output));
+    }
+    if let Some(output_path) = &task.output_path {
+        lines.push(format!("  {:<11} {}", "Log file:", output_path.display()));
     }
 
-    #[test]
-    fn test_default_config() {
-        let config = Config::default();
-        assert_eq!(config.stt.provider, "macos-native");
-        assert_eq!(config.intent.confidence_threshold, 0.80);
+    if let Some(error) = &task.error {
+        lines.push(format!("  {:<11} {}", "Error:".red(), error));
+    }
+
+    if let Some(output) = &task.output {
+        if task.output_lossy {
+            lines.push(format!("  {}", "Output (invalid UTF-8 replaced):".dimmed()));
+        } else {
+            lines.push(format!("  {}", "Output:".dimmed()));
+        }
+        lines.extend(output.lines().map(|line| format!("    {}", line)));
+    }
+
+    lines.join("\n")
+}
+
+async fn cancel_task(config: &Config, task_id: &str) -> anyhow::Result<()> {
+    let queue = open_queue(config).await?;
+
+    let task = queue.get_required(task_id).await?;
+    if task.status == TaskStatus::Running {
+        println!("{} Cannot cancel running task", "✗".red());
+        println!("  Kill the daemon to stop it");
+        return Ok(());
+    }
+    if !task.status.can_transition_to(TaskStatus::Cancelled) {
+        println!("{} Task is already {}", "✗".red(), task.status.as_str());
+        return Ok(());
+    }
+
+    queue.update_status(&task.id, TaskStatus::Cancelled).await?;
+    println!("{} Cancelled [{}]", "✓".green(), task.id.bright_cyan());
+
+    queue.close().await?;
+    Ok(())
+}
+
+/// Cancel queued and draft tasks, only those containing `matching` if given, asking first unless `yes`
+async fn cancel_all(config: &Config, matching: Option<&str>, yes: bool) -> anyhow::Result<u64> {
+    let queue = open_queue(config).await?;
+
+    if !yes {
+        let pending = queue.count_queued_matching(matching).await?;
+        let matching_note = matching.map(|m| format!(" matching '{}'", m)).unwrap_or_default();
+        if pending == 0 {
+            println!("{}", format!("No queued tasks{}", matching_note).dimmed());
+            return Ok(0);
+        }
+
+        println!("{} Cancel {} queued task(s){}? [y/N]", "?".yellow(), pending, matching_note);
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+            println!("{}", "Nothing cancelled".dimmed());
+            return Ok(0);
+        }
+    }
+
+    let count = match matching {
+        Some(pattern) => queue.cancel_queued_matching(pattern).await?,
+        None => queue.cancel_all_queued().await?,
+    };
+    queue.close().await?;
+    println!("{} Cancelled {} task(s)", "✓".green(), count);
+
+    Ok(count)
+}
+
+async fn clear_completed(config: &Config) -> anyhow::Result<()> {
+    let queue = open_queue(config).await?;
+    let count = queue.clear_completed().await?;
+    queue.close().await?;
+
+    println!("{} Cleared {} completed task(s)", "✓".green(), count);
+
+    Ok(())
+}
+
+/// Delete every task after checking no daemon is using the queue, asking first unless `yes`
+async fn reset_queue(config: &Config, yes: bool) -> anyhow::Result<bool> {
+    let queue = open_queue(config).await?;
+    if let Some(lock) = queue.daemon_lock().await? {
+        if daemon::pid_alive(lock.pid) {
+            anyhow::bail!("Daemon (pid {}) is running; stop it before resetting the queue", lock.pid);
+        }
+    }
+
+    let count = queue.list().await?.len();
+    if count == 0 {
+        println!("{}", "The queue is already empty".dimmed());
+        return Ok(false);
+    }
+    if !yes {
+        println!("{}", format!("⚠️  This will delete all {} task(s), including their history!", count).red().bold());
+        println!("Type 'yes' to confirm:");
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if answer.trim() != "yes" {
+            println!("{}", "Nothing deleted".dimmed());
+            return Ok(false);
+        }
+    }
+
+    queue.reset().await?;
+    queue.close().await?;
+    println!("{} Deleted {} task(s)", "✓".green(), count);
+    Ok(true)
+}
+
+/// Vacuum the queue database and report how much smaller it got
+async fn compact(config: &Config) -> anyhow::Result<()> {
+    if config.queue.ephemeral {
+        println!("{} The queue is in memory; there's nothing to compact", "✓".green());
+        return Ok(());
+    }
+
+    let path = &config.knowledge.local_db;
+    let before = db_file_size(path);
+    let queue = open_queue(config).await?;
+    queue
+        .vacuum()
+        .await
+        .context("Couldn't compact the queue; VACUUM needs exclusive access, so stop the daemon and retry")?;
+    queue.close().await?;
+    let after = db_file_size(path);
+
+    println!(
+        "{} Compacted {}: {} → {}",
+        "✓".green(),
+        path.display(),
+        format_size(before),
+        format_size(after)
+    );
+    Ok(())
+}
+
+/// Bytes used by a SQLite database and its write-ahead log
+fn db_file_size(path: &Path) -> u64 {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    [path, Path::new(&wal)].iter().filter_map(|p| std::fs::metadata(p).ok()).map(|m| m.len()).sum()
+}
+
+/// A byte count in B, KiB or MiB
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Queue a draft, re-running classification first if asked
+async fn promote(config: &Config, task_id: &str, reclassify: bool) -> anyhow::Result<Intent> {
+    let queue = open_queue(config).await?;
+    let task = queue.get_required(task_id).await?;
+    if task.status != TaskStatus::Draft {
+        anyhow::bail!("Task {} is {}, not a draft", task.id, task.status.as_str());
+    }
+
+    let intent = if reclassify {
+        let processor = IntentProcessor::new(config.intent.confidence_threshold);
+        processor.classify_scores(&task.command)[0].0
+    } else {
+        task.intent.unwrap_or(Intent::Code)
+    };
+
+    queue.promote(&task.id, Some(intent)).await?;
+    queue.close().await?;
+    println!("{} Promoted [{}] as {}", "✓".green(), task.id.bright_cyan(), intent);
+
+    Ok(intent)
+}
+
+/// Move a queued task to blocked, or a blocked one back to the queue
+async fn set_held(config: &Config, task_id: &str, held: bool) -> anyhow::Result<()> {
+    let queue = open_queue(config).await?;
+    let task = queue.get_required(task_id).await?;
+
+    // Checked here too since unholding e.g. a failed task would be a legal retry
+    let (from, to) = if held { (TaskStatus::Queued, TaskStatus::Blocked) } else { (TaskStatus::Blocked, TaskStatus::Queued) };
+    if task.status != from {
+        anyhow::bail!("Task {} is {}, not {}", task.id, task.status.as_str(), from.as_str());
+    }
+    queue.update_status(&task.id, to).await?;
+    queue.close().await?;
+
+    let verb = if held { "Held" } else { "Unheld" };
+    println!("{} {} [{}]", "✓".green(), verb, task.id.bright_cyan());
+    Ok(())
+}
+
+async fn requeue(config: &Config, task_id: Option<&str>, all_failed: bool) -> anyhow::Result<u64> {
+    let queue = open_queue(config).await?;
+
+    let count = if all_failed {
+        queue.update_status_where(TaskStatus::Failed, TaskStatus::Queued).await?
+    } else {
+        let id = task_id.unwrap_or_default();
+        let task = queue.get_required(id).await?;
+        queue.update_status(&task.id, TaskStatus::Queued).await?;
+        1
+    };
+
+    queue.close().await?;
+    println!("{} Requeued {} task(s)", "✓".green(), count);
+
+    Ok(count)
+}
+
+/// Queue a copy of a task, returning the copy's id
+async fn replay(config: &Config, task_id: &str) -> anyhow::Result<String> {
+    let queue = open_queue(config).await?;
+    let task = queue.get_required(task_id).await?;
+    let copy = queue.replay(&task.id).await?;
+    queue.close().await?;
+
+    println!("{} Queued [{}] as a replay of [{}]", "✓".green(), copy.bright_cyan(), task.id.bright_cyan());
+    Ok(copy)
+}
+
+/// Print up to `limit` failed tasks, newest failure first, returning them
+///
+/// With `retry_all`, every failed task (not just those shown) is requeued afterwards.
+async fn show_failures(config: &Config, limit: usize, retry_all: bool, timezone: &Timezone) -> anyhow::Result<Vec<Task>> {
+    let queue = open_queue(config).await?;
+    let mut failed = queue.query(&ListQuery::default().with_status(TaskStatus::Failed)).await?;
+    failed.sort_by_key(|task| std::cmp::Reverse(task.completed_at));
+    let total = failed.len();
+    if limit > 0 {
+        failed.truncate(limit);
+    }
+
+    if failed.is_empty() {
+        println!("{}", "No failed tasks".dimmed());
+    }
+    for task in &failed {
+        let attempts = queue.attempts(&task.id).await?.len();
+        println!("{}", format_failure(task, attempts, timezone));
+    }
+    if failed.len() < total {
+        println!("{}", format!("... and {} more (use --limit 0 for all)", total - failed.len()).dimmed());
+    }
+
+    if retry_all && total > 0 {
+        let requeued = queue.update_status_where(TaskStatus::Failed, TaskStatus::Queued).await?;
+        println!("{} Requeued {} task(s)", "✓".green(), requeued);
+    }
+    queue.close().await?;
+    Ok(failed)
+}
+
+/// A failed task as `faster failures` shows it: id and command, error, then attempts and timing
+fn format_failure(task: &Task, attempts: usize, timezone: &Timezone) -> String {
+    let mut lines = vec![format!("{} [{}] {}", "✗".red(), task.id.bright_cyan(), task.command)];
+    lines.push(format!("    {}: {}", "Error".red(), task.error.as_deref().unwrap_or("(none recorded)")));
+
+    let mut details = vec![format!("{} attempt(s)", attempts)];
+    if let Some(at) = task.completed_at {
+        details.push(format!("failed {}", timezone.format(at)));
+    }
+    if let Some(ms) = task.duration_ms {
+        details.push(format!("ran {:.1}s", ms as f64 / 1000.0));
+    }
+    lines.push(format!("    {}", details.join(" · ").dimmed()));
+    lines.join("\n")
+}
+
+/// Print counts and latencies for tasks created within `since`, else `queue.stats_window_secs`
+async fn show_stats(config: &Config, since: Option<chrono::Duration>, json: bool) -> anyhow::Result<()> {
+    let window = since.or_else(|| match config.queue.stats_window_secs {
+        0 => None,
+        secs => chrono::Duration::try_seconds(secs as i64),
+    });
+    let queue = open_queue(config).await?;
+    let stats = queue.stats(window.map(|window| chrono::Utc::now() - window)).await?;
+    queue.close().await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats_json(&stats))?);
+    } else {
+        for line in format_stats(&stats) {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// `faster stats` lines: the total by status, then wait and execution percentiles
+fn format_stats(stats: &QueueStats) -> Vec<String> {
+    let counts: Vec<String> = stats.by_status.iter().map(|(status, n)| format!("{} {}", n, status.as_str())).collect();
+    let mut lines = vec![match counts.is_empty() {
+        true => "No tasks".to_string(),
+        false => format!("{} task(s): {}", stats.total, counts.join(", ")),
+    }];
+
+    for (label, latency) in [("Wait", &stats.wait), ("Execution", &stats.execution)] {
+        lines.push(match latency {
+            Some(l) => format!(
+                "{:<10} p50 {:.1}s  p95 {:.1}s  max {:.1}s  ({} task(s))",
+                format!("{}:", label),
+                l.p50.as_secs_f64(),
+                l.p95.as_secs_f64(),
+                l.max.as_secs_f64(),
+                l.count
+            ),
+            None => format!("{:<10} {}", format!("{}:", label), "no data".dimmed()),
+        });
+    }
+    lines
+}
+
+/// `faster stats --json`, with latencies in milliseconds
+fn stats_json(stats: &QueueStats) -> serde_json::Value {
+    let latency = |l: &Option<LatencyStats>| {
+        l.map(|l| {
+            serde_json::json!({
+                "count": l.count,
+                "p50_ms": l.p50.as_millis() as u64,
+                "p95_ms": l.p95.as_millis() as u64,
+                "max_ms": l.max.as_millis() as u64,
+            })
+        })
+    };
+    let by_status: serde_json::Map<String, serde_json::Value> =
+        stats.by_status.iter().map(|(status, n)| (status.as_str().to_string(), (*n).into())).collect();
+    serde_json::json!({
+        "total": stats.total,
+        "by_status": by_status,
+        "wait": latency(&stats.wait),
+        "execution": latency(&stats.execution),
+    })
+}
+
+/// Print recent distinct commands, numbered for `history --run`
+async fn show_history(config: &Config, limit: u64) -> anyhow::Result<Vec<String>> {
+    let queue = open_queue(config).await?;
+    let commands = queue.recent_commands(limit).await?;
+    queue.close().await?;
+
+    if commands.is_empty() {
+        println!("{}", "No commands yet".dimmed());
+    }
+    let width = commands.len().to_string().len();
+    for (n, command) in commands.iter().enumerate() {
+        println!("{}  {}", format!("{:>width$}", n + 1, width = width).dimmed(), command);
+    }
+    Ok(commands)
+}
+
+/// Queue the `n`th most recent distinct command again, classified afresh
+async fn rerun_history(config: &Config, n: u64) -> anyhow::Result<String> {
+    let queue = open_queue(config).await?;
+    let commands = queue.recent_commands(n).await?;
+    queue.close().await?;
+
+    let Some(text) = commands.get(n as usize - 1) else {
+        anyhow::bail!("History has {} command(s), so there's no #{}", commands.len(), n);
+    };
+    let command = classify_typed(&intent_processor(config), text, None);
+    queue_command(&command, config, None, false).await
+}
+
+/// Queue every spec in a JSON file, printing the new ids in order
+///
+/// Normally all or nothing; with `best_effort` the valid specs are queued
+/// anyway and the rest reported, still failing the command.
+async fn enqueue_json_file(
+    config: &Config,
+    path: &std::path::Path,
+    metadata: &[(String, String)],
+    best_effort: bool,
+) -> anyhow::Result<Vec<String>> {
+    let json = if path.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+    };
+    let timezone = config.general.timezone()?;
+
+    // With best effort, items that don't parse are failures like any other;
+    // `indices` maps each parsed spec back to its place in the file
+    let (mut specs, mut indices, mut failed) = (Vec::new(), Vec::new(), Vec::new());
+    if best_effort {
+        for (index, item) in parse_specs_each(&json, &timezone)?.into_iter().enumerate() {
+            match item {
+                Ok(spec) => {
+                    specs.push(spec);
+                    indices.push(index);
+                }
+                Err(e) => failed.push(e),
+            }
+        }
+    } else {
+        specs = parse_specs(&json, &timezone)?;
+        indices = (0..specs.len()).collect();
+    }
+    for spec in &mut specs {
+        spec.model = spec.model.take().map(|model| config.claude.model_aliases.resolve(&model));
+        for (key, value) in metadata {
+            spec.metadata.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+
+    let queue = open_queue(config).await?;
+    let result = if best_effort {
+        Ok(queue.enqueue_specs_best_effort(&specs).await)
+    } else {
+        queue.enqueue_specs(&specs).await.map(|succeeded| BatchResult { succeeded, failed: Vec::new() })
+    };
+    queue.close().await?;
+    let result = result?;
+
+    failed.extend(result.failed.into_iter().map(|e| SpecError { index: indices[e.index], ..e }));
+    failed.sort_by_key(|e| e.index);
+    let queued = indices.iter().zip(&specs).filter(|(index, _)| !failed.iter().any(|e| e.index == **index));
+    for (id, (_, spec)) in result.succeeded.iter().zip(queued) {
+        println!("{} Queued [{}] {}", "✓".green(), id.bright_cyan(), spec.command);
+    }
+
+    if failed.is_empty() {
+        return Ok(result.succeeded);
+    }
+    println!("{}", format_batch_summary(result.succeeded.len(), &failed).yellow());
+    Err(QueueError::InvalidSpecs { errors: failed }.into())
+}
+
+/// "8 queued, 2 failed (items 3, 7)", by the items' index in the file
+fn format_batch_summary(queued: usize, failed: &[SpecError]) -> String {
+    let indices: Vec<String> = failed.iter().map(|e| e.index.to_string()).collect();
+    format!("{} queued, {} failed (items {})", queued, failed.len(), indices.join(", "))
+}
+
+fn parse_status(s: &str) -> Result<TaskStatus, String> {
+    TaskStatus::from_str(&s.to_ascii_lowercase()).ok_or_else(|| {
+        format!("unknown status '{}' (expected queued, running, completed, failed, cancelled, draft or blocked)", s)
+    })
+}
+
+/// Parse a `--meta` pair; the key must be non-empty, the value may be anything
+fn parse_meta_pair(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+        _ => Err(format!("invalid metadata '{}' (expected KEY=VALUE)", s)),
+    }
+}
+
+/// Parse a duration like `90s`, `15m`, `1h30m` or `2 days`
+fn parse_duration(s: &str) -> Result<chrono::Duration, String> {
+    let invalid = || format!("invalid duration '{}' (e.g. 30m, 1h, 2d)", s);
+    let mut total = chrono::Duration::zero();
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let amount: i64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = rest[digits..].trim_start();
+
+        let letters = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let unit = match &rest[..letters] {
+            "s" | "sec" | "secs" | "second" | "seconds" => chrono::Duration::try_seconds(amount),
+            "m" | "min" | "mins" | "minute" | "minutes" => chrono::Duration::try_minutes(amount),
+            "h" | "hr" | "hrs" | "hour" | "hours" => chrono::Duration::try_hours(amount),
+            "d" | "day" | "days" => chrono::Duration::try_days(amount),
+            "w" | "week" | "weeks" => chrono::Duration::try_weeks(amount),
+            _ => return Err(invalid()),
+        };
+        total = unit.and_then(|unit| total.checked_add(&unit)).ok_or_else(invalid)?;
+        rest = rest[letters..].trim_start();
+    }
+
+    Ok(total)
+}
+
+/// The in-memory queue every `open_queue` shares during an ephemeral run
+static EPHEMERAL_QUEUE: tokio::sync::OnceCell<TaskQueue> = tokio::sync::OnceCell::const_new();
+
+/// Open the task queue described by the config
+async fn open_queue(config: &Config) -> anyhow::Result<TaskQueue> {
+    let queue = if config.queue.ephemeral {
+        EPHEMERAL_QUEUE.get_or_try_init(|| TaskQueue::new(":memory:")).await?.clone()
+    } else {
+        TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await?
+    };
+    let queue = queue
+        .with_max_pending(config.queue.max_pending)
+        .with_max_output_bytes(config.queue.max_output_bytes)
+        .with_keep_last_outputs(config.queue.keep_last_outputs)
+        .with_max_command_len(config.queue.max_command_len)
+        .with_directive_templates(config.intent.templates.clone());
+    Ok(if config.queue.snapshot_env {
+        queue.with_env_snapshot(config.queue.snapshot_env_vars.clone())
+    } else {
+        queue
+    })
+}
+
+/// Config file in use: the `--config` path if given, else the default location
+fn config_path(config_file: Option<&Path>) -> anyhow::Result<PathBuf> {
+    match config_file {
+        Some(path) => Ok(path.to_path_buf()),
+        None => Ok(Config::path()?),
+    }
+}
+
+/// The config file if there is one, else defaults
+///
+/// An explicit `config_file` must exist.
+fn load_config(config_file: Option<&Path>) -> anyhow::Result<Config> {
+    let config_path = config_path(config_file)?;
+    if config_path.exists() {
+        Ok(Config::load(&config_path)?)
+    } else if config_file.is_some() {
+        Err(ConfigError::NotFound { path: config_path }.into())
+    } else {
+        Ok(Config::default())
+    }
+}
+
+/// Load the config, creating a default one on first run
+///
+/// Only the default location is created; an explicit `config_file` must exist.
+fn load_or_create_config(config_file: Option<&Path>) -> anyhow::Result<Config> {
+    let config_path = config_path(config_file)?;
+
+    if config_path.exists() {
+        Ok(Config::load(&config_path)?)
+    } else if config_file.is_some() {
+        Err(ConfigError::NotFound { path: config_path }.into())
+    } else {
+        // Create default config silently
+        let config_dir = config_path.parent().unwrap();
+        std::fs::create_dir_all(config_dir)?;
+
+        let config = Config::default();
+        config.save(&config_path)?;
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_parsing() {
+        let cli = Cli::parse_from(["faster", "--debug"]);
+        assert!(cli.debug);
+    }
+
+    #[test]
+    fn test_explicit_config_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("team.toml");
+        std::fs::write(&path, "[claude]\nmodel = \"opus\"\n").unwrap();
+
+        let cli = Cli::parse_from(["faster", "status", "--config", path.to_str().unwrap()]);
+        assert_eq!(cli.config.as_deref(), Some(path.as_path()));
+        assert_eq!(load_or_create_config(Some(&path)).unwrap().claude.model, "opus");
+        assert_eq!(load_config(Some(&path)).unwrap().claude.model, "opus");
+    }
+
+    #[test]
+    fn test_missing_explicit_config_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.toml");
+
+        let err = load_or_create_config(Some(&path)).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(ConfigError::NotFound { .. })));
+        assert_eq!(exit_code(&err), exit::CONFIG);
+        assert!(load_config(Some(&path)).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = Config::default();
+        assert_eq!(config.stt.provider, "macos-native");
+        assert_eq!(config.intent.confidence_threshold, 0.80);
+    }
+
+    fn test_config(dir: &tempfile::TempDir) -> Config {
+        let mut config = Config::default();
+        config.knowledge.local_db = dir.path().join("queue.db");
+        config
+    }
+
+    #[test]
+    #[serial_test::serial(colors)]
+    fn test_format_intents() {
+        colored::control::set_override(false);
+
+        let mut config = Config::default();
+        let listing = format_intents(&config);
+        for intent in Intent::all() {
+            assert!(listing.contains(&format!("[{}] {}", intent, intent.description())), "{}", listing);
+            for example in intent.examples() {
+                assert!(listing.contains(example));
+            }
+        }
+        assert!(!listing.contains("confirm:"));
+        assert!(!listing.contains("Aliases:"));
+
+        config.confirmation.per_intent.insert("orchestrate".to_string(), "always".to_string());
+        config.aliases.insert("ship it".to_string(), "deploy to production".to_string());
+        let listing = format_intents(&config);
+        assert!(listing.contains("confirm: always"));
+        assert!(listing.contains("ship it → deploy to production"));
+    }
+
+    #[test]
+    fn test_cli_parsing_intent_override() {
+        let cli = Cli::parse_from(["faster", "refactor x", "--intent", "code"]);
+        assert_eq!(cli.quick_command.as_deref(), Some("refactor x"));
+        assert_eq!(cli.intent, Some(Intent::Code));
+
+        assert!(Cli::try_parse_from(["faster", "x", "--intent", "deploy"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_queue_command_with_intent_override() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let processor = IntentProcessor::new(0.80);
+
+        let command = classify_typed(&processor, "run the parser refactor", Some(Intent::Research));
+        assert_eq!(command.intent, Intent::Research);
+        assert_eq!(command.confidence, 1.0);
+        queue_command(&command, &config, None, false).await.unwrap();
+
+        let queue = TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await.unwrap();
+        let tasks = queue.list().await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].intent, Some(Intent::Research));
+    }
+
+    #[tokio::test]
+    async fn test_queue_command_records_classified_intent() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let processor = IntentProcessor::new(0.80);
+
+        // Voice path: the processor's result goes straight to the queue
+        let spoken = processor.process("um find the auth files").unwrap();
+        queue_command(&spoken, &config, None, false).await.unwrap();
+
+        // Typed path: classified, text kept verbatim
+        let typed = classify_typed(&processor, "fix the flaky check", None);
+        queue_command(&typed, &config, None, false).await.unwrap();
+
+        let queue = TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await.unwrap();
+        let tasks = queue.list().await.unwrap();
+        let intent_of = |command: &str| tasks.iter().find(|t| t.command == command).unwrap().intent;
+        assert_eq!(intent_of("find the auth files"), Some(Intent::Research));
+        assert_eq!(intent_of("fix the flaky check"), Some(Intent::Test));
+    }
+
+    #[tokio::test]
+    async fn test_queue_command_respects_max_pending() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&dir);
+        config.queue.max_pending = 1;
+        let command = Command::new(Intent::Test, "Run tests", vec![], 0.9);
+
+        queue_command(&command, &config, None, false).await.unwrap();
+        let err = queue_command(&command, &config, None, false).await.unwrap_err();
+        assert_eq!(queue_error(&err), Some(QueueError::QueueFull { limit: 1 }));
+    }
+
+    #[test]
+    fn test_intent_badge_handles_missing_intent() {
+        assert_eq!(intent_badge(None), "");
+        assert!(intent_badge(Some(Intent::Code)).contains("[CODE]"));
+
+        let queue_task = Task {
+            id: "legacy01".to_string(),
+            command: "old task".to_string(),
+            status: TaskStatus::Queued,
+            model: None,
+            created_at: chrono::Utc::now(),
+            started_at: None,
+            completed_at: None,
+            error: None,
+            rendered_prompt: None,
+            intent: None,
+            confidence: None,
+            output: None,
+            original_command: None,
+            parent_id: None,
+            session_id: None,
+            priority: 0,
+            tags: Vec::new(),
+            cwd: None,
+            scheduled_for: None,
+            processed_by: None,
+            output_lossy: false,
+            depends_on: None,
+            output_path: None,
+            duration_ms: None,
+            input_tokens: None,
+            output_tokens: None,
+            env_snapshot: None,
+            timeout_ms: None,
+            replayed_from: None,
+            metadata: Default::default(),
+            prompt_prefix: None,
+            prompt_suffix: None,
+        };
+        let details = format_task_details(&queue_task, &Timezone::Utc);
+        assert!(details.contains("Intent:     -"));
+        assert!(details.contains("Confidence: n/a"));
+    }
+
+    fn status_task(id: &str, command: &str, status: TaskStatus, intent: Option<Intent>) -> Task {
+        Task {
+            id: id.to_string(),
+            command: command.to_string(),
+            status,
+            model: None,
+            created_at: chrono::Utc::now(),
+            started_at: None,
+            completed_at: None,
+            error: None,
+            rendered_prompt: None,
+            intent,
+            confidence: None,
+            output: None,
+            original_command: None,
+            parent_id: None,
+            session_id: None,
+            priority: 0,
+            tags: Vec::new(),
+            cwd: None,
+            scheduled_for: None,
+            processed_by: None,
+            output_lossy: false,
+            depends_on: None,
+            output_path: None,
+            duration_ms: None,
+            input_tokens: None,
+            output_tokens: None,
+            env_snapshot: None,
+            timeout_ms: None,
+            replayed_from: None,
+            metadata: Default::default(),
+            prompt_prefix: None,
+            prompt_suffix: None,
+        }
+    }
+
+    #[test]
+    #[serial_test::serial(colors)]
+    fn test_status_rows_align_and_truncate() {
+        colored::control::set_override(false);
+
+        let tasks = vec![
+            status_task("abc12345", "Run tests", TaskStatus::Queued, Some(Intent::Test)),
+            status_task("def67890", &"refactor the parser ".repeat(10), TaskStatus::Completed, None),
+        ];
+        let layout = StatusLayout::for_tasks(&tasks, 0.8);
+
+        let short = format_status_row(&tasks[0], &layout, Some(60));
+        let long = format_status_row(&tasks[1], &layout, Some(60));
+        assert!(short.ends_with("Run tests"));
+        assert!(long.ends_with('…'));
+        assert_eq!(long.width(), 60);
+        // ⏳ is two columns wide, so the queued row has no room to spare either
+        let queued_long = Task { status: TaskStatus::Queued, ..tasks[1].clone() };
+        assert_eq!(format_status_row(&queued_long, &layout, Some(60)).width(), 60);
+
+        // Commands start in the same column whatever the status or intent
+        let column = |row: &str, text: &str| row[..row.find(text).unwrap()].width();
+        assert_eq!(column(&short, "Run tests"), column(&long, "refactor"));
+
+        // No width means no truncation
+        let wide = format_status_row(&tasks[1], &layout, None);
+        assert!(wide.ends_with("refactor the parser "));
+    }
+
+    #[test]
+    #[serial_test::serial(colors)]
+    fn test_format_event() {
+        colored::control::set_override(false);
+
+        let at = "2026-06-01T10:15:30Z".parse().unwrap();
+        let event = TaskEvent {
+            at,
+            task_id: "abc12345".to_string(),
+            status: TaskStatus::Failed,
+            command: "Run\nthe tests".to_string(),
+            error: Some("exit 1".to_string()),
+        };
+        assert_eq!(
+            format_event(&event, &Timezone::Utc),
+            "10:15:30 ✗ abc12345  failed     Run the tests (exit 1)"
+        );
+
+        let started = TaskEvent { status: TaskStatus::Running, error: None, ..event };
+        let plus_two: Timezone = "+02:00".parse().unwrap();
+        assert_eq!(format_event(&started, &plus_two), "12:15:30 → abc12345  running    Run the tests");
+    }
+
+    #[test]
+    fn test_group_by_tag() {
+        let tagged = |id: &str, tags: &[&str]| Task {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..status_task(id, "Work", TaskStatus::Queued, None)
+        };
+        let tasks = vec![
+            tagged("a", &["deploy"]),
+            tagged("b", &[]),
+            tagged("c", &["backend", "deploy"]),
+            tagged("d", &["deploy"]),
+            tagged("e", &[]),
+        ];
+
+        let sections: Vec<(&str, Vec<&str>)> = group_by_tag(&tasks)
+            .into_iter()
+            .map(|(tag, group)| (tag, group.iter().map(|t| t.id.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![
+                ("backend", vec!["c"]),
+                ("deploy", vec!["a", "c", "d"]),
+                (UNTAGGED, vec!["b", "e"]),
+            ]
+        );
+
+        assert!(group_by_tag(&tasks[..1]).iter().all(|(tag, _)| *tag != UNTAGGED));
+        assert_eq!("TAG".parse::<GroupBy>(), Ok(GroupBy::Tag));
+        assert!("intent".parse::<GroupBy>().is_err());
+    }
+
+    #[test]
+    #[serial_test::serial(colors)]
+    fn test_status_row_narrow_width_keeps_some_command() {
+        colored::control::set_override(false);
+
+        let task = status_task("abc12345", "Summarize the failing integration tests", TaskStatus::Failed, None);
+        let layout = StatusLayout::for_tasks(std::slice::from_ref(&task), 0.8);
+        let row = format_status_row(&task, &layout, Some(20));
+
+        assert!(row.ends_with("Summarize…"));
+        assert_eq!(truncate_to_width("héllo wörld", 6), "héllo…");
+        assert_eq!(truncate_to_width("short", 6), "short");
+        assert_eq!(truncate_to_width("日本語のテスト", 6), "日本…");
+    }
+
+    #[test]
+    fn test_cli_parsing_context_files() {
+        let cli = Cli::parse_from([
+            "faster", "explain", "--context-file", "a.rs", "--context-file", "/abs/b.rs",
+        ]);
+        assert_eq!(cli.context_files, vec![PathBuf::from("a.rs"), PathBuf::from("/abs/b.rs")]);
+    }
+
+    #[tokio::test]
+    async fn test_continue_resolves_parent() {
+        let cli = Cli::parse_from(["faster", "and now the tests", "--continue", "abc"]);
+        assert_eq!(cli.continue_from.as_deref(), Some("abc"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let id = open_queue(&config).await.unwrap().enqueue("First", None).await.unwrap();
+
+        assert_eq!(resolve_task_id(&config, &id[..4]).await.unwrap(), id);
+        let err = resolve_task_id(&config, "zzzz").await.unwrap_err();
+        assert_eq!(exit_code(&err), exit::USAGE);
+
+        // The continuation waits for its parent, linked in the same insert
+        let command = classify_typed(&intent_processor(&config), "and now the tests", Some(Intent::Code));
+        let options = QueueOptions { parent: Some(id.clone()), ..Default::default() };
+        let child = queue_command_after(&command, &config, None, false, Some(&id), &options).await.unwrap();
+        let queue = open_queue(&config).await.unwrap();
+        let task = queue.get(&child).await.unwrap().unwrap();
+        assert_eq!((task.parent_id.as_deref(), task.depends_on.as_deref()), (Some(id.as_str()), Some(id.as_str())));
+        assert_eq!(queue.claim_next().await.unwrap().unwrap().id, id);
+        assert!(queue.claim_next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_quick_command_timeout_set_on_insert() {
+        let cli = Cli::parse_from(["faster", "run the slow suite", "--timeout", "90s"]);
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+
+        let command = classify_typed(&intent_processor(&config), "run the slow suite", Some(Intent::Code));
+        let options = QueueOptions { timeout: cli.timeout.and_then(|t| t.to_std().ok()), ..Default::default() };
+        let id = queue_command_after(&command, &config, None, false, None, &options).await.unwrap();
+        let task = open_queue(&config).await.unwrap().get(&id).await.unwrap().unwrap();
+        assert_eq!(task.timeout_ms, Some(90_000));
+    }
+
+    #[tokio::test]
+    async fn test_replay_by_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let id = open_queue(&config).await.unwrap().enqueue("Run tests", None).await.unwrap();
+
+        let copy = replay(&config, &id[..4]).await.unwrap();
+        let task = open_queue(&config).await.unwrap().get(&copy).await.unwrap().unwrap();
+        assert_eq!(task.replayed_from.as_deref(), Some(id.as_str()));
+        assert_eq!(exit_code(&replay(&config, "zzzz").await.unwrap_err()), exit::USAGE);
+    }
+
+    #[tokio::test]
+    async fn test_history_rerun_by_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let queue = open_queue(&config).await.unwrap();
+        for command in ["Run the tests", "Fix the login bug", "Run the tests"] {
+            queue.enqueue(command, None).await.unwrap();
+        }
+
+        assert_eq!(show_history(&config, 0).await.unwrap(), vec!["Run the tests", "Fix the login bug"]);
+        let id = rerun_history(&config, 2).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().command, "Fix the login bug");
+        // Re-running moves it back to the front
+        assert_eq!(show_history(&config, 1).await.unwrap(), vec!["Fix the login bug"]);
+
+        let err = rerun_history(&config, 3).await.unwrap_err();
+        assert!(err.to_string().contains("no #3"), "{}", err);
+        assert!(Cli::try_parse_from(["faster", "history", "--run", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_format_stats() {
+        colored::control::set_override(false);
+        let latency = |p50, p95, max| LatencyStats {
+            count: 4,
+            p50: Duration::from_millis(p50),
+            p95: Duration::from_millis(p95),
+            max: Duration::from_millis(max),
+        };
+        let stats = QueueStats {
+            total: 5,
+            by_status: vec![(TaskStatus::Queued, 1), (TaskStatus::Completed, 4)],
+            wait: Some(latency(1500, 9000, 12_250)),
+            execution: None,
+        };
+
+        assert_eq!(
+            format_stats(&stats),
+            vec![
+                "5 task(s): 1 queued, 4 completed",
+                "Wait:      p50 1.5s  p95 9.0s  max 12.2s  (4 task(s))",
+                "Execution: no data",
+            ]
+        );
+        let json = stats_json(&stats);
+        assert_eq!(json["by_status"]["completed"], 4);
+        assert_eq!(json["wait"]["p95_ms"], 9000);
+        assert!(json["execution"].is_null());
+    }
+
+    #[test]
+    fn test_cli_parsing_get() {
+        let cli = Cli::parse_from(["faster", "get", "abc123", "--json"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Get { ref task_id, json: true, prompt: false, attempts: false, env: false, utc: false }) if task_id == "abc123"
+        ));
+
+        let cli = Cli::parse_from(["faster", "get", "--attempts", "abc123"]);
+        assert!(matches!(cli.command, Some(Commands::Get { attempts: true, .. })));
+        assert!(Cli::try_parse_from(["faster", "get", "abc123", "--attempts", "--prompt"]).is_err());
+        assert!(Cli::try_parse_from(["faster", "get", "abc123", "--env", "--attempts"]).is_err());
+    }
+
+    #[test]
+    fn test_format_env_snapshot() {
+        let mut snapshot = EnvSnapshot { cwd: PathBuf::from("/src/app"), ..EnvSnapshot::default() };
+        assert_eq!(format_env_snapshot(&snapshot), "  Cwd:     /src/app");
+
+        snapshot.git_branch = Some("main".to_string());
+        snapshot.git_commit = Some("abc123".to_string());
+        snapshot.env.insert("LANG".to_string(), "C.UTF-8".to_string());
+        assert_eq!(
+            format_env_snapshot(&snapshot),
+            "  Cwd:     /src/app\n  Branch:  main\n  Commit:  abc123\n  Env:\n    LANG=C.UTF-8"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_task_found_and_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+
+        let queue = TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await.unwrap();
+        let id = queue.enqueue("Run tests", None).await.unwrap();
+
+        assert!(get_task(&config, &id, false, false, false, false, &Timezone::Utc).await.is_ok());
+        assert!(get_task(&config, &id[..3], true, false, false, false, &Timezone::Utc).await.is_ok());
+        assert!(get_task(&config, &id, false, true, false, false, &Timezone::Utc).await.is_ok());
+        assert!(get_task(&config, &id, false, false, true, false, &Timezone::Utc).await.is_ok());
+        assert!(get_task(&config, &id, true, false, true, false, &Timezone::Utc).await.is_ok());
+        assert!(get_task(&config, &id, false, false, false, true, &Timezone::Utc).await.is_ok());
+
+        let err = get_task(&config, "missing", false, false, false, false, &Timezone::Utc).await.unwrap_err();
+        assert!(err.to_string().contains("Task not found"));
+    }
+
+    #[tokio::test]
+    async fn test_reset_queue_refuses_while_daemon_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let queue = open_queue(&config).await.unwrap();
+        queue.enqueue("Run tests", None).await.unwrap();
+
+        queue.acquire_daemon_lock(std::process::id(), false, daemon::pid_alive).await.unwrap();
+        let err = reset_queue(&config, true).await.unwrap_err();
+        assert!(err.to_string().contains("stop it before resetting"));
+        assert_eq!(queue.list().await.unwrap().len(), 1);
+
+        queue.release_daemon_lock(std::process::id()).await.unwrap();
+        assert!(reset_queue(&config, true).await.unwrap());
+        assert!(queue.list().await.unwrap().is_empty());
+        assert!(!reset_queue(&config, true).await.unwrap());
+    }
+
+    #[test]
+    fn test_reset_requires_flag_or_prompt() {
+        assert!(matches!(Cli::try_parse_from(["faster", "reset", "--yes"]).unwrap().command, Some(Commands::Reset { yes: true })));
+        assert!(matches!(Cli::try_parse_from(["faster", "reset"]).unwrap().command, Some(Commands::Reset { yes: false })));
+    }
+
+    #[tokio::test]
+    async fn test_compact_shrinks_cleared_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let queue = open_queue(&config).await.unwrap();
+        for i in 0..100 {
+            queue.enqueue(&format!("Task {} {}", i, "x".repeat(4096)), None).await.unwrap();
+        }
+        queue.cancel_all_queued().await.unwrap();
+        queue.clear_completed().await.unwrap();
+        queue.close().await.unwrap();
+
+        let before = db_file_size(&config.knowledge.local_db);
+        compact(&config).await.unwrap();
+        assert!(db_file_size(&config.knowledge.local_db) < before);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1_048_576), "3.0 MiB");
+    }
+
+    #[tokio::test]
+    async fn test_task_logs_prefers_output_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let queue = TaskQueue::new(&config.knowledge.local_db.to_string_lossy()).await.unwrap();
+        let id = queue.enqueue("Run tests", None).await.unwrap();
+
+        assert_eq!(task_logs(&queue.get(&id).await.unwrap().unwrap()).unwrap(), None);
+
+        queue.set_output(&id, "truncated", false).await.unwrap();
+        let path = dir.path().join("logs").join(format!("{}.log", id));
+        queue.set_output_path(&id, &path).await.unwrap();
+        // Recorded but gone from disk, so the DB copy is all there is
+        assert_eq!(task_logs(&queue.get(&id).await.unwrap().unwrap()).unwrap().as_deref(), Some("truncated"));
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "full output\n").unwrap();
+        assert_eq!(task_logs(&queue.get(&id).await.unwrap().unwrap()).unwrap().as_deref(), Some("full output\n"));
+
+        assert!(show_logs(&config, &id[..4]).await.is_ok());
+        assert!(show_logs(&config, "missing").await.unwrap_err().to_string().contains("Task not found"));
+    }
+
+    #[test]
+    #[serial_test::serial(colors)]
+    fn test_format_diagnostics_groups_and_hints() {
+        colored::control::set_override(false);
+        let diagnostics = vec![
+            doctor::check_claude_cli("/nonexistent/claude"),
+            doctor::check_daemon_lock(None, |_| false),
+            doctor::check_claude_cli("true"),
+        ];
+
+        let text = format_diagnostics(&diagnostics);
+        colored::control::unset_override();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "Claude");
+        assert!(lines[1].starts_with("  ✗ Claude CLI not found"));
+        assert!(lines[2].starts_with("    → Install it from"));
+        assert!(lines[3].starts_with("  ✓ Claude CLI runs"));
+        assert_eq!(lines[4], "Daemon");
+        assert_eq!(lines.len(), 6);
+    }
+
+    #[test]
+    #[serial_test::serial(colors)]
+    fn test_format_attempt() {
+        colored::control::set_override(false);
+        let started_at = chrono::Utc::now();
+        let attempt = TaskAttempt {
+            task_id: "abc12345".to_string(),
+            attempt_no: 2,
+            model: Some("opus".to_string()),
+            started_at,
+            completed_at: Some(started_at + chrono::Duration::milliseconds(1500)),
+            status: TaskStatus::Failed,
+            error: Some("boom".to_string()),
+        };
+
+        let text = format_attempt(&attempt, &Timezone::Utc);
+        colored::control::unset_override();
+        assert!(text.starts_with("  #2 failed"));
+        assert!(text.contains("opus (1.5s)"));
+        assert!(text.contains("Error: boom"));
+
+        let running = TaskAttempt { completed_at: None, error: None, model: None, ..attempt };
+        let text = format_attempt(&running, &Timezone::Utc);
+        assert!(text.ends_with("default"));
+        assert!(!text.contains('\n'));
+    }
+
+    #[tokio::test]
+    async fn test_exit_codes_for_handler_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&dir);
+
+        let err = get_task(&config, "missing", false, false, false, false, &Timezone::Utc).await.unwrap_err();
+        assert_eq!(exit_code(&err), exit::USAGE);
+
+        config.claude.cli_path = "/nonexistent/claude".to_string();
+        let err = run_daemon(&config, false, false).await.unwrap_err();
+        assert_eq!(exit_code(&err), exit::CLAUDE_UNAVAILABLE);
+
+        config.queue.max_pending = 1;
+        let command = Command::new(Intent::Code, "one".to_string(), vec![], 1.0);
+        queue_command(&command, &config, None, false).await.unwrap();
+        let err = queue_command(&command, &config, None, false).await.unwrap_err();
+        assert_eq!(exit_code(&err), exit::QUEUE);
+
+        // A directory can't be opened as a database file
+        config.knowledge.local_db = dir.path().to_path_buf();
+        let err = show_status(&config, &status_query(false, None, None), false, false, None, &Timezone::Utc).await.unwrap_err();
+        assert_eq!(exit_code(&err), exit::QUEUE);
+    }
+
+    #[test]
+    fn test_exit_code_for_invalid_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[audio\nsample_rate = ").unwrap();
+
+        let err = Config::load(&path).unwrap_err().into();
+        assert_eq!(exit_code(&err), exit::CONFIG);
+
+        assert_eq!(exit_code(&anyhow::anyhow!("boom")), exit::FAILURE);
+    }
+
+    #[test]
+    fn test_exit_code_for_killed_runs() {
+        let unavailable = || ExecutorError::Unavailable { cli_path: "claude".to_string() };
+        let timed_out = || ExecutorError::TimedOut { after: Duration::from_secs(5) };
+        assert_eq!(exit_code(&FasterError::from(unavailable()).into()), exit::CLAUDE_UNAVAILABLE);
+        assert_eq!(exit_code(&unavailable().into()), exit::CLAUDE_UNAVAILABLE);
+
+        for err in [FasterError::from(ExecutorError::Cancelled).into(), FasterError::from(timed_out()).into()] {
+            assert_eq!(exit_code(&err), exit::FAILURE);
+        }
+        let bare = anyhow::Error::from(timed_out()).context("Task failed");
+        assert_eq!(exit_code(&bare), exit::FAILURE);
+        assert_eq!(exit_code(&ExecutorError::Cancelled.into()), exit::FAILURE);
+    }
+
+    #[tokio::test]
+    async fn test_low_confidence_command_saved_as_draft() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let processor = IntentProcessor::new(config.intent.confidence_threshold);
+
+        // No intent keywords, so classification falls back below the threshold
+        let vague = classify_typed(&processor, "hmm that thing from before", None);
+        let id = queue_command(&vague, &config, None, false).await.unwrap();
+
+        let queue = open_queue(&config).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Draft);
+
+        let clear = classify_typed(&processor, "run the tests", None);
+        let id = queue_command(&clear, &config, None, false).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Queued);
+    }
+
+    #[tokio::test]
+    async fn test_typed_alias_queues_expansion() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&dir);
+        config
+            .aliases
+            .insert("ship it".to_string(), "run the CI pipeline and deploy to staging".to_string());
+        let processor = IntentProcessor::new(config.intent.confidence_threshold)
+            .with_aliases(config.aliases.clone());
+
+        let command = classify_typed(&processor, "Ship it", None);
+        assert_eq!(command.intent, Intent::Orchestrate);
+        let id = queue_command(&command, &config, None, false).await.unwrap();
+
+        let queue = open_queue(&config).await.unwrap();
+        assert_eq!(
+            queue.get(&id).await.unwrap().unwrap().command,
+            "run the CI pipeline and deploy to staging"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queue_command_resolves_model_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&dir);
+        config.claude.model = "smart".to_string();
+        config.claude.model_aliases =
+            ModelAliases::new([("fast".to_string(), "haiku".to_string()), ("smart".to_string(), "opus".to_string())])
+                .unwrap();
+        let processor = IntentProcessor::new(config.intent.confidence_threshold);
+        let command = classify_typed(&processor, "run the tests", None);
+
+        let overridden = queue_command(&command, &config, Some("fast".to_string()), false).await.unwrap();
+        let default = queue_command(&command, &config, None, false).await.unwrap();
+
+        let queue = open_queue(&config).await.unwrap();
+        assert_eq!(queue.get(&overridden).await.unwrap().unwrap().model.as_deref(), Some("haiku"));
+        assert_eq!(queue.get(&default).await.unwrap().unwrap().model.as_deref(), Some("opus"));
+    }
+
+    #[tokio::test]
+    async fn test_queue_command_stores_confidence() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let processor = IntentProcessor::new(config.intent.confidence_threshold);
+
+        let command = classify_typed(&processor, "run the tests", None);
+        let id = queue_command(&command, &config, None, false).await.unwrap();
+
+        let queue = open_queue(&config).await.unwrap();
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.confidence, Some(command.confidence));
+        assert!(format_task_details(&task, &Timezone::Utc)
+            .contains(&format!("Confidence: {:.0}%", command.confidence * 100.0)));
+        assert!(serde_json::to_value(&task).unwrap()["confidence"].is_number());
+    }
+
+    #[test]
+    #[serial_test::serial(colors)]
+    fn test_status_row_italicizes_low_confidence() {
+        colored::control::set_override(true);
+
+        let mut task = status_task("abc12345", "maybe this", TaskStatus::Queued, Some(Intent::Code));
+        let layout = StatusLayout::for_tasks(std::slice::from_ref(&task), 0.8);
+
+        task.confidence = Some(0.3);
+        let guessed = format_status_row(&task, &layout, None);
+        task.confidence = None;
+        let legacy = format_status_row(&task, &layout, None);
+        colored::control::unset_override();
+
+        assert!(guessed.contains("\x1b[2;3m"));
+        assert!(!legacy.contains("\x1b[2;3m"));
+    }
+
+    #[tokio::test]
+    async fn test_promote_moves_draft_to_queued() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let queue = open_queue(&config).await.unwrap();
+
+        let draft = Command::new(Intent::Code, "search for the old parser".to_string(), vec![], 0.5);
+        let id = queue.enqueue_draft(&draft, None).await.unwrap();
+
+        assert_eq!(promote(&config, &id[..4], true).await.unwrap(), Intent::Research);
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Queued);
+        assert_eq!(task.intent, Some(Intent::Research));
+
+        // Already queued, so there is nothing to promote
+        assert!(promote(&config, &id, false).await.is_err());
+
+        let other = queue.enqueue_draft(&draft, None).await.unwrap();
+        assert_eq!(promote(&config, &other, false).await.unwrap(), Intent::Code);
+    }
+
+    #[tokio::test]
+    async fn test_failures_lists_failed_tasks_and_retries_them() {
+        colored::control::set_override(false);
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+
+        let queue = open_queue(&config).await.unwrap();
+        let first = queue.enqueue("Flaky test", None).await.unwrap();
+        queue.start_attempt(&first, None).await.unwrap();
+        queue.update_status(&first, TaskStatus::Running).await.unwrap();
+        queue.fail(&first, "exit status 1").await.unwrap();
+        let second = queue.enqueue("Broken build", None).await.unwrap();
+        queue.update_status(&second, TaskStatus::Running).await.unwrap();
+        queue.fail(&second, "missing dependency").await.unwrap();
+        let done = queue.enqueue("Fine", None).await.unwrap();
+        queue.update_status(&done, TaskStatus::Running).await.unwrap();
+        queue.update_status(&done, TaskStatus::Completed).await.unwrap();
+        queue.enqueue("Waiting", None).await.unwrap();
+
+        let failed = show_failures(&config, 0, false, &Timezone::Utc).await.unwrap();
+        let mut ids: Vec<&str> = failed.iter().map(|t| t.id.as_str()).collect();
+        ids.sort();
+        let mut expected = vec![first.as_str(), second.as_str()];
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert!(failed.iter().all(|t| t.status == TaskStatus::Failed && t.error.is_some()));
+        assert_eq!(show_failures(&config, 1, false, &Timezone::Utc).await.unwrap().len(), 1);
+
+        let task = queue.get(&first).await.unwrap().unwrap();
+        let shown = format_failure(&task, 1, &Timezone::Utc);
+        assert!(shown.starts_with(&format!("✗ [{}] Flaky test\n    Error: exit status 1\n    1 attempt(s) · failed ", first)), "{}", shown);
+
+        // Listing alone changes nothing; --retry-all requeues every failure
+        assert_eq!(queue.get(&second).await.unwrap().unwrap().status, TaskStatus::Failed);
+        show_failures(&config, 1, true, &Timezone::Utc).await.unwrap();
+        for id in [&first, &second] {
+            assert_eq!(queue.get(id).await.unwrap().unwrap().status, TaskStatus::Queued);
+        }
+        assert_eq!(queue.get(&done).await.unwrap().unwrap().status, TaskStatus::Completed);
+        assert!(show_failures(&config, 0, false, &Timezone::Utc).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_requeue_all_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+
+        let queue = open_queue(&config).await.unwrap();
+        let a = queue.enqueue("A", None).await.unwrap();
+        let b = queue.enqueue("B", None).await.unwrap();
+        queue.update_status(&a, TaskStatus::Running).await.unwrap();
+        queue.fail(&a, "boom").await.unwrap();
+        queue.update_status(&b, TaskStatus::Running).await.unwrap();
+        queue.fail(&b, "boom").await.unwrap();
+
+        assert_eq!(requeue(&config, None, true).await.unwrap(), 2);
+        assert_eq!(queue.get(&a).await.unwrap().unwrap().status, TaskStatus::Queued);
+
+        // A retried task runs fresh, without the last run's error, times or claimer
+        let claimed = queue.claim_next_as("worker-1").await.unwrap().unwrap();
+        queue.fail(&claimed.id, "boom again").await.unwrap();
+        assert_eq!(requeue(&config, Some(&claimed.id[..4]), false).await.unwrap(), 1);
+        let task = queue.get(&claimed.id).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Queued);
+        assert_eq!((task.error, task.started_at, task.completed_at, task.processed_by), (None, None, None, None));
+        // Already queued, so there's nothing to retry
+        assert!(requeue(&config, Some(&claimed.id[..4]), false).await.is_err());
+        assert!(requeue(&config, Some("missing"), false).await.is_err());
+
+        assert!(Cli::try_parse_from(["faster", "requeue"]).is_err());
+        assert!(Cli::try_parse_from(["faster", "requeue", "--all-failed"]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_hold_and_unhold() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let queue = open_queue(&config).await.unwrap();
+        let id = queue.enqueue("Wait for the release", None).await.unwrap();
+
+        set_held(&config, &id[..4], true).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Blocked);
+        assert!(set_held(&config, &id, true).await.unwrap_err().to_string().contains("is blocked, not queued"));
+
+        set_held(&config, &id, false).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Queued);
+        assert!(set_held(&config, &id, false).await.is_err());
+
+        let failed = queue.enqueue("Broken", None).await.unwrap();
+        queue.update_status(&failed, TaskStatus::Running).await.unwrap();
+        queue.fail(&failed, "boom").await.unwrap();
+        assert!(set_held(&config, &failed, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_task_by_prefix_or_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let queue = open_queue(&config).await.unwrap();
+        let id = queue.enqueue("Stale idea", None).await.unwrap();
+
+        cancel_task(&config, &id[..4]).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Cancelled);
+
+        let err = cancel_task(&config, "missing").await.unwrap_err();
+        assert_eq!(err.to_string(), "Task not found: missing");
+        assert_eq!(exit_code(&err), exit::USAGE);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+
+        let queue = open_queue(&config).await.unwrap();
+        queue.enqueue("A", None).await.unwrap();
+        queue.enqueue("B", None).await.unwrap();
+        let running = queue.enqueue("C", None).await.unwrap();
+        queue.update_status(&running, TaskStatus::Running).await.unwrap();
+
+        assert_eq!(cancel_all(&config, None, true).await.unwrap(), 2);
+        assert_eq!(queue.get(&running).await.unwrap().unwrap().status, TaskStatus::Running);
+
+        let old = queue.enqueue("Call the OLD api", None).await.unwrap();
+        let new = queue.enqueue("Call the new api", None).await.unwrap();
+        assert_eq!(cancel_all(&config, Some("old API"), true).await.unwrap(), 1);
+        assert_eq!(queue.get(&old).await.unwrap().unwrap().status, TaskStatus::Cancelled);
+        assert_eq!(queue.get(&new).await.unwrap().unwrap().status, TaskStatus::Queued);
+
+        assert!(Cli::try_parse_from(["faster", "cancel"]).is_err());
+        assert!(Cli::try_parse_from(["faster", "cancel", "abc", "--all"]).is_err());
+        assert!(matches!(
+            Cli::parse_from(["faster", "cancel", "--all", "-y"]).command,
+            Some(Commands::Cancel { task_id: None, all: true, matching: None, yes: true })
+        ));
+        assert!(matches!(
+            Cli::parse_from(["faster", "cancel", "--matching", "old api"]).command,
+            Some(Commands::Cancel { task_id: None, all: false, matching: Some(m), yes: false }) if m == "old api"
+        ));
+        assert!(Cli::try_parse_from(["faster", "cancel", "--all", "--matching", "x"]).is_err());
+        assert!(Cli::try_parse_from(["faster", "cancel", "abc", "--matching", "x"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_tasks_exit_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&dir);
+        config.claude.cli_path = "true".to_string();
+
+        let queue = open_queue(&config).await.unwrap();
+        queue.enqueue("A", None).await.unwrap();
+        queue.enqueue("B", None).await.unwrap();
+        queue.enqueue("C", None).await.unwrap();
+
+        run_tasks(&config, Some(2)).await.unwrap();
+        assert_eq!(queue.list().await.unwrap().iter().filter(|t| t.status == TaskStatus::Queued).count(), 1);
+
+        // `false` exits non-zero, so the remaining task fails
+        config.claude.cli_path = "false".to_string();
+        let err = run_tasks(&config, None).await.unwrap_err();
+        assert!(err.to_string().contains("1 of 1 task(s) failed"));
+        assert_eq!(exit_code(&err), exit::FAILURE);
+
+        assert!(Cli::try_parse_from(["faster", "run", "--max", "5"]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_task_json_shape() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let id = queue.enqueue("Run tests", Some("opus".to_string())).await.unwrap();
+        queue.update_status(&id, TaskStatus::Running).await.unwrap();
+        queue.fail(&id, "boom").await.unwrap();
+
+        let task = queue.get(&id).await.unwrap().unwrap();
+        let json: serde_json::Value = serde_json::to_value(&task).unwrap();
+
+        for key in [
+            "id", "command", "status", "model", "created_at",
+            "started_at", "completed_at", "error", "rendered_prompt", "intent",
+        ] {
+            assert!(json.get(key).is_some(), "missing key {}", key);
+        }
+        assert_eq!(json["status"], "failed");
+        assert_eq!(json["error"], "boom");
+
+        let text = format_task_details(&task, &Timezone::Utc);
+        assert!(text.contains("Run tests"));
+        assert!(text.contains("boom"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Ok(chrono::Duration::seconds(90)));
+        assert_eq!(parse_duration("1h"), Ok(chrono::Duration::hours(1)));
+        assert_eq!(parse_duration("1h30m"), Ok(chrono::Duration::minutes(90)));
+        assert_eq!(parse_duration("2 days"), Ok(chrono::Duration::days(2)));
+        assert_eq!(parse_duration("1w 1d"), Ok(chrono::Duration::days(8)));
+
+        for bad in ["", "h", "10", "5 parsecs", "-1h", "1h x"] {
+            assert!(parse_duration(bad).is_err(), "{:?} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_status_flags() {
+        assert_eq!(parse_status("Failed"), Ok(TaskStatus::Failed));
+        assert!(parse_status("done").is_err());
+
+        let cli = Cli::try_parse_from(["faster", "status", "--status", "failed", "--since", "1h"]).unwrap();
+        let Some(Commands::Status { all, status, since, .. }) = cli.command else {
+            panic!("expected status");
+        };
+        assert_eq!(status, Some(TaskStatus::Failed));
+        assert_eq!(since, Some(chrono::Duration::hours(1)));
+
+        // An explicit status shows finished tasks without --all
+        let query = status_query(all, status, since);
+        assert!(!query.active_only);
+        assert_eq!(query.status, Some(TaskStatus::Failed));
+        let since = query.since.unwrap();
+        assert!(since <= chrono::Utc::now() - chrono::Duration::minutes(59));
+
+        assert!(status_query(false, None, None).active_only);
+        assert!(!status_query(true, None, None).active_only);
+        assert!(Cli::try_parse_from(["faster", "status", "--since", "soon"]).is_err());
+    }
+
+    /// Records speech and stops as "say:<text>" and "stop"
+    #[derive(Default)]
+    struct SpyTts(std::sync::Mutex<Vec<String>>);
+
+    impl TtsBackend for SpyTts {
+        fn speak(&self, text: &str) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(format!("say:{}", text));
+            Ok(())
+        }
+
+        fn speak_async(&self, text: &str) -> anyhow::Result<()> {
+            self.speak(text)
+        }
+
+        fn stop(&self) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push("stop".to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_listen_push_to_talk_turns() {
+        use std::io::BufRead;
+
+        let tts = SpyTts::default();
+        let session = VoiceSignals::default();
+        let mut keys = std::io::Cursor::new("\n\n\nuse opus\n");
+        let mut read_line = |line: &mut String| keys.read_line(line);
+        let mut utterances = vec![
+            Err(Cancelled.into()),
+            Err(anyhow::anyhow!("No speech detected")),
+            Ok(Transcription::new("run the tests")),
+        ];
+        let mut transcribe = || utterances.pop().unwrap();
+
+        // Turn 1: Enter, then an utterance; the reply plays into turn 2
+        let heard = listen(true, &tts, &mut read_line, &session, &ModelAliases::default(), &mut transcribe).unwrap();
+        assert!(matches!(heard, Heard::Utterance(t) if t.text == "run the tests"));
+        tts.speak_async("Test").unwrap();
+
+        // Turn 2: pressing Enter cuts the reply off before listening
+        let heard = listen(true, &tts, &mut read_line, &session, &ModelAliases::default(), &mut transcribe).unwrap();
+        assert!(matches!(heard, Heard::Nothing(_)));
+        assert_eq!(*tts.0.lock().unwrap(), ["stop", "say:Test", "stop"]);
+
+        // Turn 3: dismissing the dialog isn't an error
+        let heard = listen(true, &tts, &mut read_line, &session, &ModelAliases::default(), &mut transcribe).unwrap();
+        assert!(matches!(heard, Heard::Cancelled));
+
+        // Turn 4: a typed meta-command skips transcription
+        let heard = listen(true, &tts, &mut read_line, &session, &ModelAliases::default(), &mut transcribe).unwrap();
+        assert!(matches!(heard, Heard::Meta(MetaCommand::UseModel(m)) if m == "opus"));
+
+        // Turn 5: end of input
+        assert!(matches!(listen(true, &tts, &mut read_line, &session, &ModelAliases::default(), &mut transcribe).unwrap(), Heard::Exit));
+    }
+
+    #[test]
+    fn test_listen_without_push_to_talk_and_interrupt() {
+        let stt = audio::NullStt::from_reader(std::io::Cursor::new("run the tests\n"));
+        let tts = SpyTts::default();
+        let session = VoiceSignals::default();
+        let mut read_line = |_: &mut String| -> std::io::Result<usize> { panic!("no Enter without push-to-talk") };
+        let mut transcribe = || audio::SttBackend::transcribe(&stt);
+
+        let heard = listen(false, &tts, &mut read_line, &session, &ModelAliases::default(), &mut transcribe).unwrap();
+        assert!(matches!(heard, Heard::Utterance(t) if t.text == "run the tests"));
+        assert_eq!(*tts.0.lock().unwrap(), ["stop"]);
+        assert!(matches!(listen(false, &tts, &mut read_line, &session, &ModelAliases::default(), &mut transcribe).unwrap(), Heard::Exit));
+
+        // Ctrl+C during a turn ends the loop at the next listen
+        let stt = audio::NullStt::from_reader(std::io::Cursor::new("run the tests\n"));
+        let mut transcribe = || audio::SttBackend::transcribe(&stt);
+        session.interrupted.store(true, Ordering::SeqCst);
+        assert!(matches!(listen(false, &tts, &mut read_line, &session, &ModelAliases::default(), &mut transcribe).unwrap(), Heard::Exit));
+    }
+
+    /// Fails every transcription, like a backend denied the microphone
+    struct FailingStt;
+
+    impl audio::SttBackend for FailingStt {
+        fn transcribe(&self) -> anyhow::Result<Transcription> {
+            anyhow::bail!("Microphone access denied")
+        }
+
+        fn availability(&self) -> audio::AvailabilityStatus {
+            audio::AvailabilityStatus { available: true, reason: None }
+        }
+
+        fn push_to_talk(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_throttle_backs_off_and_pauses_failing_stt() {
+        let stt = FailingStt;
+        let tts = SpyTts::default();
+        let session = VoiceSignals::default();
+        let mut read_line = |_: &mut String| -> std::io::Result<usize> { Ok(0) };
+        let mut transcribe = || audio::SttBackend::transcribe(&stt);
+        let config = faster::config::SttConfig { min_turn_interval_ms: 100, max_consecutive_errors: 3, ..Default::default() };
+        let mut throttle = TurnThrottle::new(&config);
+
+        let start = Instant::now();
+        let mut delays = Vec::new();
+        let mut paused_at = None;
+        for turn in 1..=4 {
+            // Each turn returns immediately, so the whole interval is waited
+            delays.push(throttle.next_turn_delay(start + delays.iter().sum::<Duration>()));
+            let heard = listen(audio::SttBackend::push_to_talk(&stt), &tts, &mut read_line, &session, &ModelAliases::default(), &mut transcribe).unwrap();
+            assert!(matches!(heard, Heard::Nothing(_)));
+            if throttle.record_error() && paused_at.is_none() {
+                paused_at = Some(turn);
+            }
+        }
+
+        let ms = |ms| Duration::from_millis(ms);
+        assert_eq!(delays, [Duration::ZERO, ms(200), ms(400), ms(100)]);
+        assert_eq!(paused_at, Some(3));
+    }
+
+    #[test]
+    fn test_throttle_success_resets_and_zero_cap_never_pauses() {
+        let config = faster::config::SttConfig { min_turn_interval_ms: 100, max_consecutive_errors: 0, ..Default::default() };
+        let mut throttle = TurnThrottle::new(&config);
+        let start = Instant::now();
+
+        assert!((0..10).all(|_| !throttle.record_error()));
+        throttle.record_success();
+        throttle.next_turn_delay(start);
+        // A slow turn already used up the interval
+        assert_eq!(throttle.next_turn_delay(start + Duration::from_secs(1)), Duration::ZERO);
+        assert_eq!(throttle.next_turn_delay(start + Duration::from_millis(1030)), Duration::from_millis(70));
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_queue_stays_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(&dir);
+        config.knowledge.local_db = dir.path().join("nested/queue.db");
+        config.queue.ephemeral = true;
+        assert!(Cli::parse_from(["faster", "--ephemeral", "run tests"]).ephemeral);
+
+        let command = Command::new(Intent::Test, "run the tests", vec![], 1.0);
+        let id = queue_command(&command, &config, None, false).await.unwrap();
+
+        // Later opens in the same process see the task, despite the close above
+        let queue = open_queue(&config).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().command, "run the tests");
+        assert!(!queue.backing_file_missing());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_json_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let specs = dir.path().join("specs.json");
+        std::fs::write(&specs, r#"[{"command": "Lint", "priority": 2}, {"command": "Test", "tags": ["ci"]}]"#).unwrap();
+
+        let ids = enqueue_json_file(&config, &specs, &[], false).await.unwrap();
+
+        let queue = open_queue(&config).await.unwrap();
+        let lint = queue.get(&ids[0]).await.unwrap().unwrap();
+        assert_eq!((lint.command.as_str(), lint.priority), ("Lint", 2));
+        assert_eq!(queue.get(&ids[1]).await.unwrap().unwrap().tags, vec!["ci"]);
+
+        std::fs::write(&specs, r#"[{"command": "Ok"}, {"command": "Bad", "model": "gpt"}]"#).unwrap();
+        let err = enqueue_json_file(&config, &specs, &[], false).await.unwrap_err();
+        assert_eq!(exit_code(&err), exit::USAGE);
+        assert_eq!(queue.list().await.unwrap().len(), 2);
+
+        let cli = Cli::parse_from(["faster", "enqueue", "--json-file", "-"]);
+        assert!(matches!(cli.command, Some(Commands::Enqueue { json_file, .. }) if json_file.as_os_str() == "-"));
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_best_effort_reports_failed_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let specs = dir.path().join("specs.json");
+        std::fs::write(
+            &specs,
+            r#"[{"command": "Lint"}, {"command": "Bad", "model": "gpt"}, {"command": "Test"},
+                {"comand": "typo"}, {"command": "Build"}]"#,
+        )
+        .unwrap();
+
+        // All or nothing by default
+        let err = enqueue_json_file(&config, &specs, &[], false).await.unwrap_err();
+        assert_eq!(exit_code(&err), exit::USAGE);
+        let queue = open_queue(&config).await.unwrap();
+        assert!(queue.list().await.unwrap().is_empty());
+
+        let err = enqueue_json_file(&config, &specs, &[], true).await.unwrap_err();
+        assert_eq!(exit_code(&err), exit::USAGE);
+        let Some(QueueError::InvalidSpecs { errors }) = err.downcast_ref() else {
+            panic!("expected InvalidSpecs, got {}", err);
+        };
+        assert_eq!(format_batch_summary(3, errors), "3 queued, 2 failed (items 1, 3)");
+        let mut queued: Vec<_> = queue.list().await.unwrap().into_iter().map(|t| t.command).collect();
+        queued.sort();
+        assert_eq!(queued, vec!["Build", "Lint", "Test"]);
+
+        let cli = Cli::parse_from(["faster", "enqueue", "--json-file", "-", "--best-effort"]);
+        assert!(matches!(cli.command, Some(Commands::Enqueue { best_effort: true, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_meta_flags() {
+        let cli = Cli::parse_from(["faster", "enqueue", "--json-file", "-", "--meta", "pr=12", "--meta", "q= a=b"]);
+        let Some(Commands::Enqueue { metadata, .. }) = cli.command else {
+            panic!("expected enqueue");
+        };
+        assert_eq!(metadata, vec![("pr".to_string(), "12".to_string()), ("q".to_string(), " a=b".to_string())]);
+
+        for bad in ["novalue", "=12", " =12"] {
+            assert!(Cli::try_parse_from(["faster", "enqueue", "--json-file", "-", "--meta", bad]).is_err(), "{}", bad);
+        }
+
+        // A spec's own metadata wins over the flag
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let specs = dir.path().join("specs.json");
+        std::fs::write(&specs, r#"[{"command": "Lint"}, {"command": "Test", "metadata": {"pr": "7"}}]"#).unwrap();
+        let ids = enqueue_json_file(&config, &specs, &metadata, false).await.unwrap();
+
+        let queue = open_queue(&config).await.unwrap();
+        let lint = queue.get(&ids[0]).await.unwrap().unwrap();
+        let test = queue.get(&ids[1]).await.unwrap().unwrap();
+        assert_eq!(lint.metadata.get("pr").map(String::as_str), Some("12"));
+        assert_eq!(test.metadata.get("pr").map(String::as_str), Some("7"));
+        assert_eq!(test.metadata.get("q").map(String::as_str), Some(" a=b"));
+
+        let json: serde_json::Value = serde_json::to_value(&test).unwrap();
+        assert_eq!(json["metadata"]["pr"], "7");
+        assert!(format_task_details(&test, &Timezone::Utc).contains("pr=7"));
     }
 }
@@ -7,10 +7,62 @@ use std::collections::BTreeMap;
 
 #[derive(Default)]
 struct State {
-    tasks: Vec<String>,
+    tasks: Vec<PluginTask>,
     input_mode: bool,
     current_input: String,
-    selected_index: usize,
+    selection: Selection,
+}
+
+/// A queue row as the plugin shows it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PluginTask {
+    id: String,
+    label: String,
+}
+
+/// Highlighted task, tracked by id so a refresh that reorders the list keeps it
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Selection {
+    id: Option<String>,
+    index: usize,
+    /// First task row on screen
+    scroll: usize,
+}
+
+impl Selection {
+    /// Find the selected task again in a refreshed list
+    ///
+    /// If it's gone, whichever task took its old position is selected,
+    /// clamped to the end of the list.
+    fn reconcile(&mut self, tasks: &[PluginTask]) {
+        if let Some(index) = self.id.as_ref().and_then(|id| tasks.iter().position(|t| &t.id == id)) {
+            self.index = index;
+        } else {
+            self.index = self.index.min(tasks.len().saturating_sub(1));
+            self.id = tasks.get(self.index).map(|t| t.id.clone());
+        }
+    }
+
+    /// Move the highlight by `delta` rows, stopping at either end
+    fn move_by(&mut self, tasks: &[PluginTask], delta: isize) {
+        if tasks.is_empty() {
+            return;
+        }
+        self.index = self.index.saturating_add_signed(delta).min(tasks.len() - 1);
+        self.id = Some(tasks[self.index].id.clone());
+    }
+
+    /// Scroll just enough that the selection is among `visible` rows
+    fn follow(&mut self, visible: usize) {
+        if visible == 0 {
+            return;
+        }
+        if self.index < self.scroll {
+            self.scroll = self.index;
+        } else if self.index >= self.scroll + visible {
+            self.scroll = self.index + 1 - visible;
+        }
+    }
 }
 
 register_plugin!(State);
@@ -45,6 +97,9 @@ impl ZellijPlugin for State {
 
     fn render(&mut self, rows: usize, cols: usize) {
         let mut text = String::new();
+        // Header, input line and footer take the other rows
+        let visible = rows.saturating_sub(4);
+        self.selection.follow(visible);
 
         // Header
         text.push_str(&format!("┌─ Faster Queue {}┐\n", "─".repeat(cols - 18)));
@@ -53,8 +108,9 @@ impl ZellijPlugin for State {
         if self.tasks.is_empty() {
             text.push_str("│ No tasks in queue                             │\n");
         } else {
-            for (i, task) in self.tasks.iter().enumerate() {
-                let marker = if i == self.selected_index { "→" } else { " " };
+            for (i, task) in self.tasks.iter().enumerate().skip(self.selection.scroll).take(visible) {
+                let marker = if i == self.selection.index { "→" } else { " " };
+                let task = &task.label;
                 let task_display = if task.len() > cols - 10 {
                     format!("{}...", &task[..cols - 13])
                 } else {
@@ -69,7 +125,8 @@ impl ZellijPlugin for State {
         }
 
         // Input area
-        let padding = rows.saturating_sub(self.tasks.len() + 4);
+        let shown = self.tasks.len().saturating_sub(self.selection.scroll).min(visible);
+        let padding = visible - shown;
         for _ in 0..padding {
             text.push_str(&format!("│{}│\n", " ".repeat(cols - 2)));
         }
@@ -127,15 +184,11 @@ impl State {
                     true
                 }
                 Key::Char('j') | Key::Down => {
-                    if self.selected_index < self.tasks.len().saturating_sub(1) {
-                        self.selected_index += 1;
-                    }
+                    self.selection.move_by(&self.tasks, 1);
                     true
                 }
                 Key::Char('k') | Key::Up => {
-                    if self.selected_index > 0 {
-                        self.selected_index -= 1;
-                    }
+                    self.selection.move_by(&self.tasks, -1);
                     true
                 }
                 Key::Char('d') => {
@@ -150,11 +203,15 @@ impl State {
     fn refresh_tasks(&mut self) {
         // TODO: Read from SQLite database
         // For now, use mock data
-        self.tasks = vec![
-            "⏳ run tests".to_string(),
-            "→ fix auth bug [45%]".to_string(),
-            "✓ list files".to_string(),
-        ];
+        self.tasks = [
+            ("a1b2c3d4", "⏳ run tests"),
+            ("e5f6a7b8", "→ fix auth bug [45%]"),
+            ("c9d0e1f2", "✓ list files"),
+        ]
+        .into_iter()
+        .map(|(id, label)| PluginTask { id: id.to_string(), label: label.to_string() })
+        .collect();
+        self.selection.reconcile(&self.tasks);
     }
 
     fn submit_command(&mut self) {
@@ -166,10 +223,70 @@ impl State {
     }
 
     fn cancel_selected_task(&mut self) {
-        if self.selected_index < self.tasks.len() {
+        if let Some(id) = &self.selection.id {
             // TODO: Cancel task in SQLite
-            eprintln!("Canceling task at index {}", self.selected_index);
+            eprintln!("Canceling task {}", id);
             self.refresh_tasks();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(ids: &[&str]) -> Vec<PluginTask> {
+        ids.iter()
+            .map(|id| PluginTask { id: id.to_string(), label: format!("task {}", id) })
+            .collect()
+    }
+
+    #[test]
+    fn test_selection_follows_task_across_reorder() {
+        let mut selection = Selection::default();
+        let before = tasks(&["a", "b", "c"]);
+        selection.reconcile(&before);
+        assert_eq!(selection.id.as_deref(), Some("a"));
+
+        selection.move_by(&before, 1);
+        assert_eq!(selection.id.as_deref(), Some("b"));
+
+        // "b" moved to the end when its status changed
+        selection.reconcile(&tasks(&["c", "a", "b"]));
+        assert_eq!((selection.id.as_deref(), selection.index), (Some("b"), 2));
+    }
+
+    #[test]
+    fn test_selection_clamps_when_task_disappears() {
+        let mut selection = Selection::default();
+        let before = tasks(&["a", "b", "c"]);
+        selection.reconcile(&before);
+        selection.move_by(&before, 2);
+        selection.move_by(&before, 5);
+        assert_eq!(selection.id.as_deref(), Some("c"));
+
+        // The last task was removed, so its new neighbour takes over
+        selection.reconcile(&tasks(&["a", "b"]));
+        assert_eq!((selection.id.as_deref(), selection.index), (Some("b"), 1));
+
+        selection.reconcile(&[]);
+        assert_eq!(selection.id, None);
+        selection.move_by(&[], 1);
+        assert_eq!(selection.index, 0);
+    }
+
+    #[test]
+    fn test_scroll_follows_selection() {
+        let list = tasks(&["a", "b", "c", "d", "e"]);
+        let mut selection = Selection::default();
+        selection.reconcile(&list);
+
+        selection.move_by(&list, 4);
+        selection.follow(2);
+        assert_eq!(selection.scroll, 3);
+
+        selection.move_by(&list, -4);
+        selection.follow(2);
+        assert_eq!(selection.scroll, 0);
+    }
+}
@@ -0,0 +1,113 @@
+//! Synchronous wrapper around `TaskQueue` for callers without a tokio runtime
+
+use crate::error::Result;
+use tokio::runtime::{Builder, Runtime};
+
+use super::db::{Task, TaskQueue, TaskStatus};
+
+/// `TaskQueue` with blocking methods, driven by its own current-thread runtime
+///
+/// The runtime is created once and reused for every call. Don't use this
+/// from inside an async context; blocking on a runtime from within another
+/// panics. Use `TaskQueue` directly there.
+///
+/// ```no_run
+/// let queue = faster::queue::BlockingTaskQueue::new("/tmp/faster.db")?;
+/// let id = queue.enqueue("Run the tests", None)?;
+/// println!("{:?}", queue.get(&id)?.map(|task| task.status));
+/// # Ok::<(), faster::FasterError>(())
+/// ```
+pub struct BlockingTaskQueue {
+    runtime: Runtime,
+    queue: TaskQueue,
+}
+
+impl BlockingTaskQueue {
+    /// Open a SQLite-backed queue
+    pub fn new(db_path: &str) -> Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let queue = runtime.block_on(TaskQueue::new(db_path))?;
+        Ok(Self { runtime, queue })
+    }
+
+    /// Queue a command, returning its id
+    pub fn enqueue(&self, command: &str, model: Option<String>) -> Result<String> {
+        self.runtime.block_on(self.queue.enqueue(command, model))
+    }
+
+    /// All tasks, newest first
+    pub fn list(&self) -> Result<Vec<Task>> {
+        self.runtime.block_on(self.queue.list())
+    }
+
+    /// Queued and running tasks, running first
+    pub fn list_active(&self) -> Result<Vec<Task>> {
+        self.runtime.block_on(self.queue.list_active())
+    }
+
+    /// Task by exact id
+    pub fn get(&self, id: &str) -> Result<Option<Task>> {
+        self.runtime.block_on(self.queue.get(id))
+    }
+
+    /// Cancel a queued or draft task; false if it doesn't exist or has already started
+    pub fn cancel(&self, id: &str) -> Result<bool> {
+        self.runtime.block_on(async {
+            match self.queue.get(id).await? {
+                Some(task) if matches!(task.status, TaskStatus::Queued | TaskStatus::Draft) => {
+                    self.queue.update_status(id, TaskStatus::Cancelled).await?;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        })
+    }
+
+    /// Checkpoint and close the database
+    pub fn close(self) -> Result<()> {
+        self.runtime.block_on(self.queue.close())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking_enqueue_list_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.db");
+        let queue = BlockingTaskQueue::new(&path.to_string_lossy()).unwrap();
+
+        let first = queue.enqueue("Run tests", None).unwrap();
+        let second = queue.enqueue("Deploy", Some("opus".to_string())).unwrap();
+
+        let ids: Vec<_> = queue.list().unwrap().into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![second.clone(), first]);
+
+        let task = queue.get(&second).unwrap().unwrap();
+        assert_eq!(task.model.as_deref(), Some("opus"));
+        assert_eq!(task.status, TaskStatus::Queued);
+        assert!(queue.get("missing").unwrap().is_none());
+        queue.close().unwrap();
+
+        // Data outlives the handle
+        let reopened = BlockingTaskQueue::new(&path.to_string_lossy()).unwrap();
+        assert_eq!(reopened.list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_blocking_cancel() {
+        let queue = BlockingTaskQueue::new(":memory:").unwrap();
+        let queued = queue.enqueue("Queued", None).unwrap();
+        let running = queue.enqueue("Running", None).unwrap();
+        queue.runtime.block_on(queue.queue.update_status(&running, TaskStatus::Running)).unwrap();
+
+        assert!(queue.cancel(&queued).unwrap());
+        assert_eq!(queue.get(&queued).unwrap().unwrap().status, TaskStatus::Cancelled);
+
+        assert!(!queue.cancel(&queued).unwrap());
+        assert!(!queue.cancel(&running).unwrap());
+        assert!(!queue.cancel("missing").unwrap());
+    }
+}
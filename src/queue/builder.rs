@@ -0,0 +1,309 @@
+//! Fluent construction of tasks for library callers
+
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::db::{TaskQueue, TaskStatus};
+use super::store::NewTask;
+use crate::intent::Intent;
+
+/// A task being assembled for `TaskQueue`, inserted by `enqueue`
+///
+/// ```no_run
+/// # async fn example(queue: &faster::TaskQueue) -> anyhow::Result<()> {
+/// let id = queue
+///     .build("Run the integration tests")
+///     .model("opus")
+///     .priority(10)
+///     .tag("ci")
+///     .cwd("/src/app")
+///     .enqueue()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[must_use = "a TaskBuilder does nothing until `enqueue` is awaited"]
+pub struct TaskBuilder<'q> {
+    queue: &'q TaskQueue,
+    task: NewTask,
+}
+
+impl<'q> TaskBuilder<'q> {
+    pub(super) fn new(queue: &'q TaskQueue, task: NewTask) -> Self {
+        Self { queue, task }
+    }
+
+    /// Claude model to run with
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.task.model = Some(model.into());
+        self
+    }
+
+    /// Intent to record, instead of leaving the task unclassified
+    pub fn intent(mut self, intent: Intent) -> Self {
+        self.task.intent = Some(intent);
+        self
+    }
+
+    /// Higher priorities are claimed before older, lower ones (default 0)
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.task.priority = priority;
+        self
+    }
+
+    /// Add a label; repeated tags are stored once
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        let tag = tag.into();
+        if !self.task.tags.contains(&tag) {
+            self.task.tags.push(tag);
+        }
+        self
+    }
+
+    /// Attach an opaque key/value pair, replacing any earlier value for `key`
+    pub fn meta(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.task.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Put `prefix` before the prompt instead of `claude.prompt_prefix` ("" for none)
+    pub fn prompt_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.task.prompt_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Put `suffix` after the prompt instead of `claude.prompt_suffix` ("" for none)
+    pub fn prompt_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.task.prompt_suffix = Some(suffix.into());
+        self
+    }
+
+    /// Directory Claude runs in
+    pub fn cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.task.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Don't claim the task before `at`
+    pub fn schedule(mut self, at: DateTime<Utc>) -> Self {
+        self.task.scheduled_for = Some(at);
+        self
+    }
+
+    /// Kill the run after `timeout`, instead of after `claude.timeout_secs`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.task.timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Don't claim the task until task `id` has completed
+    pub fn after(mut self, id: impl Into<String>) -> Self {
+        self.task.depends_on = Some(id.into());
+        self
+    }
+
+    /// Continue task `id`'s Claude session, once it has one
+    pub fn continues(mut self, id: impl Into<String>) -> Self {
+        self.task.parent_id = Some(id.into());
+        self
+    }
+
+    /// Append `path`'s contents to the prompt, after any files added before
+    pub fn context_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.task.context_files.push(path.into());
+        self
+    }
+
+    /// Save as a draft, claimed only once promoted
+    pub fn draft(mut self) -> Self {
+        self.task.status = TaskStatus::Draft;
+        self
+    }
+
+    /// Insert the task, returning its id
+    pub async fn enqueue(self) -> Result<String> {
+        self.queue.insert(self.task).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intent::Intent;
+    use crate::queue::{QueueError, TaskQueue, TaskStatus};
+    use crate::clock::MockClock;
+    use chrono::{Duration, Utc};
+
+    async fn queue() -> TaskQueue {
+        TaskQueue::new(":memory:").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_builder_stores_every_field() {
+        let queue = queue().await;
+        let at = Utc::now() + Duration::hours(1);
+        let dir = tempfile::tempdir().unwrap();
+
+        let id = queue
+            .build("  Deploy   staging ")
+            .model("opus")
+            .intent(Intent::Orchestrate)
+            .priority(5)
+            .tag("deploy")
+            .tag("staging")
+            .tag("deploy")
+            .cwd(dir.path())
+            .schedule(at)
+            .meta("pr", "17")
+            .meta("trace_id", "abc")
+            .meta("pr", "18")
+            .enqueue()
+            .await
+            .unwrap();
+
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.command, "Deploy staging");
+        assert_eq!(task.original_command.as_deref(), Some("  Deploy   staging "));
+        assert_eq!(task.model.as_deref(), Some("opus"));
+        assert_eq!(task.intent, Some(Intent::Orchestrate));
+        assert_eq!(task.status, TaskStatus::Queued);
+        assert_eq!(task.priority, 5);
+        assert_eq!(task.tags, vec!["deploy", "staging"]);
+        assert_eq!(task.cwd, Some(dir.path().canonicalize().unwrap()));
+        assert_eq!(task.scheduled_for.map(|t| t.timestamp_micros()), Some(at.timestamp_micros()));
+        let metadata: Vec<_> = task.metadata.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        assert_eq!(metadata, vec![("pr", "18"), ("trace_id", "abc")]);
+    }
+
+    #[tokio::test]
+    async fn test_builder_defaults_match_enqueue() {
+        let queue = queue().await;
+
+        let built = queue.build("Run tests").enqueue().await.unwrap();
+        let plain = queue.enqueue("Run tests", None).await.unwrap();
+
+        for id in [built, plain] {
+            let task = queue.get(&id).await.unwrap().unwrap();
+            assert_eq!(task.model, None);
+            assert_eq!(task.intent, None);
+            assert_eq!(task.priority, 0);
+            assert!(task.tags.is_empty());
+            assert_eq!(task.cwd, None);
+            assert_eq!(task.scheduled_for, None);
+            assert!(task.metadata.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_priority_is_claimed_first() {
+        let queue = queue().await;
+
+        let low = queue.build("Low").enqueue().await.unwrap();
+        let high = queue.build("High").priority(10).enqueue().await.unwrap();
+        let negative = queue.build("Later").priority(-1).enqueue().await.unwrap();
+        let low_too = queue.enqueue("Low too", None).await.unwrap();
+
+        for id in [high, low, low_too, negative] {
+            assert_eq!(queue.claim_next().await.unwrap().unwrap().id, id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_task_waits_until_due() {
+        let queue = queue().await;
+
+        queue
+            .build("Tomorrow")
+            .priority(100)
+            .schedule(Utc::now() + Duration::days(1))
+            .enqueue()
+            .await
+            .unwrap();
+        let due = queue
+            .build("Due")
+            .schedule(Utc::now() - Duration::minutes(1))
+            .enqueue()
+            .await
+            .unwrap();
+
+        assert_eq!(queue.dequeue().await.unwrap().unwrap().id, due);
+        assert_eq!(queue.claim_next().await.unwrap().unwrap().id, due);
+        assert!(queue.claim_next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_schedule_follows_injected_clock() {
+        let start = "2026-06-01T09:00:00Z".parse().unwrap();
+        let clock = MockClock::new(start);
+        let queue = queue().await.with_clock(clock.clone());
+
+        let id = queue.build("Standup notes").schedule(start + Duration::hours(1)).enqueue().await.unwrap();
+        assert!(queue.claim_next().await.unwrap().is_none());
+
+        clock.advance(Duration::minutes(59));
+        assert!(queue.claim_next().await.unwrap().is_none());
+
+        clock.advance(Duration::minutes(1));
+        let task = queue.claim_next().await.unwrap().unwrap();
+        assert_eq!(task.id, id);
+        assert_eq!(task.created_at, start);
+        assert_eq!(task.started_at, Some(start + Duration::hours(1)));
+    }
+
+    #[tokio::test]
+    async fn test_builder_respects_max_pending() {
+        let queue = queue().await.with_max_pending(1);
+
+        queue.build("One").enqueue().await.unwrap();
+        assert!(queue.build("Two").priority(99).enqueue().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_relative_cwd_is_canonicalized() {
+        let queue = queue().await;
+
+        let id = queue.build("Build").cwd("src/../src").enqueue().await.unwrap();
+
+        let cwd = queue.get(&id).await.unwrap().unwrap().cwd.unwrap();
+        assert!(cwd.is_absolute());
+        assert_eq!(cwd, std::env::current_dir().unwrap().join("src").canonicalize().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlinked_cwd_is_resolved() {
+        let queue = queue().await;
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("project");
+        let link = dir.path().join("link");
+        std::fs::create_dir(&target).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let id = queue.build("Build").cwd(&link).enqueue().await.unwrap();
+
+        let cwd = queue.get(&id).await.unwrap().unwrap().cwd.unwrap();
+        assert_eq!(cwd, target.canonicalize().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_missing_cwd_fails_fast() {
+        let queue = queue().await;
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("gone");
+        let file = dir.path().join("file.txt");
+        std::fs::write(&file, "").unwrap();
+
+        let err = queue.build("Build").cwd(&missing).enqueue().await.unwrap_err();
+        assert!(matches!(
+            err.queue_error().as_ref(),
+            Some(QueueError::InvalidCwd { path, .. }) if *path == missing
+        ));
+        assert!(err.to_string().contains("gone"));
+
+        let err = queue.build("Build").cwd(&file).enqueue().await.unwrap_err();
+        assert!(err.to_string().contains("not a directory"));
+
+        assert!(queue.list().await.unwrap().is_empty());
+    }
+}
@@ -1,9 +1,30 @@
-//! SQLite task queue
+//! Task queue types and the policy layer over a `TaskStore`
 
-use anyhow::Result;
+use crate::error::{FasterError, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{SqlitePool, Row};
+
+use super::builder::TaskBuilder;
+use super::error::QueueError;
+use super::snapshot::EnvSnapshot;
+use super::stats::QueueStats;
+use super::sqlite::SqliteStore;
+use super::store::{ListQuery, NewTask, TaskStore};
+use crate::clock::Clock;
+use crate::intent::{Command, DirectiveTemplates, Intent};
+use futures::Stream;
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Extra attempts at a mutation that hit lock contention
+const BUSY_RETRIES: u32 = 4;
+
+/// Wait before the first retry of a busy mutation
+const BUSY_BACKOFF: Duration = Duration::from_millis(10);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
@@ -15,6 +36,65 @@ pub struct Task {
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub error: Option<String>,
+    pub rendered_prompt: Option<String>,
+    /// Intent the command was classified as (None for raw or legacy tasks)
+    pub intent: Option<Intent>,
+    /// Classifier confidence in `intent`, 0.0-1.0 (None for raw or legacy tasks)
+    pub confidence: Option<f32>,
+    /// Captured Claude output, possibly truncated or evicted
+    pub output: Option<String>,
+    /// Invalid UTF-8 in the output was replaced while decoding
+    #[serde(default)]
+    pub output_lossy: bool,
+    /// Command as submitted, when normalizing or a directive template changed it
+    pub original_command: Option<String>,
+    /// Task whose Claude session this one continues
+    pub parent_id: Option<String>,
+    /// Claude session the task runs in, resumed from its parent or captured from output
+    pub session_id: Option<String>,
+    /// Higher runs first among due tasks (default 0)
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Directory Claude runs in (None = the daemon's own)
+    pub cwd: Option<std::path::PathBuf>,
+    /// Earliest time the task may be claimed (None = immediately)
+    pub scheduled_for: Option<DateTime<Utc>>,
+    /// Daemon that claimed the task (None until claimed, or for older rows)
+    pub processed_by: Option<String>,
+    /// Task that must complete before this one is claimed
+    #[serde(default)]
+    pub depends_on: Option<String>,
+    /// File the full output is written to, alongside `output`
+    #[serde(default)]
+    pub output_path: Option<std::path::PathBuf>,
+    /// How long the last run took, in milliseconds
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// Tokens the last run used, when Claude reported them
+    #[serde(default)]
+    pub input_tokens: Option<u64>,
+    #[serde(default)]
+    pub output_tokens: Option<u64>,
+    /// Directory, git state and environment at enqueue time, when recorded
+    #[serde(default)]
+    pub env_snapshot: Option<EnvSnapshot>,
+    /// How long a run may take before it's killed (None or 0 = `claude.timeout_secs`)
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Task this one is a verbatim re-run of
+    #[serde(default)]
+    pub replayed_from: Option<String>,
+    /// Caller-supplied key/value pairs, stored and echoed but never interpreted
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Replaces `claude.prompt_prefix` for this task; empty means none
+    #[serde(default)]
+    pub prompt_prefix: Option<String>,
+    /// Replaces `claude.prompt_suffix` for this task; empty means none
+    #[serde(default)]
+    pub prompt_suffix: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -25,9 +105,25 @@ pub enum TaskStatus {
     Completed,
     Failed,
     Cancelled,
+    /// Low-confidence command parked until promoted; never claimed
+    Draft,
+    /// Held back by the user until unheld; never claimed
+    Blocked,
 }
 
 impl TaskStatus {
+    /// Every status, in lifecycle order
+    pub fn all() -> &'static [TaskStatus] {
+        use TaskStatus::*;
+        // Exhaustive, so a new variant doesn't compile until it's listed in ALL too
+        let _ = |status: TaskStatus| match status {
+            Queued | Running | Completed | Failed | Cancelled | Draft | Blocked => (),
+        };
+        const ALL: &[TaskStatus] = &[Draft, Queued, Blocked, Running, Completed, Failed, Cancelled];
+        ALL
+    }
+
+    /// Name stored in the database, the same as the serde form
     pub fn as_str(&self) -> &'static str {
         match self {
             TaskStatus::Queued => "queued",
@@ -35,255 +131,722 @@ impl TaskStatus {
             TaskStatus::Completed => "completed",
             TaskStatus::Failed => "failed",
             TaskStatus::Cancelled => "cancelled",
+            TaskStatus::Draft => "draft",
+            TaskStatus::Blocked => "blocked",
         }
     }
 
+    /// Status named `s` in its database/serde form
+    ///
+    /// Parsed by the serde derive, so it knows every variant by construction.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
-        match s {
-            "queued" => Some(TaskStatus::Queued),
-            "running" => Some(TaskStatus::Running),
-            "completed" => Some(TaskStatus::Completed),
-            "failed" => Some(TaskStatus::Failed),
-            "cancelled" => Some(TaskStatus::Cancelled),
-            _ => None,
-        }
+        use serde::de::value::{Error, StrDeserializer};
+        Self::deserialize(StrDeserializer::<Error>::new(s)).ok()
+    }
+
+    /// Whether a task in this status may move to `next`
+    ///
+    /// Completed is final. Failed and cancelled tasks can only be retried,
+    /// which queues them again. Only queued tasks can be held.
+    pub fn can_transition_to(self, next: TaskStatus) -> bool {
+        use TaskStatus::*;
+        matches!(
+            (self, next),
+            (Draft, Queued | Cancelled)
+                | (Queued, Running | Cancelled | Blocked)
+                | (Blocked, Queued | Cancelled)
+                | (Running, Completed | Failed | Cancelled)
+                | (Failed | Cancelled, Queued)
+        )
+    }
+}
+
+/// Collapse runs of spaces/tabs within each line and trim the whole command
+///
+/// Line breaks are kept so multi-line prompts stay readable.
+pub fn normalize_command(command: &str) -> String {
+    command
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Cut `s` to at most `max_bytes`, marking how much was dropped
+pub fn truncate_with_marker(s: &str, max_bytes: usize) -> String {
+    if s.len() <= max_bytes {
+        return s.to_string();
+    }
+
+    let cut = floor_char_boundary(s, max_bytes);
+    format!("{}\n…[truncated {} bytes]", &s[..cut], s.len() - cut)
+}
+
+/// Largest index <= `index` that lies on a char boundary
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    let mut index = index.min(s.len());
+    while !s.is_char_boundary(index) {
+        index -= 1;
     }
+    index
+}
+
+/// One run of a task, appended by the daemon each time it's claimed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskAttempt {
+    pub task_id: String,
+    /// 1 for the first run, counting up with each requeue
+    pub attempt_no: u32,
+    /// Model the attempt ran with (None = Claude's default)
+    pub model: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Running until the attempt finishes
+    pub status: TaskStatus,
+    pub error: Option<String>,
+}
+
+/// The daemon holding a queue's lock
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaemonLock {
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
 }
 
 pub struct TaskQueue {
-    pool: SqlitePool,
+    store: Box<dyn TaskStore>,
+    /// Maximum queued tasks before enqueue is rejected (0 = unbounded)
+    max_pending: u64,
+    /// Per-task stored output cap in bytes (0 = unbounded)
+    max_output_bytes: usize,
+    /// Tasks that keep their output; older outputs are dropped (0 = all)
+    keep_last_outputs: u64,
+    /// Longest accepted command in bytes (0 = unbounded)
+    max_command_len: usize,
+    /// Variables to record in each task's environment snapshot (None = no snapshots)
+    env_snapshot: Option<Vec<String>>,
+    /// Rewrites applied to processed commands' directives by intent
+    directive_templates: DirectiveTemplates,
+}
+
+impl Clone for TaskQueue {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone_box(),
+            max_pending: self.max_pending,
+            max_output_bytes: self.max_output_bytes,
+            keep_last_outputs: self.keep_last_outputs,
+            max_command_len: self.max_command_len,
+            env_snapshot: self.env_snapshot.clone(),
+            directive_templates: self.directive_templates.clone(),
+        }
+    }
 }
 
 impl TaskQueue {
-    /// Create new task queue
+    /// Create new task queue backed by SQLite
     pub async fn new(db_path: &str) -> Result<Self> {
-        // Ensure parent directory exists
-        if let Some(parent) = std::path::Path::new(db_path).parent() {
-            std::fs::create_dir_all(parent)?;
+        Ok(Self::with_store(Box::new(SqliteStore::new(db_path).await?)))
+    }
+
+    /// Create a task queue over any storage backend
+    pub fn with_store(store: Box<dyn TaskStore>) -> Self {
+        Self {
+            store,
+            max_pending: 0,
+            max_output_bytes: 0,
+            keep_last_outputs: 0,
+            max_command_len: 0,
+            env_snapshot: None,
+            directive_templates: DirectiveTemplates::default(),
         }
+    }
 
-        let pool = SqlitePool::connect(&format!("sqlite://{}?mode=rwc", db_path)).await?;
-
-        let queue = Self { pool };
-        queue.init_schema().await?;
-
-        Ok(queue)
-    }
-
-    /// Initialize database schema
-    async fn init_schema(&self) -> Result<()> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS tasks (
-                id TEXT PRIMARY KEY,
-                command TEXT NOT NULL,
-                status TEXT NOT NULL,
-                model TEXT,
-                created_at TEXT NOT NULL,
-                started_at TEXT,
-                completed_at TEXT,
-                error TEXT
-            )
-            "#
-        )
-        .execute(&self.pool)
-        .await?;
+    /// Reject enqueues once this many tasks are queued (0 = unbounded)
+    pub fn with_max_pending(mut self, max_pending: u64) -> Self {
+        self.max_pending = max_pending;
+        self
+    }
 
-        // Create index on status for efficient querying
-        sqlx::query(
-            "CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status)"
-        )
-        .execute(&self.pool)
-        .await?;
+    /// Truncate each task's stored output to this many bytes (0 = unbounded)
+    pub fn with_max_output_bytes(mut self, max_output_bytes: usize) -> Self {
+        self.max_output_bytes = max_output_bytes;
+        self
+    }
 
-        Ok(())
+    /// Keep output only for the most recently completed tasks (0 = all)
+    pub fn with_keep_last_outputs(mut self, keep_last_outputs: u64) -> Self {
+        self.keep_last_outputs = keep_last_outputs;
+        self
+    }
+
+    /// Reject commands longer than this many bytes (0 = unbounded)
+    pub fn with_max_command_len(mut self, max_command_len: usize) -> Self {
+        self.max_command_len = max_command_len;
+        self
+    }
+
+    /// Record an `EnvSnapshot` with each task, including the variables in `allowlist`
+    pub fn with_env_snapshot(mut self, allowlist: Vec<String>) -> Self {
+        self.env_snapshot = Some(allowlist);
+        self
+    }
+
+    /// Queue processed commands with their directive wrapped in the intent's template
+    pub fn with_directive_templates(mut self, templates: DirectiveTemplates) -> Self {
+        self.directive_templates = templates;
+        self
+    }
+
+    /// Read the time from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.store.set_clock(Arc::new(clock));
+        self
+    }
+
+    /// Drop the backend's connections and open fresh ones
+    ///
+    /// Used to recover when the database file disappears underneath us.
+    /// Clones share the backend, so they all follow the new connections.
+    pub async fn reconnect(&mut self) -> Result<()> {
+        Ok(self.store.reconnect().await?)
+    }
+
+    /// Check the backend is reachable
+    pub async fn ping(&self) -> Result<()> {
+        Ok(self.store.ping().await?)
+    }
+
+    /// Flush pending writes and close the backend
+    ///
+    /// Clones share the backend, so closing one closes them all.
+    pub async fn close(self) -> Result<()> {
+        Ok(self.store.close().await?)
+    }
+
+    /// Whether the on-disk database file has gone missing
+    pub fn backing_file_missing(&self) -> bool {
+        self.store.backing_file_missing()
     }
 
     /// Add task to queue
+    ///
+    /// Shortcut for `build(command)` with just a model; see `TaskBuilder`.
     pub async fn enqueue(&self, command: &str, model: Option<String>) -> Result<String> {
-        let id = nanoid::nanoid!(8);
-        let now = Utc::now();
-
-        sqlx::query(
-            r#"
-            INSERT INTO tasks (id, command, status, model, created_at)
-            VALUES (?, ?, ?, ?, ?)
-            "#
-        )
-        .bind(&id)
-        .bind(command)
-        .bind(TaskStatus::Queued.as_str())
-        .bind(model)
-        .bind(now.to_rfc3339())
-        .execute(&self.pool)
-        .await?;
+        self.insert(NewTask { model, ..NewTask::new(command) }).await
+    }
 
-        Ok(id)
+    /// Start building a task with priority, tags, cwd or a schedule
+    pub fn build(&self, command: impl Into<String>) -> TaskBuilder<'_> {
+        TaskBuilder::new(self, NewTask::new(command))
     }
 
-    /// Get next queued task
-    pub async fn dequeue(&self) -> Result<Option<Task>> {
-        let row = sqlx::query(
-            r#"
-            SELECT id, command, status, model, created_at, started_at, completed_at, error
-            FROM tasks
-            WHERE status = ?
-            ORDER BY created_at ASC
-            LIMIT 1
-            "#
-        )
-        .bind(TaskStatus::Queued.as_str())
-        .fetch_optional(&self.pool)
-        .await?;
-
-        match row {
-            Some(row) => Ok(Some(Task {
-                id: row.get("id"),
-                command: row.get("command"),
-                status: TaskStatus::from_str(row.get("status")).unwrap(),
-                model: row.get("model"),
-                created_at: DateTime::parse_from_rfc3339(row.get("created_at"))?.with_timezone(&Utc),
-                started_at: row.get::<Option<String>, _>("started_at")
-                    .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                    .map(|dt| dt.with_timezone(&Utc)),
-                completed_at: row.get::<Option<String>, _>("completed_at")
-                    .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                    .map(|dt| dt.with_timezone(&Utc)),
-                error: row.get("error"),
-            })),
-            None => Ok(None),
+    /// Like `build`, for a processed command, keeping its intent and confidence
+    pub fn build_command(&self, command: &Command) -> TaskBuilder<'_> {
+        TaskBuilder::new(self, NewTask {
+            intent: Some(command.intent),
+            confidence: Some(command.confidence),
+            ..self.templated(command)
+        })
+    }
+
+    /// Add a processed command to the queue, keeping its intent and confidence
+    pub async fn enqueue_command(&self, command: &Command, model: Option<String>) -> Result<String> {
+        self.enqueue_command_after(command, model, None).await
+    }
+
+    /// Like `enqueue_command`, but not claimed until `depends_on` completes
+    pub async fn enqueue_command_after(
+        &self,
+        command: &Command,
+        model: Option<String>,
+        depends_on: Option<&str>,
+    ) -> Result<String> {
+        self.insert(NewTask {
+            model,
+            intent: Some(command.intent),
+            confidence: Some(command.confidence),
+            depends_on: depends_on.map(str::to_string),
+            ..self.templated(command)
+        })
+        .await
+    }
+
+    /// Save a processed command as a draft, to run only once promoted
+    ///
+    /// Drafts don't count against `max_pending`.
+    pub async fn enqueue_draft(&self, command: &Command, model: Option<String>) -> Result<String> {
+        self.enqueue_draft_after(command, model, None).await
+    }
+
+    /// Like `enqueue_draft`, but once promoted still waits for `depends_on`
+    pub async fn enqueue_draft_after(
+        &self,
+        command: &Command,
+        model: Option<String>,
+        depends_on: Option<&str>,
+    ) -> Result<String> {
+        self.insert(NewTask {
+            model,
+            intent: Some(command.intent),
+            confidence: Some(command.confidence),
+            status: TaskStatus::Draft,
+            depends_on: depends_on.map(str::to_string),
+            ..self.templated(command)
+        })
+        .await
+    }
+
+    /// Queue a fresh copy of task `id`, whatever its status, returning the copy's id
+    ///
+    /// The copy has the same command, model, intent, priority, tags, cwd,
+    /// timeout, context files and environment snapshot. A prompt the
+    /// original rendered is kept too, so the copy runs exactly that prompt.
+    /// It starts a new Claude session and waits on nothing.
+    pub async fn replay(&self, id: &str) -> Result<String> {
+        let Some(original) = self.get(id).await? else {
+            return Err(QueueError::NotFound { id: id.to_string() }.into());
+        };
+
+        let copy = self
+            .insert(NewTask {
+                original_command: original.original_command.clone(),
+                model: original.model.clone(),
+                intent: original.intent,
+                confidence: original.confidence,
+                priority: original.priority,
+                tags: original.tags.clone(),
+                cwd: original.cwd.clone(),
+                env_snapshot: original.env_snapshot.clone(),
+                timeout_ms: original.timeout_ms,
+                replayed_from: Some(original.id.clone()),
+                metadata: original.metadata.clone(),
+                prompt_prefix: original.prompt_prefix.clone(),
+                prompt_suffix: original.prompt_suffix.clone(),
+                ..NewTask::new(&original.command)
+            })
+            .await?;
+
+        let context_files = self.context_files(&original.id).await?;
+        if !context_files.is_empty() {
+            self.add_context_files(&copy, &context_files).await?;
         }
+        if let Some(prompt) = &original.rendered_prompt {
+            self.set_rendered_prompt(&copy, prompt).await?;
+        }
+        Ok(copy)
     }
 
-    /// Update task status
-    pub async fn update_status(&self, id: &str, status: TaskStatus) -> Result<()> {
-        let mut query = String::from("UPDATE tasks SET status = ?");
+    /// Move a draft to the queue, optionally with a new intent
+    ///
+    /// Returns false if the task isn't a draft. Promoting counts against
+    /// `max_pending` like enqueueing, failing with `QueueFull` at the cap.
+    pub async fn promote(&self, id: &str, intent: Option<Intent>) -> Result<bool> {
+        Ok(self.store.promote(id, intent, self.max_pending).await?)
+    }
 
-        if status == TaskStatus::Running {
-            query.push_str(", started_at = ?");
-        } else if status == TaskStatus::Completed || status == TaskStatus::Failed {
-            query.push_str(", completed_at = ?");
+    /// A task running `command`'s templated directive, remembering the directive if it changed
+    fn templated(&self, command: &Command) -> NewTask {
+        let rendered = command.render_directive(&self.directive_templates);
+        NewTask {
+            original_command: (rendered != command.directive).then(|| command.directive.clone()),
+            ..NewTask::new(rendered)
         }
+    }
 
-        query.push_str(" WHERE id = ?");
-
-        let now = Utc::now().to_rfc3339();
-
-        if status == TaskStatus::Running {
-            sqlx::query(&query)
-                .bind(status.as_str())
-                .bind(&now)
-                .bind(id)
-                .execute(&self.pool)
-                .await?;
-        } else if status == TaskStatus::Completed || status == TaskStatus::Failed {
-            sqlx::query(&query)
-                .bind(status.as_str())
-                .bind(&now)
-                .bind(id)
-                .execute(&self.pool)
-                .await?;
-        } else {
-            sqlx::query("UPDATE tasks SET status = ? WHERE id = ?")
-                .bind(status.as_str())
-                .bind(id)
-                .execute(&self.pool)
-                .await?;
+    /// Normalize and insert a task, applying the pending cap
+    pub(super) async fn insert(&self, task: NewTask) -> Result<String> {
+        let task = self.prepare(task)?;
+
+        // Drafts aren't pending work, so they skip the cap
+        let limit = if task.status == TaskStatus::Queued { self.max_pending } else { 0 };
+
+        self.retrying(|| self.store.insert(task.clone(), limit)).await
+    }
+
+    /// Like `insert` for several tasks, inserting all of them or none
+    pub(super) async fn insert_all(&self, tasks: Vec<NewTask>) -> Result<Vec<String>> {
+        let tasks = tasks.into_iter().map(|task| self.prepare(task)).collect::<Result<Vec<_>>>()?;
+        self.retrying(|| self.store.insert_all(tasks.clone(), self.max_pending)).await
+    }
+
+    /// Normalize a task's command, check its limits and resolve its cwd
+    fn prepare(&self, mut task: NewTask) -> Result<NewTask> {
+        let normalized = normalize_command(&task.command);
+        if normalized != task.command {
+            let submitted = std::mem::replace(&mut task.command, normalized);
+            task.original_command.get_or_insert(submitted);
         }
 
-        Ok(())
+        if self.max_command_len > 0 && task.command.len() > self.max_command_len {
+            return Err(QueueError::CommandTooLong {
+                len: task.command.len(),
+                limit: self.max_command_len,
+            }
+            .into());
+        }
+
+        // Resolve now, against the enqueuing process, not the daemon's cwd
+        if let Some(cwd) = task.cwd.take() {
+            let resolved = std::fs::canonicalize(&cwd).map_err(|e| QueueError::InvalidCwd {
+                path: cwd.clone(),
+                reason: e.to_string(),
+            })?;
+            if !resolved.is_dir() {
+                return Err(QueueError::InvalidCwd { path: cwd, reason: "not a directory".to_string() }.into());
+            }
+            task.cwd = Some(resolved);
+        }
+
+        if let (None, Some(allowlist)) = (&task.env_snapshot, &self.env_snapshot) {
+            let dir = match &task.cwd {
+                Some(cwd) => cwd.clone(),
+                None => std::env::current_dir()?,
+            };
+            task.env_snapshot = Some(EnvSnapshot::capture(&dir, allowlist));
+        }
+
+        Ok(task)
     }
 
-    /// Mark task as failed with error
-    pub async fn fail(&self, id: &str, error: &str) -> Result<()> {
-        sqlx::query(
-            "UPDATE tasks SET status = ?, completed_at = ?, error = ? WHERE id = ?"
-        )
-        .bind(TaskStatus::Failed.as_str())
-        .bind(Utc::now().to_rfc3339())
-        .bind(error)
-        .bind(id)
-        .execute(&self.pool)
-        .await?;
+    /// Run `op`, trying again a few times if the backend reports lock contention
+    ///
+    /// Waits double each time from `BUSY_BACKOFF`, with jitter so contending
+    /// processes don't retry in lockstep.
+    async fn retrying<T, F, Fut>(&self, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut retries = 0;
+        loop {
+            match op().await {
+                Err(e) if retries < BUSY_RETRIES && self.store.is_transient(&e) => {
+                    let backoff = BUSY_BACKOFF * 2u32.pow(retries);
+                    let jitter = RandomState::new().build_hasher().finish() % (backoff.as_millis() as u64 + 1);
+                    tracing::debug!("Queue busy, retrying: {}", e);
+                    tokio::time::sleep(backoff + Duration::from_millis(jitter)).await;
+                    retries += 1;
+                }
+                result => return result.map_err(Into::into),
+            }
+        }
+    }
 
+    /// Get next queued task
+    pub async fn dequeue(&self) -> Result<Option<Task>> {
+        Ok(self.store.dequeue().await?)
+    }
+
+    /// Atomically take the next due queued task, marking it running
+    ///
+    /// Higher priority goes first, then oldest. Tasks scheduled in the
+    /// future are skipped. Unlike `dequeue`, two callers can never get the
+    /// same task.
+    pub async fn claim_next(&self) -> Result<Option<Task>> {
+        self.retrying(|| self.store.claim_next(None)).await
+    }
+
+    /// Like `claim_next`, recording `processed_by` as the claimer
+    pub async fn claim_next_as(&self, processed_by: &str) -> Result<Option<Task>> {
+        self.retrying(|| self.store.claim_next(Some(processed_by))).await
+    }
+
+    /// Like `claim_next_as`, but only taking one of the tasks in `ids`
+    pub async fn claim_next_among(&self, ids: &[String], processed_by: &str) -> Result<Option<Task>> {
+        self.retrying(|| self.store.claim_next_among(ids, Some(processed_by))).await
+    }
+
+    /// Stream of claimed tasks, polling every `poll_interval` while the queue is empty
+    ///
+    /// Each item has already been claimed via `claim_next`. Dropping the
+    /// stream stops polling. Database errors are logged and retried.
+    pub fn into_stream(self, poll_interval: Duration) -> impl Stream<Item = Task> {
+        futures::stream::unfold(self, move |queue| async move {
+            loop {
+                match queue.claim_next().await {
+                    Ok(Some(task)) => return Some((task, queue)),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Failed to claim task: {}", e),
+                }
+                tokio::time::sleep(poll_interval).await;
+            }
+        })
+    }
+
+    /// Update task status, rejecting moves `can_transition_to` doesn't allow
+    ///
+    /// The status is checked in the same statement that changes it, so a
+    /// concurrent move can't slip in between.
+    pub async fn update_status(&self, id: &str, status: TaskStatus) -> Result<()> {
+        let from: Vec<TaskStatus> = TaskStatus::all().iter().copied().filter(|s| s.can_transition_to(status)).collect();
+        self.retrying(|| async {
+            if self.store.update_status(id, &from, status).await? {
+                if matches!(status, TaskStatus::Failed | TaskStatus::Cancelled) {
+                    self.store.block_orphaned_dependents().await?;
+                }
+                return Ok(());
+            }
+            match self.store.get(id).await? {
+                Some(task) => Err(QueueError::InvalidTransition { id: id.to_string(), from: task.status, to: status }.into()),
+                None => Err(QueueError::NotFound { id: id.to_string() }.into()),
+            }
+        })
+        .await
+    }
+
+    /// Put a task whose run was cut short straight back in the queue
+    ///
+    /// Unlike `update_status`, this takes a running task to queued, clearing
+    /// its start time and claimer like any requeue. A task that is no longer
+    /// running is left alone.
+    pub async fn requeue_interrupted(&self, id: &str) -> Result<()> {
+        self.store.update_status(id, &[TaskStatus::Running], TaskStatus::Queued).await?;
         Ok(())
     }
 
+    /// Requeue running tasks left behind by a crash or a lost database, returning how many
+    ///
+    /// Those claimed by `processed_by` can't still be running if its daemon is
+    /// asking; others count as abandoned once they've run longer than `max_age`.
+    pub async fn requeue_stale(&self, processed_by: &str, max_age: Option<Duration>) -> Result<u64> {
+        self.retrying(|| self.store.requeue_stale(processed_by, max_age)).await
+    }
+
+    /// Move every task in `from` to `to` in one statement, returning how many moved
+    ///
+    /// Requeued tasks have their timestamps and error cleared so they run fresh.
+    /// Pairs `can_transition_to` doesn't allow are rejected.
+    pub async fn update_status_where(&self, from: TaskStatus, to: TaskStatus) -> Result<u64> {
+        if !from.can_transition_to(to) {
+            return Err(QueueError::InvalidBulkTransition { from, to }.into());
+        }
+        let moved = self.store.update_status_where(from, to).await?;
+        if matches!(to, TaskStatus::Failed | TaskStatus::Cancelled) {
+            self.store.block_orphaned_dependents().await?;
+        }
+        Ok(moved)
+    }
+
+    /// Cancel all pending work (queued and draft tasks), returning how many were cancelled
+    ///
+    /// Running and finished tasks are left alone, so history is kept.
+    pub async fn cancel_all_queued(&self) -> Result<u64> {
+        Ok(self.store.cancel_queued(None).await?)
+    }
+
+    /// How many tasks `cancel_queued_matching` would cancel (all pending ones with None)
+    pub async fn count_queued_matching(&self, pattern: Option<&str>) -> Result<u64> {
+        Ok(self.store.count_queued(pattern).await?)
+    }
+
+    /// Cancel queued and draft tasks whose command contains `pattern`, ignoring ASCII case
+    ///
+    /// Queued tasks waiting on a cancelled one are held, as after `fail`.
+    pub async fn cancel_queued_matching(&self, pattern: &str) -> Result<u64> {
+        let cancelled = self.store.cancel_queued(Some(pattern)).await?;
+        self.store.block_orphaned_dependents().await?;
+        Ok(cancelled)
+    }
+
+    /// Mark a running task as failed with error
+    ///
+    /// Like `update_status`, a task that isn't running is rejected with
+    /// `InvalidTransition` (or `NotFound`), checked in the same statement.
+    /// Queued tasks that depend on it, directly or down a chain, can never be
+    /// claimed now, so they're moved to blocked with an error naming it.
+    /// Requeueing it and unholding them lets the chain run again.
+    pub async fn fail(&self, id: &str, error: &str) -> Result<()> {
+        self.retrying(|| async {
+            if !self.store.fail(id, error).await? {
+                return Err(match self.store.get(id).await? {
+                    Some(task) => QueueError::InvalidTransition { id: id.to_string(), from: task.status, to: TaskStatus::Failed }.into(),
+                    None => QueueError::NotFound { id: id.to_string() }.into(),
+                });
+            }
+            self.store.block_orphaned_dependents().await?;
+            Ok(())
+        })
+        .await
+    }
+
     /// Get all tasks
     pub async fn list(&self) -> Result<Vec<Task>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT id, command, status, model, created_at, started_at, completed_at, error
-            FROM tasks
-            ORDER BY created_at DESC
-            "#
-        )
-        .fetch_all(&self.pool)
-        .await?;
-
-        Ok(rows.into_iter().map(|row| Task {
-            id: row.get("id"),
-            command: row.get("command"),
-            status: TaskStatus::from_str(row.get("status")).unwrap(),
-            model: row.get("model"),
-            created_at: DateTime::parse_from_rfc3339(row.get("created_at")).unwrap().with_timezone(&Utc),
-            started_at: row.get::<Option<String>, _>("started_at")
-                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                .map(|dt| dt.with_timezone(&Utc)),
-            completed_at: row.get::<Option<String>, _>("completed_at")
-                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                .map(|dt| dt.with_timezone(&Utc)),
-            error: row.get("error"),
-        }).collect())
+        Ok(self.store.list().await?)
+    }
+
+    /// Queued and running tasks, without reading finished history
+    ///
+    /// Running tasks come first, then queued ones in the order they'll be claimed.
+    pub async fn list_active(&self) -> Result<Vec<Task>> {
+        Ok(self.store.list_active().await?)
+    }
+
+    /// Distinct commands, most recently enqueued first, at most `limit` (0 = all)
+    ///
+    /// A command queued again moves back to the front rather than repeating.
+    pub async fn recent_commands(&self, limit: u64) -> Result<Vec<String>> {
+        Ok(self.store.recent_commands(limit).await?)
+    }
+
+    /// Tasks matching every filter in `query`, newest first
+    pub async fn query(&self, query: &ListQuery) -> Result<Vec<Task>> {
+        Ok(self.store.query(query).await?)
+    }
+
+    /// Counts and latencies over the tasks created at or after `since` (all of them if None)
+    pub async fn stats(&self, since: Option<DateTime<Utc>>) -> Result<QueueStats> {
+        let query = ListQuery { since, ..ListQuery::default() };
+        Ok(QueueStats::from_tasks(&self.query(&query).await?))
     }
 
     /// Get task by ID
     pub async fn get(&self, id: &str) -> Result<Option<Task>> {
-        let row = sqlx::query(
-            r#"
-            SELECT id, command, status, model, created_at, started_at, completed_at, error
-            FROM tasks
-            WHERE id = ?
-            "#
-        )
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await?;
-
-        Ok(row.map(|row| Task {
-            id: row.get("id"),
-            command: row.get("command"),
-            status: TaskStatus::from_str(row.get("status")).unwrap(),
-            model: row.get("model"),
-            created_at: DateTime::parse_from_rfc3339(row.get("created_at")).unwrap().with_timezone(&Utc),
-            started_at: row.get::<Option<String>, _>("started_at")
-                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                .map(|dt| dt.with_timezone(&Utc)),
-            completed_at: row.get::<Option<String>, _>("completed_at")
-                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                .map(|dt| dt.with_timezone(&Utc)),
-            error: row.get("error"),
-        }))
+        Ok(self.store.get(id).await?)
+    }
+
+    /// Store the fully-rendered prompt sent to Claude
+    pub async fn set_rendered_prompt(&self, id: &str, prompt: &str) -> Result<()> {
+        Ok(self.store.set_rendered_prompt(id, prompt).await?)
+    }
+
+    /// Store a task's output, applying the per-task cap and output ring
+    ///
+    /// Once more than `keep_last_outputs` tasks have output, the oldest
+    /// completed tasks lose theirs; the tasks themselves are kept.
+    ///
+    /// `lossy` flags output that had invalid UTF-8 replaced.
+    pub async fn set_output(&self, id: &str, output: &str, lossy: bool) -> Result<()> {
+        let output = if self.max_output_bytes > 0 {
+            truncate_with_marker(output, self.max_output_bytes)
+        } else {
+            output.to_string()
+        };
+
+        Ok(self.store.set_output(id, &output, lossy, self.keep_last_outputs).await?)
+    }
+
+    /// Record how long a task's run took and, if known, its (input, output) tokens
+    pub async fn set_run_stats(&self, id: &str, duration: Duration, tokens: Option<(u64, u64)>) -> Result<()> {
+        Ok(self.store.set_run_stats(id, duration.as_millis() as u64, tokens).await?)
+    }
+
+    /// Record the file a task's full output is written to
+    pub async fn set_output_path(&self, id: &str, path: &std::path::Path) -> Result<()> {
+        Ok(self.store.set_output_path(id, path).await?)
+    }
+
+    /// Link a task to the one whose Claude session it continues
+    pub async fn set_parent(&self, id: &str, parent_id: &str) -> Result<()> {
+        Ok(self.store.set_parent(id, parent_id).await?)
+    }
+
+    /// Give a task its own run time limit, or None for the global one
+    pub async fn set_timeout(&self, id: &str, timeout: Option<Duration>) -> Result<()> {
+        Ok(self.store.set_timeout(id, timeout.map(|t| t.as_millis() as u64)).await?)
+    }
+
+    /// Record the Claude session a task ran in
+    pub async fn set_session_id(&self, id: &str, session_id: &str) -> Result<()> {
+        Ok(self.store.set_session_id(id, session_id).await?)
+    }
+
+    /// Current daemon lock holder, if any
+    pub async fn daemon_lock(&self) -> Result<Option<DaemonLock>> {
+        Ok(self.store.daemon_lock().await?)
+    }
+
+    /// Claim the daemon lock for `pid`
+    ///
+    /// A lock whose holder `is_alive` rejects is stale and gets reclaimed. A
+    /// live holder is an error unless `force` is set; the displaced lock is
+    /// returned so the caller can warn about it.
+    pub async fn acquire_daemon_lock(
+        &self,
+        pid: u32,
+        force: bool,
+        is_alive: impl Fn(u32) -> bool + Send + Sync,
+    ) -> Result<Option<DaemonLock>> {
+        Ok(self.store.acquire_daemon_lock(pid, force, &is_alive).await?)
+    }
+
+    /// Release the daemon lock if `pid` still holds it
+    pub async fn release_daemon_lock(&self, pid: u32) -> Result<()> {
+        Ok(self.store.release_daemon_lock(pid).await?)
+    }
+
+    /// Attach files whose contents are appended to the prompt at execution time
+    pub async fn add_context_files(&self, id: &str, paths: &[std::path::PathBuf]) -> Result<()> {
+        Ok(self.store.add_context_files(id, paths).await?)
+    }
+
+    /// Context files attached to a task, in attachment order
+    pub async fn context_files(&self, id: &str) -> Result<Vec<std::path::PathBuf>> {
+        Ok(self.store.context_files(id).await?)
+    }
+
+    /// Record the start of a task's next attempt, returning its number
+    pub async fn start_attempt(&self, id: &str, model: Option<&str>) -> Result<u32> {
+        Ok(self.store.start_attempt(id, model).await?)
+    }
+
+    /// Record how an attempt ended
+    pub async fn finish_attempt(
+        &self,
+        id: &str,
+        attempt_no: u32,
+        status: TaskStatus,
+        error: Option<&str>,
+    ) -> Result<()> {
+        Ok(self.store.finish_attempt(id, attempt_no, status, error).await?)
+    }
+
+    /// Every attempt at a task, oldest first
+    pub async fn attempts(&self, id: &str) -> Result<Vec<TaskAttempt>> {
+        Ok(self.store.attempts(id).await?)
+    }
+
+    /// Get task by ID or unique ID prefix
+    ///
+    /// An exact match always wins. Errors if the prefix matches more than one task.
+    pub async fn get_by_prefix(&self, prefix: &str) -> Result<Option<Task>> {
+        Ok(self.store.get_by_prefix(prefix).await?)
+    }
+
+    /// Like `get_by_prefix`, but a missing task is `FasterError::NotFound`
+    pub async fn get_required(&self, id: &str) -> Result<Task> {
+        self.get_by_prefix(id).await?.ok_or_else(|| FasterError::NotFound { id: id.to_string() })
     }
 
     /// Clear completed tasks
     pub async fn clear_completed(&self) -> Result<u64> {
-        let result = sqlx::query(
-            "DELETE FROM tasks WHERE status IN (?, ?)"
-        )
-        .bind(TaskStatus::Completed.as_str())
-        .bind(TaskStatus::Cancelled.as_str())
-        .execute(&self.pool)
-        .await?;
+        Ok(self.store.clear_completed().await?)
+    }
 
-        Ok(result.rows_affected())
+    /// Delete every task, whatever its status, leaving an empty queue
+    ///
+    /// Attempts and context files go too. The database file and anything
+    /// else stored in it stay.
+    pub async fn reset(&self) -> Result<()> {
+        Ok(self.store.reset().await?)
+    }
+
+    /// Reclaim space left by deleted tasks
+    ///
+    /// Needs exclusive access, so it fails rather than waits for long while
+    /// a daemon is in the middle of a transaction.
+    pub async fn vacuum(&self) -> Result<()> {
+        Ok(self.store.vacuum().await?)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::tempdir;
+    use crate::queue::QueueError;
 
     async fn create_test_queue() -> TaskQueue {
         // Use in-memory database for tests (faster and no permission issues)
@@ -300,9 +863,24 @@ mod tests {
 
         assert_eq!(TaskStatus::from_str("queued"), Some(TaskStatus::Queued));
         assert_eq!(TaskStatus::from_str("running"), Some(TaskStatus::Running));
+        assert_eq!(TaskStatus::from_str("draft"), Some(TaskStatus::Draft));
+        assert_eq!(TaskStatus::from_str("blocked"), Some(TaskStatus::Blocked));
         assert_eq!(TaskStatus::from_str("invalid"), None);
     }
 
+    #[test]
+    fn test_every_status_round_trips_identically() {
+        assert_eq!(TaskStatus::all().len(), 7);
+        for &status in TaskStatus::all() {
+            let name = status.as_str();
+            assert_eq!(serde_json::to_value(status).unwrap(), name);
+            assert_eq!(serde_json::from_value::<TaskStatus>(name.into()).unwrap(), status);
+            assert_eq!(TaskStatus::from_str(name), Some(status));
+        }
+        // Only the exact stored form parses
+        assert_eq!(TaskStatus::from_str("Queued"), None);
+    }
+
     #[tokio::test]
     async fn test_queue_creation() {
         let queue = create_test_queue().await;
@@ -325,6 +903,29 @@ mod tests {
         assert_eq!(task.model, Some("sonnet".to_string()));
     }
 
+    #[test]
+    fn test_normalize_command() {
+        assert_eq!(normalize_command("run tests"), "run tests");
+        assert_eq!(normalize_command("  run\t tests  "), "run tests");
+        assert_eq!(normalize_command("fix this\n  then   that\n"), "fix this\nthen that");
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_normalizes_whitespace() {
+        let queue = create_test_queue().await;
+
+        let mut commands = Vec::new();
+        for input in ["run tests", " run tests ", "run  tests", "\trun tests\n"] {
+            let id = queue.enqueue(input, None).await.unwrap();
+            commands.push(queue.get(&id).await.unwrap().unwrap());
+        }
+
+        assert!(commands.iter().all(|t| t.command == "run tests"));
+        assert_eq!(commands[0].original_command, None);
+        assert_eq!(commands[1].original_command.as_deref(), Some(" run tests "));
+        assert_eq!(commands[2].original_command.as_deref(), Some("run  tests"));
+    }
+
     #[tokio::test]
     async fn test_dequeue_empty_queue() {
         let queue = create_test_queue().await;
@@ -358,6 +959,131 @@ mod tests {
         assert_eq!(task3.command, "Task 2");
     }
 
+    #[tokio::test]
+    async fn test_claim_waits_for_dependency() {
+        let queue = create_test_queue().await;
+        let first = queue.enqueue("Run tests", None).await.unwrap();
+        let command = Command::new(Intent::Orchestrate, "Deploy", vec![], 0.9);
+        let second = queue.enqueue_command_after(&command, None, Some(&first)).await.unwrap();
+        queue.build("Fix lint").priority(-1).enqueue().await.unwrap();
+
+        assert_eq!(queue.get(&second).await.unwrap().unwrap().depends_on.as_deref(), Some(first.as_str()));
+        assert_eq!(queue.claim_next().await.unwrap().unwrap().id, first);
+
+        // Still running, so the dependent is skipped for lower-priority work
+        assert_eq!(queue.claim_next().await.unwrap().unwrap().command, "Fix lint");
+        assert!(queue.claim_next().await.unwrap().is_none());
+
+        queue.update_status(&first, TaskStatus::Completed).await.unwrap();
+        assert_eq!(queue.claim_next().await.unwrap().unwrap().id, second);
+    }
+
+    #[tokio::test]
+    async fn test_dependents_of_failed_or_cancelled_task_are_held() {
+        let queue = create_test_queue().await;
+        let tests = queue.enqueue("Run tests", None).await.unwrap();
+        let deploy = queue.build("Deploy").after(&tests).enqueue().await.unwrap();
+        let announce = queue.build("Announce").after(&deploy).enqueue().await.unwrap();
+        let lint = queue.enqueue("Fix lint", None).await.unwrap();
+
+        queue.update_status(&tests, TaskStatus::Running).await.unwrap();
+        queue.fail(&tests, "3 failed").await.unwrap();
+
+        for id in [&deploy, &announce] {
+            let task = queue.get(id).await.unwrap().unwrap();
+            assert_eq!(task.status, TaskStatus::Blocked);
+            assert_eq!(task.error, Some(format!("Depends on task {}, which failed", tests)));
+        }
+        assert_eq!(queue.get(&lint).await.unwrap().unwrap().status, TaskStatus::Queued);
+
+        // Retrying the parent and unholding lets the chain run again
+        queue.update_status(&tests, TaskStatus::Queued).await.unwrap();
+        queue.update_status(&deploy, TaskStatus::Queued).await.unwrap();
+        let deploy_task = queue.get(&deploy).await.unwrap().unwrap();
+        assert_eq!((deploy_task.status, deploy_task.error), (TaskStatus::Queued, None));
+
+        let docs = queue.enqueue("Write docs", None).await.unwrap();
+        let publish = queue.build("Publish").after(&docs).enqueue().await.unwrap();
+        queue.update_status(&docs, TaskStatus::Cancelled).await.unwrap();
+        let task = queue.get(&publish).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Blocked);
+        assert_eq!(task.error, Some(format!("Depends on task {}, which was cancelled", docs)));
+    }
+
+    #[tokio::test]
+    async fn test_claim_next_is_exclusive() {
+        let queue = create_test_queue().await;
+        let id1 = queue.enqueue("Task 1", None).await.unwrap();
+        let id2 = queue.enqueue("Task 2", None).await.unwrap();
+
+        let first = queue.claim_next().await.unwrap().unwrap();
+        assert_eq!(first.id, id1);
+        assert_eq!(first.status, TaskStatus::Running);
+        assert!(first.started_at.is_some());
+
+        let second = queue.claim_next().await.unwrap().unwrap();
+        assert_eq!(second.id, id2);
+        assert!(queue.claim_next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_claim_next_as_records_claimer() {
+        let queue = create_test_queue().await;
+        let id = queue.enqueue("Task 1", None).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().processed_by, None);
+
+        let claimed = queue.claim_next_as("build-box:42").await.unwrap().unwrap();
+        assert_eq!(claimed.processed_by.as_deref(), Some("build-box:42"));
+        let stored = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(stored.processed_by.as_deref(), Some("build-box:42"));
+
+        // Requeued tasks are claimed afresh
+        queue.update_status(&id, TaskStatus::Failed).await.unwrap();
+        queue.update_status_where(TaskStatus::Failed, TaskStatus::Queued).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().processed_by, None);
+    }
+
+    #[tokio::test]
+    async fn test_claim_next_among_only_takes_listed_tasks() {
+        let queue = create_test_queue().await;
+        let low = queue.enqueue("Task low", None).await.unwrap();
+        let urgent = queue.build("Task urgent").priority(10).enqueue().await.unwrap();
+        let listed = [low.clone()];
+
+        // Higher priority, but not in the list
+        let claimed = queue.claim_next_among(&listed, "box:1").await.unwrap().unwrap();
+        assert_eq!(claimed.id, low);
+        assert_eq!(claimed.processed_by.as_deref(), Some("box:1"));
+        assert!(queue.claim_next_among(&listed, "box:1").await.unwrap().is_none());
+        assert!(queue.claim_next_among(&[], "box:1").await.unwrap().is_none());
+        assert_eq!(queue.get(&urgent).await.unwrap().unwrap().status, TaskStatus::Queued);
+    }
+
+    #[tokio::test]
+    async fn test_into_stream_yields_claimed_tasks() {
+        use futures::StreamExt;
+
+        let queue = create_test_queue().await;
+        let id1 = queue.enqueue("Task 1", None).await.unwrap();
+        let id2 = queue.enqueue("Task 2", None).await.unwrap();
+
+        let stream = queue.clone().into_stream(Duration::from_millis(10));
+        let producer = queue.clone();
+        let late = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            producer.enqueue("Task 3", None).await.unwrap()
+        });
+
+        let tasks: Vec<Task> = tokio::time::timeout(Duration::from_secs(5), stream.take(3).collect())
+            .await
+            .unwrap();
+        let id3 = late.await.unwrap();
+
+        let ids: Vec<_> = tasks.iter().map(|t| t.id.clone()).collect();
+        assert_eq!(ids, vec![id1, id2, id3]);
+        assert!(tasks.iter().all(|t| t.status == TaskStatus::Running));
+    }
+
     #[tokio::test]
     async fn test_update_status_to_running() {
         let queue = create_test_queue().await;
@@ -390,6 +1116,11 @@ mod tests {
         let queue = create_test_queue().await;
         let id = queue.enqueue("Test task", None).await.unwrap();
 
+        let err = queue.fail(&id, "Not started").await.unwrap_err();
+        assert!(matches!(err.queue_error(), Some(QueueError::InvalidTransition { .. })));
+        assert!(matches!(queue.fail("missing", "boom").await.unwrap_err().queue_error(), Some(QueueError::NotFound { .. })));
+
+        queue.update_status(&id, TaskStatus::Running).await.unwrap();
         queue.fail(&id, "Something went wrong").await.unwrap();
 
         let task = queue.get(&id).await.unwrap().unwrap();
@@ -431,6 +1162,177 @@ mod tests {
         assert!(task.is_none());
     }
 
+    #[tokio::test]
+    async fn test_get_required() {
+        let queue = create_test_queue().await.with_max_pending(1);
+        let id = queue.enqueue("Find me", None).await.unwrap();
+        assert_eq!(queue.get_required(&id).await.unwrap().id, id);
+        assert_eq!(queue.get_required(&id[..4]).await.unwrap().id, id);
+
+        match queue.get_required("nonexistent").await {
+            Err(FasterError::NotFound { id }) => assert_eq!(id, "nonexistent"),
+            other => panic!("expected NotFound, got {:?}", other),
+        }
+        // Failures from the store arrive typed, not as an opaque error
+        let err = queue.enqueue("Over the limit", None).await.unwrap_err();
+        assert!(matches!(err, FasterError::Queue(QueueError::QueueFull { limit: 1 })), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_get_by_prefix() {
+        let queue = create_test_queue().await;
+        let id = queue.enqueue("Find me", None).await.unwrap();
+
+        let task = queue.get_by_prefix(&id[..4]).await.unwrap().unwrap();
+        assert_eq!(task.id, id);
+
+        let task = queue.get_by_prefix(&id).await.unwrap().unwrap();
+        assert_eq!(task.id, id);
+
+        assert!(queue.get_by_prefix("zzzzzzzzz").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_by_ambiguous_prefix() {
+        let queue = create_test_queue().await;
+        queue.enqueue("Task 1", None).await.unwrap();
+        queue.enqueue("Task 2", None).await.unwrap();
+
+        // The empty prefix matches every task
+        assert!(queue.get_by_prefix("").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_rejected_when_full() {
+        let queue = create_test_queue().await.with_max_pending(2);
+
+        let id1 = queue.enqueue("Task 1", None).await.unwrap();
+        queue.enqueue("Task 2", None).await.unwrap();
+
+        let err = queue.enqueue("Task 3", None).await.unwrap_err();
+        assert_eq!(
+            err.queue_error().as_ref(),
+            Some(&QueueError::QueueFull { limit: 2 })
+        );
+
+        // Finishing a task frees a slot
+        queue.update_status(&id1, TaskStatus::Running).await.unwrap();
+        queue.update_status(&id1, TaskStatus::Completed).await.unwrap();
+        queue.enqueue("Task 3", None).await.unwrap();
+        assert!(queue.enqueue("Task 4", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_rejects_long_command() {
+        let queue = create_test_queue().await.with_max_command_len(16);
+
+        let err = queue.enqueue(&"x".repeat(17), None).await.unwrap_err();
+        assert_eq!(
+            err.queue_error().as_ref(),
+            Some(&QueueError::CommandTooLong { len: 17, limit: 16 })
+        );
+        assert!(queue.list().await.unwrap().is_empty());
+
+        // Drafts and built tasks are held to the same cap
+        let command = Command::new(Intent::Code, "y".repeat(20), vec![], 0.1);
+        assert!(queue.enqueue_draft(&command, None).await.is_err());
+        assert!(queue.build("z".repeat(20)).enqueue().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_command_at_length_cap_is_accepted() {
+        let queue = create_test_queue().await.with_max_command_len(16);
+
+        let id = queue.enqueue(&"x".repeat(16), None).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().command.len(), 16);
+
+        // The cap applies after whitespace is collapsed
+        let padded = format!("  {}   {}  ", "a".repeat(7), "b".repeat(8));
+        assert!(queue.enqueue(&padded, None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_zero_max_pending_is_unbounded() {
+        let queue = create_test_queue().await.with_max_pending(0);
+        for i in 0..20 {
+            queue.enqueue(&format!("Task {}", i), None).await.unwrap();
+        }
+        assert_eq!(queue.list().await.unwrap().len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_context_files_round_trip() {
+        let queue = create_test_queue().await;
+        let id = queue.enqueue("Explain this", None).await.unwrap();
+        assert!(queue.context_files(&id).await.unwrap().is_empty());
+
+        queue.add_context_files(&id, &["/a.rs".into(), "/b.rs".into()]).await.unwrap();
+        queue.add_context_files(&id, &["/c.rs".into()]).await.unwrap();
+
+        let files = queue.context_files(&id).await.unwrap();
+        assert_eq!(files, vec![
+            std::path::PathBuf::from("/a.rs"),
+            std::path::PathBuf::from("/b.rs"),
+            std::path::PathBuf::from("/c.rs"),
+        ]);
+
+        // Files given up front are stored with the task itself
+        let id = queue.build("Explain these").context_file("/a.rs").context_file("/b.rs").enqueue().await.unwrap();
+        assert_eq!(queue.context_files(&id).await.unwrap(), vec![
+            std::path::PathBuf::from("/a.rs"),
+            std::path::PathBuf::from("/b.rs"),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_command_stores_intent() {
+        let queue = create_test_queue().await;
+        let command = Command::new(Intent::Code, "Refactor parser", vec![], 1.0);
+
+        let id = queue.enqueue_command(&command, None).await.unwrap();
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.command, "Refactor parser");
+        assert_eq!(task.intent, Some(Intent::Code));
+
+        // Raw enqueue has no intent
+        let id = queue.enqueue("Run tests", None).await.unwrap();
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().intent, None);
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_command_applies_directive_template() {
+        let template = "Run the test suite for {entities} and report failures ({directive})";
+        let templates = DirectiveTemplates::new([("test".to_string(), template.to_string())]).unwrap();
+        let queue = create_test_queue().await.with_directive_templates(templates);
+
+        let command = Command::new(Intent::Test, "tests for auth", vec!["auth".to_string()], 1.0);
+        let task = queue.get(&queue.enqueue_command(&command, None).await.unwrap()).await.unwrap().unwrap();
+        assert_eq!(task.command, "Run the test suite for auth and report failures (tests for auth)");
+        assert_eq!(task.original_command.as_deref(), Some("tests for auth"));
+
+        // No template for code; drafts are templated too
+        let code = Command::new(Intent::Code, "Refactor parser", vec![], 1.0);
+        let task = queue.get(&queue.enqueue_command(&code, None).await.unwrap()).await.unwrap().unwrap();
+        assert_eq!((task.command.as_str(), task.original_command), ("Refactor parser", None));
+        let draft = queue.get(&queue.enqueue_draft(&command, None).await.unwrap()).await.unwrap().unwrap();
+        assert_eq!(draft.command, "Run the test suite for auth and report failures (tests for auth)");
+    }
+
+    #[tokio::test]
+    async fn test_rendered_prompt_matches_command() {
+        let queue = create_test_queue().await;
+        let id = queue.enqueue("Run test suite", None).await.unwrap();
+        assert!(queue.get(&id).await.unwrap().unwrap().rendered_prompt.is_none());
+
+        let command = Command::new(Intent::Test, "Run test suite", vec![], 0.9)
+            .with_context("module", "auth");
+        queue.set_rendered_prompt(&id, &command.to_claude_prompt()).await.unwrap();
+
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.rendered_prompt, Some(command.to_claude_prompt()));
+        assert!(task.rendered_prompt.unwrap().contains("module: auth"));
+    }
+
     #[tokio::test]
     async fn test_clear_completed() {
         let queue = create_test_queue().await;
@@ -439,9 +1341,12 @@ mod tests {
         let id2 = queue.enqueue("Task 2", None).await.unwrap();
         let id3 = queue.enqueue("Task 3", None).await.unwrap();
 
+        queue.update_status(&id1, TaskStatus::Running).await.unwrap();
         queue.update_status(&id1, TaskStatus::Completed).await.unwrap();
         queue.update_status(&id2, TaskStatus::Cancelled).await.unwrap();
         // id3 stays queued
+        queue.add_context_files(&id1, &["/a.rs".into()]).await.unwrap();
+        queue.add_context_files(&id3, &["/b.rs".into()]).await.unwrap();
 
         let cleared = queue.clear_completed().await.unwrap();
         assert_eq!(cleared, 2);
@@ -449,6 +1354,356 @@ mod tests {
         let tasks = queue.list().await.unwrap();
         assert_eq!(tasks.len(), 1);
         assert_eq!(tasks[0].id, id3);
+
+        // Cleared tasks don't leave their context files behind
+        assert!(queue.context_files(&id1).await.unwrap().is_empty());
+        assert_eq!(queue.context_files(&id3).await.unwrap(), vec![std::path::PathBuf::from("/b.rs")]);
+    }
+
+    #[tokio::test]
+    async fn test_update_status_where_requeues_failed() {
+        let queue = create_test_queue().await;
+
+        let id1 = queue.enqueue("Task 1", None).await.unwrap();
+        let id2 = queue.enqueue("Task 2", None).await.unwrap();
+        let id3 = queue.enqueue("Task 3", None).await.unwrap();
+        let id4 = queue.enqueue("Task 4", None).await.unwrap();
+
+        queue.update_status(&id1, TaskStatus::Running).await.unwrap();
+        queue.fail(&id1, "boom").await.unwrap();
+        queue.update_status(&id2, TaskStatus::Running).await.unwrap();
+        queue.fail(&id2, "boom").await.unwrap();
+        queue.update_status(&id3, TaskStatus::Running).await.unwrap();
+        queue.fail(&id3, "boom").await.unwrap();
+        queue.update_status(&id4, TaskStatus::Running).await.unwrap();
+        queue.update_status(&id4, TaskStatus::Completed).await.unwrap();
+
+        let moved = queue
+            .update_status_where(TaskStatus::Failed, TaskStatus::Queued)
+            .await
+            .unwrap();
+        assert_eq!(moved, 3);
+
+        for id in [&id1, &id2, &id3] {
+            let task = queue.get(id).await.unwrap().unwrap();
+            assert_eq!(task.status, TaskStatus::Queued);
+            assert!(task.error.is_none());
+            assert!(task.completed_at.is_none());
+        }
+        let task = queue.get(&id4).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+
+        // Nothing left to move
+        let moved = queue
+            .update_status_where(TaskStatus::Failed, TaskStatus::Queued)
+            .await
+            .unwrap();
+        assert_eq!(moved, 0);
+    }
+
+    #[tokio::test]
+    async fn test_update_status_where_sets_timestamps() {
+        let queue = create_test_queue().await;
+        let id = queue.enqueue("Task", None).await.unwrap();
+
+        let moved = queue
+            .update_status_where(TaskStatus::Queued, TaskStatus::Running)
+            .await
+            .unwrap();
+        assert_eq!(moved, 1);
+
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Running);
+        assert!(task.started_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_update_status_where_rejects_illegal_pairs() {
+        let queue = create_test_queue().await;
+        let id = queue.enqueue("Task", None).await.unwrap();
+
+        let err = queue.update_status_where(TaskStatus::Queued, TaskStatus::Completed).await.unwrap_err();
+        assert_eq!(
+            err.queue_error(),
+            Some(QueueError::InvalidBulkTransition { from: TaskStatus::Queued, to: TaskStatus::Completed })
+        );
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Queued);
+    }
+
+    #[test]
+    fn test_status_transitions() {
+        use TaskStatus::*;
+        let all = [Queued, Running, Completed, Failed, Cancelled, Draft, Blocked];
+        let legal = [
+            (Draft, Queued),
+            (Draft, Cancelled),
+            (Queued, Running),
+            (Queued, Cancelled),
+            (Queued, Blocked),
+            (Blocked, Queued),
+            (Blocked, Cancelled),
+            (Running, Completed),
+            (Running, Failed),
+            (Running, Cancelled),
+            (Failed, Queued),
+            (Cancelled, Queued),
+        ];
+        for from in all {
+            for to in all {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    legal.contains(&(from, to)),
+                    "{} -> {}",
+                    from.as_str(),
+                    to.as_str()
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_status_rejects_illegal_transition() {
+        let queue = create_test_queue().await;
+        let id = queue.enqueue("Task", None).await.unwrap();
+        queue.update_status(&id, TaskStatus::Running).await.unwrap();
+        queue.update_status(&id, TaskStatus::Completed).await.unwrap();
+
+        let err = queue.update_status(&id, TaskStatus::Running).await.unwrap_err();
+        assert_eq!(
+            err.queue_error().as_ref(),
+            Some(&QueueError::InvalidTransition { id: id.clone(), from: TaskStatus::Completed, to: TaskStatus::Running })
+        );
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Completed);
+
+        let err = queue.update_status("missing", TaskStatus::Cancelled).await.unwrap_err();
+        assert!(matches!(err.queue_error().as_ref(), Some(QueueError::NotFound { .. })));
+    }
+
+    #[test]
+    fn test_truncate_with_marker() {
+        assert_eq!(truncate_with_marker("short", 10), "short");
+        assert_eq!(truncate_with_marker("0123456789", 4), "0123\n…[truncated 6 bytes]");
+        // Never splits a multi-byte char
+        assert_eq!(truncate_with_marker("aé", 2), "a\n…[truncated 2 bytes]");
+    }
+
+    #[tokio::test]
+    async fn test_output_truncated_per_task() {
+        let queue = create_test_queue().await.with_max_output_bytes(8);
+        let id = queue.enqueue("Task", None).await.unwrap();
+
+        queue.set_output(&id, "0123456789abcdef", false).await.unwrap();
+
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.output.as_deref(), Some("01234567\n…[truncated 8 bytes]"));
+    }
+
+    #[tokio::test]
+    async fn test_output_ring_keeps_last_n() {
+        let queue = create_test_queue().await.with_keep_last_outputs(2);
+
+        let mut ids = Vec::new();
+        for i in 0..4 {
+            let id = queue.enqueue(&format!("Task {}", i), None).await.unwrap();
+            queue.update_status(&id, TaskStatus::Running).await.unwrap();
+            queue.update_status(&id, TaskStatus::Completed).await.unwrap();
+            queue.set_output(&id, &format!("output {}", i), false).await.unwrap();
+            ids.push(id);
+        }
+
+        let tasks = queue.list().await.unwrap();
+        assert_eq!(tasks.len(), 4);
+        assert_eq!(tasks.iter().filter(|t| t.output.is_some()).count(), 2);
+
+        for (i, id) in ids.iter().enumerate() {
+            let task = queue.get(id).await.unwrap().unwrap();
+            if i < 2 {
+                assert!(task.output.is_none());
+            } else {
+                assert_eq!(task.output, Some(format!("output {}", i)));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_daemon_lock_acquire_and_reject() {
+        let queue = create_test_queue().await;
+
+        assert_eq!(queue.acquire_daemon_lock(100, false, |_| true).await.unwrap(), None);
+        assert_eq!(queue.daemon_lock().await.unwrap().unwrap().pid, 100);
+
+        let err = queue.acquire_daemon_lock(200, false, |_| true).await.unwrap_err();
+        assert_eq!(err.queue_error().as_ref(), Some(&QueueError::DaemonRunning { pid: 100 }));
+        assert_eq!(queue.daemon_lock().await.unwrap().unwrap().pid, 100);
+
+        // Forcing takes over and reports who was displaced
+        let displaced = queue.acquire_daemon_lock(200, true, |_| true).await.unwrap();
+        assert_eq!(displaced.unwrap().pid, 100);
+        assert_eq!(queue.daemon_lock().await.unwrap().unwrap().pid, 200);
+
+        queue.release_daemon_lock(100).await.unwrap();
+        assert!(queue.daemon_lock().await.unwrap().is_some());
+        queue.release_daemon_lock(200).await.unwrap();
+        assert!(queue.daemon_lock().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_daemon_lock_reclaims_stale() {
+        let queue = create_test_queue().await;
+        queue.acquire_daemon_lock(100, false, |_| true).await.unwrap();
+
+        // Holder's PID is gone, so no force is needed and nothing is displaced
+        let displaced = queue.acquire_daemon_lock(200, false, |pid| pid != 100).await.unwrap();
+        assert_eq!(displaced, None);
+        assert_eq!(queue.daemon_lock().await.unwrap().unwrap().pid, 200);
+    }
+
+    #[tokio::test]
+    async fn test_reclaimed_lock_is_stamped_by_clock() {
+        let start: DateTime<Utc> = "2026-06-01T09:00:00Z".parse().unwrap();
+        let clock = crate::clock::MockClock::new(start);
+        let queue = create_test_queue().await.with_clock(clock.clone());
+        queue.acquire_daemon_lock(100, false, |_| true).await.unwrap();
+        assert_eq!(queue.daemon_lock().await.unwrap().unwrap().started_at, start);
+
+        // A day later the holder has died, and the new lock starts now
+        clock.advance(chrono::Duration::days(1));
+        queue.acquire_daemon_lock(200, false, |pid| pid != 100).await.unwrap();
+        let lock = queue.daemon_lock().await.unwrap().unwrap();
+        assert_eq!((lock.pid, lock.started_at), (200, start + chrono::Duration::days(1)));
+    }
+
+    #[tokio::test]
+    async fn test_reset_empties_every_task_table() {
+        let queue = create_test_queue().await;
+        let first = queue.enqueue("Run tests", None).await.unwrap();
+        queue.build("Deploy").after(&first).tag("ship").enqueue().await.unwrap();
+        queue.add_context_files(&first, &[std::path::PathBuf::from("/src/lib.rs")]).await.unwrap();
+        queue.claim_next().await.unwrap();
+        queue.start_attempt(&first, None).await.unwrap();
+
+        queue.reset().await.unwrap();
+        assert!(queue.list().await.unwrap().is_empty());
+        assert!(queue.query(&ListQuery::default()).await.unwrap().is_empty());
+        assert!(queue.attempts(&first).await.unwrap().is_empty());
+        assert!(queue.context_files(&first).await.unwrap().is_empty());
+
+        // Still usable afterwards
+        queue.enqueue("Fresh start", None).await.unwrap();
+        assert_eq!(queue.list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_env_snapshot_recorded_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = create_test_queue().await;
+        let plain = queue.build("Plain").cwd(dir.path()).enqueue().await.unwrap();
+        assert_eq!(queue.get(&plain).await.unwrap().unwrap().env_snapshot, None);
+
+        let queue = queue.with_env_snapshot(vec!["PATH".to_string()]);
+        let id = queue.build("Snapshotted").cwd(dir.path()).enqueue().await.unwrap();
+        let snapshot = queue.get(&id).await.unwrap().unwrap().env_snapshot.unwrap();
+        assert_eq!(snapshot.cwd, dir.path().canonicalize().unwrap());
+        assert_eq!(snapshot.git_commit, None);
+        assert!(snapshot.env.contains_key("PATH"));
+    }
+
+    #[tokio::test]
+    async fn test_replay_copies_execution_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = create_test_queue().await.with_env_snapshot(Vec::new());
+        let id = queue
+            .build("Run the  benchmarks")
+            .model("opus")
+            .intent(Intent::Test)
+            .priority(3)
+            .tag("perf")
+            .cwd(dir.path())
+            .timeout(Duration::from_secs(90))
+            .enqueue()
+            .await
+            .unwrap();
+        queue.add_context_files(&id, &[dir.path().join("bench.rs")]).await.unwrap();
+        queue.claim_next().await.unwrap();
+        queue.set_rendered_prompt(&id, "Run the benchmarks\n\nContext file: bench.rs").await.unwrap();
+        queue.set_session_id(&id, "sess-1").await.unwrap();
+        queue.update_status(&id, TaskStatus::Completed).await.unwrap();
+
+        let copy_id = queue.replay(&id).await.unwrap();
+        let (original, copy) = (queue.get(&id).await.unwrap().unwrap(), queue.get(&copy_id).await.unwrap().unwrap());
+        assert_ne!(copy_id, id);
+        assert_eq!(copy.status, TaskStatus::Queued);
+        assert_eq!(copy.replayed_from.as_deref(), Some(id.as_str()));
+        assert_eq!(copy.command, original.command);
+        assert_eq!(copy.original_command, original.original_command);
+        assert_eq!((copy.model, copy.intent, copy.priority), (original.model, original.intent, original.priority));
+        assert_eq!((copy.tags, copy.cwd, copy.timeout_ms), (original.tags, original.cwd, original.timeout_ms));
+        assert_eq!(copy.env_snapshot, original.env_snapshot);
+        assert_eq!(copy.rendered_prompt, original.rendered_prompt);
+        assert_eq!(queue.context_files(&copy_id).await.unwrap(), queue.context_files(&id).await.unwrap());
+        // A fresh run, not a continuation
+        assert_eq!((copy.session_id, copy.started_at, copy.output), (None, None, None));
+
+        // Any status can be replayed
+        queue.update_status(&copy_id, TaskStatus::Cancelled).await.unwrap();
+        assert!(queue.replay(&copy_id).await.is_ok());
+        let err = queue.replay("missing").await.unwrap_err();
+        assert!(matches!(err.queue_error().as_ref(), Some(QueueError::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_shrinks_file_after_purge() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.db");
+        let size = || {
+            let wal = path.with_extension("db-wal");
+            [&path, &wal].iter().filter_map(|p| std::fs::metadata(p).ok()).map(|m| m.len()).sum::<u64>()
+        };
+        let queue = TaskQueue::new(&path.to_string_lossy()).await.unwrap();
+
+        let padding = "x".repeat(4096);
+        for i in 0..200 {
+            queue.enqueue(&format!("Task {} {}", i, padding), None).await.unwrap();
+        }
+        queue.vacuum().await.unwrap();
+        let full = size();
+
+        queue.cancel_all_queued().await.unwrap();
+        assert_eq!(queue.clear_completed().await.unwrap(), 200);
+        queue.vacuum().await.unwrap();
+        assert!(size() < full / 4, "{} bytes after vacuum, {} before", size(), full);
+        assert!(queue.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mutations_retry_while_database_is_locked() {
+        use sqlx::Connection;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.db").to_string_lossy().into_owned();
+        // No busy wait of its own, so contention surfaces as an error straight away
+        let store = SqliteStore::with_busy_timeout(&path, Duration::ZERO).await.unwrap();
+        let queue = TaskQueue::with_store(Box::new(store));
+        let id = queue.enqueue("Before the lock", None).await.unwrap();
+
+        let mut other = sqlx::SqliteConnection::connect(&format!("sqlite://{}", path)).await.unwrap();
+        sqlx::query("BEGIN EXCLUSIVE").execute(&mut other).await.unwrap();
+
+        // Held for longer than every retry together, so it gives up
+        let err = queue.enqueue("Blocked", None).await.unwrap_err();
+        assert!(matches!(err, FasterError::Db(_)), "{:#}", err);
+
+        // Released partway through the retries, so it gets through
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            sqlx::query("COMMIT").execute(&mut other).await.unwrap();
+            other
+        });
+        queue.update_status(&id, TaskStatus::Running).await.unwrap();
+        release.await.unwrap().close().await.unwrap();
+
+        assert_eq!(queue.get(&id).await.unwrap().unwrap().status, TaskStatus::Running);
+        assert!(queue.enqueue("After the lock", None).await.is_ok());
     }
 
     #[tokio::test]
@@ -462,6 +1717,30 @@ mod tests {
             started_at: Some(Utc::now()),
             completed_at: None,
             error: None,
+            rendered_prompt: None,
+            intent: Some(Intent::Test),
+            confidence: Some(0.9),
+            output: None,
+            original_command: None,
+            parent_id: None,
+            session_id: None,
+            priority: 0,
+            tags: Vec::new(),
+            cwd: None,
+            scheduled_for: None,
+            processed_by: None,
+            output_lossy: false,
+            depends_on: None,
+            output_path: None,
+            duration_ms: None,
+            input_tokens: None,
+            output_tokens: None,
+            env_snapshot: None,
+            timeout_ms: None,
+            replayed_from: None,
+            metadata: BTreeMap::from([("pr".to_string(), "42".to_string())]),
+            prompt_prefix: None,
+            prompt_suffix: None,
         };
 
         let json = serde_json::to_string(&task).unwrap();
@@ -472,6 +1751,130 @@ mod tests {
         let deserialized: Task = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized.id, task.id);
         assert_eq!(deserialized.command, task.command);
+        assert_eq!(deserialized.metadata, task.metadata);
+    }
+
+    #[tokio::test]
+    async fn test_list_active_excludes_terminal_tasks() {
+        let queue = create_test_queue().await;
+
+        let older = queue.enqueue("Older", None).await.unwrap();
+        let newer = queue.enqueue("Newer", None).await.unwrap();
+        let urgent = queue.insert(NewTask { priority: 5, ..NewTask::new("Urgent") }).await.unwrap();
+        let running = queue.enqueue("Running", None).await.unwrap();
+        queue.update_status(&running, TaskStatus::Running).await.unwrap();
+        let done = queue.enqueue("Done", None).await.unwrap();
+        queue.update_status(&done, TaskStatus::Running).await.unwrap();
+        queue.update_status(&done, TaskStatus::Completed).await.unwrap();
+        let failed = queue.enqueue("Failed", None).await.unwrap();
+        queue.update_status(&failed, TaskStatus::Running).await.unwrap();
+        queue.fail(&failed, "boom").await.unwrap();
+        let cancelled = queue.enqueue("Cancelled", None).await.unwrap();
+        queue.update_status(&cancelled, TaskStatus::Cancelled).await.unwrap();
+        queue
+            .enqueue_draft(&Command::new(Intent::Code, "Maybe", vec![], 0.5), None)
+            .await
+            .unwrap();
+
+        let active: Vec<_> = queue.list_active().await.unwrap().into_iter().map(|t| t.id).collect();
+        // Running first, then queued in claim order
+        assert_eq!(active, vec![running, urgent, older, newer]);
+    }
+
+    #[tokio::test]
+    async fn test_recent_commands_are_distinct_newest_first() {
+        let queue = create_test_queue().await;
+        for command in ["Run tests", "Fix the build", "Run tests", "Deploy", "Fix  the build"] {
+            queue.enqueue(command, None).await.unwrap();
+        }
+
+        // Whitespace is normalized on enqueue, so "Fix  the build" repeats "Fix the build"
+        assert_eq!(queue.recent_commands(0).await.unwrap(), vec!["Fix the build", "Deploy", "Run tests"]);
+        assert_eq!(queue.recent_commands(2).await.unwrap(), vec!["Fix the build", "Deploy"]);
+        assert!(create_test_queue().await.recent_commands(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_all_queued_leaves_others_alone() {
+        let queue = create_test_queue().await;
+
+        let queued = queue.enqueue("Queued", None).await.unwrap();
+        let draft = queue
+            .enqueue_draft(&Command::new(Intent::Code, "Maybe", vec![], 0.5), None)
+            .await
+            .unwrap();
+        let running = queue.enqueue("Running", None).await.unwrap();
+        queue.update_status(&running, TaskStatus::Running).await.unwrap();
+        let done = queue.enqueue("Done", None).await.unwrap();
+        queue.update_status(&done, TaskStatus::Running).await.unwrap();
+        queue.update_status(&done, TaskStatus::Completed).await.unwrap();
+        let failed = queue.enqueue("Failed", None).await.unwrap();
+        queue.update_status(&failed, TaskStatus::Running).await.unwrap();
+        queue.fail(&failed, "boom").await.unwrap();
+
+        assert_eq!(queue.cancel_all_queued().await.unwrap(), 2);
+
+        let status = |id: String| {
+            let queue = queue.clone();
+            async move { queue.get(&id).await.unwrap().unwrap().status }
+        };
+        assert_eq!(status(queued).await, TaskStatus::Cancelled);
+        assert_eq!(status(draft).await, TaskStatus::Cancelled);
+        assert_eq!(status(running).await, TaskStatus::Running);
+        assert_eq!(status(done).await, TaskStatus::Completed);
+        assert_eq!(status(failed).await, TaskStatus::Failed);
+
+        // Nothing left to cancel
+        assert_eq!(queue.cancel_all_queued().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_held_task_is_skipped_until_unheld() {
+        let queue = create_test_queue().await;
+        let held = queue.enqueue("Wait for the vendor fix", None).await.unwrap();
+        let next = queue.enqueue("Something else", None).await.unwrap();
+
+        queue.update_status(&held, TaskStatus::Blocked).await.unwrap();
+        assert_eq!(queue.claim_next().await.unwrap().unwrap().id, next);
+        assert!(queue.claim_next().await.unwrap().is_none());
+        assert!(queue.dequeue().await.unwrap().is_none());
+
+        queue.update_status(&held, TaskStatus::Queued).await.unwrap();
+        assert_eq!(queue.claim_next().await.unwrap().unwrap().id, held);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_queued_matching() {
+        let queue = create_test_queue().await;
+
+        let old_api = queue.enqueue("Update the /v1/Users handler", None).await.unwrap();
+        let draft = queue
+            .enqueue_draft(&Command::new(Intent::Code, "Document /V1/users", vec![], 0.5), None)
+            .await
+            .unwrap();
+        let other = queue.enqueue("Update the /v2/users handler", None).await.unwrap();
+        let running = queue.enqueue("Test /v1/users", None).await.unwrap();
+        queue.update_status(&running, TaskStatus::Running).await.unwrap();
+        let wildcard = queue.enqueue("Fix 100% of /v1_users", None).await.unwrap();
+
+        // The count matches exactly what the cancel then takes
+        assert_eq!(queue.count_queued_matching(Some("/v1/users")).await.unwrap(), 2);
+        assert_eq!(queue.count_queued_matching(None).await.unwrap(), 4);
+        assert_eq!(queue.cancel_queued_matching("/v1/users").await.unwrap(), 2);
+
+        let status = |id: String| {
+            let queue = queue.clone();
+            async move { queue.get(&id).await.unwrap().unwrap().status }
+        };
+        assert_eq!(status(old_api).await, TaskStatus::Cancelled);
+        assert_eq!(status(draft).await, TaskStatus::Cancelled);
+        assert_eq!(status(other).await, TaskStatus::Queued);
+        assert_eq!(status(running).await, TaskStatus::Running);
+        assert_eq!(status(wildcard).await, TaskStatus::Queued);
+
+        // Pattern characters are literal, not LIKE wildcards
+        assert_eq!(queue.cancel_queued_matching("100%").await.unwrap(), 1);
+        assert_eq!(queue.cancel_queued_matching("nothing like this").await.unwrap(), 0);
     }
 
     #[tokio::test]
@@ -495,12 +1898,53 @@ mod tests {
         let tasks = queue.list().await.unwrap();
         assert_eq!(tasks.len(), 10);
     }
-}
 
-impl Clone for TaskQueue {
-    fn clone(&self) -> Self {
-        Self {
-            pool: self.pool.clone(),
-        }
+    #[tokio::test]
+    async fn test_drafts_are_never_claimed_until_promoted() {
+        let queue = create_test_queue().await.with_max_pending(1);
+        let command = Command::new(Intent::Code, "maybe refactor".to_string(), vec![], 0.6);
+
+        let draft = queue.enqueue_draft(&command, None).await.unwrap();
+        assert_eq!(queue.get(&draft).await.unwrap().unwrap().status, TaskStatus::Draft);
+        assert!(queue.claim_next().await.unwrap().is_none());
+
+        // Drafts don't use up the pending cap
+        let queued = queue.enqueue("Run tests", None).await.unwrap();
+        assert_eq!(queue.claim_next().await.unwrap().unwrap().id, queued);
+
+        assert!(queue.promote(&draft, Some(Intent::Research)).await.unwrap());
+        let task = queue.get(&draft).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Queued);
+        assert_eq!(task.intent, Some(Intent::Research));
+
+        // Only drafts can be promoted
+        assert!(!queue.promote(&draft, None).await.unwrap());
+
+        // ...and only while there's room under the cap
+        let waiting = queue.enqueue_draft(&command, None).await.unwrap();
+        let err = queue.promote(&waiting, None).await.unwrap_err();
+        assert!(matches!(err.queue_error(), Some(QueueError::QueueFull { limit: 1 })));
+        assert_eq!(queue.get(&waiting).await.unwrap().unwrap().status, TaskStatus::Draft);
+
+        assert_eq!(queue.claim_next().await.unwrap().unwrap().id, draft);
+        assert!(queue.promote(&waiting, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_parent_and_session_round_trip() {
+        let queue = create_test_queue().await;
+        let first = queue.enqueue("First", None).await.unwrap();
+        let second = queue.enqueue("Follow up", None).await.unwrap();
+
+        queue.set_session_id(&first, "sess-1").await.unwrap();
+        queue.set_parent(&second, &first).await.unwrap();
+
+        let task = queue.get(&second).await.unwrap().unwrap();
+        assert_eq!(task.parent_id.as_deref(), Some(first.as_str()));
+        assert_eq!(task.session_id, None);
+        assert_eq!(
+            queue.get(&first).await.unwrap().unwrap().session_id.as_deref(),
+            Some("sess-1")
+        );
     }
 }
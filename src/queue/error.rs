@@ -0,0 +1,54 @@
+//! Queue errors callers may want to handle specifically
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+use super::db::TaskStatus;
+
+/// Why one spec in a batch was rejected
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("[{index}] {message}")]
+pub struct SpecError {
+    /// Position of the spec in the batch, from 0
+    pub index: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum QueueError {
+    /// No task with this id (or id prefix)
+    #[error("Task not found: {id}")]
+    NotFound { id: String },
+
+    /// Another live daemon holds the queue lock
+    #[error("Another daemon (pid {pid}) is already processing this queue; use --force to start anyway")]
+    DaemonRunning { pid: u32 },
+
+    /// Pending task cap reached
+    #[error("Queue is full ({limit} tasks pending)")]
+    QueueFull { limit: u64 },
+
+    /// Command longer than the configured cap
+    #[error("Command is too long ({len} bytes, limit {limit})")]
+    CommandTooLong { len: usize, limit: usize },
+
+    /// Some task specs in a batch were rejected; nothing was enqueued unless best effort
+    #[error("{} invalid task spec(s): {}", errors.len(), errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidSpecs { errors: Vec<SpecError> },
+
+    /// A task's working directory can't be resolved
+    #[error("Working directory {} is not accessible: {reason}", path.display())]
+    InvalidCwd { path: PathBuf, reason: String },
+
+    /// A stored row has a value that can't be read back, e.g. after a manual edit
+    #[error("Task {id} has an unreadable {field} in the database")]
+    CorruptRow { id: String, field: &'static str },
+
+    /// A status change `TaskStatus::can_transition_to` doesn't allow
+    #[error("Task {id} can't go from {} to {}", from.as_str(), to.as_str())]
+    InvalidTransition { id: String, from: TaskStatus, to: TaskStatus },
+
+    /// A bulk status change `TaskStatus::can_transition_to` doesn't allow
+    #[error("Tasks can't go from {} to {}", from.as_str(), to.as_str())]
+    InvalidBulkTransition { from: TaskStatus, to: TaskStatus },
+}
@@ -0,0 +1,197 @@
+//! Task status transitions, found by polling the queue
+
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use serde::Serialize;
+use std::time::Duration;
+
+use super::db::{Task, TaskQueue, TaskStatus};
+
+/// One task entering a status
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskEvent {
+    pub at: DateTime<Utc>,
+    pub task_id: String,
+    pub status: TaskStatus,
+    pub command: String,
+    /// Why the task failed, on failure events
+    pub error: Option<String>,
+}
+
+impl TaskEvent {
+    fn new(task: &Task, status: TaskStatus, at: DateTime<Utc>) -> Self {
+        Self {
+            at,
+            task_id: task.id.clone(),
+            status,
+            command: task.command.clone(),
+            error: task.error.clone().filter(|_| status == TaskStatus::Failed),
+        }
+    }
+}
+
+/// Events recorded in tasks' timestamps, oldest first
+///
+/// Each task contributes its creation, its start and how it ended, as far
+/// as it has got. Earlier attempts of retried tasks aren't included.
+pub fn history(tasks: &[Task]) -> Vec<TaskEvent> {
+    let mut events = Vec::new();
+    for task in tasks {
+        let created = if task.status == TaskStatus::Draft { TaskStatus::Draft } else { TaskStatus::Queued };
+        events.push(TaskEvent::new(task, created, task.created_at));
+
+        if let Some(started) = task.started_at {
+            events.push(TaskEvent::new(task, TaskStatus::Running, started));
+        }
+        if matches!(task.status, TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled) {
+            // Cancelling doesn't stamp a time, so use the latest one there is
+            let at = task.completed_at.or(task.started_at).unwrap_or(task.created_at);
+            events.push(TaskEvent::new(task, task.status, at));
+        }
+    }
+    events.sort_by_key(|event| event.at);
+    events
+}
+
+/// Turns successive queue snapshots into transition events
+#[derive(Debug, Default)]
+pub struct TransitionTracker {
+    seen: HashMap<String, TaskStatus>,
+}
+
+impl TransitionTracker {
+    /// Remember tasks as they are now without reporting them
+    pub fn seed(&mut self, tasks: &[Task]) {
+        self.seen.extend(tasks.iter().map(|task| (task.id.clone(), task.status)));
+    }
+
+    /// Events for tasks that are new or changed status since the last snapshot
+    ///
+    /// A task that moves through several statuses between snapshots is
+    /// reported once, in the status it's in now. New tasks are stamped with
+    /// their creation time.
+    pub fn observe(&mut self, tasks: &[Task], now: DateTime<Utc>) -> Vec<TaskEvent> {
+        let mut events: Vec<TaskEvent> = tasks
+            .iter()
+            .filter_map(|task| {
+                let previous = self.seen.insert(task.id.clone(), task.status);
+                let at = match (previous, task.status) {
+                    (Some(status), _) if status == task.status => return None,
+                    (None, _) => Some(task.created_at),
+                    (_, TaskStatus::Running) => task.started_at,
+                    (_, TaskStatus::Completed | TaskStatus::Failed) => task.completed_at,
+                    _ => None,
+                };
+                Some(TaskEvent::new(task, task.status, at.unwrap_or(now)))
+            })
+            .collect();
+        events.sort_by_key(|event| event.at);
+        events
+    }
+}
+
+/// Follows a queue's transitions, like `tail -f`
+pub struct EventSource<'q> {
+    queue: &'q TaskQueue,
+    tracker: TransitionTracker,
+    pending: VecDeque<TaskEvent>,
+}
+
+impl<'q> EventSource<'q> {
+    /// Start following from now, or replay history first if `from_start`
+    pub async fn start(queue: &'q TaskQueue, from_start: bool) -> Result<Self> {
+        let tasks = queue.list().await?;
+        let mut tracker = TransitionTracker::default();
+        tracker.seed(&tasks);
+        let pending = if from_start { history(&tasks).into() } else { VecDeque::new() };
+
+        Ok(Self { queue, tracker, pending })
+    }
+
+    /// Wait for the next event, polling every `poll_interval`
+    pub async fn next(&mut self, poll_interval: Duration) -> Result<TaskEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+            tokio::time::sleep(poll_interval).await;
+            let tasks = self.queue.list().await?;
+            self.pending.extend(self.tracker.observe(&tasks, Utc::now()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLL: Duration = Duration::from_millis(5);
+
+    async fn seeded() -> (TaskQueue, String, String) {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let done = queue.enqueue("Run tests", None).await.unwrap();
+        let waiting = queue.enqueue("Deploy", None).await.unwrap();
+        queue.claim_next().await.unwrap();
+        queue.update_status(&done, TaskStatus::Completed).await.unwrap();
+        (queue, done, waiting)
+    }
+
+    fn statuses(events: &[TaskEvent]) -> Vec<(&str, TaskStatus)> {
+        events.iter().map(|e| (e.task_id.as_str(), e.status)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_from_start_replays_history() {
+        let (queue, done, waiting) = seeded().await;
+        let mut source = EventSource::start(&queue, true).await.unwrap();
+
+        let mut events = Vec::new();
+        for _ in 0..4 {
+            events.push(source.next(POLL).await.unwrap());
+        }
+        assert_eq!(
+            statuses(&events),
+            vec![
+                (done.as_str(), TaskStatus::Queued),
+                (waiting.as_str(), TaskStatus::Queued),
+                (done.as_str(), TaskStatus::Running),
+                (done.as_str(), TaskStatus::Completed),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_from_now_reports_only_new_transitions() {
+        let (queue, _, waiting) = seeded().await;
+        let mut source = EventSource::start(&queue, false).await.unwrap();
+
+        queue.claim_next().await.unwrap();
+        let event = source.next(POLL).await.unwrap();
+        assert_eq!((event.task_id.as_str(), event.status), (waiting.as_str(), TaskStatus::Running));
+
+        let added = queue.enqueue("Lint", None).await.unwrap();
+        queue.fail(&waiting, "boom").await.unwrap();
+        let events = [source.next(POLL).await.unwrap(), source.next(POLL).await.unwrap()];
+        assert_eq!(
+            statuses(&events),
+            vec![(added.as_str(), TaskStatus::Queued), (waiting.as_str(), TaskStatus::Failed)]
+        );
+        assert_eq!(events[1].error.as_deref(), Some("boom"));
+    }
+
+    #[tokio::test]
+    async fn test_tracker_ignores_unchanged_tasks() {
+        let (queue, _, waiting) = seeded().await;
+        let mut tracker = TransitionTracker::default();
+        tracker.seed(&queue.list().await.unwrap());
+
+        let now = Utc::now();
+        assert!(tracker.observe(&queue.list().await.unwrap(), now).is_empty());
+
+        queue.update_status(&waiting, TaskStatus::Cancelled).await.unwrap();
+        let events = tracker.observe(&queue.list().await.unwrap(), now);
+        assert_eq!(statuses(&events), vec![(waiting.as_str(), TaskStatus::Cancelled)]);
+        assert_eq!(events[0].at, now);
+    }
+}
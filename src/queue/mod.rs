@@ -1,5 +1,23 @@
 //! Task queue management
 
+pub mod blocking;
+pub mod builder;
 pub mod db;
+pub mod error;
+pub mod events;
+pub mod spec;
+pub mod stats;
+pub mod snapshot;
+pub mod sqlite;
+pub mod store;
 
-pub use db::{Task, TaskQueue, TaskStatus};
+pub use blocking::BlockingTaskQueue;
+pub use builder::TaskBuilder;
+pub use db::{normalize_command, truncate_with_marker, DaemonLock, Task, TaskAttempt, TaskQueue, TaskStatus};
+pub use error::{QueueError, SpecError};
+pub use events::{EventSource, TaskEvent};
+pub use spec::{parse_specs, parse_specs_each, BatchResult, TaskSpec};
+pub use stats::{LatencyStats, QueueStats};
+pub use snapshot::EnvSnapshot;
+pub use sqlite::SqliteStore;
+pub use store::{ListQuery, NewTask, TaskStore};
@@ -0,0 +1,103 @@
+//! Where a task was enqueued from, recorded so its run can be reproduced
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Directory, git state and selected environment variables at enqueue time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnvSnapshot {
+    pub cwd: PathBuf,
+    /// Checked-out commit, when `cwd` is in a git repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<String>,
+    /// Current branch (None when detached or outside a repository)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_branch: Option<String>,
+    /// Allowlisted variables that were set, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+impl EnvSnapshot {
+    /// Record `dir` and the variables in `allowlist` that are set
+    pub fn capture(dir: &Path, allowlist: &[String]) -> Self {
+        let env = allowlist
+            .iter()
+            .filter_map(|name| std::env::var(name).ok().map(|value| (name.clone(), value)))
+            .collect();
+
+        Self {
+            cwd: dir.to_path_buf(),
+            git_commit: git(dir, &["rev-parse", "HEAD"]),
+            git_branch: git(dir, &["rev-parse", "--abbrev-ref", "HEAD"]).filter(|branch| branch != "HEAD"),
+            env,
+        }
+    }
+}
+
+/// Trimmed stdout of `git <args>` in `dir`, if it succeeds
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let stdout = stdout.trim();
+    (output.status.success() && !stdout.is_empty()).then(|| stdout.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    #[test]
+    fn test_capture_in_git_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        run_git(dir.path(), &["init", "-q", "-b", "feature"]);
+        run_git(dir.path(), &["commit", "-q", "--allow-empty", "-m", "init"]);
+
+        let snapshot = EnvSnapshot::capture(dir.path(), &["PATH".to_string(), "FASTER_TEST_UNSET".to_string()]);
+        assert_eq!(snapshot.cwd, dir.path());
+        assert_eq!(snapshot.git_branch.as_deref(), Some("feature"));
+        let commit = snapshot.git_commit.as_deref().unwrap();
+        assert_eq!(commit.len(), 40);
+        assert!(commit.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(snapshot.env.keys().collect::<Vec<_>>(), vec!["PATH"]);
+
+        // Detached HEAD keeps the commit but has no branch
+        run_git(dir.path(), &["checkout", "-q", "--detach"]);
+        let detached = EnvSnapshot::capture(dir.path(), &[]);
+        assert_eq!(detached.git_commit.as_deref(), Some(commit));
+        assert_eq!(detached.git_branch, None);
+    }
+
+    #[test]
+    fn test_capture_outside_git_omits_git_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = EnvSnapshot::capture(dir.path(), &[]);
+        assert_eq!(snapshot, EnvSnapshot { cwd: dir.path().to_path_buf(), ..EnvSnapshot::default() });
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(!json.contains("git_"), "{}", json);
+        assert_eq!(serde_json::from_str::<EnvSnapshot>(&json).unwrap(), snapshot);
+    }
+}
@@ -0,0 +1,320 @@
+//! Full task descriptions for programmatic, batch submission
+
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::db::{normalize_command, TaskQueue};
+use super::error::{QueueError, SpecError};
+use super::store::NewTask;
+use crate::intent::meta::is_known_model;
+use crate::timezone::Timezone;
+
+/// One task as submitted in a JSON spec file
+///
+/// ```json
+/// {"command": "Run the tests", "model": "haiku", "priority": 5,
+///  "tags": ["ci"], "cwd": "/src/app", "scheduled_for": "2026-01-01T09:00:00Z",
+///  "timeout_ms": 300000, "metadata": {"pr": "1234"}}
+/// ```
+///
+/// `scheduled_for` may also be a wall-clock time such as `"2026-01-01 09:00"`
+/// or `"9am"`, read in the timezone given to `parse_specs`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskSpec {
+    pub command: String,
+    pub model: Option<String>,
+    pub priority: i32,
+    pub tags: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    /// Run time limit in milliseconds, overriding `claude.timeout_secs`
+    pub timeout_ms: Option<u64>,
+    /// Opaque key/value pairs, echoed back by `get`
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl TaskSpec {
+    /// Problems that would stop this spec from being enqueued
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if normalize_command(&self.command).is_empty() {
+            problems.push("command is empty".to_string());
+        }
+        if let Some(model) = self.model.as_deref().filter(|m| !is_known_model(m)) {
+            problems.push(format!("unknown model '{}'", model));
+        }
+        if let Some(cwd) = self.cwd.as_ref().filter(|cwd| !cwd.is_dir()) {
+            problems.push(format!("cwd {} is not a directory", cwd.display()));
+        }
+        if self.metadata.keys().any(|key| key.trim().is_empty()) {
+            problems.push("metadata key is empty".to_string());
+        }
+        problems
+    }
+
+    fn into_new_task(self) -> NewTask {
+        let mut tags = Vec::new();
+        for tag in self.tags {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        NewTask {
+            model: self.model,
+            priority: self.priority,
+            tags,
+            cwd: self.cwd,
+            scheduled_for: self.scheduled_for,
+            timeout_ms: self.timeout_ms,
+            metadata: self.metadata,
+            ..NewTask::new(self.command)
+        }
+    }
+}
+
+/// Outcome of `TaskQueue::enqueue_specs_best_effort`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchResult {
+    /// Ids of the enqueued tasks, in spec order
+    pub succeeded: Vec<String>,
+    /// Each spec that wasn't enqueued, by index, with why
+    pub failed: Vec<SpecError>,
+}
+
+/// Parse a JSON array of specs, reporting every malformed item by index
+///
+/// Schedules without an offset are wall-clock times in `timezone`.
+pub fn parse_specs(json: &str, timezone: &Timezone) -> Result<Vec<TaskSpec>> {
+    let (specs, errors): (Vec<_>, Vec<_>) = parse_specs_each(json, timezone)?.into_iter().partition(|item| item.is_ok());
+
+    if !errors.is_empty() {
+        let errors = errors.into_iter().filter_map(|item| item.err()).collect();
+        return Err(QueueError::InvalidSpecs { errors }.into());
+    }
+    Ok(specs.into_iter().filter_map(|item| item.ok()).collect())
+}
+
+/// Like `parse_specs`, but with a result per item, so good items survive bad ones
+///
+/// Only fails if `json` isn't an array at all.
+pub fn parse_specs_each(json: &str, timezone: &Timezone) -> Result<Vec<std::result::Result<TaskSpec, SpecError>>> {
+    let items: Vec<serde_json::Value> = serde_json::from_str(json)?;
+
+    Ok(items
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut item)| {
+            if let Some(scheduled_for) = item.get_mut("scheduled_for").filter(|v| v.is_string()) {
+                let at = timezone
+                    .parse_schedule(scheduled_for.as_str().unwrap_or_default(), Utc::now())
+                    .map_err(|message| SpecError { index, message })?;
+                *scheduled_for = at.to_rfc3339().into();
+            }
+            serde_json::from_value(item).map_err(|e| SpecError { index, message: e.to_string() })
+        })
+        .collect())
+}
+
+impl TaskQueue {
+    /// Enqueue a batch of specs, returning their ids in order
+    ///
+    /// All or nothing: every spec is validated first, and if any is invalid
+    /// `QueueError::InvalidSpecs` lists each problem with its index. The
+    /// inserts share a transaction, so a full queue partway rolls back too.
+    pub async fn enqueue_specs(&self, specs: &[TaskSpec]) -> Result<Vec<String>> {
+        let errors: Vec<SpecError> = specs
+            .iter()
+            .enumerate()
+            .flat_map(|(index, spec)| {
+                spec.problems().into_iter().map(move |message| SpecError { index, message })
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(QueueError::InvalidSpecs { errors }.into());
+        }
+
+        self.insert_all(specs.iter().cloned().map(TaskSpec::into_new_task).collect()).await
+    }
+
+    /// Enqueue each valid spec on its own, reporting the rest instead of failing
+    ///
+    /// A spec that's invalid, or that the queue rejects (e.g. because it's
+    /// full), lands in `failed`; the others are enqueued regardless.
+    pub async fn enqueue_specs_best_effort(&self, specs: &[TaskSpec]) -> BatchResult {
+        let mut result = BatchResult::default();
+        for (index, spec) in specs.iter().enumerate() {
+            let problems = spec.problems();
+            if !problems.is_empty() {
+                result.failed.push(SpecError { index, message: problems.join("; ") });
+                continue;
+            }
+            match self.insert(spec.clone().into_new_task()).await {
+                Ok(id) => result.succeeded.push(id),
+                Err(e) => result.failed.push(SpecError { index, message: e.to_string() }),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn queue() -> TaskQueue {
+        TaskQueue::new(":memory:").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_specs_file_stores_every_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = format!(
+            r#"[
+                {{"command": "Run the tests", "model": "haiku", "priority": 5,
+                  "tags": ["ci", "fast", "ci"], "cwd": {:?},
+                  "scheduled_for": "2030-01-01T09:00:00Z", "timeout_ms": 300000,
+                  "metadata": {{"pr": "1234", "trace": "t-1"}}}},
+                {{"command": "Summarize the changelog"}}
+            ]"#,
+            dir.path()
+        );
+        let queue = queue().await;
+
+        let ids = queue.enqueue_specs(&parse_specs(&json, &Timezone::Utc).unwrap()).await.unwrap();
+        assert_eq!(ids.len(), 2);
+
+        let task = queue.get(&ids[0]).await.unwrap().unwrap();
+        assert_eq!(task.command, "Run the tests");
+        assert_eq!(task.model.as_deref(), Some("haiku"));
+        assert_eq!(task.priority, 5);
+        assert_eq!(task.tags, vec!["ci", "fast"]);
+        assert_eq!(task.cwd, Some(dir.path().canonicalize().unwrap()));
+        assert_eq!(task.scheduled_for, Some("2030-01-01T09:00:00Z".parse().unwrap()));
+        assert_eq!(task.timeout_ms, Some(300_000));
+        assert_eq!(task.metadata.get("pr").map(String::as_str), Some("1234"));
+        assert_eq!(task.metadata.len(), 2);
+
+        let plain = queue.get(&ids[1]).await.unwrap().unwrap();
+        assert_eq!(plain.model, None);
+        assert_eq!(plain.priority, 0);
+        assert!(plain.tags.is_empty());
+        assert_eq!(plain.scheduled_for, None);
+        assert_eq!(plain.timeout_ms, None);
+        assert!(plain.metadata.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_specs_are_reported_by_index() {
+        let queue = queue().await;
+        let specs = vec![
+            TaskSpec { command: "Fine".to_string(), ..Default::default() },
+            TaskSpec { command: "Big job".to_string(), model: Some("gpt-4".to_string()), ..Default::default() },
+            TaskSpec { command: "   ".to_string(), cwd: Some("/nonexistent/dir".into()), ..Default::default() },
+            TaskSpec {
+                command: "Tracked".to_string(),
+                metadata: BTreeMap::from([(" ".to_string(), "x".to_string())]),
+                ..Default::default()
+            },
+        ];
+
+        let err = queue.enqueue_specs(&specs).await.unwrap_err();
+        let Some(QueueError::InvalidSpecs { errors }) = err.queue_error() else {
+            panic!("expected InvalidSpecs, got {}", err);
+        };
+        let indices: Vec<_> = errors.iter().map(|e| e.index).collect();
+        assert_eq!(indices, vec![1, 2, 2, 3]);
+        assert!(err.to_string().contains("[1] unknown model 'gpt-4'"));
+        assert!(err.to_string().contains("[2] command is empty"));
+        assert!(err.to_string().contains("[3] metadata key is empty"));
+
+        // Nothing from a rejected batch is enqueued
+        assert!(queue.list().await.unwrap().is_empty());
+    }
+
+    fn commands(n: usize) -> Vec<TaskSpec> {
+        (0..n).map(|i| TaskSpec { command: format!("Task {}", i), ..Default::default() }).collect()
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_rolls_back_whole_batch() {
+        let queue = queue().await.with_max_pending(3);
+        queue.enqueue("Already queued", None).await.unwrap();
+
+        // Valid specs, but the third doesn't fit
+        let err = queue.enqueue_specs(&commands(3)).await.unwrap_err();
+        assert_eq!(err.queue_error().as_ref(), Some(&QueueError::QueueFull { limit: 3 }));
+        assert_eq!(queue.list().await.unwrap().len(), 1);
+
+        assert_eq!(queue.enqueue_specs(&commands(2)).await.unwrap().len(), 2);
+        assert_eq!(queue.list().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_enqueues_the_valid_specs() {
+        let queue = queue().await.with_max_pending(3);
+        let mut specs = commands(5);
+        specs[1].model = Some("gpt-4".to_string());
+        specs[3].command = " ".to_string();
+
+        let result = queue.enqueue_specs_best_effort(&specs).await;
+        assert_eq!(result.succeeded.len(), 3);
+        assert_eq!(
+            result.failed,
+            vec![
+                SpecError { index: 1, message: "unknown model 'gpt-4'".to_string() },
+                SpecError { index: 3, message: "command is empty".to_string() },
+            ]
+        );
+        let queued: Vec<_> = queue.list().await.unwrap().into_iter().map(|t| t.command).collect();
+        assert_eq!(queued.len(), 3);
+        assert!(queued.iter().all(|command| ["Task 0", "Task 2", "Task 4"].contains(&command.as_str())));
+
+        // Now full, so every spec is a failure rather than an error
+        let result = queue.enqueue_specs_best_effort(&commands(2)).await;
+        assert!(result.succeeded.is_empty());
+        assert_eq!(result.failed.iter().map(|e| e.index).collect::<Vec<_>>(), vec![0, 1]);
+        assert!(result.failed[0].message.contains("Queue is full"));
+    }
+
+    #[test]
+    fn test_malformed_items_are_reported_by_index() {
+        let json = r#"[{"command": "ok"}, {"command": "x", "priority": "high"}, {"comand": "typo"}]"#;
+
+        let err = parse_specs(json, &Timezone::Utc).unwrap_err();
+        let Some(QueueError::InvalidSpecs { errors }) = err.queue_error() else {
+            panic!("expected InvalidSpecs, got {}", err);
+        };
+        assert_eq!(errors.iter().map(|e| e.index).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(errors[1].message.contains("comand"));
+
+        assert!(parse_specs("{\"command\": \"not an array\"}", &Timezone::Utc).is_err());
+
+        let each = parse_specs_each(json, &Timezone::Utc).unwrap();
+        assert_eq!(each[0].as_ref().map(|spec| spec.command.as_str()), Ok("ok"));
+        assert_eq!(each.iter().filter(|item| item.is_err()).count(), 2);
+    }
+
+    #[test]
+    fn test_wall_clock_schedules_use_the_timezone() {
+        let json = r#"[{"command": "a", "scheduled_for": "2030-01-01 09:00"},
+                       {"command": "b", "scheduled_for": "2030-01-01T09:00:00Z"},
+                       {"command": "c", "scheduled_for": "whenever"}]"#;
+        let timezone: Timezone = "-05:00".parse().unwrap();
+
+        let err = parse_specs(json, &timezone).unwrap_err();
+        let Some(QueueError::InvalidSpecs { errors }) = err.queue_error() else {
+            panic!("expected InvalidSpecs, got {}", err);
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].index, 2);
+
+        let specs = parse_specs(&json.replace("whenever", "2030-01-01 10:00"), &timezone).unwrap();
+        assert_eq!(specs[0].scheduled_for, Some("2030-01-01T14:00:00Z".parse().unwrap()));
+        assert_eq!(specs[1].scheduled_for, Some("2030-01-01T09:00:00Z".parse().unwrap()));
+    }
+}
@@ -0,0 +1,1384 @@
+//! SQLite storage backend
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::future::BoxFuture;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteRow};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use super::db::{DaemonLock, Task, TaskAttempt, TaskStatus};
+use super::error::QueueError;
+use super::store::{ListQuery, NewTask, TaskStore};
+use crate::clock::{Clock, SystemClock};
+use crate::intent::Intent;
+
+/// Columns selected for every task query, in `task_from_row` order
+const TASK_COLUMNS: &str = "id, command, status, model, created_at, started_at, completed_at, \
+                            error, rendered_prompt, intent, output, original_command, \
+                            parent_id, session_id, confidence, priority, tags, cwd, \
+                            scheduled_for, processed_by, output_lossy, depends_on, \
+                            output_path, duration_ms, input_tokens, output_tokens, \
+                            env_snapshot, timeout_ms, replayed_from, metadata, \
+                            prompt_prefix, prompt_suffix";
+
+/// Columns added after the original schema, as (name, definition)
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("rendered_prompt", "TEXT"),
+    ("intent", "TEXT"),
+    ("output", "TEXT"),
+    ("original_command", "TEXT"),
+    ("seq", "INTEGER"),
+    ("parent_id", "TEXT"),
+    ("session_id", "TEXT"),
+    ("confidence", "REAL"),
+    ("priority", "INTEGER NOT NULL DEFAULT 0"),
+    ("tags", "TEXT"),
+    ("cwd", "TEXT"),
+    ("scheduled_for", "TEXT"),
+    ("processed_by", "TEXT"),
+    ("output_lossy", "INTEGER NOT NULL DEFAULT 0"),
+    ("depends_on", "TEXT"),
+    ("output_path", "TEXT"),
+    ("duration_ms", "INTEGER"),
+    ("input_tokens", "INTEGER"),
+    ("output_tokens", "INTEGER"),
+    ("env_snapshot", "TEXT"),
+    ("timeout_ms", "INTEGER"),
+    ("replayed_from", "TEXT"),
+    ("metadata", "TEXT"),
+    ("prompt_prefix", "TEXT"),
+    ("prompt_suffix", "TEXT"),
+];
+
+/// Which queued tasks may be claimed: due, with any dependency completed
+const CLAIMABLE: &str = "(scheduled_for IS NULL OR scheduled_for <= ?) \
+                         AND (depends_on IS NULL OR depends_on IN (SELECT id FROM tasks WHERE status = 'completed'))";
+
+/// Queued and draft tasks whose command contains the pattern (NULL = any),
+/// ignoring ASCII case; instr rather than LIKE so % and _ in it are literal
+const PENDING_MATCHING: &str = "status IN (?, ?) AND (? IS NULL OR instr(lower(command), lower(?)) > 0)";
+
+/// Claim order: highest priority, then oldest, with `seq` breaking timestamp ties
+const CLAIM_ORDER: &str = "priority DESC, created_at ASC, seq ASC";
+
+/// Columns a move to `status` also sets; a trailing `?` takes the current time
+///
+/// Requeued tasks lose their timestamps, error and claimer so they run fresh.
+fn status_columns(status: TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Queued => ", started_at = NULL, completed_at = NULL, error = NULL, processed_by = NULL",
+        TaskStatus::Running => ", started_at = ?",
+        TaskStatus::Completed | TaskStatus::Failed => ", completed_at = ?",
+        TaskStatus::Cancelled | TaskStatus::Draft | TaskStatus::Blocked => "",
+    }
+}
+
+/// Fixed-width UTC timestamp, so string comparison in SQL matches time order
+fn sortable_timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_timestamp(value: Option<String>) -> Option<DateTime<Utc>> {
+    value
+        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// `QueueError::CorruptRow` for `field` of the task whose id is in `id_column`
+fn corrupt(row: &SqliteRow, id_column: &str, field: &'static str) -> QueueError {
+    QueueError::CorruptRow { id: row.try_get(id_column).unwrap_or_default(), field }
+}
+
+fn status_from_row(row: &SqliteRow, id_column: &str) -> Result<TaskStatus> {
+    row.try_get::<&str, _>("status")
+        .ok()
+        .and_then(TaskStatus::from_str)
+        .ok_or_else(|| corrupt(row, id_column, "status").into())
+}
+
+/// A required RFC 3339 timestamp column
+fn timestamp_from_row(row: &SqliteRow, id_column: &str, field: &'static str) -> Result<DateTime<Utc>> {
+    row.try_get::<&str, _>(field)
+        .ok()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| corrupt(row, id_column, field).into())
+}
+
+fn attempt_from_row(row: &SqliteRow) -> Result<TaskAttempt> {
+    Ok(TaskAttempt {
+        task_id: row.get("task_id"),
+        attempt_no: row.get::<i64, _>("attempt_no") as u32,
+        model: row.get("model"),
+        started_at: timestamp_from_row(row, "task_id", "started_at")?,
+        completed_at: parse_timestamp(row.get("completed_at")),
+        status: status_from_row(row, "task_id")?,
+        error: row.get("error"),
+    })
+}
+
+fn task_from_row(row: &SqliteRow) -> Result<Task> {
+    Ok(Task {
+        id: row.get("id"),
+        command: row.get("command"),
+        status: status_from_row(row, "id")?,
+        model: row.get("model"),
+        created_at: timestamp_from_row(row, "id", "created_at")?,
+        started_at: parse_timestamp(row.get("started_at")),
+        completed_at: parse_timestamp(row.get("completed_at")),
+        error: row.get("error"),
+        rendered_prompt: row.get("rendered_prompt"),
+        intent: row
+            .get::<Option<String>, _>("intent")
+            .and_then(|s| s.parse().ok()),
+        confidence: row.get::<Option<f64>, _>("confidence").map(|c| c as f32),
+        output: row.get("output"),
+        original_command: row.get("original_command"),
+        parent_id: row.get("parent_id"),
+        session_id: row.get("session_id"),
+        priority: row.get("priority"),
+        tags: row
+            .get::<Option<String>, _>("tags")
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
+        cwd: row.get::<Option<String>, _>("cwd").map(PathBuf::from),
+        scheduled_for: parse_timestamp(row.get("scheduled_for")),
+        processed_by: row.get("processed_by"),
+        output_lossy: row.get("output_lossy"),
+        depends_on: row.get("depends_on"),
+        output_path: row.get::<Option<String>, _>("output_path").map(PathBuf::from),
+        duration_ms: row.get::<Option<i64>, _>("duration_ms").map(|ms| ms as u64),
+        input_tokens: row.get::<Option<i64>, _>("input_tokens").map(|n| n as u64),
+        output_tokens: row.get::<Option<i64>, _>("output_tokens").map(|n| n as u64),
+        env_snapshot: row
+            .get::<Option<String>, _>("env_snapshot")
+            .and_then(|s| serde_json::from_str(&s).ok()),
+        timeout_ms: row.get::<Option<i64>, _>("timeout_ms").map(|ms| ms as u64),
+        replayed_from: row.get("replayed_from"),
+        metadata: row
+            .get::<Option<String>, _>("metadata")
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
+        prompt_prefix: row.get("prompt_prefix"),
+        prompt_suffix: row.get("prompt_suffix"),
+    })
+}
+
+/// Task store backed by a local SQLite database file
+#[derive(Clone)]
+pub struct SqliteStore {
+    /// Shared by every clone, so a reconnect through one reaches them all
+    pool: Arc<RwLock<SqlitePool>>,
+    db_path: String,
+    clock: Arc<dyn Clock>,
+    busy_timeout: Duration,
+}
+
+/// How long a statement waits on another connection's lock before failing busy
+const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl SqliteStore {
+    /// Open (creating if needed) the database at `db_path`
+    pub async fn new(db_path: &str) -> Result<Self> {
+        Self::with_busy_timeout(db_path, DEFAULT_BUSY_TIMEOUT).await
+    }
+
+    /// Like `new`, waiting at most `busy_timeout` for other connections' locks
+    pub async fn with_busy_timeout(db_path: &str, busy_timeout: Duration) -> Result<Self> {
+        let store = Self {
+            pool: Arc::new(RwLock::new(Self::connect(db_path, busy_timeout).await?)),
+            db_path: db_path.to_string(),
+            clock: Arc::new(SystemClock),
+            busy_timeout,
+        };
+        store.init_schema().await?;
+
+        Ok(store)
+    }
+
+    /// The current pool; a cheap handle that stays valid until the next reconnect
+    fn pool(&self) -> SqlitePool {
+        self.pool.read().unwrap().clone()
+    }
+
+    async fn connect(db_path: &str, busy_timeout: Duration) -> Result<SqlitePool> {
+        // Ensure parent directory exists
+        if let Some(parent) = std::path::Path::new(db_path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let options = SqliteConnectOptions::from_str(&format!("sqlite://{}?mode=rwc", db_path))?
+            .busy_timeout(busy_timeout);
+        let pool = SqlitePool::connect_with(options).await?;
+
+        // WAL lets the CLI read while the daemon writes; in-memory databases can't use it
+        if db_path != ":memory:" {
+            sqlx::query("PRAGMA journal_mode = WAL").execute(&pool).await?;
+        }
+
+        Ok(pool)
+    }
+
+    /// Initialize database schema
+    async fn init_schema(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS tasks (
+                id TEXT PRIMARY KEY,
+                command TEXT NOT NULL,
+                status TEXT NOT NULL,
+                model TEXT,
+                created_at TEXT NOT NULL,
+                started_at TEXT,
+                completed_at TEXT,
+                error TEXT,
+                rendered_prompt TEXT,
+                intent TEXT,
+                output TEXT,
+                original_command TEXT,
+                seq INTEGER,
+                parent_id TEXT,
+                session_id TEXT,
+                confidence REAL,
+                priority INTEGER NOT NULL DEFAULT 0,
+                tags TEXT,
+                cwd TEXT,
+                scheduled_for TEXT,
+                processed_by TEXT,
+                output_lossy INTEGER NOT NULL DEFAULT 0,
+                depends_on TEXT,
+                output_path TEXT,
+                duration_ms INTEGER,
+                input_tokens INTEGER,
+                output_tokens INTEGER,
+                env_snapshot TEXT,
+                timeout_ms INTEGER,
+                replayed_from TEXT,
+                metadata TEXT,
+                prompt_prefix TEXT,
+                prompt_suffix TEXT
+            )
+            "#
+        )
+        .execute(&self.pool())
+        .await?;
+
+        // Bring databases created by older versions up to date
+        for (column, definition) in ADDED_COLUMNS {
+            self.ensure_column(column, definition).await?;
+        }
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS task_context_files (
+                task_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                PRIMARY KEY (task_id, position)
+            )
+            "#
+        )
+        .execute(&self.pool())
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS task_attempts (
+                task_id TEXT NOT NULL,
+                attempt_no INTEGER NOT NULL,
+                model TEXT,
+                started_at TEXT NOT NULL,
+                completed_at TEXT,
+                status TEXT NOT NULL,
+                error TEXT,
+                PRIMARY KEY (task_id, attempt_no)
+            )
+            "#
+        )
+        .execute(&self.pool())
+        .await?;
+
+        // Single-row table naming the daemon currently serving this queue
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS daemon_lock (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                pid INTEGER NOT NULL,
+                started_at TEXT NOT NULL
+            )
+            "#
+        )
+        .execute(&self.pool())
+        .await?;
+
+        // Rows from before `seq` existed keep their insertion order
+        sqlx::query("UPDATE tasks SET seq = rowid WHERE seq IS NULL")
+            .execute(&self.pool())
+            .await?;
+
+        // Create index on status for efficient querying
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status)"
+        )
+        .execute(&self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Add a column to the tasks table if it doesn't exist yet
+    async fn ensure_column(&self, column: &str, definition: &str) -> Result<()> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('tasks') WHERE name = ?"
+        )
+        .bind(column)
+        .fetch_one(&self.pool())
+        .await?;
+
+        if !exists {
+            sqlx::query(&format!("ALTER TABLE tasks ADD COLUMN {} {}", column, definition))
+                .execute(&self.pool())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_task(&self, id: &str) -> Result<Option<Task>> {
+        let row = sqlx::query(&format!("SELECT {} FROM tasks WHERE id = ?", TASK_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool())
+            .await?;
+
+        row.as_ref().map(task_from_row).transpose()
+    }
+
+    async fn current_lock(&self) -> Result<Option<DaemonLock>> {
+        let row = sqlx::query("SELECT pid, started_at FROM daemon_lock WHERE id = 1")
+            .fetch_optional(&self.pool())
+            .await?;
+
+        row.map(|row| {
+            Ok(DaemonLock {
+                pid: row.get::<i64, _>("pid") as u32,
+                started_at: DateTime::parse_from_rfc3339(row.get("started_at"))?
+                    .with_timezone(&Utc),
+            })
+        })
+        .transpose()
+    }
+
+    /// Insert one task over `conn`, so a batch can share a transaction
+    async fn insert_on(&self, conn: &mut SqliteConnection, task: NewTask, max_pending: u64) -> Result<String> {
+        let id = nanoid::nanoid!(8);
+
+        // Count and insert in one statement so concurrent enqueues can't overshoot the cap
+        let result = sqlx::query(
+            r#"
+            INSERT INTO tasks (id, command, status, model, created_at, intent, confidence,
+                               original_command, priority, tags, cwd, scheduled_for, depends_on,
+                               parent_id, env_snapshot, timeout_ms, replayed_from, metadata,
+                               prompt_prefix, prompt_suffix, seq)
+            SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
+                   (SELECT COALESCE(MAX(seq), 0) + 1 FROM tasks)
+            WHERE ? = 0 OR (SELECT COUNT(*) FROM tasks WHERE status = ?) < ?
+            "#
+        )
+        .bind(&id)
+        .bind(&task.command)
+        .bind(task.status.as_str())
+        .bind(task.model)
+        .bind(self.clock.now().to_rfc3339())
+        .bind(task.intent.map(|i| i.to_string()))
+        .bind(task.confidence.map(f64::from))
+        .bind(task.original_command)
+        .bind(task.priority)
+        .bind((!task.tags.is_empty()).then(|| serde_json::to_string(&task.tags)).transpose()?)
+        .bind(task.cwd.map(|p| p.to_string_lossy().into_owned()))
+        .bind(task.scheduled_for.map(sortable_timestamp))
+        .bind(task.depends_on)
+        .bind(task.parent_id)
+        .bind(task.env_snapshot.as_ref().map(serde_json::to_string).transpose()?)
+        .bind(task.timeout_ms.map(|ms| ms as i64))
+        .bind(task.replayed_from)
+        .bind((!task.metadata.is_empty()).then(|| serde_json::to_string(&task.metadata)).transpose()?)
+        .bind(task.prompt_prefix)
+        .bind(task.prompt_suffix)
+        .bind(max_pending as i64)
+        .bind(TaskStatus::Queued.as_str())
+        .bind(max_pending as i64)
+        .execute(&mut *conn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(QueueError::QueueFull { limit: max_pending }.into());
+        }
+
+        for (position, path) in task.context_files.iter().enumerate() {
+            sqlx::query("INSERT INTO task_context_files (task_id, position, path) VALUES (?, ?, ?)")
+                .bind(&id)
+                .bind(position as i64)
+                .bind(path.to_string_lossy().to_string())
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(id)
+    }
+
+    /// Claim the next claimable task, limited to `among` when given
+    async fn claim(&self, among: Option<&[String]>, processed_by: Option<&str>) -> Result<Option<Task>> {
+        let among_clause = match among {
+            Some(ids) => format!("AND id IN ({})", vec!["?"; ids.len()].join(", ")),
+            None => String::new(),
+        };
+        let sql = format!(
+            r#"
+            UPDATE tasks SET status = ?, started_at = ?, processed_by = ?
+            WHERE id = (
+                SELECT id FROM tasks
+                WHERE status = ? AND {} {}
+                ORDER BY {} LIMIT 1
+            )
+            RETURNING {}
+            "#,
+            CLAIMABLE, among_clause, CLAIM_ORDER, TASK_COLUMNS
+        );
+        let mut query = sqlx::query(&sql)
+            .bind(TaskStatus::Running.as_str())
+            .bind(self.clock.now().to_rfc3339())
+            .bind(processed_by)
+            .bind(TaskStatus::Queued.as_str())
+            .bind(sortable_timestamp(self.clock.now()));
+        for id in among.unwrap_or_default() {
+            query = query.bind(id);
+        }
+        let row = query.fetch_optional(&self.pool()).await?;
+
+        row.as_ref().map(task_from_row).transpose()
+    }
+}
+
+impl TaskStore for SqliteStore {
+    fn clone_box(&self) -> Box<dyn TaskStore> {
+        Box::new(self.clone())
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    fn insert<'a>(&'a self, task: NewTask, max_pending: u64) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            // One transaction, so the task is never claimable without its context files
+            let mut tx = self.pool().begin().await?;
+            let id = self.insert_on(&mut tx, task, max_pending).await?;
+            tx.commit().await?;
+            Ok(id)
+        })
+    }
+
+    fn insert_all<'a>(&'a self, tasks: Vec<NewTask>, max_pending: u64) -> BoxFuture<'a, Result<Vec<String>>> {
+        Box::pin(async move {
+            // Dropping the transaction on an error rolls back what was inserted so far
+            let mut tx = self.pool().begin().await?;
+            let mut ids = Vec::with_capacity(tasks.len());
+            for task in tasks {
+                let limit = if task.status == TaskStatus::Queued { max_pending } else { 0 };
+                ids.push(self.insert_on(&mut tx, task, limit).await?);
+            }
+            tx.commit().await?;
+            Ok(ids)
+        })
+    }
+
+    fn promote<'a>(&'a self, id: &'a str, intent: Option<Intent>, max_pending: u64) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(async move {
+            let result = sqlx::query(
+                "UPDATE tasks SET status = ?, intent = COALESCE(?, intent) WHERE id = ? AND status = ? \
+                 AND (? = 0 OR (SELECT COUNT(*) FROM tasks WHERE status = ?) < ?)"
+            )
+            .bind(TaskStatus::Queued.as_str())
+            .bind(intent.map(|i| i.to_string()))
+            .bind(id)
+            .bind(TaskStatus::Draft.as_str())
+            .bind(max_pending as i64)
+            .bind(TaskStatus::Queued.as_str())
+            .bind(max_pending as i64)
+            .execute(&self.pool())
+            .await?;
+            if result.rows_affected() == 1 {
+                return Ok(true);
+            }
+
+            // Still a draft, so the cap is what stopped it
+            let status: Option<String> = sqlx::query_scalar("SELECT status FROM tasks WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&self.pool())
+                .await?;
+            if status.as_deref() == Some(TaskStatus::Draft.as_str()) {
+                return Err(QueueError::QueueFull { limit: max_pending }.into());
+            }
+            Ok(false)
+        })
+    }
+
+    fn dequeue(&self) -> BoxFuture<'_, Result<Option<Task>>> {
+        Box::pin(async move {
+            let row = sqlx::query(&format!(
+                "SELECT {} FROM tasks \
+                 WHERE status = ? AND {} \
+                 ORDER BY {} LIMIT 1",
+                TASK_COLUMNS, CLAIMABLE, CLAIM_ORDER
+            ))
+            .bind(TaskStatus::Queued.as_str())
+            .bind(sortable_timestamp(self.clock.now()))
+            .fetch_optional(&self.pool())
+            .await?;
+
+            row.as_ref().map(task_from_row).transpose()
+        })
+    }
+
+    fn claim_next<'a>(&'a self, processed_by: Option<&'a str>) -> BoxFuture<'a, Result<Option<Task>>> {
+        Box::pin(self.claim(None, processed_by))
+    }
+
+    fn claim_next_among<'a>(
+        &'a self,
+        ids: &'a [String],
+        processed_by: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Option<Task>>> {
+        Box::pin(async move {
+            if ids.is_empty() {
+                return Ok(None);
+            }
+            self.claim(Some(ids), processed_by).await
+        })
+    }
+
+    fn update_status<'a>(&'a self, id: &'a str, from: &'a [TaskStatus], to: TaskStatus) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(async move {
+            if from.is_empty() {
+                return Ok(false);
+            }
+            let set = status_columns(to);
+            let placeholders = vec!["?"; from.len()].join(", ");
+            let query = format!("UPDATE tasks SET status = ?{} WHERE id = ? AND status IN ({})", set, placeholders);
+
+            let mut q = sqlx::query(&query).bind(to.as_str());
+            if set.ends_with('?') {
+                q = q.bind(self.clock.now().to_rfc3339());
+            }
+            q = q.bind(id);
+            for status in from {
+                q = q.bind(status.as_str());
+            }
+            let result = q.execute(&self.pool()).await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    fn update_status_where(&self, from: TaskStatus, to: TaskStatus) -> BoxFuture<'_, Result<u64>> {
+        Box::pin(async move {
+            let set = status_columns(to);
+            let query = format!("UPDATE tasks SET status = ?{} WHERE status = ?", set);
+
+            let mut q = sqlx::query(&query).bind(to.as_str());
+            if set.ends_with('?') {
+                q = q.bind(self.clock.now().to_rfc3339());
+            }
+            let result = q.bind(from.as_str()).execute(&self.pool()).await?;
+
+            Ok(result.rows_affected())
+        })
+    }
+
+    fn requeue_stale<'a>(
+        &'a self,
+        processed_by: &'a str,
+        max_age: Option<std::time::Duration>,
+    ) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(async move {
+            let started_before = max_age
+                .and_then(|age| chrono::Duration::from_std(age).ok())
+                .map(|age| self.clock.now() - age);
+            // julianday, since stored start times don't all have the same precision
+            let query = format!(
+                "UPDATE tasks SET status = ?{} WHERE status = ? \
+                 AND (processed_by = ? OR julianday(started_at) < julianday(?))",
+                status_columns(TaskStatus::Queued)
+            );
+            let result = sqlx::query(&query)
+                .bind(TaskStatus::Queued.as_str())
+                .bind(TaskStatus::Running.as_str())
+                .bind(processed_by)
+                .bind(started_before.map(|at| at.to_rfc3339()))
+                .execute(&self.pool())
+                .await?;
+
+            Ok(result.rows_affected())
+        })
+    }
+
+    fn block_orphaned_dependents(&self) -> BoxFuture<'_, Result<u64>> {
+        Box::pin(async move {
+            // Each orphan carries the task at the root of its chain, for the message
+            let result = sqlx::query(
+                "WITH RECURSIVE orphans(id, root, root_status) AS ( \
+                     SELECT t.id, p.id, p.status FROM tasks t JOIN tasks p ON t.depends_on = p.id \
+                     WHERE t.status = ? AND p.status IN (?, ?) \
+                     UNION \
+                     SELECT t.id, o.root, o.root_status FROM tasks t JOIN orphans o ON t.depends_on = o.id \
+                     WHERE t.status = ? \
+                 ) \
+                 UPDATE tasks SET status = ?, error = ( \
+                     SELECT 'Depends on task ' || root || ', which ' \
+                         || CASE root_status WHEN 'failed' THEN 'failed' ELSE 'was cancelled' END \
+                     FROM orphans WHERE orphans.id = tasks.id LIMIT 1 \
+                 ) \
+                 WHERE id IN (SELECT id FROM orphans)"
+            )
+            .bind(TaskStatus::Queued.as_str())
+            .bind(TaskStatus::Failed.as_str())
+            .bind(TaskStatus::Cancelled.as_str())
+            .bind(TaskStatus::Queued.as_str())
+            .bind(TaskStatus::Blocked.as_str())
+            .execute(&self.pool())
+            .await?;
+
+            Ok(result.rows_affected())
+        })
+    }
+
+    fn cancel_queued<'a>(&'a self, matching: Option<&'a str>) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(async move {
+            let result = sqlx::query(&format!("UPDATE tasks SET status = ? WHERE {}", PENDING_MATCHING))
+                .bind(TaskStatus::Cancelled.as_str())
+                .bind(TaskStatus::Queued.as_str())
+                .bind(TaskStatus::Draft.as_str())
+                .bind(matching)
+                .bind(matching)
+                .execute(&self.pool())
+                .await?;
+
+            Ok(result.rows_affected())
+        })
+    }
+
+    fn count_queued<'a>(&'a self, matching: Option<&'a str>) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(async move {
+            let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM tasks WHERE {}", PENDING_MATCHING))
+                .bind(TaskStatus::Queued.as_str())
+                .bind(TaskStatus::Draft.as_str())
+                .bind(matching)
+                .bind(matching)
+                .fetch_one(&self.pool())
+                .await?;
+
+            Ok(count as u64)
+        })
+    }
+
+    fn fail<'a>(&'a self, id: &'a str, error: &'a str) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(async move {
+            let result = sqlx::query(
+                "UPDATE tasks SET status = ?, completed_at = ?, error = ? WHERE id = ? AND status = ?"
+            )
+            .bind(TaskStatus::Failed.as_str())
+            .bind(self.clock.now().to_rfc3339())
+            .bind(error)
+            .bind(id)
+            .bind(TaskStatus::Running.as_str())
+            .execute(&self.pool())
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    fn list(&self) -> BoxFuture<'_, Result<Vec<Task>>> {
+        Box::pin(async move {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM tasks ORDER BY created_at DESC, seq DESC",
+                TASK_COLUMNS
+            ))
+            .fetch_all(&self.pool())
+            .await?;
+
+            rows.iter().map(task_from_row).collect()
+        })
+    }
+
+    fn list_active(&self) -> BoxFuture<'_, Result<Vec<Task>>> {
+        Box::pin(async move {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM tasks WHERE status IN (?, ?) ORDER BY status = ? DESC, {}",
+                TASK_COLUMNS, CLAIM_ORDER
+            ))
+            .bind(TaskStatus::Queued.as_str())
+            .bind(TaskStatus::Running.as_str())
+            .bind(TaskStatus::Running.as_str())
+            .fetch_all(&self.pool())
+            .await?;
+
+            rows.iter().map(task_from_row).collect()
+        })
+    }
+
+    fn recent_commands(&self, limit: u64) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            // Negative LIMIT is no limit in SQLite
+            let limit = if limit == 0 { -1 } else { limit as i64 };
+            let commands = sqlx::query_scalar(
+                "SELECT command FROM tasks GROUP BY command ORDER BY MAX(seq) DESC LIMIT ?"
+            )
+            .bind(limit)
+            .fetch_all(&self.pool())
+            .await?;
+
+            Ok(commands)
+        })
+    }
+
+    fn query<'a>(&'a self, query: &'a ListQuery) -> BoxFuture<'a, Result<Vec<Task>>> {
+        Box::pin(async move {
+            // julianday() because legacy created_at values vary in fractional digits
+            let rows = sqlx::query(&format!(
+                r#"
+                SELECT {} FROM tasks
+                WHERE (?1 IS NULL OR status = ?1)
+                  AND (?2 IS NULL OR julianday(created_at) >= julianday(?2))
+                  AND (?3 = 0 OR status NOT IN (?4, ?5))
+                ORDER BY created_at DESC, seq DESC
+                "#,
+                TASK_COLUMNS
+            ))
+            .bind(query.status.map(|s| s.as_str()))
+            .bind(query.since.map(sortable_timestamp))
+            .bind(query.active_only)
+            .bind(TaskStatus::Completed.as_str())
+            .bind(TaskStatus::Cancelled.as_str())
+            .fetch_all(&self.pool())
+            .await?;
+
+            if !query.skip_corrupt {
+                return rows.iter().map(task_from_row).collect();
+            }
+            Ok(rows
+                .iter()
+                .filter_map(|row| match task_from_row(row) {
+                    Ok(task) => Some(task),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Skipping corrupt task row");
+                        None
+                    }
+                })
+                .collect())
+        })
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Option<Task>>> {
+        Box::pin(self.get_task(id))
+    }
+
+    fn get_by_prefix<'a>(&'a self, prefix: &'a str) -> BoxFuture<'a, Result<Option<Task>>> {
+        Box::pin(async move {
+            if let Some(task) = self.get_task(prefix).await? {
+                return Ok(Some(task));
+            }
+
+            // substr() rather than LIKE because nanoid ids can contain '_'
+            let ids: Vec<String> = sqlx::query_scalar(
+                "SELECT id FROM tasks WHERE substr(id, 1, length(?)) = ? LIMIT 2"
+            )
+            .bind(prefix)
+            .bind(prefix)
+            .fetch_all(&self.pool())
+            .await?;
+
+            match ids.as_slice() {
+                [] => Ok(None),
+                [id] => self.get_task(id).await,
+                _ => anyhow::bail!("Ambiguous task id prefix: {}", prefix),
+            }
+        })
+    }
+
+    fn set_rendered_prompt<'a>(&'a self, id: &'a str, prompt: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("UPDATE tasks SET rendered_prompt = ? WHERE id = ?")
+                .bind(prompt)
+                .bind(id)
+                .execute(&self.pool())
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn set_output<'a>(
+        &'a self,
+        id: &'a str,
+        output: &'a str,
+        lossy: bool,
+        keep_last: u64,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut tx = self.pool().begin().await?;
+
+            sqlx::query("UPDATE tasks SET output = ?, output_lossy = ? WHERE id = ?")
+                .bind(output)
+                .bind(lossy)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+
+            if keep_last > 0 {
+                sqlx::query(
+                    r#"
+                    UPDATE tasks SET output = NULL, output_lossy = 0
+                    WHERE output IS NOT NULL AND id NOT IN (
+                        SELECT id FROM tasks WHERE output IS NOT NULL
+                        ORDER BY completed_at DESC, seq DESC
+                        LIMIT ?
+                    )
+                    "#
+                )
+                .bind(keep_last as i64)
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+
+    fn set_parent<'a>(&'a self, id: &'a str, parent_id: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("UPDATE tasks SET parent_id = ? WHERE id = ?")
+                .bind(parent_id)
+                .bind(id)
+                .execute(&self.pool())
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn set_run_stats<'a>(
+        &'a self,
+        id: &'a str,
+        duration_ms: u64,
+        tokens: Option<(u64, u64)>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("UPDATE tasks SET duration_ms = ?, input_tokens = ?, output_tokens = ? WHERE id = ?")
+                .bind(duration_ms as i64)
+                .bind(tokens.map(|(input, _)| input as i64))
+                .bind(tokens.map(|(_, output)| output as i64))
+                .bind(id)
+                .execute(&self.pool())
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn set_output_path<'a>(&'a self, id: &'a str, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("UPDATE tasks SET output_path = ? WHERE id = ?")
+                .bind(path.to_string_lossy().into_owned())
+                .bind(id)
+                .execute(&self.pool())
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn set_timeout<'a>(&'a self, id: &'a str, timeout_ms: Option<u64>) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("UPDATE tasks SET timeout_ms = ? WHERE id = ?")
+                .bind(timeout_ms.map(|ms| ms as i64))
+                .bind(id)
+                .execute(&self.pool())
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn set_session_id<'a>(&'a self, id: &'a str, session_id: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("UPDATE tasks SET session_id = ? WHERE id = ?")
+                .bind(session_id)
+                .bind(id)
+                .execute(&self.pool())
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn daemon_lock(&self) -> BoxFuture<'_, Result<Option<DaemonLock>>> {
+        Box::pin(self.current_lock())
+    }
+
+    fn acquire_daemon_lock<'a>(
+        &'a self,
+        pid: u32,
+        force: bool,
+        is_alive: &'a (dyn Fn(u32) -> bool + Send + Sync),
+    ) -> BoxFuture<'a, Result<Option<DaemonLock>>> {
+        Box::pin(async move {
+            let now = self.clock.now().to_rfc3339();
+
+            let existing = loop {
+                let inserted = sqlx::query(
+                    "INSERT INTO daemon_lock (id, pid, started_at) VALUES (1, ?, ?) ON CONFLICT DO NOTHING"
+                )
+                .bind(pid as i64)
+                .bind(&now)
+                .execute(&self.pool())
+                .await?
+                .rows_affected();
+                if inserted == 1 {
+                    return Ok(None);
+                }
+
+                // None means it was released between our insert and read; retry
+                if let Some(existing) = self.current_lock().await? {
+                    break existing;
+                }
+            };
+
+            let live = existing.pid != pid && is_alive(existing.pid);
+            if live && !force {
+                return Err(QueueError::DaemonRunning { pid: existing.pid }.into());
+            }
+
+            // Only take over the lock we inspected, not one claimed since
+            let replaced = sqlx::query("UPDATE daemon_lock SET pid = ?, started_at = ? WHERE id = 1 AND pid = ?")
+                .bind(pid as i64)
+                .bind(&now)
+                .bind(existing.pid as i64)
+                .execute(&self.pool())
+                .await?
+                .rows_affected();
+            if replaced == 0 {
+                let holder = self.current_lock().await?.map(|l| l.pid).unwrap_or(0);
+                return Err(QueueError::DaemonRunning { pid: holder }.into());
+            }
+
+            Ok(live.then_some(existing))
+        })
+    }
+
+    fn release_daemon_lock(&self, pid: u32) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("DELETE FROM daemon_lock WHERE id = 1 AND pid = ?")
+                .bind(pid as i64)
+                .execute(&self.pool())
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn add_context_files<'a>(&'a self, id: &'a str, paths: &'a [PathBuf]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut tx = self.pool().begin().await?;
+
+            let start: i64 = sqlx::query_scalar(
+                "SELECT COALESCE(MAX(position) + 1, 0) FROM task_context_files WHERE task_id = ?"
+            )
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            for (offset, path) in paths.iter().enumerate() {
+                sqlx::query("INSERT INTO task_context_files (task_id, position, path) VALUES (?, ?, ?)")
+                    .bind(id)
+                    .bind(start + offset as i64)
+                    .bind(path.to_string_lossy().to_string())
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+
+    fn context_files<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Vec<PathBuf>>> {
+        Box::pin(async move {
+            let paths: Vec<String> = sqlx::query_scalar(
+                "SELECT path FROM task_context_files WHERE task_id = ? ORDER BY position"
+            )
+            .bind(id)
+            .fetch_all(&self.pool())
+            .await?;
+
+            Ok(paths.into_iter().map(Into::into).collect())
+        })
+    }
+
+    fn start_attempt<'a>(&'a self, id: &'a str, model: Option<&'a str>) -> BoxFuture<'a, Result<u32>> {
+        Box::pin(async move {
+            let attempt_no: i64 = sqlx::query_scalar(
+                r#"
+                INSERT INTO task_attempts (task_id, attempt_no, model, started_at, status)
+                SELECT ?, COALESCE(MAX(attempt_no), 0) + 1, ?, ?, ?
+                FROM task_attempts WHERE task_id = ?
+                RETURNING attempt_no
+                "#
+            )
+            .bind(id)
+            .bind(model)
+            .bind(self.clock.now().to_rfc3339())
+            .bind(TaskStatus::Running.as_str())
+            .bind(id)
+            .fetch_one(&self.pool())
+            .await?;
+
+            Ok(attempt_no as u32)
+        })
+    }
+
+    fn finish_attempt<'a>(
+        &'a self,
+        id: &'a str,
+        attempt_no: u32,
+        status: TaskStatus,
+        error: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            sqlx::query(
+                "UPDATE task_attempts SET status = ?, completed_at = ?, error = ? \
+                 WHERE task_id = ? AND attempt_no = ?"
+            )
+            .bind(status.as_str())
+            .bind(self.clock.now().to_rfc3339())
+            .bind(error)
+            .bind(id)
+            .bind(attempt_no as i64)
+            .execute(&self.pool())
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn attempts<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Vec<TaskAttempt>>> {
+        Box::pin(async move {
+            let rows = sqlx::query(
+                "SELECT task_id, attempt_no, model, started_at, completed_at, status, error \
+                 FROM task_attempts WHERE task_id = ? ORDER BY attempt_no"
+            )
+            .bind(id)
+            .fetch_all(&self.pool())
+            .await?;
+
+            rows.iter().map(attempt_from_row).collect()
+        })
+    }
+
+    fn clear_completed(&self) -> BoxFuture<'_, Result<u64>> {
+        Box::pin(async move {
+            let mut tx = self.pool().begin().await?;
+
+            for table in ["task_attempts", "task_context_files"] {
+                sqlx::query(&format!(
+                    "DELETE FROM {} WHERE task_id IN (SELECT id FROM tasks WHERE status IN (?, ?))",
+                    table
+                ))
+                .bind(TaskStatus::Completed.as_str())
+                .bind(TaskStatus::Cancelled.as_str())
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            let result = sqlx::query(
+                "DELETE FROM tasks WHERE status IN (?, ?)"
+            )
+            .bind(TaskStatus::Completed.as_str())
+            .bind(TaskStatus::Cancelled.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+            Ok(result.rows_affected())
+        })
+    }
+
+    /// Empty the task tables in one transaction; the daemon lock is left alone
+    fn reset(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let mut tx = self.pool().begin().await?;
+            for table in ["task_attempts", "task_context_files", "tasks"] {
+                sqlx::query(&format!("DELETE FROM {}", table)).execute(&mut *tx).await?;
+            }
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+
+    /// `VACUUM`, then `PRAGMA optimize` and a WAL checkpoint so the file shrinks now
+    ///
+    /// VACUUM needs the database to itself: it fails with SQLITE_BUSY while
+    /// another connection, such as a running daemon's, is mid-transaction.
+    fn vacuum(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("VACUUM").execute(&self.pool()).await?;
+            sqlx::query("PRAGMA optimize").execute(&self.pool()).await?;
+            if self.db_path != ":memory:" {
+                sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&self.pool()).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn ping(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("SELECT 1").execute(&self.pool()).await?;
+            Ok(())
+        })
+    }
+
+    /// Checkpoint the WAL into the main database file and close the pool
+    ///
+    /// Dropping a store without closing it is safe, since SQLite replays the
+    /// WAL on next open, but it can leave `-wal`/`-shm` files behind. Clones
+    /// share the pool, so closing one closes them all.
+    /// An in-memory database is left open, since closing would wipe it for
+    /// every clone; it goes away with the last handle
+    fn close(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            if self.db_path == ":memory:" {
+                return Ok(());
+            }
+            sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                .execute(&self.pool())
+                .await?;
+            self.pool().close().await;
+            Ok(())
+        })
+    }
+
+    fn reconnect(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            self.pool().close().await;
+            let pool = Self::connect(&self.db_path, self.busy_timeout).await?;
+            *self.pool.write().unwrap() = pool;
+            self.init_schema().await
+        })
+    }
+
+    fn backing_file_missing(&self) -> bool {
+        self.db_path != ":memory:" && !std::path::Path::new(&self.db_path).exists()
+    }
+
+    /// SQLITE_BUSY or SQLITE_LOCKED, including their extended codes
+    fn is_transient(&self, err: &anyhow::Error) -> bool {
+        err.chain().any(|cause| match cause.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::Database(db)) => {
+                let primary = db.code().and_then(|code| code.parse::<i32>().ok()).map(|code| code & 0xff);
+                matches!(primary, Some(5 | 6))
+            }
+            _ => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::TaskQueue;
+
+    #[tokio::test]
+    async fn test_schema_migrates_old_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.db");
+        let path = path.to_string_lossy();
+
+        // Database created before rendered_prompt existed
+        let pool = SqlitePool::connect(&format!("sqlite://{}?mode=rwc", path)).await.unwrap();
+        sqlx::query(
+            "CREATE TABLE tasks (id TEXT PRIMARY KEY, command TEXT NOT NULL, status TEXT NOT NULL, \
+             model TEXT, created_at TEXT NOT NULL, started_at TEXT, completed_at TEXT, error TEXT)"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+
+        let queue = TaskQueue::new(&path).await.unwrap();
+        let id = queue.enqueue("Run tests", None).await.unwrap();
+        queue.set_rendered_prompt(&id, "Run tests").await.unwrap();
+        let task = queue.get(&id).await.unwrap().unwrap();
+        assert_eq!(task.rendered_prompt.as_deref(), Some("Run tests"));
+    }
+
+    #[tokio::test]
+    async fn test_close_checkpoints_wal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.db");
+        let path_str = path.to_string_lossy().to_string();
+        let wal = dir.path().join("queue.db-wal");
+
+        let store = SqliteStore::new(&path_str).await.unwrap();
+        let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&store.pool())
+            .await
+            .unwrap();
+        assert_eq!(mode, "wal");
+
+        let queue = TaskQueue::with_store(Box::new(store));
+        let id = queue.enqueue("Durable", None).await.unwrap();
+        assert!(std::fs::metadata(&wal).unwrap().len() > 0);
+
+        queue.close().await.unwrap();
+        let wal_len = std::fs::metadata(&wal).map(|m| m.len()).unwrap_or(0);
+        assert_eq!(wal_len, 0);
+
+        let reopened = TaskQueue::new(&path_str).await.unwrap();
+        assert_eq!(reopened.get(&id).await.unwrap().unwrap().command, "Durable");
+    }
+
+    #[tokio::test]
+    async fn test_identical_timestamps_order_by_seq() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let queue = TaskQueue::with_store(Box::new(store.clone()));
+
+        let mut ids = Vec::new();
+        for i in 0..20 {
+            ids.push(queue.enqueue(&format!("Task {}", i), None).await.unwrap());
+        }
+
+        // Simulate every task landing in the same instant
+        sqlx::query("UPDATE tasks SET created_at = ?")
+            .bind(Utc::now().to_rfc3339())
+            .execute(&store.pool())
+            .await
+            .unwrap();
+
+        let listed: Vec<_> = queue.list().await.unwrap().into_iter().map(|t| t.id).collect();
+        let mut newest_first = ids.clone();
+        newest_first.reverse();
+        assert_eq!(listed, newest_first);
+
+        assert_eq!(queue.dequeue().await.unwrap().unwrap().id, ids[0]);
+        for id in &ids {
+            assert_eq!(&queue.claim_next().await.unwrap().unwrap().id, id);
+        }
+    }
+
+    /// Queue with tasks of varied status and age, returning (command, id) pairs
+    async fn seeded_queue() -> (SqliteStore, Vec<(&'static str, String)>) {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let seeds = [
+            ("old failed", TaskStatus::Failed, 180),
+            ("old completed", TaskStatus::Completed, 120),
+            ("recent failed", TaskStatus::Failed, 30),
+            ("recent completed", TaskStatus::Completed, 20),
+            ("recent queued", TaskStatus::Queued, 5),
+        ];
+
+        let mut ids = Vec::new();
+        for (command, status, minutes_ago) in seeds {
+            let id = store.insert(NewTask { status, ..NewTask::new(command) }, 0).await.unwrap();
+            // Legacy-style timestamp, to check mixed formats still compare by time
+            sqlx::query("UPDATE tasks SET created_at = ? WHERE id = ?")
+                .bind((Utc::now() - chrono::Duration::minutes(minutes_ago)).to_rfc3339())
+                .bind(&id)
+                .execute(&store.pool())
+                .await
+                .unwrap();
+            ids.push((command, id));
+        }
+        (store, ids)
+    }
+
+    async fn commands(store: &SqliteStore, query: ListQuery) -> Vec<String> {
+        store.query(&query).await.unwrap().into_iter().map(|t| t.command).collect()
+    }
+
+    #[tokio::test]
+    async fn test_query_by_status() {
+        let (store, _) = seeded_queue().await;
+
+        let failed = commands(&store, ListQuery::default().with_status(TaskStatus::Failed)).await;
+        assert_eq!(failed, vec!["recent failed", "old failed"]);
+
+        assert!(commands(&store, ListQuery::default().with_status(TaskStatus::Running)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_query_since() {
+        let (store, _) = seeded_queue().await;
+
+        let since = Utc::now() - chrono::Duration::hours(1);
+        let recent = commands(&store, ListQuery::default().with_since(since)).await;
+        assert_eq!(recent, vec!["recent queued", "recent completed", "recent failed"]);
+
+        let all = commands(&store, ListQuery::default()).await;
+        assert_eq!(all.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_query_filters_combine() {
+        let (store, ids) = seeded_queue().await;
+        let since = Utc::now() - chrono::Duration::hours(1);
+
+        let query = ListQuery::default().with_status(TaskStatus::Failed).with_since(since);
+        let tasks = store.query(&query).await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, ids[2].1);
+
+        let active = ListQuery::default().with_active_only(true).with_since(since);
+        assert_eq!(commands(&store, active).await, vec!["recent queued", "recent failed"]);
+
+        let active = ListQuery::default().with_active_only(true);
+        assert_eq!(commands(&store, active).await, vec!["recent queued", "recent failed", "old failed"]);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_rows_are_errors_not_panics() {
+        let (store, ids) = seeded_queue().await;
+        let (bad_status, bad_time) = (&ids[0].1, &ids[3].1);
+        sqlx::query("UPDATE tasks SET status = 'exploded' WHERE id = ?")
+            .bind(bad_status)
+            .execute(&store.pool())
+            .await
+            .unwrap();
+        sqlx::query("UPDATE tasks SET created_at = 'last tuesday' WHERE id = ?")
+            .bind(bad_time)
+            .execute(&store.pool())
+            .await
+            .unwrap();
+
+        let err = store.get(bad_status).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<QueueError>(),
+            Some(&QueueError::CorruptRow { id: bad_status.clone(), field: "status" })
+        );
+        let err = store.get(bad_time).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<QueueError>(),
+            Some(&QueueError::CorruptRow { id: bad_time.clone(), field: "created_at" })
+        );
+        assert!(store.list().await.is_err());
+        assert!(store.query(&ListQuery::default()).await.is_err());
+
+        // Skipping drops just the two bad rows
+        let skipping = ListQuery::default().with_skip_corrupt(true);
+        assert_eq!(commands(&store, skipping).await, vec!["recent queued", "recent failed", "old completed"]);
+    }
+}
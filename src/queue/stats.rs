@@ -0,0 +1,142 @@
+//! Task counts and latency percentiles for `faster stats`
+
+use std::time::Duration;
+
+use super::db::{Task, TaskStatus};
+
+/// Spread of one kind of latency over a set of tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Tasks the durations were taken from
+    pub count: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    /// Percentiles of `durations`, or None when there are none
+    pub fn from_durations(mut durations: Vec<Duration>) -> Option<Self> {
+        durations.sort();
+        Some(Self {
+            count: durations.len(),
+            p50: percentile(&durations, 50.0)?,
+            p95: percentile(&durations, 95.0)?,
+            max: *durations.last()?,
+        })
+    }
+}
+
+/// Nearest-rank percentile of already sorted durations
+fn percentile(sorted: &[Duration], p: f64) -> Option<Duration> {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.saturating_sub(1)).copied()
+}
+
+/// What a set of tasks looks like: how many are in each status and how long they took
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueStats {
+    pub total: usize,
+    /// Statuses with at least one task, in lifecycle order
+    pub by_status: Vec<(TaskStatus, usize)>,
+    /// Time from enqueue to start, for tasks that started
+    pub wait: Option<LatencyStats>,
+    /// Time from start to finish, for tasks that finished
+    pub execution: Option<LatencyStats>,
+}
+
+impl QueueStats {
+    /// Tasks missing a timestamp a latency needs are left out of that latency
+    pub fn from_tasks(tasks: &[Task]) -> Self {
+        let by_status = TaskStatus::all()
+            .iter()
+            .map(|&status| (status, tasks.iter().filter(|t| t.status == status).count()))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+
+        // A negative span (the clock moved back) isn't a latency
+        let wait = tasks
+            .iter()
+            .filter_map(|t| (t.started_at? - t.created_at).to_std().ok())
+            .collect();
+        let execution = tasks
+            .iter()
+            .filter_map(|t| (t.completed_at? - t.started_at?).to_std().ok())
+            .collect();
+
+        Self {
+            total: tasks.len(),
+            by_status,
+            wait: LatencyStats::from_durations(wait),
+            execution: LatencyStats::from_durations(execution),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, MockClock};
+    use crate::queue::TaskQueue;
+
+    fn secs(secs: &[u64]) -> Vec<Duration> {
+        secs.iter().map(|&s| Duration::from_secs(s)).collect()
+    }
+
+    #[test]
+    fn test_percentiles_use_nearest_rank() {
+        let stats = LatencyStats::from_durations(secs(&[7, 1, 10, 3, 2, 9, 4, 6, 5, 8])).unwrap();
+        assert_eq!(stats.count, 10);
+        assert_eq!(stats.p50, Duration::from_secs(5));
+        assert_eq!(stats.p95, Duration::from_secs(10));
+        assert_eq!(stats.max, Duration::from_secs(10));
+
+        let one = LatencyStats::from_durations(secs(&[4])).unwrap();
+        assert_eq!((one.p50, one.p95, one.max), (Duration::from_secs(4), Duration::from_secs(4), Duration::from_secs(4)));
+        assert_eq!(LatencyStats::from_durations(Vec::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_stats_from_seeded_tasks() {
+        let clock = MockClock::new(chrono::Utc::now());
+        let queue = TaskQueue::new(":memory:").await.unwrap().with_clock(clock.clone());
+
+        // (wait, run) in seconds
+        for (wait, run) in [(10, 100), (20, 200), (30, 300), (40, 400)] {
+            let id = queue.enqueue("Timed task", None).await.unwrap();
+            clock.advance(chrono::Duration::seconds(wait));
+            queue.update_status(&id, TaskStatus::Running).await.unwrap();
+            clock.advance(chrono::Duration::seconds(run));
+            queue.update_status(&id, TaskStatus::Completed).await.unwrap();
+        }
+        // Started but unfinished counts toward wait only; never started counts toward neither
+        let running = queue.enqueue("Still running", None).await.unwrap();
+        clock.advance(chrono::Duration::seconds(50));
+        queue.update_status(&running, TaskStatus::Running).await.unwrap();
+        queue.enqueue("Still queued", None).await.unwrap();
+
+        let stats = queue.stats(None).await.unwrap();
+        assert_eq!(stats.total, 6);
+        assert_eq!(
+            stats.by_status,
+            vec![(TaskStatus::Queued, 1), (TaskStatus::Running, 1), (TaskStatus::Completed, 4)]
+        );
+
+        let wait = stats.wait.unwrap();
+        assert_eq!(wait.count, 5);
+        assert_eq!(wait.p50, Duration::from_secs(30));
+        assert_eq!(wait.p95, Duration::from_secs(50));
+        assert_eq!(wait.max, Duration::from_secs(50));
+
+        let execution = stats.execution.unwrap();
+        assert_eq!(execution.count, 4);
+        assert_eq!(execution.p50, Duration::from_secs(200));
+        assert_eq!(execution.p95, Duration::from_secs(400));
+        assert_eq!(execution.max, Duration::from_secs(400));
+
+        // The window only counts tasks created since its start
+        let recent = queue.stats(Some(clock.now())).await.unwrap();
+        assert_eq!(recent.total, 1);
+        assert_eq!((recent.wait, recent.execution), (None, None));
+    }
+}
@@ -0,0 +1,355 @@
+//! Storage backend abstraction for the task queue
+//!
+//! `TaskQueue` owns queue policy (normalization, caps, output truncation) and
+//! delegates persistence to a `TaskStore`. `SqliteStore` is the only backend
+//! today; a shared backend such as Postgres would implement this trait behind
+//! its own cargo feature.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::db::{DaemonLock, Task, TaskAttempt, TaskStatus};
+use super::snapshot::EnvSnapshot;
+use crate::clock::Clock;
+use crate::intent::Intent;
+
+/// A task about to be inserted, already normalized by the queue
+#[derive(Debug, Clone)]
+pub struct NewTask {
+    pub command: String,
+    /// Command as submitted, when normalizing or a directive template changed it
+    pub original_command: Option<String>,
+    pub model: Option<String>,
+    pub intent: Option<Intent>,
+    pub confidence: Option<f32>,
+    pub status: TaskStatus,
+    pub priority: i32,
+    pub tags: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    /// Task that must complete before this one is claimed
+    pub depends_on: Option<String>,
+    /// Task whose Claude session this one continues
+    pub parent_id: Option<String>,
+    /// Files whose contents are appended to the prompt, in order
+    pub context_files: Vec<PathBuf>,
+    /// Where the task was enqueued from
+    pub env_snapshot: Option<EnvSnapshot>,
+    /// Run time limit overriding the global one, in milliseconds
+    pub timeout_ms: Option<u64>,
+    /// Task this one re-runs verbatim
+    pub replayed_from: Option<String>,
+    /// Opaque key/value pairs for the caller's own bookkeeping
+    pub metadata: BTreeMap<String, String>,
+    /// Overrides for `claude.prompt_prefix` and `claude.prompt_suffix`
+    pub prompt_prefix: Option<String>,
+    pub prompt_suffix: Option<String>,
+}
+
+impl NewTask {
+    /// A plain queued task with every optional field unset
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            original_command: None,
+            model: None,
+            intent: None,
+            confidence: None,
+            status: TaskStatus::Queued,
+            priority: 0,
+            tags: Vec::new(),
+            cwd: None,
+            scheduled_for: None,
+            depends_on: None,
+            parent_id: None,
+            context_files: Vec::new(),
+            env_snapshot: None,
+            timeout_ms: None,
+            replayed_from: None,
+            metadata: BTreeMap::new(),
+            prompt_prefix: None,
+            prompt_suffix: None,
+        }
+    }
+}
+
+/// Filters for `TaskStore::query`, ANDed together; unset fields match everything
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListQuery {
+    /// Only tasks in this status
+    pub status: Option<TaskStatus>,
+    /// Only tasks created at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Leave out completed and cancelled tasks
+    pub active_only: bool,
+    /// Drop rows that can't be read, logging each, instead of failing
+    pub skip_corrupt: bool,
+}
+
+impl ListQuery {
+    pub fn with_status(mut self, status: TaskStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn with_active_only(mut self, active_only: bool) -> Self {
+        self.active_only = active_only;
+        self
+    }
+
+    pub fn with_skip_corrupt(mut self, skip_corrupt: bool) -> Self {
+        self.skip_corrupt = skip_corrupt;
+        self
+    }
+}
+
+/// Persistence operations behind `TaskQueue`
+///
+/// Methods return boxed futures so the trait stays object-safe.
+pub trait TaskStore: Send + Sync {
+    /// Clone into a new handle sharing the same underlying storage
+    fn clone_box(&self) -> Box<dyn TaskStore>;
+
+    /// Take the time from `clock` for timestamps and schedule checks
+    fn set_clock(&mut self, clock: Arc<dyn Clock>);
+
+    /// Insert a task, returning its id
+    ///
+    /// Fails with `QueueError::QueueFull` if `max_pending` (0 = unbounded)
+    /// tasks are already queued. The check and insert must be atomic.
+    fn insert<'a>(&'a self, task: NewTask, max_pending: u64) -> BoxFuture<'a, Result<String>>;
+
+    /// Insert every task or none of them, returning ids in order
+    ///
+    /// `max_pending` applies as in `insert`, but only to queued tasks; if
+    /// any insert fails, those before it are rolled back.
+    fn insert_all<'a>(&'a self, tasks: Vec<NewTask>, max_pending: u64) -> BoxFuture<'a, Result<Vec<String>>>;
+
+    /// Move a draft to the queue, optionally with a new intent; false if not a draft
+    ///
+    /// `max_pending` applies as in `insert`, checked in the same statement.
+    fn promote<'a>(&'a self, id: &'a str, intent: Option<Intent>, max_pending: u64) -> BoxFuture<'a, Result<bool>>;
+
+    /// Next due queued task, without claiming it
+    fn dequeue(&self) -> BoxFuture<'_, Result<Option<Task>>>;
+
+    /// Atomically take the next due queued task, marking it running
+    ///
+    /// Higher priority goes first, then oldest; tasks scheduled in the future are skipped.
+    /// `processed_by`, when given, is recorded as the claimer.
+    fn claim_next<'a>(&'a self, processed_by: Option<&'a str>) -> BoxFuture<'a, Result<Option<Task>>>;
+
+    /// Like `claim_next`, but only considering the tasks in `ids`
+    fn claim_next_among<'a>(
+        &'a self,
+        ids: &'a [String],
+        processed_by: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Option<Task>>>;
+
+    /// Set a task's status if it's currently one of `from`, returning whether it was
+    ///
+    /// Checked and written in one statement, stamping started/completed times as appropriate.
+    fn update_status<'a>(&'a self, id: &'a str, from: &'a [TaskStatus], to: TaskStatus) -> BoxFuture<'a, Result<bool>>;
+
+    /// Move every task in `from` to `to`, returning how many moved
+    fn update_status_where(&self, from: TaskStatus, to: TaskStatus) -> BoxFuture<'_, Result<u64>>;
+
+    /// Requeue running tasks claimed by `processed_by`, or started more than
+    /// `max_age` ago, returning how many
+    fn requeue_stale<'a>(
+        &'a self,
+        processed_by: &'a str,
+        max_age: Option<std::time::Duration>,
+    ) -> BoxFuture<'a, Result<u64>>;
+
+    /// Hold queued tasks whose dependency, direct or further up the chain,
+    /// failed or was cancelled, recording why; returns how many
+    fn block_orphaned_dependents(&self) -> BoxFuture<'_, Result<u64>>;
+
+    /// Cancel every queued and draft task in one statement, returning how many
+    ///
+    /// With `matching`, only tasks whose command contains it, ignoring ASCII case.
+    fn cancel_queued<'a>(&'a self, matching: Option<&'a str>) -> BoxFuture<'a, Result<u64>>;
+
+    /// How many tasks `cancel_queued` would cancel, matching the same way
+    fn count_queued<'a>(&'a self, matching: Option<&'a str>) -> BoxFuture<'a, Result<u64>>;
+
+    /// Mark a running task failed with an error message, returning whether it was running
+    fn fail<'a>(&'a self, id: &'a str, error: &'a str) -> BoxFuture<'a, Result<bool>>;
+
+    /// All tasks, newest first
+    fn list(&self) -> BoxFuture<'_, Result<Vec<Task>>>;
+
+    /// Queued and running tasks only: running first, then queued in claim order
+    fn list_active(&self) -> BoxFuture<'_, Result<Vec<Task>>>;
+
+    /// Distinct commands, most recently enqueued first, at most `limit` (0 = all)
+    fn recent_commands(&self, limit: u64) -> BoxFuture<'_, Result<Vec<String>>>;
+
+    /// Tasks matching `query`, newest first, filtered by the backend
+    fn query<'a>(&'a self, query: &'a ListQuery) -> BoxFuture<'a, Result<Vec<Task>>>;
+
+    /// Task by exact id
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Option<Task>>>;
+
+    /// Task by exact id or unique id prefix; errors if ambiguous
+    fn get_by_prefix<'a>(&'a self, prefix: &'a str) -> BoxFuture<'a, Result<Option<Task>>>;
+
+    fn set_rendered_prompt<'a>(&'a self, id: &'a str, prompt: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Store output, then drop output from all but the `keep_last` most recent tasks (0 = all)
+    fn set_output<'a>(
+        &'a self,
+        id: &'a str,
+        output: &'a str,
+        lossy: bool,
+        keep_last: u64,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    fn set_run_stats<'a>(
+        &'a self,
+        id: &'a str,
+        duration_ms: u64,
+        tokens: Option<(u64, u64)>,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    fn set_output_path<'a>(&'a self, id: &'a str, path: &'a Path) -> BoxFuture<'a, Result<()>>;
+
+    fn set_parent<'a>(&'a self, id: &'a str, parent_id: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    fn set_timeout<'a>(&'a self, id: &'a str, timeout_ms: Option<u64>) -> BoxFuture<'a, Result<()>>;
+
+    fn set_session_id<'a>(&'a self, id: &'a str, session_id: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Current daemon lock holder, if any
+    fn daemon_lock(&self) -> BoxFuture<'_, Result<Option<DaemonLock>>>;
+
+    /// Claim the daemon lock; see `TaskQueue::acquire_daemon_lock`
+    fn acquire_daemon_lock<'a>(
+        &'a self,
+        pid: u32,
+        force: bool,
+        is_alive: &'a (dyn Fn(u32) -> bool + Send + Sync),
+    ) -> BoxFuture<'a, Result<Option<DaemonLock>>>;
+
+    /// Release the daemon lock if `pid` still holds it
+    fn release_daemon_lock(&self, pid: u32) -> BoxFuture<'_, Result<()>>;
+
+    fn add_context_files<'a>(&'a self, id: &'a str, paths: &'a [PathBuf]) -> BoxFuture<'a, Result<()>>;
+
+    fn context_files<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Vec<PathBuf>>>;
+
+    /// Append a running attempt numbered one past the task's last, returning its number
+    fn start_attempt<'a>(&'a self, id: &'a str, model: Option<&'a str>) -> BoxFuture<'a, Result<u32>>;
+
+    /// Stamp an attempt's completion time, final status and error
+    fn finish_attempt<'a>(
+        &'a self,
+        id: &'a str,
+        attempt_no: u32,
+        status: TaskStatus,
+        error: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// A task's attempts, oldest first
+    fn attempts<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Vec<TaskAttempt>>>;
+
+    /// Delete completed and cancelled tasks, returning how many were removed
+    fn clear_completed(&self) -> BoxFuture<'_, Result<u64>>;
+
+    /// Delete every task with its attempts and context files, all or nothing
+    fn reset(&self) -> BoxFuture<'_, Result<()>>;
+
+    /// Rebuild storage to give space freed by deletions back to the system
+    fn vacuum(&self) -> BoxFuture<'_, Result<()>>;
+
+    /// Check the backend is reachable
+    fn ping(&self) -> BoxFuture<'_, Result<()>>;
+
+    /// Flush pending writes and release connections
+    fn close(&self) -> BoxFuture<'_, Result<()>>;
+
+    /// Drop connections and open fresh ones, recreating the schema if needed
+    ///
+    /// Clones share the connections, so every clone picks up the fresh ones.
+    fn reconnect(&mut self) -> BoxFuture<'_, Result<()>>;
+
+    /// Whether the backing storage has disappeared (e.g. a deleted file)
+    fn backing_file_missing(&self) -> bool {
+        false
+    }
+
+    /// Whether `err` is a momentary conflict with another writer, worth retrying
+    fn is_transient(&self, _err: &anyhow::Error) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::SqliteStore;
+
+    async fn store() -> Box<dyn TaskStore> {
+        Box::new(SqliteStore::new(":memory:").await.unwrap())
+    }
+
+    fn new_task(command: &str, status: TaskStatus) -> NewTask {
+        NewTask { status, ..NewTask::new(command) }
+    }
+
+    #[tokio::test]
+    async fn test_trait_object_insert_and_claim() {
+        let store = store().await;
+
+        let first = store.insert(new_task("First", TaskStatus::Queued), 0).await.unwrap();
+        store.insert(new_task("Second", TaskStatus::Queued), 0).await.unwrap();
+
+        let claimed = store.claim_next(None).await.unwrap().unwrap();
+        assert_eq!(claimed.id, first);
+        assert_eq!(claimed.status, TaskStatus::Running);
+
+        assert!(store.update_status(&first, &[TaskStatus::Running], TaskStatus::Completed).await.unwrap());
+        assert!(!store.update_status(&first, &[TaskStatus::Running], TaskStatus::Failed).await.unwrap());
+        let task = store.get(&first).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert!(task.completed_at.is_some());
+
+        let commands: Vec<_> = store.list().await.unwrap().into_iter().map(|t| t.command).collect();
+        assert_eq!(commands, vec!["Second", "First"]);
+    }
+
+    #[tokio::test]
+    async fn test_trait_object_cap_and_drafts() {
+        let store = store().await;
+
+        store.insert(new_task("One", TaskStatus::Queued), 1).await.unwrap();
+        assert!(store.insert(new_task("Two", TaskStatus::Queued), 1).await.is_err());
+
+        // Drafts are inserted with no cap and never claimed
+        let draft = store.insert(new_task("Maybe", TaskStatus::Draft), 0).await.unwrap();
+        store.claim_next(None).await.unwrap();
+        assert!(store.claim_next(None).await.unwrap().is_none());
+
+        assert!(store.promote(&draft, None, 0).await.unwrap());
+        assert_eq!(store.claim_next(None).await.unwrap().unwrap().id, draft);
+    }
+
+    #[tokio::test]
+    async fn test_clone_box_shares_storage() {
+        let store = store().await;
+        let clone = store.clone_box();
+
+        let id = store.insert(new_task("Shared", TaskStatus::Queued), 0).await.unwrap();
+        assert_eq!(clone.get(&id).await.unwrap().unwrap().command, "Shared");
+    }
+}
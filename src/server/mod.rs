@@ -0,0 +1,335 @@
+//! Minimal HTTP server for daemon health probes and queue events
+//!
+//! `GET /livez` answers as long as the process runs. `GET /healthz` pings the
+//! queue database and checks the daemon loop ticked recently. `GET /events`
+//! streams task transitions as server-sent events.
+
+mod sse;
+
+pub use sse::{EventHub, NumberedEvent};
+
+use anyhow::Result;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::daemon::Heartbeat;
+use crate::queue::TaskQueue;
+
+/// Largest request head we read before giving up
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// What the health endpoints inspect
+pub struct HealthState {
+    pub queue: TaskQueue,
+    pub heartbeat: Heartbeat,
+    /// Tick age after which the daemon counts as stuck
+    pub max_tick_age: Duration,
+    /// Transitions streamed from `/events`
+    pub events: EventHub,
+}
+
+/// HTTP response status and JSON body
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+impl Response {
+    fn ok(body: serde_json::Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn unavailable(reason: impl Into<String>) -> Self {
+        Self {
+            status: 503,
+            body: json!({ "status": "unhealthy", "reason": reason.into() }),
+        }
+    }
+
+    fn not_found() -> Self {
+        Self {
+            status: 404,
+            body: json!({ "error": "not found" }),
+        }
+    }
+
+    fn reason_phrase(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            404 => "Not Found",
+            503 => "Service Unavailable",
+            _ => "",
+        }
+    }
+}
+
+impl HealthState {
+    /// Readiness: database reachable and the loop ticked recently
+    pub async fn readiness(&self) -> Response {
+        if let Err(e) = self.queue.ping().await {
+            return Response::unavailable(format!("database unreachable: {}", e));
+        }
+
+        let Some(age) = self.heartbeat.age() else {
+            return Response::unavailable("daemon loop has not ticked yet");
+        };
+        if age > self.max_tick_age {
+            return Response::unavailable(format!(
+                "daemon loop last ticked {}s ago",
+                age.as_secs()
+            ));
+        }
+
+        Response::ok(json!({
+            "status": "ok",
+            "database": "ok",
+            "last_tick_age_ms": age.as_millis() as u64,
+        }))
+    }
+
+    /// Route a request to its handler
+    pub async fn handle(&self, method: &str, path: &str) -> Response {
+        match (method, path) {
+            ("GET", "/livez") => Response::ok(json!({ "status": "ok" })),
+            ("GET", "/healthz") => self.readiness().await,
+            _ => Response::not_found(),
+        }
+    }
+}
+
+/// Accept connections forever, answering each on its own task
+pub async fn serve(listener: TcpListener, state: Arc<HealthState>) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &state).await {
+                tracing::debug!("Health connection error: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: &HealthState) -> Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 || buf.len() > MAX_REQUEST_BYTES {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&buf);
+    let mut lines = head.lines();
+    let mut parts = lines.next().unwrap_or("").split_whitespace();
+    let method = parts.next().unwrap_or("");
+    // Ignore any query string
+    let path = parts.next().unwrap_or("").split('?').next().unwrap_or("");
+
+    if (method, path) == ("GET", "/events") {
+        let last_id = lines
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("last-event-id"))
+            .and_then(|(_, value)| value.trim().parse().ok());
+        return sse::stream_events(stream, &state.events, last_id).await;
+    }
+
+    let response = state.handle(method, path).await;
+    let body = response.body.to_string();
+    let reply = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.reason_phrase(),
+        body.len(),
+        body
+    );
+    stream.write_all(reply.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn start(state: HealthState) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(state)));
+        addr
+    }
+
+    async fn get(addr: std::net::SocketAddr, path: &str) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+            .await
+            .unwrap();
+
+        let mut raw = String::new();
+        stream.read_to_string(&mut raw).await.unwrap();
+
+        let status = raw.split_whitespace().nth(1).unwrap().parse().unwrap();
+        let body = raw.split("\r\n\r\n").nth(1).unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn state(queue: TaskQueue) -> HealthState {
+        HealthState {
+            queue,
+            heartbeat: Heartbeat::default(),
+            max_tick_age: Duration::from_secs(30),
+            events: EventHub::default(),
+        }
+    }
+
+    /// Open `/events` and return the stream once the response head has arrived
+    async fn subscribe(addr: std::net::SocketAddr, last_id: Option<u64>) -> tokio::io::BufReader<TcpStream> {
+        use tokio::io::AsyncBufReadExt;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let resume = last_id.map(|id| format!("Last-Event-ID: {}\r\n", id)).unwrap_or_default();
+        stream
+            .write_all(format!("GET /events HTTP/1.1\r\nHost: localhost\r\n{}\r\n", resume).as_bytes())
+            .await
+            .unwrap();
+
+        let mut reader = tokio::io::BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert!(line.starts_with("HTTP/1.1 200"), "{}", line);
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).await.unwrap();
+            assert!(!line.is_empty(), "stream closed in the head");
+        }
+        reader
+    }
+
+    /// The id and JSON of the next event on an SSE stream
+    async fn next_event(reader: &mut tokio::io::BufReader<TcpStream>) -> (u64, serde_json::Value) {
+        use tokio::io::AsyncBufReadExt;
+
+        let (mut id, mut line) = (None, String::new());
+        loop {
+            line.clear();
+            reader.read_line(&mut line).await.unwrap();
+            if let Some(n) = line.strip_prefix("id: ") {
+                id = Some(n.trim().parse().unwrap());
+            } else if let Some(data) = line.strip_prefix("data: ") {
+                return (id.unwrap(), serde_json::from_str(data).unwrap());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_stream_transitions() {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let state = state(queue.clone());
+        let hub = state.events.clone();
+        let addr = start(state).await;
+        let source = crate::queue::EventSource::start(&queue, false).await.unwrap();
+
+        let client = async {
+            let mut reader = subscribe(addr, None).await;
+            let id = queue.enqueue("Run tests", None).await.unwrap();
+            let (n, event) = next_event(&mut reader).await;
+            assert_eq!((n, event["task_id"].as_str()), (1, Some(id.as_str())));
+            assert_eq!(event["status"], "queued");
+
+            queue.claim_next().await.unwrap();
+            let (n, event) = next_event(&mut reader).await;
+            assert_eq!((n, event["status"].as_str()), (2, Some("running")));
+
+            // A client reconnecting after the first event gets the second again
+            let mut resumed = subscribe(addr, Some(1)).await;
+            assert_eq!(next_event(&mut resumed).await, (n, event));
+        };
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::select! {
+                _ = client => {}
+                result = hub.publish_from(source, Duration::from_millis(5)) => panic!("publisher stopped: {:?}", result),
+            }
+        })
+        .await
+        .expect("no event within 5s");
+    }
+
+    #[tokio::test]
+    async fn test_healthy() {
+        let state = state(TaskQueue::new(":memory:").await.unwrap());
+        state.heartbeat.beat();
+        let addr = start(state).await;
+
+        let (status, body) = get(addr, "/healthz").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["status"], "ok");
+        assert!(body["last_tick_age_ms"].is_u64());
+
+        let (status, _) = get(addr, "/livez").await;
+        assert_eq!(status, 200);
+
+        let (status, _) = get(addr, "/nope").await;
+        assert_eq!(status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_database_down() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = TaskQueue::new(&dir.path().join("queue.db").to_string_lossy()).await.unwrap();
+        let state = state(queue.clone());
+        state.heartbeat.beat();
+        let addr = start(state).await;
+
+        queue.close().await.unwrap();
+
+        let (status, body) = get(addr, "/healthz").await;
+        assert_eq!(status, 503);
+        assert!(body["reason"].as_str().unwrap().contains("database unreachable"));
+
+        // Liveness doesn't depend on the database
+        let (status, _) = get(addr, "/livez").await;
+        assert_eq!(status, 200);
+    }
+
+    #[tokio::test]
+    async fn test_healthy_after_daemon_reconnects() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.db");
+        let mut daemon_queue = TaskQueue::new(&path.to_string_lossy()).await.unwrap();
+        let state = state(daemon_queue.clone());
+        state.heartbeat.beat();
+        let addr = start(state).await;
+
+        // The daemon's copy reconnects after the file vanished; the server's follows it
+        std::fs::remove_file(&path).unwrap();
+        daemon_queue.reconnect().await.unwrap();
+
+        let (status, body) = get(addr, "/healthz").await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_stale_or_missing_tick_is_unhealthy() {
+        let mut state = state(TaskQueue::new(":memory:").await.unwrap());
+
+        let response = state.readiness().await;
+        assert_eq!(response.status, 503);
+        assert!(response.body["reason"].as_str().unwrap().contains("not ticked"));
+
+        state.heartbeat.beat();
+        state.max_tick_age = Duration::ZERO;
+        std::thread::sleep(Duration::from_millis(5));
+        let response = state.readiness().await;
+        assert_eq!(response.status, 503);
+        assert!(response.body["reason"].as_str().unwrap().contains("last ticked"));
+    }
+}
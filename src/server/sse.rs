@@ -0,0 +1,189 @@
+//! Task transitions fanned out to `GET /events` subscribers
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::queue::{EventSource, TaskEvent};
+
+/// Events kept for clients reconnecting with `Last-Event-ID`
+const REPLAY_CAPACITY: usize = 256;
+
+/// Events a subscriber may fall behind by before it starts missing them
+const SUBSCRIBER_BUFFER: usize = 256;
+
+/// Comment sent to idle streams, so proxies keep them open and dead clients are noticed
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// A task event with its position in the stream, starting at 1
+pub type NumberedEvent = (u64, Arc<TaskEvent>);
+
+/// Broadcasts task events, remembering the latest few for replay
+///
+/// Publishing never waits on subscribers: one that falls more than
+/// `SUBSCRIBER_BUFFER` events behind skips ahead instead.
+#[derive(Clone)]
+pub struct EventHub {
+    sender: broadcast::Sender<NumberedEvent>,
+    recent: Arc<Mutex<Recent>>,
+}
+
+#[derive(Default)]
+struct Recent {
+    last_id: u64,
+    events: VecDeque<NumberedEvent>,
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(SUBSCRIBER_BUFFER).0,
+            recent: Arc::default(),
+        }
+    }
+}
+
+impl EventHub {
+    /// Number `event` and send it to every subscriber
+    pub fn publish(&self, event: TaskEvent) -> u64 {
+        let mut recent = self.recent.lock().unwrap();
+        recent.last_id += 1;
+        let numbered = (recent.last_id, Arc::new(event));
+        if recent.events.len() == REPLAY_CAPACITY {
+            recent.events.pop_front();
+        }
+        recent.events.push_back(numbered.clone());
+        // No subscribers isn't an error; the event is still kept for replay
+        let _ = self.sender.send(numbered);
+        recent.last_id
+    }
+
+    /// Events after `last_id` still remembered, and a receiver for the rest
+    ///
+    /// An id from beyond the latest event means the stream restarted since
+    /// the client last saw it, so everything remembered is replayed.
+    pub fn subscribe(&self, last_id: Option<u64>) -> (Vec<NumberedEvent>, broadcast::Receiver<NumberedEvent>) {
+        // Subscribing under the lock means nothing is both replayed and received
+        let recent = self.recent.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let replay = match last_id {
+            None => Vec::new(),
+            Some(id) if id > recent.last_id => recent.events.iter().cloned().collect(),
+            Some(id) => recent.events.iter().filter(|(n, _)| *n > id).cloned().collect(),
+        };
+        (replay, receiver)
+    }
+
+    /// Publish everything `source` reports, until the queue can't be read
+    pub async fn publish_from(&self, mut source: EventSource<'_>, poll_interval: Duration) -> Result<()> {
+        loop {
+            let event = source.next(poll_interval).await?;
+            self.publish(event);
+        }
+    }
+}
+
+/// One event in SSE framing: its id, then its JSON on a `data:` line
+pub fn format_event(id: u64, event: &TaskEvent) -> String {
+    format!("id: {}\ndata: {}\n\n", id, serde_json::to_string(event).unwrap_or_default())
+}
+
+/// Answer a `GET /events` request, streaming until the client goes away
+pub async fn stream_events(mut stream: TcpStream, hub: &EventHub, last_id: Option<u64>) -> Result<()> {
+    let (replay, mut receiver) = hub.subscribe(last_id);
+
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+              Connection: keep-alive\r\n\r\n",
+        )
+        .await?;
+    for (id, event) in &replay {
+        stream.write_all(format_event(*id, event).as_bytes()).await?;
+    }
+    stream.flush().await?;
+
+    loop {
+        let frame = tokio::select! {
+            received = receiver.recv() => match received {
+                Ok((id, event)) => format_event(id, &event),
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::debug!("SSE client fell {} events behind; skipping ahead", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            _ = tokio::time::sleep(KEEP_ALIVE) => ": keep-alive\n\n".to_string(),
+        };
+        stream.write_all(frame.as_bytes()).await?;
+        stream.flush().await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::TaskStatus;
+    use chrono::Utc;
+
+    fn event(task_id: &str) -> TaskEvent {
+        TaskEvent {
+            at: Utc::now(),
+            task_id: task_id.to_string(),
+            status: TaskStatus::Queued,
+            command: "Run tests".to_string(),
+            error: None,
+        }
+    }
+
+    fn ids(events: &[NumberedEvent]) -> Vec<u64> {
+        events.iter().map(|(id, _)| *id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_replays_after_last_id() {
+        let hub = EventHub::default();
+        for task in ["a", "b", "c"] {
+            hub.publish(event(task));
+        }
+
+        assert!(hub.subscribe(None).0.is_empty());
+        assert_eq!(ids(&hub.subscribe(Some(1)).0), vec![2, 3]);
+        assert!(hub.subscribe(Some(3)).0.is_empty());
+        // From before a restart, so everything remembered is new to the client
+        assert_eq!(ids(&hub.subscribe(Some(99)).0), vec![1, 2, 3]);
+
+        let (_, mut receiver) = hub.subscribe(Some(3));
+        hub.publish(event("d"));
+        let (id, received) = receiver.recv().await.unwrap();
+        assert_eq!((id, received.task_id.as_str()), (4, "d"));
+    }
+
+    #[test]
+    fn test_replay_is_bounded_and_publishing_never_waits() {
+        let hub = EventHub::default();
+        // A subscriber that never reads
+        let (_, _receiver) = hub.subscribe(None);
+        for i in 0..(REPLAY_CAPACITY + SUBSCRIBER_BUFFER) as u64 {
+            assert_eq!(hub.publish(event("a")), i + 1);
+        }
+
+        let replay = hub.subscribe(Some(0)).0;
+        assert_eq!(replay.len(), REPLAY_CAPACITY);
+        assert_eq!(replay[0].0, SUBSCRIBER_BUFFER as u64 + 1);
+    }
+
+    #[test]
+    fn test_format_event() {
+        let frame = format_event(7, &event("abc"));
+        let (id, data) = frame.strip_suffix("\n\n").unwrap().split_once('\n').unwrap();
+        assert_eq!(id, "id: 7");
+        let json: serde_json::Value = serde_json::from_str(data.strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(json["task_id"], "abc");
+        assert_eq!(json["status"], "queued");
+    }
+}
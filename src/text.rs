@@ -0,0 +1,29 @@
+//! Decoding bytes from external processes
+
+/// Decode `bytes` as UTF-8, replacing invalid sequences with U+FFFD
+///
+/// Valid text is kept as-is. The flag is true if anything was replaced.
+pub fn decode_output(bytes: &[u8]) -> (String, bool) {
+    match String::from_utf8_lossy(bytes) {
+        std::borrow::Cow::Borrowed(text) => (text.to_string(), false),
+        std::borrow::Cow::Owned(text) => (text, true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_output_valid() {
+        assert_eq!(decode_output("héllo ✓".as_bytes()), ("héllo ✓".to_string(), false));
+        assert_eq!(decode_output(b""), (String::new(), false));
+    }
+
+    #[test]
+    fn test_decode_output_keeps_valid_parts() {
+        let (text, lossy) = decode_output(b"ok \xff\xfe then more");
+        assert!(lossy);
+        assert_eq!(text, "ok \u{FFFD}\u{FFFD} then more");
+    }
+}
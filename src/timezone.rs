@@ -0,0 +1,257 @@
+//! Wall-clock times in the user's timezone, stored as UTC
+//!
+//! Schedules are entered and timestamps shown in `general.timezone`; the
+//! queue itself only ever holds UTC.
+
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDateTime, NaiveTime, Offset, SecondsFormat, TimeZone,
+    Timelike, Utc,
+};
+use std::str::FromStr;
+
+/// Naive date-time layouts accepted for a schedule
+const DATE_TIME_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"];
+
+/// Zone schedules are read in and times displayed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timezone {
+    /// The system zone, from `$TZ` or /etc/localtime
+    #[default]
+    Local,
+    Utc,
+    /// A fixed offset such as `+05:30`, with no DST
+    Fixed(FixedOffset),
+}
+
+impl FromStr for Timezone {
+    type Err = String;
+
+    /// `local`, `UTC`, or an offset like `+02:00`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "local" | "" => Ok(Timezone::Local),
+            "utc" | "z" | "gmt" => Ok(Timezone::Utc),
+            other => other
+                .parse::<FixedOffset>()
+                .map(Timezone::Fixed)
+                .map_err(|_| format!("unknown timezone '{}'", s)),
+        }
+    }
+}
+
+impl Timezone {
+    /// UTC instant of a wall-clock time in this zone; see `resolve_local`
+    pub fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            Timezone::Local => resolve_local(&Local, local),
+            Timezone::Utc => local.and_utc(),
+            Timezone::Fixed(offset) => resolve_local(offset, local),
+        }
+    }
+
+    /// Wall-clock time in this zone at `at`
+    pub fn to_local(&self, at: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Timezone::Local => at.with_timezone(&Local).naive_local(),
+            Timezone::Utc => at.naive_utc(),
+            Timezone::Fixed(offset) => at.with_timezone(offset).naive_local(),
+        }
+    }
+
+    /// `at` as RFC 3339 in this zone, to the second
+    pub fn format(&self, at: DateTime<Utc>) -> String {
+        match self {
+            Timezone::Local => at.with_timezone(&Local).to_rfc3339_opts(SecondsFormat::Secs, true),
+            Timezone::Utc => at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            Timezone::Fixed(offset) => at.with_timezone(offset).to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+
+    /// Parse when a task should run
+    ///
+    /// RFC 3339 with an offset is taken as is. `2026-03-08 09:00` is a
+    /// wall-clock time in this zone, and a bare time (`09:00`, `9am`) is
+    /// the next time the clock here reads that after `now`.
+    pub fn parse_schedule(&self, input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+        let input = input.trim();
+        if let Ok(at) = DateTime::parse_from_rfc3339(input) {
+            return Ok(at.with_timezone(&Utc));
+        }
+        if let Some(local) = DATE_TIME_FORMATS
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        {
+            return Ok(self.to_utc(local));
+        }
+
+        let time = parse_time_of_day(input)
+            .ok_or_else(|| format!("can't read '{}' as a time (try 2026-03-08 09:00 or 9am)", input))?;
+
+        let today = self.to_local(now).date();
+        let at = self.to_utc(today.and_time(time));
+        if at > now {
+            Ok(at)
+        } else {
+            Ok(self.to_utc((today + Duration::days(1)).and_time(time)))
+        }
+    }
+}
+
+/// A time of day: `09:00`, `21:30:15`, `9am` or `9:30pm`
+fn parse_time_of_day(input: &str) -> Option<NaiveTime> {
+    let input = input.to_ascii_lowercase();
+    let (clock, pm) = match (input.strip_suffix("am"), input.strip_suffix("pm")) {
+        (Some(clock), _) => (clock.trim(), Some(false)),
+        (_, Some(clock)) => (clock.trim(), Some(true)),
+        _ => (input.as_str(), None),
+    };
+    let clock = match (clock.contains(':'), pm) {
+        (true, _) => clock.to_string(),
+        (false, Some(_)) => format!("{}:00", clock),
+        (false, None) => return None,
+    };
+    let time = ["%H:%M:%S", "%H:%M"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(&clock, format).ok())?;
+
+    match pm {
+        None => Some(time),
+        Some(_) if !(1..=12).contains(&time.hour()) => None,
+        Some(pm) => time.with_hour(time.hour() % 12 + if pm { 12 } else { 0 }),
+    }
+}
+
+/// UTC instant of a wall-clock time in `tz`, across DST transitions
+///
+/// A time that happens twice when clocks go back means the first one. A
+/// time skipped when they go forward is read with the offset from before
+/// the jump, so 02:30 on a spring-forward night lands at 03:30.
+pub fn resolve_local<Tz: TimeZone>(tz: &Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(at) => at.with_timezone(&Utc),
+        LocalResult::Ambiguous(earlier, _) => earlier.with_timezone(&Utc),
+        LocalResult::None => {
+            // Transitions are never a day apart, so a day earlier has the old offset
+            let before = tz.offset_from_utc_datetime(&(local - Duration::days(1))).fix();
+            (local - Duration::seconds(before.local_minus_utc().into())).and_utc()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// US Eastern for 2026: EDT from 2026-03-08 07:00 UTC to 2026-11-01 06:00 UTC
+    #[derive(Debug, Clone, Copy)]
+    struct Eastern;
+
+    const EST: i32 = -5 * 3600;
+    const EDT: i32 = -4 * 3600;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, min, 0).unwrap().and_utc()
+    }
+
+    fn wall(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        utc(y, m, d, h, min).naive_utc()
+    }
+
+    impl Eastern {
+        fn offset_at(utc_time: &NaiveDateTime) -> FixedOffset {
+            let dst = *utc_time >= wall(2026, 3, 8, 7, 0) && *utc_time < wall(2026, 11, 1, 6, 0);
+            FixedOffset::east_opt(if dst { EDT } else { EST }).unwrap()
+        }
+    }
+
+    impl TimeZone for Eastern {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Eastern
+        }
+
+        fn offset_from_local_date(&self, local: &chrono::NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(12, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let candidates: Vec<FixedOffset> = [EST, EDT]
+                .into_iter()
+                .map(|secs| FixedOffset::east_opt(secs).unwrap())
+                .filter(|offset| Eastern::offset_at(&(*local - Duration::seconds(offset.local_minus_utc().into()))) == *offset)
+                .collect();
+            match candidates[..] {
+                [] => LocalResult::None,
+                [only] => LocalResult::Single(only),
+                // EDT's reading of a repeated hour is the earlier instant
+                [est, edt] => LocalResult::Ambiguous(edt, est),
+                _ => unreachable!(),
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &chrono::NaiveDate) -> FixedOffset {
+            Eastern::offset_at(&utc.and_hms_opt(12, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            Eastern::offset_at(utc)
+        }
+    }
+
+    #[test]
+    fn test_resolve_local_across_dst() {
+        // 9am either side of spring forward shifts by an hour in UTC
+        assert_eq!(resolve_local(&Eastern, wall(2026, 3, 7, 9, 0)), utc(2026, 3, 7, 14, 0));
+        assert_eq!(resolve_local(&Eastern, wall(2026, 3, 8, 9, 0)), utc(2026, 3, 8, 13, 0));
+
+        // 02:30 doesn't exist that night, so it means 03:30 EDT
+        let skipped = resolve_local(&Eastern, wall(2026, 3, 8, 2, 30));
+        assert_eq!(skipped, utc(2026, 3, 8, 7, 30));
+        assert_eq!(skipped.with_timezone(&Eastern).hour(), 3);
+
+        // 01:30 happens twice when clocks fall back; the first is EDT
+        assert_eq!(resolve_local(&Eastern, wall(2026, 11, 1, 1, 30)), utc(2026, 11, 1, 5, 30));
+    }
+
+    #[test]
+    fn test_display_round_trips_wall_clock_time() {
+        for local in [wall(2026, 3, 7, 9, 0), wall(2026, 3, 8, 9, 0), wall(2026, 11, 1, 9, 0)] {
+            let stored = resolve_local(&Eastern, local);
+            assert_eq!(stored.with_timezone(&Eastern).naive_local(), local);
+        }
+        assert_eq!(
+            utc(2026, 3, 8, 13, 0).with_timezone(&Eastern).to_rfc3339_opts(SecondsFormat::Secs, true),
+            "2026-03-08T09:00:00-04:00"
+        );
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!("local".parse::<Timezone>(), Ok(Timezone::Local));
+        assert_eq!("UTC".parse::<Timezone>(), Ok(Timezone::Utc));
+        assert_eq!("+05:30".parse::<Timezone>(), Ok(Timezone::Fixed(FixedOffset::east_opt(19_800).unwrap())));
+        assert!("Mars/Olympus".parse::<Timezone>().is_err());
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let tz: Timezone = "+02:00".parse().unwrap();
+        let now = utc(2026, 6, 1, 10, 0); // 12:00 at +02:00
+
+        assert_eq!(tz.parse_schedule("2026-06-02 09:00", now), Ok(utc(2026, 6, 2, 7, 0)));
+        assert_eq!(tz.parse_schedule("2026-06-02T09:00:00Z", now), Ok(utc(2026, 6, 2, 9, 0)));
+
+        // Bare times are the next occurrence: 15:00 is still today, 9am is tomorrow
+        assert_eq!(tz.parse_schedule("15:00", now), Ok(utc(2026, 6, 1, 13, 0)));
+        assert_eq!(tz.parse_schedule("9am", now), Ok(utc(2026, 6, 2, 7, 0)));
+        assert_eq!(tz.parse_schedule("9:30PM", now), Ok(utc(2026, 6, 1, 19, 30)));
+
+        assert!(tz.parse_schedule("soonish", now).unwrap_err().contains("soonish"));
+        assert!(tz.parse_schedule("13pm", now).is_err());
+        assert!(tz.parse_schedule("9", now).is_err());
+        assert_eq!(tz.format(utc(2026, 6, 2, 7, 0)), "2026-06-02T09:00:00+02:00");
+        assert_eq!(Timezone::Utc.format(utc(2026, 6, 2, 7, 0)), "2026-06-02T07:00:00Z");
+    }
+}
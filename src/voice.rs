@@ -0,0 +1,412 @@
+//! The voice pipeline without a terminal: transcribe, classify, enqueue
+//!
+//! `VoiceSession` runs one turn at a time and reports what happened, so the
+//! flow can be embedded or tested; printing and speaking are left to callers.
+
+use anyhow::{Context, Result};
+
+use crate::audio::{Cancelled, EndOfInput, SttBackend, Transcription};
+use crate::config::{Config, ConfirmationConfig};
+use crate::intent::{parse_meta_with, Command, IntentProcessor, MetaCommand, ModelAliases};
+use crate::queue::TaskQueue;
+
+/// Asked before queueing a command that needs confirmation; false skips it
+pub type ConfirmFn = Box<dyn FnMut(&Command) -> Result<bool> + Send>;
+
+/// What became of one command heard in a turn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// Queued to run, with this task id
+    Queued(String),
+    /// Saved as a draft because classification wasn't confident
+    Draft(String),
+    /// Declined when asked to confirm
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct TurnStep {
+    pub command: Command,
+    pub outcome: StepOutcome,
+}
+
+impl TurnStep {
+    /// The task this step became, if it was queued or saved as a draft
+    pub fn task_id(&self) -> Option<&str> {
+        match &self.outcome {
+            StepOutcome::Queued(id) | StepOutcome::Draft(id) => Some(id),
+            StepOutcome::Skipped => None,
+        }
+    }
+}
+
+/// A transcript and the commands queued from it, in order
+#[derive(Debug)]
+pub struct Turn {
+    pub transcript: Transcription,
+    pub steps: Vec<TurnStep>,
+    /// Why queueing stopped before the last command, e.g. a full queue
+    pub error: Option<anyhow::Error>,
+}
+
+impl Turn {
+    /// Ids of every task created this turn
+    pub fn task_ids(&self) -> impl Iterator<Item = &str> {
+        self.steps.iter().filter_map(TurnStep::task_id)
+    }
+}
+
+/// What one call to `VoiceSession::next_turn` did
+#[derive(Debug)]
+pub enum TurnOutcome {
+    /// Heard one or more commands
+    Commands(Turn),
+    /// Heard a session meta-command; a model switch is already applied
+    Meta(MetaCommand),
+    /// Heard something that isn't recognizably a command, so nothing was queued
+    NeedsClarification(Transcription),
+    /// Transcription came back blank
+    Empty,
+    /// The user dismissed the prompt without saying anything
+    Cancelled,
+    /// Input ended, so there are no more turns
+    EndOfInput,
+}
+
+/// Speech-to-text, intent processing and the queue, one turn at a time
+pub struct VoiceSession {
+    stt: Box<dyn SttBackend>,
+    processor: IntentProcessor,
+    queue: TaskQueue,
+    aliases: ModelAliases,
+    /// Used when the session hasn't switched models
+    default_model: Option<String>,
+    /// Set by "use opus" and friends; lasts for this session only
+    model: Option<String>,
+    confirmation: ConfirmationConfig,
+    confirm: Option<ConfirmFn>,
+}
+
+impl VoiceSession {
+    /// A session that never asks for confirmation: uncertain commands become drafts
+    pub fn new(stt: Box<dyn SttBackend>, processor: IntentProcessor, queue: TaskQueue) -> Self {
+        Self {
+            stt,
+            processor,
+            queue,
+            aliases: ModelAliases::default(),
+            default_model: None,
+            model: None,
+            confirmation: ConfirmationConfig::default(),
+            confirm: None,
+        }
+    }
+
+    /// A session with the processor, model aliases and default model from `config`
+    pub fn from_config(config: &Config, stt: Box<dyn SttBackend>, queue: TaskQueue) -> Self {
+        let processor = IntentProcessor::new(config.intent.confidence_threshold)
+            .with_aliases(config.aliases.clone())
+            .with_extractors(config.intent.extractors.clone())
+            .with_fillers(config.intent.fillers.clone())
+            .with_directive_cleaning(config.intent.clean_directive)
+            .with_clarify_threshold(config.intent.clarify_threshold);
+
+        Self::new(stt, processor, queue)
+            .with_model_aliases(config.claude.model_aliases.clone())
+            .with_default_model(config.claude.default_model())
+    }
+
+    pub fn with_model_aliases(mut self, aliases: ModelAliases) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn with_default_model(mut self, model: Option<String>) -> Self {
+        self.default_model = model;
+        self
+    }
+
+    /// Ask `confirm` about commands `confirmation` says need it
+    ///
+    /// A confirmed command is queued to run even if it would have been a draft.
+    pub fn with_confirmation(mut self, confirmation: ConfirmationConfig, confirm: ConfirmFn) -> Self {
+        self.confirmation = confirmation;
+        self.confirm = Some(confirm);
+        self
+    }
+
+    pub fn stt(&self) -> &dyn SttBackend {
+        self.stt.as_ref()
+    }
+
+    pub fn processor(&self) -> &IntentProcessor {
+        &self.processor
+    }
+
+    /// Model new tasks are queued with
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref().or(self.default_model.as_deref())
+    }
+
+    /// Switch models for the rest of the session, resolving aliases
+    pub fn set_model(&mut self, model: impl AsRef<str>) {
+        self.model = Some(self.aliases.resolve(model.as_ref()));
+    }
+
+    /// Apply a meta-command heard elsewhere, e.g. typed at a push-to-talk prompt
+    pub fn apply_meta(&mut self, meta: &MetaCommand) {
+        if let MetaCommand::UseModel(model) = meta {
+            self.set_model(model);
+        }
+    }
+
+    /// Transcribe one utterance and act on it
+    pub async fn next_turn(&mut self) -> Result<TurnOutcome> {
+        match self.stt.transcribe() {
+            Ok(transcription) => self.handle(transcription).await,
+            Err(e) if e.is::<EndOfInput>() => Ok(TurnOutcome::EndOfInput),
+            Err(e) if e.is::<Cancelled>() => Ok(TurnOutcome::Cancelled),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Act on an utterance transcribed by the caller
+    ///
+    /// "X and then Y" queues one task per step, each waiting on the one
+    /// before. A queue error stops the turn but keeps the steps already queued.
+    pub async fn handle(&mut self, transcription: Transcription) -> Result<TurnOutcome> {
+        if transcription.text.trim().is_empty() {
+            return Ok(TurnOutcome::Empty);
+        }
+        if let Some(meta) = parse_meta_with(&transcription.text, &self.aliases) {
+            self.apply_meta(&meta);
+            return Ok(TurnOutcome::Meta(meta));
+        }
+
+        let commands = self
+            .processor
+            .process_multi(&transcription.text)
+            .context("Failed to process intent")?;
+        if commands.iter().any(|command| self.processor.needs_clarification(command)) {
+            return Ok(TurnOutcome::NeedsClarification(transcription));
+        }
+
+        let mut steps = Vec::new();
+        let mut error = None;
+        let mut previous: Option<String> = None;
+        // Set once a step is skipped, so steps meant to follow it are skipped too
+        let mut chain_broken = false;
+        for mut command in commands {
+            if chain_broken && command.after_previous {
+                steps.push(TurnStep { command, outcome: StepOutcome::Skipped });
+                continue;
+            }
+            let ask = self.needs_confirmation(&mut command, &transcription);
+            if let Some(confirm) = self.confirm.as_mut().filter(|_| ask) {
+                if !confirm(&command)? {
+                    steps.push(TurnStep { command, outcome: StepOutcome::Skipped });
+                    chain_broken = true;
+                    continue;
+                }
+                // Confirmed by the user, so it runs instead of waiting as a draft
+                command.confidence = 1.0;
+            }
+
+            let after = previous.as_deref().filter(|_| command.after_previous);
+            match self.enqueue(&command, after).await {
+                Ok(outcome) => {
+                    let step = TurnStep { command, outcome };
+                    previous = step.task_id().map(str::to_string);
+                    chain_broken = false;
+                    steps.push(step);
+                }
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        Ok(TurnOutcome::Commands(Turn { transcript: transcription, steps, error }))
+    }
+
+    /// Fold STT confidence into the command's, returning whether to ask before queueing
+    ///
+    /// A mishearing is as risky as a misclassification, so either being
+    /// uncertain makes the command uncertain.
+    fn needs_confirmation(&self, command: &mut Command, transcription: &Transcription) -> bool {
+        command.confidence = transcription.combined_confidence(command.confidence);
+        self.confirmation.should_confirm(Some(command.intent), self.processor.is_confident(command))
+    }
+
+    /// Queue `command`, as a draft when classification isn't confident
+    async fn enqueue(&self, command: &Command, after: Option<&str>) -> Result<StepOutcome> {
+        let model = self.model().map(str::to_string);
+        if self.processor.is_confident(command) {
+            Ok(StepOutcome::Queued(self.queue.enqueue_command_after(command, model, after).await?))
+        } else {
+            Ok(StepOutcome::Draft(self.queue.enqueue_draft_after(command, model, after).await?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::NullStt;
+    use crate::intent::Intent;
+    use crate::queue::TaskStatus;
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    async fn voice_session(input: &str) -> (VoiceSession, TaskQueue) {
+        let queue = TaskQueue::new(":memory:").await.unwrap();
+        let stt = Box::new(NullStt::from_reader(Cursor::new(input.to_string())));
+        let session = VoiceSession::new(stt, IntentProcessor::new(0.8), queue.clone());
+        (session, queue)
+    }
+
+    fn commands(outcome: TurnOutcome) -> Turn {
+        match outcome {
+            TurnOutcome::Commands(turn) => turn,
+            other => panic!("expected commands, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_turn_queues_command() {
+        let (mut session, queue) = voice_session("run the tests\n").await;
+
+        let turn = commands(session.next_turn().await.unwrap());
+        assert_eq!(turn.transcript.text, "run the tests");
+        assert!(turn.error.is_none());
+        let [step] = &turn.steps[..] else { panic!("{:?}", turn.steps) };
+
+        let task = queue.get(step.task_id().unwrap()).await.unwrap().unwrap();
+        assert!(matches!(step.outcome, StepOutcome::Queued(_)));
+        assert_eq!(task.status, TaskStatus::Queued);
+        assert_eq!(task.intent, Some(step.command.intent));
+        assert_eq!(task.command, step.command.directive);
+
+        assert!(matches!(session.next_turn().await.unwrap(), TurnOutcome::EndOfInput));
+    }
+
+    #[tokio::test]
+    async fn test_turn_chains_steps_and_applies_model_switches() {
+        let (mut session, queue) = voice_session("use fast\nrun the tests and then deploy to staging\n").await;
+        session.aliases = ModelAliases::new([("fast".to_string(), "haiku".to_string())]).unwrap();
+
+        let meta = session.next_turn().await.unwrap();
+        assert!(matches!(meta, TurnOutcome::Meta(MetaCommand::UseModel(ref m)) if m == "haiku"), "{:?}", meta);
+        assert_eq!(session.model(), Some("haiku"));
+        let blank = session.handle(Transcription::new("  ")).await.unwrap();
+        assert!(matches!(blank, TurnOutcome::Empty));
+
+        let turn = commands(session.next_turn().await.unwrap());
+        let ids: Vec<_> = turn.task_ids().collect();
+        assert_eq!(ids.len(), 2);
+        let second = queue.get(ids[1]).await.unwrap().unwrap();
+        assert_eq!(second.depends_on.as_deref(), Some(ids[0]));
+        assert_eq!(second.model.as_deref(), Some("haiku"));
+    }
+
+    #[tokio::test]
+    async fn test_unclassifiable_turn_asks_for_clarification() {
+        let (session, queue) = voice_session("hmm the login page\nfix the login page\n").await;
+        let processor = IntentProcessor::new(0.8).with_clarify_threshold(0.65);
+        let mut session = VoiceSession { processor, ..session };
+
+        let unclear = session.next_turn().await.unwrap();
+        assert!(
+            matches!(&unclear, TurnOutcome::NeedsClarification(t) if t.text == "hmm the login page"),
+            "{:?}",
+            unclear
+        );
+        assert!(queue.list().await.unwrap().is_empty());
+
+        let turn = commands(session.next_turn().await.unwrap());
+        assert!(matches!(turn.steps[0].outcome, StepOutcome::Queued(_)));
+    }
+
+    #[tokio::test]
+    async fn test_confirmation_when_either_confidence_is_low() {
+        let (session, _) = voice_session("").await;
+        let confirmation = ConfirmationConfig { mode: "smart".to_string(), ..ConfirmationConfig::default() };
+        let session = VoiceSession { processor: IntentProcessor::new(0.7), confirmation, ..session };
+        let command = || Command::new(Intent::Test, "run the tests", vec![], 0.9);
+
+        // Both confident, or the backend doesn't say
+        let mut confident = command();
+        assert!(!session.needs_confirmation(&mut confident, &Transcription::new("x")));
+        let heard = Transcription::new("x").with_confidence(0.95);
+        assert!(!session.needs_confirmation(&mut confident, &heard));
+        assert_eq!(confident.confidence, 0.9);
+
+        // Misheard, though clearly classified
+        let mut misheard = command();
+        let mumbled = Transcription::new("x").with_confidence(0.3);
+        assert!(session.needs_confirmation(&mut misheard, &mumbled));
+        assert_eq!(misheard.confidence, 0.3);
+
+        // Heard clearly, but ambiguous
+        let mut ambiguous = Command { confidence: 0.5, ..command() };
+        assert!(session.needs_confirmation(&mut ambiguous, &heard));
+    }
+
+    #[tokio::test]
+    async fn test_uncertain_commands_are_drafts_or_confirmed() {
+        let (session, queue) = voice_session("run the tests\nrun the tests\n").await;
+        let asked = Arc::new(Mutex::new(0));
+        let answers = asked.clone();
+        let confirmation = ConfirmationConfig { mode: "always".to_string(), ..ConfirmationConfig::default() };
+        let mut session = session.with_confirmation(
+            confirmation,
+            Box::new(move |_| {
+                let mut asked = answers.lock().unwrap();
+                *asked += 1;
+                Ok(*asked == 1)
+            }),
+        );
+
+        // Confirmed, so queued to run
+        let turn = commands(session.next_turn().await.unwrap());
+        let task = queue.get(turn.steps[0].task_id().unwrap()).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Queued);
+
+        // Declined, so nothing is queued
+        let turn = commands(session.next_turn().await.unwrap());
+        assert_eq!(turn.steps[0].outcome, StepOutcome::Skipped);
+        assert_eq!(*asked.lock().unwrap(), 2);
+        assert_eq!(queue.list().await.unwrap().len(), 1);
+
+        // Without a confirmer, a low-confidence transcription waits as a draft
+        let (mut session, queue) = voice_session("").await;
+        let turn = commands(session.handle(Transcription::new("run the tests").with_confidence(0.3)).await.unwrap());
+        assert!(matches!(turn.steps[0].outcome, StepOutcome::Draft(_)));
+        let task = queue.get(turn.steps[0].task_id().unwrap()).await.unwrap().unwrap();
+        assert_eq!(task.status, TaskStatus::Draft);
+    }
+
+    #[tokio::test]
+    async fn test_declined_step_skips_steps_after_it() {
+        let (session, queue) = voice_session("run the tests and then deploy to staging
+").await;
+        let asked = Arc::new(Mutex::new(0));
+        let answers = asked.clone();
+        let confirmation = ConfirmationConfig { mode: "always".to_string(), ..ConfirmationConfig::default() };
+        let mut session = session.with_confirmation(
+            confirmation,
+            Box::new(move |_| {
+                *answers.lock().unwrap() += 1;
+                Ok(false)
+            }),
+        );
+
+        // Deploying after tests that won't run isn't asked about, let alone queued
+        let turn = commands(session.next_turn().await.unwrap());
+        assert_eq!(turn.steps.len(), 2);
+        assert!(turn.steps.iter().all(|step| step.outcome == StepOutcome::Skipped));
+        assert_eq!(*asked.lock().unwrap(), 1);
+        assert!(queue.list().await.unwrap().is_empty());
+    }
+}